@@ -0,0 +1,65 @@
+//! Benchmarks for the lookup-table hand evaluator on 5, 6 and 7 card hands, next to the `get_hand_type`
+//! brute force over the 21 five-card hands of 7 cards that it replaced.
+//! 
+//! Run with `cargo bench --bench hand_evaluator`.
+use criterion::{criterion_group, criterion_main, Criterion};
+use poker_game::{evaluator, hand_type};
+use rand::seq::SliceRandom;
+use std::hint::black_box;
+
+/// Deals `count` random hands of `size` cards each.
+fn random_hands(size: usize, count: usize) -> Vec<Vec<i32>> {
+    let mut rng = rand::rng();
+    let mut cards: Vec<i32> = (0..52).collect();
+    (0..count)
+        .map(|_| {
+            cards.shuffle(&mut rng);
+            cards[..size].to_vec()
+        })
+        .collect()
+}
+
+fn bench_evaluate(c: &mut Criterion) {
+    for size in [5, 6, 7] {
+        let hands = random_hands(size, 1000);
+        c.bench_function(&format!("evaluate {} card hands x1000", size), |b| {
+            b.iter(|| {
+                for hand in hands.iter() {
+                    black_box(evaluator::evaluate(black_box(hand)));
+                }
+            })
+        });
+    }
+}
+
+/// The best hand of 7 cards the way the server scored it before `evaluator`: every 5-card hand through `get_hand_type`.
+fn best_of_21(hand: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
+    let mut best = (0, 0, 0, 0, 0, 0);
+    for i in 0..=2 {
+        for j in i + 1..=3 {
+            for k in j + 1..=4 {
+                for l in k + 1..=5 {
+                    for m in l + 1..=6 {
+                        let cards = vec![hand[i], hand[j], hand[k], hand[l], hand[m]];
+                        best = best.max(hand_type::get_hand_type(&cards));
+                    }
+                }
+            }
+        }
+    }
+    best
+}
+
+fn bench_get_hand_type(c: &mut Criterion) {
+    let hands = random_hands(7, 1000);
+    c.bench_function("get_hand_type over 21 subsets of 7 card hands x1000", |b| {
+        b.iter(|| {
+            for hand in hands.iter() {
+                black_box(best_of_21(black_box(hand)));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench_evaluate, bench_get_hand_type);
+criterion_main!(benches);
@@ -0,0 +1,114 @@
+//! The dealer button and the blinds, moved round the table from hand to hand.
+//!
+//! Positions are the lobby's seat numbers, so a seat keeps its number while it is empty and the button can be
+//! left on it. Every hand the big blind moves on to the next player dealt in, and the small blind and the button
+//! move to the seats the big blind and the small blind had the hand before, whoever sits there now. This is the
+//! dead button rule: when a player leaves or busts, the hand after may have a dead small blind or a button on an
+//! empty seat, but nobody posts the big blind twice or misses it.
+//!
+//! Heads-up the button posts the small blind, acts first before the flop and last after it.
+
+/// Where the button and the blinds are for one hand.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Positions {
+    pub button: usize,      // may be a seat that is not dealt in, a dead button
+    pub small_blind: usize, // dead when the seat is not dealt in, then nobody posts it
+    pub big_blind: usize,
+}
+
+/// The next seat after `seat` that is dealt in, going round the table.
+fn next_active(active: &[bool], seat: usize) -> usize {
+    (1..=active.len()).map(|k| (seat + k) % active.len()).find(|&i| active[i]).unwrap_or(seat)
+}
+
+/// The last seat before `seat` that is dealt in.
+fn previous_active(active: &[bool], seat: usize) -> usize {
+    (1..=active.len()).map(|k| (seat + active.len() - k) % active.len()).find(|&i| active[i]).unwrap_or(seat)
+}
+
+/// The first seat dealt in after the button, who is dealt to first and acts first after the flop.
+pub fn first_to_act(positions: &Positions, active: &[bool]) -> usize {
+    next_active(active, positions.button)
+}
+
+/// Moves the button and the blinds on for the next hand.
+///
+/// # Arguments
+/// * `previous` - The positions of the last hand, `None` for the first hand at the table.
+/// * `active` - For every seat, whether its player is dealt in to the next hand.
+///
+/// # Returns
+/// The positions for the next hand, or `None` with fewer than two players dealt in.
+pub fn next_positions(previous: Option<Positions>, active: &[bool]) -> Option<Positions> {
+    let players = active.iter().filter(|&&a| a).count();
+    if players < 2 {
+        return None;
+    }
+    let seats = active.len();
+    let big_blind = match previous {
+        Some(previous) => next_active(active, previous.big_blind % seats),
+        // the first hand: the button goes to the first player and the blinds to the players after them
+        None => (0..players.min(3)).fold(seats - 1, |seat, _| next_active(active, seat)),
+    };
+    let small_blind = previous_active(active, big_blind);
+    if players == 2 {
+        return Some(Positions { button: small_blind, small_blind, big_blind });
+    }
+    match previous {
+        Some(previous) if previous.small_blind % seats != big_blind => Some(Positions {
+            button: previous.small_blind % seats,
+            small_blind: previous.big_blind % seats,
+            big_blind,
+        }),
+        // a player sat down between the blinds, or the table was heads-up
+        _ => Some(Positions { button: previous_active(active, small_blind), small_blind, big_blind }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(button: usize, small_blind: usize, big_blind: usize) -> Positions {
+        Positions { button, small_blind, big_blind }
+    }
+
+    #[test]
+    fn test_first_hand() {
+        assert_eq!(next_positions(None, &[true, true, true, true]), Some(positions(0, 1, 2)));
+        assert_eq!(next_positions(None, &[false, true, false, true, true]), Some(positions(1, 3, 4)));
+        assert_eq!(next_positions(None, &[true, false, true]), Some(positions(0, 0, 2)));
+        assert_eq!(next_positions(None, &[true, false, false]), None);
+    }
+
+    #[test]
+    fn test_button_moves_one_seat() {
+        let active = [true, true, true, true];
+        let second = next_positions(Some(positions(0, 1, 2)), &active).unwrap();
+        assert_eq!(second, positions(1, 2, 3));
+        assert_eq!(next_positions(Some(second), &active), Some(positions(2, 3, 0)));
+        assert_eq!(first_to_act(&second, &active), 2);
+    }
+
+    #[test]
+    fn test_dead_small_blind_and_dead_button() {
+        // the big blind of the last hand busted: nobody posts the small blind
+        let positions_after = next_positions(Some(positions(0, 1, 2)), &[true, true, false, true, true]).unwrap();
+        assert_eq!(positions_after, positions(1, 2, 3));
+        // then the button moves onto the empty seat and the player after it acts first after the flop
+        let active = [true, true, false, true, true];
+        let next = next_positions(Some(positions_after), &active).unwrap();
+        assert_eq!(next, positions(2, 3, 4));
+        assert_eq!(first_to_act(&next, &active), 3);
+    }
+
+    #[test]
+    fn test_heads_up() {
+        let active = [true, false, true];
+        let next = next_positions(Some(positions(0, 0, 2)), &active).unwrap();
+        assert_eq!(next, positions(2, 2, 0));
+        assert_eq!(first_to_act(&next, &active), 0);
+        // down to two players: the big blind still moves on and the button posts the small blind
+        assert_eq!(next_positions(Some(positions(0, 1, 2)), &[true, false, true]), Some(positions(2, 2, 0)));
+    }
+}
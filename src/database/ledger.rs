@@ -0,0 +1,304 @@
+//! The chip ledger: every change to a balance of chips, in the `chip_transactions` table.
+//!
+//! Chips are held in accounts: a player's wallet, their stack at a cash table, or the prize pool of a tournament.
+//! Balances are only ever changed by `post`, which adds a signed amount to the balance and writes the ledger row
+//! that explains it in the caller's transaction, so nothing writes an absolute balance worked out from a copy that
+//! may be stale. A transfer posts the amount out of one account and into another in one transaction, and a cash
+//! hand posts what every player put in and won at the table with the ID of the hand.
+//!
+//! Only the sign-up bonus and the opening balances, the chips players had when the ledger was introduced, create
+//! chips. Everything else moves them, so the chips in all wallets, at all cash tables and in the prize pools of
+//! running tournaments always add up to the chips created. `Database::reconcile` checks this, and that every
+//! balance is the sum of its ledger rows.
+use super::Database;
+use crate::history::HandRecord;
+use sqlx::{Row, SqliteConnection};
+use std::collections::HashMap;
+
+// Why chips moved
+pub const BONUS: &str = "bonus";
+pub const OPENING_BALANCE: &str = "opening_balance";
+pub const BUY_IN: &str = "buy_in";
+pub const CASH_OUT: &str = "cash_out";
+pub const ANTE: &str = "ante";
+pub const BLIND: &str = "blind";
+pub const BRING_IN: &str = "bring_in";
+pub const BET: &str = "bet";
+pub const POT_WIN: &str = "pot_win";
+pub const TOURNAMENT_BUY_IN: &str = "tournament_buy_in";
+pub const TOURNAMENT_REFUND: &str = "tournament_refund";
+pub const PRIZE: &str = "prize";
+
+/// Where a player's chips are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Account<'a> {
+    Wallet,
+    Table(&'a str),      // the player's stack at a cash table, by lobby name
+    Tournament(&'a str), // the prize pool of a tournament, by name, which holds the buy-ins until they are paid out
+}
+
+impl Account<'_> {
+    /// The account as stored in the `account` column.
+    pub fn key(&self) -> String {
+        match self {
+            Account::Wallet => "wallet".to_string(),
+            Account::Table(lobby_name) => format!("table:{}", lobby_name),
+            Account::Tournament(name) => format!("tournament:{}", name),
+        }
+    }
+}
+
+/// The ledger rows of a hand at a cash table: `(player, reason, amount)` for what each player dealt in put into
+/// the pot, as antes, blinds, the bring-in and bets, and what they won. A player's rows add up to the change in
+/// their stack, and all the rows of a hand to zero.
+pub fn hand_entries(hand: &HandRecord) -> Vec<(String, &'static str, i32)> {
+    let mut entries = Vec::new();
+    for seat in hand.seats.iter().filter(|seat| seat.dealt_in) {
+        let contributed = seat.won - (seat.final_stack - seat.starting_stack);
+        let mut forced = 0;
+        for action in hand.actions.iter().filter(|a| a.player == seat.player && a.amount > 0) {
+            let reason = match action.action.as_str() {
+                "ante" => ANTE,
+                "small_blind" | "big_blind" => BLIND,
+                "bring_in" => BRING_IN,
+                _ => continue,
+            };
+            forced += action.amount;
+            entries.push((seat.player.clone(), reason, -action.amount));
+        }
+        if contributed > forced {
+            entries.push((seat.player.clone(), BET, forced - contributed));
+        }
+        if seat.won > 0 {
+            entries.push((seat.player.clone(), POT_WIN, seat.won));
+        }
+    }
+    entries
+}
+
+/// Adds `amount` to a balance and records it in the ledger. Wallets and table stacks never go below zero;
+/// a prize pool is not held anywhere but in the ledger.
+///
+/// # Arguments
+/// * `conn` - The connection of the transaction the posting is part of.
+/// * `player_name` - The player whose chips moved.
+/// * `account` - Where they moved in or out of.
+/// * `amount` - The chips, negative when they leave the account.
+/// * `reason` - Why, one of the reasons above.
+/// * `hand_id` - The hand the chips moved in, if any.
+///
+/// # Returns
+/// * `Ok(true)` - If the balance was changed and the row recorded.
+/// * `Ok(false)` - If there is no such player or the balance would go below zero. Nothing is changed then.
+/// * `Err(sqlx::Error)` - If a database error occurs.
+pub(super) async fn post(
+    conn: &mut SqliteConnection,
+    player_name: &str,
+    account: Account<'_>,
+    amount: i32,
+    reason: &str,
+    hand_id: Option<i64>,
+) -> Result<bool, sqlx::Error> {
+    let applied = match account {
+        Account::Wallet => sqlx::query("UPDATE players SET wallet = wallet + ?1 WHERE name = ?2 AND wallet + ?1 >= 0")
+            .bind(amount)
+            .bind(player_name)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() == 1,
+        Account::Table(lobby_name) if amount >= 0 => sqlx::query(
+            "INSERT INTO table_stacks (player_name, lobby_name, stack) VALUES (?1, ?2, ?3)
+             ON CONFLICT (player_name, lobby_name) DO UPDATE SET stack = stack + excluded.stack",
+        )
+        .bind(player_name)
+        .bind(lobby_name)
+        .bind(amount)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() == 1,
+        Account::Table(lobby_name) => sqlx::query(
+            "UPDATE table_stacks SET stack = stack + ?1 WHERE player_name = ?2 AND lobby_name = ?3 AND stack + ?1 >= 0",
+        )
+        .bind(amount)
+        .bind(player_name)
+        .bind(lobby_name)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() == 1,
+        Account::Tournament(_) => true,
+    };
+    if !applied {
+        return Ok(false);
+    }
+    sqlx::query("INSERT INTO chip_transactions (player_name, account, amount, reason, hand_id) VALUES (?1, ?2, ?3, ?4, ?5)")
+        .bind(player_name)
+        .bind(account.key())
+        .bind(amount)
+        .bind(reason)
+        .bind(hand_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(true)
+}
+
+/// What `Database::reconcile` found.
+#[derive(Debug, Default, PartialEq)]
+pub struct Reconciliation {
+    pub created: i64,         // chips given as bonuses and opening balances
+    pub wallets: i64,         // chips in all wallets
+    pub tables: i64,          // chips at all cash tables
+    pub tournaments: i64,     // buy-ins in prize pools not yet paid out
+    pub problems: Vec<String>, // balances and hands whose ledger rows do not add up
+}
+
+impl Reconciliation {
+    /// Whether no chips were created or destroyed outside the ledger.
+    pub fn balanced(&self) -> bool {
+        self.problems.is_empty() && self.wallets + self.tables + self.tournaments == self.created
+    }
+}
+
+impl Database {
+    /// Moves chips from one of a player's accounts to another, as one transaction.
+    ///
+    /// # Arguments
+    /// * `player_name` - The player whose chips move.
+    /// * `from` - The account they leave.
+    /// * `to` - The account they go to.
+    /// * `amount` - The chips to move.
+    /// * `reason` - Why, recorded on both ledger rows.
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If the chips were moved.
+    /// * `Ok(false)` - If there is no such player or `from` does not hold `amount`. Nothing is changed then.
+    /// * `Err(sqlx::Error)` - If a database error occurs. Nothing is changed in that case.
+    pub async fn transfer(&self, player_name: &str, from: Account<'_>, to: Account<'_>, amount: i32, reason: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        if !post(&mut tx, player_name, from, -amount, reason, None).await? || !post(&mut tx, player_name, to, amount, reason, None).await? {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Checks that no chips were created or destroyed outside the ledger: every wallet and table stack is the sum of
+    /// its ledger rows, every cash hand adds up to zero, and the chips in wallets, at tables and in prize pools add
+    /// up to the chips created.
+    pub async fn reconcile(&self) -> Result<Reconciliation, sqlx::Error> {
+        let sum = |sql: &'static str| async move {
+            sqlx::query(sql).fetch_one(&*self.pool).await.map(|row| row.get::<Option<i64>, _>(0).unwrap_or(0))
+        };
+        // the migration that opened the ledger wrote the opening balances with the same reason
+        let created: i64 = sqlx::query("SELECT COALESCE(SUM(amount), 0) FROM chip_transactions WHERE reason IN (?1, ?2)")
+            .bind(BONUS)
+            .bind(OPENING_BALANCE)
+            .fetch_one(&*self.pool)
+            .await?
+            .get(0);
+        let mut report = Reconciliation {
+            created,
+            wallets: sum("SELECT SUM(wallet) FROM players").await?,
+            tables: sum("SELECT SUM(stack) FROM table_stacks").await?,
+            tournaments: sum("SELECT SUM(amount) FROM chip_transactions WHERE account LIKE 'tournament:%'").await?,
+            problems: Vec::new(),
+        };
+
+        let wallets = sqlx::query(
+            "SELECT name, COALESCE(wallet, 0),
+                    (SELECT COALESCE(SUM(amount), 0) FROM chip_transactions WHERE player_name = players.name AND account = 'wallet')
+             FROM players",
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+        for row in wallets {
+            let (name, wallet, ledger): (String, i64, i64) = (row.get(0), row.get(1), row.get(2));
+            if wallet != ledger {
+                report.problems.push(format!("wallet of {} holds {} chips, its ledger {}", name, wallet, ledger));
+            }
+        }
+
+        let mut stacks: HashMap<(String, String), (i64, i64)> = HashMap::new();
+        for row in sqlx::query("SELECT player_name, 'table:' || lobby_name, stack FROM table_stacks").fetch_all(&*self.pool).await? {
+            stacks.entry((row.get(0), row.get(1))).or_default().0 = row.get(2);
+        }
+        let ledger = sqlx::query(
+            "SELECT player_name, account, SUM(amount) FROM chip_transactions WHERE account LIKE 'table:%' GROUP BY player_name, account",
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+        for row in ledger {
+            stacks.entry((row.get(0), row.get(1))).or_default().1 = row.get(2);
+        }
+        for ((name, account), (stack, ledger)) in stacks {
+            if stack != ledger {
+                report.problems.push(format!("{} of {} holds {} chips, its ledger {}", account, name, stack, ledger));
+            }
+        }
+
+        let hands = sqlx::query(
+            "SELECT hand_id, SUM(amount) FROM chip_transactions WHERE hand_id IS NOT NULL GROUP BY hand_id HAVING SUM(amount) <> 0",
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+        for row in hands {
+            report.problems.push(format!("hand {} moved {} chips more than it took", row.get::<i64, _>(0), row.get::<i64, _>(1)));
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::tests::{played_hand, setup_database};
+
+    #[test]
+    fn test_hand_entries_add_up_to_the_stacks() {
+        let hand = played_hand(&["alice", "bob", "carol"]);
+        let entries = hand_entries(&hand);
+        assert_eq!(entries.iter().map(|(_, _, amount)| amount).sum::<i32>(), 0);
+        for seat in hand.seats.iter() {
+            let change: i32 = entries.iter().filter(|(name, _, _)| *name == seat.player).map(|(_, _, amount)| amount).sum();
+            assert_eq!(change, seat.final_stack - seat.starting_stack);
+        }
+        // everyone antes in 5 Card Draw
+        assert_eq!(entries.iter().filter(|(_, reason, _)| *reason == ANTE).count(), 3);
+        assert!(entries.iter().any(|(_, reason, amount)| *reason == POT_WIN && *amount > 0));
+    }
+
+    #[tokio::test]
+    async fn test_transfers_are_recorded_and_reconcile() {
+        let db = setup_database().await;
+        db.register_player("alice", "secret").await.unwrap();
+        db.register_player("bob", "secret").await.unwrap();
+        assert!(db.transfer("alice", Account::Wallet, Account::Tournament("sng"), 100, TOURNAMENT_BUY_IN).await.unwrap());
+        assert!(db.transfer("bob", Account::Wallet, Account::Tournament("sng"), 100, TOURNAMENT_BUY_IN).await.unwrap());
+        assert!(!db.transfer("bob", Account::Wallet, Account::Table("cash"), 901, BUY_IN).await.unwrap());
+
+        let report = db.reconcile().await.unwrap();
+        assert!(report.balanced(), "{:?}", report);
+        assert_eq!((report.created, report.wallets, report.tournaments), (2000, 1800, 200));
+
+        assert!(db.transfer("bob", Account::Tournament("sng"), Account::Wallet, 200, PRIZE).await.unwrap());
+        let report = db.reconcile().await.unwrap();
+        assert!(report.balanced());
+        assert_eq!((report.wallets, report.tournaments), (2000, 0));
+        let rows = sqlx::query("SELECT COUNT(*) FROM chip_transactions").fetch_one(&*db.pool).await.unwrap();
+        assert_eq!(rows.get::<i64, _>(0), 2 + 3 * 2);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_finds_chips_changed_outside_the_ledger() {
+        let db = setup_database().await;
+        db.register_player("alice", "secret").await.unwrap();
+        db.buy_in("alice", "cash", 500).await.unwrap();
+        assert!(db.reconcile().await.unwrap().balanced());
+
+        sqlx::query("UPDATE players SET wallet = 5000 WHERE name = 'alice'").execute(&*db.pool).await.unwrap();
+        sqlx::query("UPDATE table_stacks SET stack = 0").execute(&*db.pool).await.unwrap();
+        let report = db.reconcile().await.unwrap();
+        assert!(!report.balanced());
+        assert_eq!(report.problems.len(), 2);
+    }
+}
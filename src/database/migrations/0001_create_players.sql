@@ -3,6 +3,6 @@ CREATE TABLE IF NOT EXISTS players (
     name TEXT UNIQUE NOT NULL,
     games_played INTEGER DEFAULT 0,
     games_won INTEGER DEFAULT 0,
-    wallet INTEGER DEFAULT 0,
+    wallet INTEGER DEFAULT 1000,
     logged_in BOOLEAN DEFAULT FALSE
 );
@@ -0,0 +1,3 @@
+-- accounts from before passwords are left without one, which locks them until the operator sets one
+-- with `cargo run -- reset-password <username>`
+ALTER TABLE players ADD COLUMN password_hash TEXT;
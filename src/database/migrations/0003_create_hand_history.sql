@@ -0,0 +1,35 @@
+CREATE TABLE IF NOT EXISTS hands (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    lobby_name TEXT NOT NULL,
+    game_type INTEGER NOT NULL,
+    board TEXT NOT NULL DEFAULT '[]',
+    pots TEXT NOT NULL DEFAULT '[]',
+    played_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS hand_players (
+    hand_id INTEGER NOT NULL REFERENCES hands(id) ON DELETE CASCADE,
+    seat INTEGER NOT NULL,
+    player_name TEXT NOT NULL,
+    starting_stack INTEGER NOT NULL,
+    final_stack INTEGER NOT NULL,
+    starting_hand TEXT NOT NULL DEFAULT '[]',
+    hole_cards TEXT NOT NULL DEFAULT '[]',
+    dealt_in BOOLEAN NOT NULL DEFAULT TRUE,
+    folded BOOLEAN NOT NULL DEFAULT FALSE,
+    won INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (hand_id, seat)
+);
+
+CREATE INDEX IF NOT EXISTS hand_players_by_player ON hand_players (player_name, hand_id);
+
+CREATE TABLE IF NOT EXISTS hand_actions (
+    hand_id INTEGER NOT NULL REFERENCES hands(id) ON DELETE CASCADE,
+    seq INTEGER NOT NULL,
+    player_name TEXT NOT NULL,
+    phase INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    amount INTEGER NOT NULL DEFAULT 0,
+    cards TEXT NOT NULL DEFAULT '[]',
+    PRIMARY KEY (hand_id, seq)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE hands ADD COLUMN button INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE hand_actions ADD COLUMN street INTEGER NOT NULL DEFAULT 0;
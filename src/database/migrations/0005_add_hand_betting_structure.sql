@@ -0,0 +1,3 @@
+ALTER TABLE hands ADD COLUMN betting_structure INTEGER NOT NULL DEFAULT 0;
+-- hands played before lobbies chose a betting structure were pot-limit in Omaha, no-limit otherwise
+UPDATE hands SET betting_structure = 1 WHERE game_type IN (14, 15);
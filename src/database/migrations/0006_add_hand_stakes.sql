@@ -0,0 +1,6 @@
+ALTER TABLE hands ADD COLUMN small_blind INTEGER NOT NULL DEFAULT 5;
+ALTER TABLE hands ADD COLUMN big_blind INTEGER NOT NULL DEFAULT 10;
+ALTER TABLE hands ADD COLUMN ante INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE hands ADD COLUMN bring_in INTEGER NOT NULL DEFAULT 15;
+-- hands played before lobbies set their stakes had an ante of 10 in the draw games only
+UPDATE hands SET ante = 10 WHERE game_type IN (10, 18);
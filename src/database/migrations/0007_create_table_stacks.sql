@@ -0,0 +1,7 @@
+-- chips a player has bought in for at a cash table, out of their wallet until they cash out
+CREATE TABLE IF NOT EXISTS table_stacks (
+    player_name TEXT NOT NULL REFERENCES players(name),
+    lobby_name TEXT NOT NULL,
+    stack INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (player_name, lobby_name)
+);
@@ -0,0 +1,19 @@
+-- the ledger of every change to a wallet, a cash table stack or a tournament prize pool
+CREATE TABLE IF NOT EXISTS chip_transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    player_name TEXT NOT NULL,
+    account TEXT NOT NULL, -- 'wallet', 'table:<lobby name>' or 'tournament:<tournament name>'
+    amount INTEGER NOT NULL, -- chips into the account, negative for chips out of it
+    reason TEXT NOT NULL,
+    hand_id INTEGER REFERENCES hands(id),
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS chip_transactions_by_player ON chip_transactions (player_name, account);
+CREATE INDEX IF NOT EXISTS chip_transactions_by_hand ON chip_transactions (hand_id);
+
+-- the chips players already hold open their balances
+INSERT INTO chip_transactions (player_name, account, amount, reason)
+    SELECT name, 'wallet', wallet, 'opening_balance' FROM players WHERE COALESCE(wallet, 0) <> 0;
+INSERT INTO chip_transactions (player_name, account, amount, reason)
+    SELECT player_name, 'table:' || lobby_name, stack, 'opening_balance' FROM table_stacks WHERE stack <> 0;
@@ -0,0 +1,243 @@
+//! Versioned schema migrations for the poker database.
+//!
+//! Every change to the schema is a numbered SQL file in this directory, embedded in the binary with
+//! `include_str!` and listed in `MIGRATIONS`. The `schema_version` table records which migrations a database
+//! has had. `run` applies the missing ones in order at startup, each in its own transaction, so a database
+//! from any earlier version of the server is brought up to date.
+//!
+//! Databases created before this table existed are adopted: `run` works out which migrations their schema
+//! already matches and records those as applied without running them again.
+//!
+//! Migrations are never edited once released. A change to the schema is a new file with the next number.
+use sqlx::{Executor, Row, SqlitePool};
+
+/// One schema change.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration, in the order they are applied.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_players",
+        sql: include_str!("0001_create_players.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "add_password_hash",
+        sql: include_str!("0002_add_password_hash.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "create_hand_history",
+        sql: include_str!("0003_create_hand_history.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "add_hand_button_and_street",
+        sql: include_str!("0004_add_hand_button_and_street.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "add_hand_betting_structure",
+        sql: include_str!("0005_add_hand_betting_structure.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "add_hand_stakes",
+        sql: include_str!("0006_add_hand_stakes.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "create_table_stacks",
+        sql: include_str!("0007_create_table_stacks.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "create_chip_transactions",
+        sql: include_str!("0008_create_chip_transactions.sql"),
+    },
+];
+
+/// Brings the database up to the latest schema version.
+///
+/// # Arguments
+/// * `pool` - The database to migrate.
+///
+/// # Returns
+/// * `Ok(i64)` - The schema version of the database afterwards.
+/// * `Err(sqlx::Error)` - If a migration fails. The failed migration is rolled back and later ones are not run.
+pub async fn run(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let adopting = !table_exists(pool, "schema_version").await?;
+    pool.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT DEFAULT CURRENT_TIMESTAMP
+        )",
+    )
+    .await?;
+    if adopting {
+        let legacy_version = legacy_version(pool).await?;
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= legacy_version) {
+            println!("Database already has migration {} ({})", migration.version, migration.name);
+            record(pool, migration).await?;
+        }
+    }
+
+    let applied = current_version(pool).await?;
+    let mut version = applied;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > applied) {
+        println!("Applying database migration {} ({})", migration.version, migration.name);
+        let mut tx = pool.begin().await?;
+        (&mut *tx).execute(migration.sql).await?;
+        sqlx::query("INSERT INTO schema_version (version, name) VALUES (?1, ?2)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        version = migration.version;
+    }
+    Ok(version)
+}
+
+/// The highest migration recorded in `schema_version`, 0 for a new database.
+pub async fn current_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(row.get(0))
+}
+
+async fn record(pool: &SqlitePool, migration: &Migration) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT OR IGNORE INTO schema_version (version, name) VALUES (?1, ?2)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Works out which migrations the schema of a database without `schema_version` already has.
+async fn legacy_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    if !table_exists(pool, "players").await? {
+        return Ok(0);
+    }
+    if !column_exists(pool, "players", "password_hash").await? {
+        return Ok(1);
+    }
+    Ok(2)
+}
+
+async fn table_exists(pool: &SqlitePool, table: &str) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")
+        .bind(table)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.is_some())
+}
+
+async fn column_exists(pool: &SqlitePool, table: &str, column: &str) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")
+        .bind(table)
+        .bind(column)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn memory_pool() -> SqlitePool {
+        SqlitePool::connect("sqlite::memory:").await.unwrap()
+    }
+
+    /// The version of a database with every migration applied.
+    fn latest_version() -> i64 {
+        MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+    }
+
+    #[test]
+    fn test_versions_are_numbered_in_order() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1, "migration {} is out of order", migration.name);
+        }
+    }
+
+    /// Every migration runs on a new database and the result has the full schema
+    #[tokio::test]
+    async fn test_all_migrations_apply_to_new_database() {
+        let pool = memory_pool().await;
+        assert_eq!(run(&pool).await.unwrap(), latest_version());
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version());
+        assert!(table_exists(&pool, "players").await.unwrap());
+        assert!(column_exists(&pool, "players", "password_hash").await.unwrap());
+
+        let applied = sqlx::query("SELECT COUNT(*) FROM schema_version").fetch_one(&pool).await.unwrap();
+        assert_eq!(applied.get::<i64, _>(0), MIGRATIONS.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_running_twice_changes_nothing() {
+        let pool = memory_pool().await;
+        run(&pool).await.unwrap();
+        assert_eq!(run(&pool).await.unwrap(), latest_version());
+        let applied = sqlx::query("SELECT COUNT(*) FROM schema_version").fetch_one(&pool).await.unwrap();
+        assert_eq!(applied.get::<i64, _>(0), MIGRATIONS.len() as i64);
+    }
+
+    /// A database made by the old inline schema keeps its players and gets the new columns
+    #[tokio::test]
+    async fn test_adopts_database_from_before_migrations() {
+        let pool = memory_pool().await;
+        pool.execute(
+            "CREATE TABLE players (
+                id TEXT PRIMARY KEY,
+                name TEXT UNIQUE,
+                games_played INTEGER DEFAULT 0,
+                games_won INTEGER DEFAULT 0,
+                wallet INTEGER DEFAULT 1000,
+                logged_in BOOLEAN DEFAULT FALSE
+            );
+            INSERT INTO players (id, name, wallet) VALUES ('1', 'alice', 750);",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(run(&pool).await.unwrap(), latest_version());
+        assert!(column_exists(&pool, "players", "password_hash").await.unwrap());
+        let row = sqlx::query("SELECT wallet FROM players WHERE name = 'alice'").fetch_one(&pool).await.unwrap();
+        assert_eq!(row.get::<i32, _>(0), 750);
+        // the chips the player had open their ledger
+        let row = sqlx::query("SELECT amount, reason FROM chip_transactions WHERE player_name = 'alice'").fetch_one(&pool).await.unwrap();
+        assert_eq!((row.get::<i32, _>(0), row.get::<String, _>(1)), (750, "opening_balance".to_string()));
+    }
+
+    /// A database that already got the password column from the old startup check is not altered again
+    #[tokio::test]
+    async fn test_adopts_database_with_password_column() {
+        let pool = memory_pool().await;
+        pool.execute(
+            "CREATE TABLE players (
+                id TEXT PRIMARY KEY,
+                name TEXT UNIQUE,
+                games_played INTEGER DEFAULT 0,
+                games_won INTEGER DEFAULT 0,
+                wallet INTEGER DEFAULT 1000,
+                logged_in BOOLEAN DEFAULT FALSE,
+                password_hash TEXT
+            )",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(legacy_version(&pool).await.unwrap(), 2);
+        assert_eq!(run(&pool).await.unwrap(), latest_version());
+    }
+}
@@ -1,18 +1,61 @@
 //! Database module to handle player registration, login, and player statistics using SQLite.
 //! 
 //! This module provides functionality for player management, including:
-//! - Registering new players with a unique ID and initial wallet balance.
-//! - Logging in players by their username.
+//! - Registering new players with a unique ID, a password and initial wallet balance.
+//! - Logging in players by their username and password, and changing passwords.
 //! - Retrieving player statistics (games played, games won, wallet balance).
 //! - Updating player statistics after a game.
+//! - Moving chips between a player's wallet and their stack at a cash table.
+//! - Recording every change to a balance of chips in the ledger of the `ledger` submodule.
+//! - Storing the history of every finished hand and reading back a player's recent hands.
+//!
+//! The chips a player brings to a cash table are kept in `table_stacks` while they play, so the wallet only holds
+//! what is not in play. Buying in, cashing out and settling a hand are each one transaction, and stacks left
+//! behind when the server stops are returned to the wallets at the next start.
+//!
+//! Passwords are never stored: the `password_hash` column holds a salted Argon2 hash in PHC string format.
+//! Accounts created before passwords were introduced have no hash and stay locked: nobody can log in to them
+//! or change their password until the operator sets one with `cargo run -- reset-password <username>`.
 //! 
 //! It uses `sqlx` for asynchronous database interactions and `uuid` for unique player IDs.
+//! The schema itself is defined by the numbered migrations in the `migrations` submodule.
 
+use crate::history::{ActionRecord, HandRecord, PotRecord, SeatRecord};
 use crate::player::Player;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use ledger::Account;
 use sqlx::{SqlitePool, Row};
 use uuid::Uuid;
 use std::sync::Arc;
 
+pub mod ledger;
+pub mod migrations;
+
+/// The chips a new player starts with in their wallet.
+pub const SIGN_UP_BONUS: i32 = 1000;
+
+/// Hashes a password with Argon2 and a random salt.
+/// 
+/// # Returns
+/// 
+/// The hash in PHC string format, which includes the salt and the Argon2 parameters.
+fn hash_password(password: &str) -> String {
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>()).expect("16 bytes is a valid salt length");
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("hashing with the default Argon2 parameters cannot fail")
+        .to_string()
+}
+
+/// Checks a password against a hash made by `hash_password`. A malformed hash never matches.
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
+}
+
 /// Represents a player's statistics, including games played, games won, and wallet balance.
 #[derive(Debug)]
 pub struct PlayerStats {
@@ -36,39 +79,44 @@ impl Database {
         }
     }
 
-    /// Registers a new player with a unique ID and an initial wallet balance of 1000.
+    /// Registers a new player with a unique ID and the sign-up bonus in their wallet.
     /// 
     /// # Arguments
     /// * `name` - The player's username (must be unique).
+    /// * `password` - The player's password, stored as a salted hash.
     /// 
     /// # Returns
     /// * `Ok(String)` - The generated player ID if registration succeeds.
     /// * `Err(sqlx::Error)` - If the insertion fails (e.g., duplicate username).
     
-    pub async fn register_player(&self, name: &str) -> Result<String, sqlx::Error> {
+    pub async fn register_player(&self, name: &str, password: &str) -> Result<String, sqlx::Error> {
         let id = Uuid::new_v4().to_string();
-        let wallet: u32 = 1000;
-        sqlx::query("INSERT INTO players (id, name, wallet, logged_in) VALUES (?1, ?2, ?3, TRUE)")
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO players (id, name, wallet, logged_in, password_hash) VALUES (?1, ?2, 0, TRUE, ?3)")
             .bind(&id)
             .bind(name)
-            .bind(&wallet)
-            .execute(&*self.pool)
+            .bind(hash_password(password))
+            .execute(&mut *tx)
             .await?;
+        ledger::post(&mut tx, name, Account::Wallet, SIGN_UP_BONUS, ledger::BONUS, None).await?;
+        tx.commit().await?;
         Ok(id)
     }
 
-    /// Logs in a player by checking if their username exists in the database.
+    /// Logs in a player by checking their username and password.
+    /// An account from before passwords existed has none and stays locked until `reset_password` sets one.
     /// 
     /// # Arguments
-    /// * `name` - The player's username.
+    /// * `username` - The player's username.
+    /// * `password` - The password the player entered.
     /// 
     /// # Returns
-    /// * `Ok(Some(String))` - The player ID if the user exists.
-    /// * `Ok(None)` - If no such user exists.
+    /// * `Ok(Some(Uuid))` - The player ID if the user exists and the password matches.
+    /// * `Ok(None)` - If no such user exists, the account is locked, the password is wrong or they are already logged in.
     /// * `Err(sqlx::Error)` - If a database error occurs.
-    pub async fn login_player(&self, username: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    pub async fn login_player(&self, username: &str, password: &str) -> Result<Option<Uuid>, sqlx::Error> {
         // First check if player exists and is not already logged in
-        let row = sqlx::query("SELECT id, logged_in FROM players WHERE name = ?1")
+        let row = sqlx::query("SELECT id, logged_in, password_hash FROM players WHERE name = ?1")
             .bind(username)
             .fetch_optional(&*self.pool)
             .await?;
@@ -76,10 +124,14 @@ impl Database {
         match row {
             Some(row) => {
                 let logged_in: bool = row.try_get("logged_in")?;
+                let password_hash: Option<String> = row.try_get("password_hash")?;
                 
                 if logged_in {
                     // Player is already logged in
                     Ok(None) // Return None to indicate login failure
+                } else if !password_hash.is_some_and(|hash| verify_password(password, &hash)) {
+                    // wrong password, or a legacy account nobody has set a password for yet
+                    Ok(None)
                 } else {
                     // Update logged_in status to true
                     sqlx::query("UPDATE players SET logged_in = TRUE WHERE name = ?1")
@@ -97,7 +149,48 @@ impl Database {
         }
     }
 
-    /// Add a function to logout a player
+    /// Changes a player's password after checking their current one.
+    /// 
+    /// # Arguments
+    /// * `username` - The player's username.
+    /// * `old_password` - The player's current password.
+    /// * `new_password` - The password to use from now on.
+    /// 
+    /// # Returns
+    /// * `Ok(true)` - If the password was changed.
+    /// * `Ok(false)` - If the user does not exist, has no password yet or `old_password` is wrong.
+    /// * `Err(sqlx::Error)` - If a database error occurs.
+    pub async fn change_password(&self, username: &str, old_password: &str, new_password: &str) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query("SELECT password_hash FROM players WHERE name = ?1")
+            .bind(username)
+            .fetch_optional(&*self.pool)
+            .await?;
+        let password_hash: Option<String> = match row {
+            Some(row) => row.try_get("password_hash")?,
+            None => return Ok(false),
+        };
+        if !password_hash.is_some_and(|hash| verify_password(old_password, &hash)) {
+            return Ok(false);
+        }
+        self.reset_password(username, new_password).await
+    }
+
+    /// Sets a player's password without checking the current one, which unlocks a legacy account.
+    /// Only the server operator can do this, with `cargo run -- reset-password <username>`.
+    /// 
+    /// # Returns
+    /// * `Ok(true)` - If the password was set.
+    /// * `Ok(false)` - If the user does not exist.
+    /// * `Err(sqlx::Error)` - If a database error occurs.
+    pub async fn reset_password(&self, username: &str, new_password: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE players SET password_hash = ?2 WHERE name = ?1")
+            .bind(username)
+            .bind(hash_password(new_password))
+            .execute(&*self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Add a function to logout a player
     pub async fn logout_player(&self, username: &str) -> Result<(), sqlx::Error> {
         sqlx::query("UPDATE players SET logged_in = FALSE WHERE name = ?1")
@@ -108,6 +201,17 @@ impl Database {
         Ok(())
     }
 
+    /// Marks a player as logged in without checking their password,
+    /// for a player who already proved who they are with a session token.
+    pub async fn mark_logged_in(&self, username: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE players SET logged_in = TRUE WHERE name = ?1")
+            .bind(username)
+            .execute(&*self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     /// Resets the logged_in status of all users to FALSE
     /// Called when the server starts to ensure clean state
     pub async fn reset_all_login_statuses(&self) -> Result<(), sqlx::Error> {
@@ -157,7 +261,50 @@ impl Database {
         Ok(row.get(0))
     }
 
-    /// Updates a player's statistics (games played, games won, wallet balance) in the database.
+    /// Moves chips from a player's wallet to their stack at a cash table.
+    /// 
+    /// # Arguments
+    /// * `username` - The player's username.
+    /// * `lobby_name` - The table they buy in at.
+    /// * `amount` - The chips to buy, added to any stack they already have there.
+    /// 
+    /// # Returns
+    /// * `Ok(true)` - If the chips were moved.
+    /// * `Ok(false)` - If there is no such player or the wallet does not hold `amount`. Nothing is changed then.
+    /// * `Err(sqlx::Error)` - If a database error occurs. Nothing is changed in that case.
+    pub async fn buy_in(&self, username: &str, lobby_name: &str, amount: i32) -> Result<bool, sqlx::Error> {
+        self.transfer(username, Account::Wallet, Account::Table(lobby_name), amount, ledger::BUY_IN).await
+    }
+
+    /// Moves a player's whole stack at a cash table back to their wallet.
+    /// 
+    /// # Returns
+    /// * `Ok(i32)` - The chips cashed out, 0 if the player had no stack at the table.
+    /// * `Err(sqlx::Error)` - If a database error occurs. Nothing is changed in that case.
+    pub async fn cash_out(&self, username: &str, lobby_name: &str) -> Result<i32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let stack = cash_out_stack(&mut tx, username, lobby_name).await?;
+        tx.commit().await?;
+        Ok(stack)
+    }
+
+    /// Returns every stack left at a cash table to its player's wallet, for the tables of a server that stopped.
+    /// 
+    /// # Returns
+    /// * `Ok(u64)` - The number of stacks cashed out.
+    /// * `Err(sqlx::Error)` - If a database error occurs. Nothing is changed in that case.
+    pub async fn cash_out_all(&self) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let stacks = sqlx::query("SELECT player_name, lobby_name FROM table_stacks").fetch_all(&mut *tx).await?;
+        for row in stacks.iter() {
+            let (player_name, lobby_name): (String, String) = (row.get(0), row.get(1));
+            cash_out_stack(&mut tx, &player_name, &lobby_name).await?;
+        }
+        tx.commit().await?;
+        Ok(stacks.len() as u64)
+    }
+
+    /// Updates a player's statistics (games played, games won) in the database. The wallet only changes through the ledger.
     /// 
     /// # Arguments
     /// * `player` - A reference to the `Player` struct containing updated stats.
@@ -166,17 +313,249 @@ impl Database {
     /// * `Ok(())` - If the update is successful.
     /// * `Err(sqlx::Error)` - If a database error occurs.
     pub async fn update_player_stats(&self, player: &Player) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "UPDATE players SET games_played = games_played + ?1, games_won = games_won + ?2, wallet = ?3 WHERE name = ?4",
-        )
+        sqlx::query("UPDATE players SET games_played = games_played + ?1, games_won = games_won + ?2 WHERE name = ?3")
         .bind(player.games_played)
         .bind(player.games_won)
-        .bind(player.wallet)
         .bind(&player.name)
         .execute(&*self.pool)
         .await?;
         Ok(())
     }
+
+    /// Stores the history of a finished hand: the hand itself, every seat and every action.
+    ///
+    /// # Arguments
+    /// * `hand` - The record of the hand, built with `HandRecord::from_engine`.
+    ///
+    /// # Returns
+    /// * `Ok(i64)` - The ID of the stored hand.
+    /// * `Err(sqlx::Error)` - If a database error occurs. Nothing is stored in that case.
+    pub async fn save_hand(&self, hand: &HandRecord) -> Result<i64, sqlx::Error> {
+        self.record_hand(hand, false).await
+    }
+
+    /// Stores the history of a hand played at a cash table and settles it: what every player put in and won is
+    /// posted to their stack at the table and to the ledger with the hand, in the same transaction as the history.
+    ///
+    /// # Returns
+    /// * `Ok(i64)` - The ID of the stored hand.
+    /// * `Err(sqlx::Error)` - If a database error occurs. Nothing is stored or settled in that case.
+    pub async fn save_cash_hand(&self, hand: &HandRecord) -> Result<i64, sqlx::Error> {
+        self.record_hand(hand, true).await
+    }
+
+    async fn record_hand(&self, hand: &HandRecord, cash_table: bool) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let hand_id = sqlx::query(
+            "INSERT INTO hands (lobby_name, game_type, betting_structure, small_blind, big_blind, ante, bring_in, button, board, pots)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )
+        .bind(&hand.lobby_name)
+        .bind(hand.game_type)
+        .bind(hand.betting_structure)
+        .bind(hand.small_blind)
+        .bind(hand.big_blind)
+        .bind(hand.ante)
+        .bind(hand.bring_in)
+        .bind(hand.button)
+        .bind(to_json(&hand.board))
+        .bind(to_json(&hand.pots))
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        for seat in hand.seats.iter() {
+            sqlx::query(
+                "INSERT INTO hand_players (hand_id, seat, player_name, starting_stack, final_stack, starting_hand, hole_cards, dealt_in, folded, won)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )
+            .bind(hand_id)
+            .bind(seat.seat)
+            .bind(&seat.player)
+            .bind(seat.starting_stack)
+            .bind(seat.final_stack)
+            .bind(to_json(&seat.starting_hand))
+            .bind(to_json(&seat.hole_cards))
+            .bind(seat.dealt_in)
+            .bind(seat.folded)
+            .bind(seat.won)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for action in hand.actions.iter() {
+            sqlx::query(
+                "INSERT INTO hand_actions (hand_id, seq, player_name, street, phase, action, amount, cards) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )
+            .bind(hand_id)
+            .bind(action.seq)
+            .bind(&action.player)
+            .bind(action.street)
+            .bind(action.phase)
+            .bind(&action.action)
+            .bind(action.amount)
+            .bind(to_json(&action.cards))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if cash_table {
+            for (player, reason, amount) in ledger::hand_entries(hand) {
+                let table = Account::Table(&hand.lobby_name);
+                if !ledger::post(&mut tx, &player, table, amount, reason, Some(hand_id)).await? {
+                    eprintln!("{} has no stack at {} to settle {} chips of hand {} against", player, hand.lobby_name, amount, hand_id);
+                }
+            }
+        }
+        tx.commit().await?;
+        Ok(hand_id)
+    }
+
+    /// Reads back a stored hand.
+    ///
+    /// # Arguments
+    /// * `hand_id` - The ID returned by `save_hand`.
+    ///
+    /// # Returns
+    /// * `Ok(Some(HandRecord))` - The hand, with its seats in seat order and its actions in the order they were taken.
+    /// * `Ok(None)` - If there is no such hand.
+    /// * `Err(sqlx::Error)` - If a database error occurs.
+    pub async fn get_hand(&self, hand_id: i64) -> Result<Option<HandRecord>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT lobby_name, game_type, betting_structure, small_blind, big_blind, ante, bring_in, button, board, pots, played_at
+             FROM hands WHERE id = ?1",
+        )
+        .bind(hand_id)
+        .fetch_optional(&*self.pool)
+        .await?;
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let board: String = row.try_get("board")?;
+        let pots: String = row.try_get("pots")?;
+
+        let seats = sqlx::query(
+            "SELECT seat, player_name, starting_stack, final_stack, starting_hand, hole_cards, dealt_in, folded, won
+             FROM hand_players WHERE hand_id = ?1 ORDER BY seat",
+        )
+        .bind(hand_id)
+        .fetch_all(&*self.pool)
+        .await?
+        .iter()
+        .map(|seat| -> Result<SeatRecord, sqlx::Error> {
+            Ok(SeatRecord {
+                seat: seat.try_get("seat")?,
+                player: seat.try_get("player_name")?,
+                starting_stack: seat.try_get("starting_stack")?,
+                final_stack: seat.try_get("final_stack")?,
+                starting_hand: from_json(&seat.try_get::<String, _>("starting_hand")?)?,
+                hole_cards: from_json(&seat.try_get::<String, _>("hole_cards")?)?,
+                dealt_in: seat.try_get("dealt_in")?,
+                folded: seat.try_get("folded")?,
+                won: seat.try_get("won")?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let actions = sqlx::query(
+            "SELECT seq, player_name, street, phase, action, amount, cards FROM hand_actions WHERE hand_id = ?1 ORDER BY seq",
+        )
+        .bind(hand_id)
+        .fetch_all(&*self.pool)
+        .await?
+        .iter()
+        .map(|action| -> Result<ActionRecord, sqlx::Error> {
+            Ok(ActionRecord {
+                seq: action.try_get("seq")?,
+                player: action.try_get("player_name")?,
+                street: action.try_get("street")?,
+                phase: action.try_get("phase")?,
+                action: action.try_get("action")?,
+                amount: action.try_get("amount")?,
+                cards: from_json(&action.try_get::<String, _>("cards")?)?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(HandRecord {
+            id: Some(hand_id),
+            played_at: row.try_get("played_at")?,
+            lobby_name: row.try_get("lobby_name")?,
+            game_type: row.try_get("game_type")?,
+            betting_structure: row.try_get("betting_structure")?,
+            small_blind: row.try_get("small_blind")?,
+            big_blind: row.try_get("big_blind")?,
+            ante: row.try_get("ante")?,
+            bring_in: row.try_get("bring_in")?,
+            button: row.try_get("button")?,
+            seats,
+            board: from_json(&board)?,
+            actions,
+            pots: from_json::<Vec<PotRecord>>(&pots)?,
+        }))
+    }
+
+    /// Retrieves the most recent hands a player was seated in, newest first.
+    ///
+    /// # Arguments
+    /// * `username` - The player's username.
+    /// * `limit` - The most hands to return.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<HandRecord>)` - The hands, empty if the player has not played any.
+    /// * `Err(sqlx::Error)` - If a database error occurs.
+    pub async fn recent_hands(&self, username: &str, limit: i64) -> Result<Vec<HandRecord>, sqlx::Error> {
+        let hand_ids: Vec<i64> = sqlx::query("SELECT hand_id FROM hand_players WHERE player_name = ?1 ORDER BY hand_id DESC LIMIT ?2")
+            .bind(username)
+            .bind(limit)
+            .fetch_all(&*self.pool)
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        let mut hands = Vec::new();
+        for hand_id in hand_ids {
+            if let Some(hand) = self.get_hand(hand_id).await? {
+                hands.push(hand);
+            }
+        }
+        Ok(hands)
+    }
+}
+
+/// Moves a player's stack at a cash table to their wallet through the ledger and gives up their place at the table.
+///
+/// # Returns
+/// The chips cashed out, 0 if the player had no stack at the table.
+async fn cash_out_stack(conn: &mut sqlx::SqliteConnection, player_name: &str, lobby_name: &str) -> Result<i32, sqlx::Error> {
+    let stack: i32 = sqlx::query("SELECT stack FROM table_stacks WHERE player_name = ?1 AND lobby_name = ?2")
+        .bind(player_name)
+        .bind(lobby_name)
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|row| row.get(0))
+        .unwrap_or(0);
+    if stack > 0 {
+        ledger::post(&mut *conn, player_name, Account::Table(lobby_name), -stack, ledger::CASH_OUT, None).await?;
+        ledger::post(&mut *conn, player_name, Account::Wallet, stack, ledger::CASH_OUT, None).await?;
+    }
+    sqlx::query("DELETE FROM table_stacks WHERE player_name = ?1 AND lobby_name = ?2")
+        .bind(player_name)
+        .bind(lobby_name)
+        .execute(&mut *conn)
+        .await?;
+    Ok(stack)
+}
+
+/// Cards and pots are stored as JSON text.
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("hand history values always serialize")
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(text: &str) -> Result<T, sqlx::Error> {
+    serde_json::from_str(text).map_err(|e| sqlx::Error::Decode(Box::new(e)))
 }
 
 #[cfg(test)]
@@ -184,21 +563,9 @@ mod tests {
     use super::*;
 
     /// Sets up an in-memory SQLite database for testing.
-    async fn setup_database() -> Database {
+    pub(super) async fn setup_database() -> Database {
         let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
-        sqlx::query(
-            "CREATE TABLE players (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL UNIQUE,
-                games_played INTEGER DEFAULT 0,
-                games_won INTEGER DEFAULT 0,
-                wallet INTEGER DEFAULT 1000,
-                logged_in BOOLEAN DEFAULT FALSE
-            )"
-        )
-        .execute(&pool)
-        .await
-        .unwrap();
+        migrations::run(&pool).await.unwrap();
 
         Database::new(pool)
     }
@@ -208,7 +575,7 @@ mod tests {
     async fn test_statistics_across_multiple_instantiations() {
         let db = setup_database().await;
         let player_name = "test_player";
-        db.register_player(player_name).await.unwrap();
+        db.register_player(player_name, "secret").await.unwrap();
         
         for _ in 0..100 {
             sqlx::query("UPDATE players SET games_played = games_played + 1, games_won = games_won + 1 WHERE name = ?1")
@@ -232,7 +599,7 @@ mod tests {
 
         // Register a player
         let player_name = "test_player";
-        db.register_player(player_name).await.unwrap();
+        db.register_player(player_name, "secret").await.unwrap();
 
         // Check player stats
         let stats = db.player_stats(player_name).await.unwrap();
@@ -248,14 +615,173 @@ mod tests {
 
         // Register a player
         let player_name = "unique_player";
-        db.register_player(player_name).await.unwrap();
+        db.register_player(player_name, "secret").await.unwrap();
 
         // Attempt to register another player with the same name
-        let result = db.register_player(player_name).await;
+        let result = db.register_player(player_name, "secret").await;
 
         // Check that the second registration attempt fails
         assert!(result.is_err());
     }
+
+    /// Only the right password logs a player in, and the hash is not the password itself
+    #[tokio::test]
+    async fn test_login_checks_password() {
+        let db = setup_database().await;
+        db.register_player("alice", "secret").await.unwrap();
+        db.logout_player("alice").await.unwrap();
+
+        let stored: String = sqlx::query("SELECT password_hash FROM players WHERE name = 'alice'")
+            .fetch_one(&*db.pool)
+            .await
+            .unwrap()
+            .get(0);
+        assert!(stored.starts_with("$argon2"));
+        assert!(!stored.contains("secret"));
+
+        assert!(db.login_player("alice", "wrong").await.unwrap().is_none());
+        assert!(db.login_player("alice", "secret").await.unwrap().is_some());
+        // already logged in
+        assert!(db.login_player("alice", "secret").await.unwrap().is_none());
+        assert!(db.login_player("nobody", "secret").await.unwrap().is_none());
+    }
+
+    /// An account from before passwords existed is locked until the operator sets a password
+    #[tokio::test]
+    async fn test_account_without_password_is_locked() {
+        let db = setup_database().await;
+        sqlx::query("INSERT INTO players (id, name) VALUES ('1', 'legacy')")
+            .execute(&*db.pool)
+            .await
+            .unwrap();
+
+        assert!(db.login_player("legacy", "anything").await.unwrap().is_none());
+        assert!(db.login_player("legacy", "").await.unwrap().is_none());
+        assert!(!db.change_password("legacy", "anything", "mine").await.unwrap());
+        assert!(db.login_player("legacy", "mine").await.unwrap().is_none());
+
+        assert!(db.reset_password("legacy", "reset").await.unwrap());
+        assert!(db.login_player("legacy", "anything").await.unwrap().is_none());
+        assert!(db.login_player("legacy", "reset").await.unwrap().is_some());
+        assert!(!db.reset_password("nobody", "reset").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_wallet_transfers() {
+        let db = setup_database().await;
+        db.register_player("alice", "secret").await.unwrap();
+        let wallet = db.get_player_wallet("alice").await.unwrap();
+        let pool = Account::Tournament("event");
+        assert!(db.transfer("alice", Account::Wallet, pool, wallet, ledger::TOURNAMENT_BUY_IN).await.unwrap());
+        assert!(!db.transfer("alice", Account::Wallet, pool, 1, ledger::TOURNAMENT_BUY_IN).await.unwrap());
+        assert!(db.transfer("alice", pool, Account::Wallet, 250, ledger::PRIZE).await.unwrap());
+        assert_eq!(db.get_player_wallet("alice").await.unwrap(), 250);
+        assert!(!db.transfer("nobody", pool, Account::Wallet, 10, ledger::PRIZE).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_buy_in_and_cash_out() {
+        let db = setup_database().await;
+        db.register_player("alice", "secret").await.unwrap();
+        assert!(db.buy_in("alice", "table", 400).await.unwrap());
+        assert!(!db.buy_in("alice", "table", 601).await.unwrap());
+        assert!(db.buy_in("alice", "table", 100).await.unwrap());
+        assert_eq!(db.get_player_wallet("alice").await.unwrap(), 500);
+        assert_eq!(db.cash_out("alice", "table").await.unwrap(), 500);
+        assert_eq!(db.get_player_wallet("alice").await.unwrap(), 1000);
+        // nothing left to cash out
+        assert_eq!(db.cash_out("alice", "table").await.unwrap(), 0);
+        assert_eq!(db.get_player_wallet("alice").await.unwrap(), 1000);
+    }
+
+    /// A cash hand moves chips between the stacks at the table and leaves the wallets alone until cash-out
+    #[tokio::test]
+    async fn test_cash_hand_settles_the_table_stacks() {
+        let db = setup_database().await;
+        let hand = played_hand(&["alice", "bob"]);
+        for seat in hand.seats.iter() {
+            db.register_player(&seat.player, "secret").await.unwrap();
+            db.buy_in(&seat.player, &hand.lobby_name, seat.starting_stack).await.unwrap();
+        }
+        let hand_id = db.save_cash_hand(&hand).await.unwrap();
+        assert!(db.reconcile().await.unwrap().balanced());
+
+        for seat in hand.seats.iter() {
+            assert_eq!(db.cash_out(&seat.player, &hand.lobby_name).await.unwrap(), seat.final_stack);
+        }
+        let wallets = db.get_player_wallet("alice").await.unwrap() + db.get_player_wallet("bob").await.unwrap();
+        assert_eq!(wallets, 2 * SIGN_UP_BONUS);
+        let rows = sqlx::query("SELECT COUNT(*) FROM chip_transactions WHERE hand_id = ?1").bind(hand_id).fetch_one(&*db.pool).await.unwrap();
+        assert_eq!(rows.get::<i64, _>(0), ledger::hand_entries(&hand).len() as i64);
+        assert!(db.reconcile().await.unwrap().balanced());
+    }
+
+    #[tokio::test]
+    async fn test_cash_out_all() {
+        let db = setup_database().await;
+        db.register_player("alice", "secret").await.unwrap();
+        db.register_player("bob", "secret").await.unwrap();
+        db.buy_in("alice", "one", 200).await.unwrap();
+        db.buy_in("alice", "two", 300).await.unwrap();
+        db.buy_in("bob", "one", 1000).await.unwrap();
+        assert_eq!(db.cash_out_all().await.unwrap(), 3);
+        assert_eq!(db.get_player_wallet("alice").await.unwrap(), 1000);
+        assert_eq!(db.get_player_wallet("bob").await.unwrap(), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_change_password() {
+        let db = setup_database().await;
+        db.register_player("alice", "old").await.unwrap();
+        db.logout_player("alice").await.unwrap();
+
+        assert!(!db.change_password("alice", "wrong", "new").await.unwrap());
+        assert!(db.change_password("alice", "old", "new").await.unwrap());
+        assert!(db.login_player("alice", "old").await.unwrap().is_none());
+        assert!(db.login_player("alice", "new").await.unwrap().is_some());
+    }
+
+    /// Plays a hand of 5 Card Draw where everyone checks it down.
+    pub(super) fn played_hand(names: &[&str]) -> HandRecord {
+        use crate::engine::{Action, Engine};
+        use crate::lobby::{DRAW, FIVE_CARD_DRAW};
+
+        let seats = names.iter().map(|name| (name.to_string(), 100)).collect();
+        let mut engine = Engine::new(FIVE_CARD_DRAW, seats, crate::deck::Deck::new(), 0);
+        engine.start_hand();
+        while !engine.finished {
+            let name = engine.current_player_name().unwrap().to_string();
+            let action = if engine.phase == DRAW { Action::DrawCards { indices: vec![0] } } else { Action::Check };
+            engine.apply(&name, action).unwrap();
+        }
+        HandRecord::from_engine("test lobby", &engine)
+    }
+
+    #[tokio::test]
+    async fn test_hand_history_round_trip() {
+        let db = setup_database().await;
+        let hand = played_hand(&["alice", "bob"]);
+        let hand_id = db.save_hand(&hand).await.unwrap();
+
+        let stored = db.get_hand(hand_id).await.unwrap().unwrap();
+        assert_eq!(stored.id, Some(hand_id));
+        assert!(stored.played_at.is_some());
+        assert_eq!(HandRecord { id: None, played_at: None, ..stored }, hand);
+        assert!(db.get_hand(hand_id + 1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recent_hands() {
+        let db = setup_database().await;
+        let first = db.save_hand(&played_hand(&["alice", "bob"])).await.unwrap();
+        let second = db.save_hand(&played_hand(&["bob", "carol"])).await.unwrap();
+        let third = db.save_hand(&played_hand(&["alice", "carol"])).await.unwrap();
+
+        let ids = |hands: Vec<HandRecord>| hands.iter().map(|h| h.id.unwrap()).collect::<Vec<_>>();
+        assert_eq!(ids(db.recent_hands("alice", 10).await.unwrap()), vec![third, first]);
+        assert_eq!(ids(db.recent_hands("bob", 1).await.unwrap()), vec![second]);
+        assert!(db.recent_hands("dave", 10).await.unwrap().is_empty());
+    }
 }
 
 
@@ -1,6 +1,9 @@
 //! A module for creating and managing a deck of cards
 //! 
 //! This module contains the `Deck` struct, which represents a deck of 52 playing cards. The deck can be shuffled, and cards can be dealt from the top of the deck.
+//! 
+//! Cards thrown away in a draw go to the discards. When the stub runs out the discards are shuffled into a new
+//! stub, the way a dealer does in a draw game, so a full table of Triple Draw can replace every card three times.
 use rand::seq::SliceRandom;
 use rand::rng;
 
@@ -8,28 +11,49 @@ use rand::rng;
 pub struct Deck {
     next_card_index: i32,
     cards: Vec<i32>,
+    discards: Vec<i32>, // thrown away since the last shuffle, never a card still in someone's hand
 }
 
 impl Deck {
     /// Create a new 52-card deck
     pub fn new() -> Deck{
-        Deck{next_card_index: 0, cards: (0..52).collect()}        
+        Deck{next_card_index: 0, cards: (0..52).collect(), discards: Vec::new()}
     }
 
-    /// Shuffle the deck
+    /// Shuffle the deck, with the discards back in it
     pub fn shuffle(&mut self){
+        self.cards.append(&mut self.discards);
         self.cards.shuffle(&mut rng());
         self.next_card_index = 0;
     }
 
-    /// Deal one card from the top of the deck
-    /// pub fn deal_one_card(&mut self) -> Option<Card> {
-    ///     self.cards.pop()
-    /// }
-    pub fn deal(&mut self) -> i32{
+    /// Deal one card from the top of the deck, shuffling the discards into a new stub when it runs out.
+    /// 
+    /// # Returns
+    /// 
+    /// The card, or `None` if every card is in someone's hand.
+    pub fn deal(&mut self) -> Option<i32> {
+        if self.next_card_index as usize >= self.cards.len() {
+            if self.discards.is_empty() {
+                return None;
+            }
+            self.cards = std::mem::take(&mut self.discards);
+            self.cards.shuffle(&mut rng());
+            self.next_card_index = 0;
+        }
         let card = self.cards[self.next_card_index as usize];
         self.next_card_index += 1;
-        card
+        Some(card)
+    }
+
+    /// Puts cards thrown away in a draw on the discards.
+    pub fn discard(&mut self, cards: &[i32]) {
+        self.discards.extend_from_slice(cards);
+    }
+
+    /// How many cards can still be dealt, counting the discards.
+    pub fn cards_left(&self) -> usize {
+        self.cards.len() - self.next_card_index as usize + self.discards.len()
     }
 }
 
@@ -59,7 +83,7 @@ mod tests {
         let mut deck = Deck::new();
         let first_card = deck.cards[0];
         let dealt_card = deck.deal();
-        assert_eq!(first_card, dealt_card);
+        assert_eq!(Some(first_card), dealt_card);
         assert_eq!(deck.next_card_index, 1);
     }
 
@@ -68,9 +92,27 @@ mod tests {
         let mut deck = Deck::new();
         for i in 0..52 {
             let card = deck.deal();
-            assert_eq!(card, i);
+            assert_eq!(card, Some(i));
         }
         assert_eq!(deck.next_card_index, 52);
+        assert_eq!(deck.deal(), None);
+    }
+
+    #[test]
+    fn test_discards_are_dealt_when_the_stub_runs_out() {
+        let mut deck = Deck::new();
+        deck.shuffle();
+        let hand: Vec<i32> = (0..50).map(|_| deck.deal().unwrap()).collect();
+        let mut expected = deck.cards[50..].to_vec();
+        deck.discard(&hand[..3]);
+        assert_eq!(deck.cards_left(), 5);
+        let mut drawn: Vec<i32> = (0..5).map(|_| deck.deal().unwrap()).collect();
+        assert_eq!(deck.deal(), None);
+        // the last two of the stub, then the discards
+        drawn.sort();
+        expected.extend_from_slice(&hand[..3]);
+        expected.sort();
+        assert_eq!(drawn, expected);
     }
 }
 
@@ -0,0 +1,1783 @@
+//! A pure, synchronous poker engine.
+//!
+//! The engine owns everything about a hand in progress: the seats and their stacks, the deck, the cards dealt,
+//! the chips each player has put in, whose turn it is and which phase the hand is in. It never touches sockets,
+//! locks, timers or the database. `Engine::apply` takes one action from one player, validates it and returns the
+//! `Event`s it caused, so whole hands can be played out in unit tests without a server.
+//!
+//! The game state machines in the `games` module are adapters around the engine: they turn client messages into
+//! `Action`s and the returned `Event`s into WebSocket messages.
+//!
+//! Phases reuse the lobby game state constants so clients keep seeing the same `gameState` values.
+use crate::deck::Deck;
+use crate::games::{
+    compare_hands, get_ace_to_five_low, get_best_hand, get_best_omaha_hand, get_deuce_to_seven_low, get_low_hand,
+    get_omaha_low_hand, HandValue, LowHand,
+};
+use crate::hand_type::get_hand_type;
+use crate::lobby::{
+    default_betting_structure, is_draw, is_lowball, is_omaha, ANTE, BETTING_ROUND, BRING_IN, DEAL_CARDS,
+    DEUCE_TO_SEVEN_TRIPLE_DRAW, DRAW, FIRST_BETTING_ROUND, FIVE_CARD_DRAW, FIXED_LIMIT, OMAHA, OMAHA_HI_LO, POT_LIMIT,
+    RAZZ, SECOND_BETTING_ROUND, SEVEN_CARD_STUD, SEVEN_CARD_STUD_HI_LO, SHOWDOWN, SMALL_AND_BIG_BLIND, START_OF_ROUND,
+};
+use crate::player;
+use std::fmt;
+
+// Default forced bets, lobbies can set their own stakes. Only the draw games have an ante by default.
+pub const ANTE_AMOUNT: i32 = 10;
+pub const BRING_IN_AMOUNT: i32 = 15;
+pub const SMALL_BLIND: i32 = 5;
+pub const BIG_BLIND: i32 = 10;
+
+/// Bets and raises allowed in one betting round of a fixed-limit game, the bet and three raises.
+pub const MAX_BETS_PER_ROUND: i32 = 4;
+
+/// Offset added to a 7 Card Stud card dealt face down.
+pub const FACE_DOWN: i32 = 53;
+
+/// A move a player can make on their turn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Check,
+    Call,
+    /// `amount` is the number of chips put in, including the chips needed to call.
+    Raise { amount: i32 },
+    Fold,
+    AllIn,
+    /// Indices into the player's hand of the cards to exchange.
+    DrawCards { indices: Vec<usize> },
+}
+
+/// Something that happened at the table, in the order it happened.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    HandStarted { first_player: String },
+    PhaseChanged { phase: i32 },
+    AntePosted { player: String, amount: i32 },
+    BlindPosted { player: String, amount: i32, big_blind: bool },
+    BringInPosted { player: String, amount: i32 },
+    /// The player's whole hand after new cards were dealt to them. Only the owner may see all of it.
+    CardsDealt { player: String, cards: Vec<i32> },
+    CommunityCardsDealt { cards: Vec<i32> },
+    TurnChanged { player: String, phase: i32, call_amount: i32 },
+    PlayerActed { player: String, action: Action, amount: i32 },
+    CardsDrawn { player: String, count: usize },
+    Showdown { hands: Vec<ShowdownHand>, pots: Vec<Pot> },
+    HandFinished { winners: Vec<String> },
+}
+
+/// A hand still in play at the showdown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShowdownHand {
+    pub player: String,
+    pub cards: Vec<i32>, // empty for a hand everyone else folded to, which is not shown
+    pub rank: HandValue,
+    pub low: Option<LowHand>, // the qualifying low in hi/lo games
+}
+
+/// Why an action was refused. The engine is left unchanged when an action is refused.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EngineError {
+    UnknownPlayer,
+    NotYourTurn,
+    WrongPhase,
+    InvalidAction,
+    HandOver,
+    NotEnoughCards,
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            EngineError::UnknownPlayer => "You are not playing in this hand",
+            EngineError::NotYourTurn => "It is not your turn",
+            EngineError::WrongPhase => "That action is not allowed right now",
+            EngineError::InvalidAction => "Invalid action",
+            EngineError::HandOver => "The hand is over",
+            EngineError::NotEnoughCards => "There are not enough cards left to draw that many",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+/// A single pot at showdown: the main pot or one of the side pots created by all-in players.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pot {
+    pub amount: i32,
+    pub eligible: Vec<String>,
+    pub winners: Vec<String>,     // the best high hands
+    pub low_winners: Vec<String>, // the best low hands in hi/lo games, empty when no low qualified
+}
+
+/// A player's seat for the duration of one hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Seat {
+    pub name: String,
+    pub stack: i32,
+    pub starting_stack: i32, // stack when the hand was set up, before any forced bets
+    pub hand: Vec<i32>,
+    pub state: i32,        // player::IN_GAME, CHECKED, CALLED, RAISED, ALL_IN or FOLDED
+    pub current_bet: i32,  // chips put in during the current betting round
+    pub contributed: i32,  // chips put in during the whole hand, antes included
+    pub acted: bool,       // acted since the last bet or raise (or drew, in the drawing round)
+    pub dealt_in: bool,    // false when the seat could not cover the forced bets and sat the hand out
+}
+
+#[derive(Clone, Debug)]
+pub struct Engine {
+    pub game_type: i32,
+    pub seats: Vec<Seat>,
+    pub deck: Deck,
+    pub phase: i32,
+    pub community_cards: Vec<i32>,
+    pub current_max_bet: i32,
+    pub current_player: Option<usize>,
+    pub first_player: usize, // first player to act after the flop, the button sits just before them
+    // (small blind, big blind) seats chosen by the lobby, None for a dead small blind. Left unset,
+    // the blinds are the two players after the button, or the button and the other player heads-up.
+    pub blinds: Option<(Option<usize>, usize)>,
+    pub street: i32,         // betting rounds completed so far
+    pub ante: i32,
+    pub bring_in: i32,
+    pub small_blind: i32,
+    pub big_blind: i32,
+    pub betting_structure: i32, // NO_LIMIT, POT_LIMIT or FIXED_LIMIT
+    pub last_raise: i32,        // biggest raise of the betting round, the next raise has to be at least as big
+    pub bets: i32,              // bets and raises in the betting round, the big blind counts as the first
+    pub pots: Vec<Pot>,
+    pub finished: bool,
+    pub log: Vec<Event>, // every event of the hand so far, in order, for the hand history
+}
+
+/// Splits the chips put in during a hand into a main pot and side pots.
+///
+/// Each all-in player caps a pot at their own contribution; only players who matched that
+/// amount (or who are still active and not all-in) can win it. Chips above the highest all-in
+/// level form the last pot, contested by the remaining active players.
+///
+/// # Arguments
+/// * `entries` - `(name, contribution, state)` for every player seated in the hand, in seat order.
+///
+/// # Returns
+///
+/// The pots in order, starting with the main pot. Winners are left empty.
+pub fn build_pots(entries: &[(String, i32, i32)]) -> Vec<Pot> {
+    let mut levels: Vec<i32> = entries
+        .iter()
+        .filter(|(_, contribution, state)| *state == player::ALL_IN && *contribution > 0)
+        .map(|(_, contribution, _)| *contribution)
+        .collect();
+    levels.sort();
+    levels.dedup();
+
+    let mut pots: Vec<Pot> = Vec::new();
+    let mut previous_level = 0;
+    for level in levels {
+        let amount: i32 = entries
+            .iter()
+            .map(|(_, contribution, _)| (*contribution).min(level) - (*contribution).min(previous_level))
+            .sum();
+        let eligible: Vec<String> = entries
+            .iter()
+            .filter(|(_, contribution, state)| {
+                *state != player::FOLDED && (*state != player::ALL_IN || *contribution >= level)
+            })
+            .map(|(name, _, _)| name.clone())
+            .collect();
+        pots.push(Pot { amount, eligible, winners: Vec::new(), low_winners: Vec::new() });
+        previous_level = level;
+    }
+
+    // whatever was bet above the highest all-in goes to the players still in the hand
+    let remaining: i32 = entries
+        .iter()
+        .map(|(_, contribution, _)| contribution - (*contribution).min(previous_level))
+        .sum();
+    let eligible: Vec<String> = entries
+        .iter()
+        .filter(|(_, _, state)| *state != player::FOLDED && *state != player::ALL_IN)
+        .map(|(name, _, _)| name.clone())
+        .collect();
+    if remaining > 0 || pots.is_empty() {
+        if eligible.is_empty() && !pots.is_empty() {
+            // only folded players put chips in above the last all-in, they are dead money
+            pots.last_mut().unwrap().amount += remaining;
+        } else {
+            pots.push(Pot { amount: remaining, eligible, winners: Vec::new(), low_winners: Vec::new() });
+        }
+    }
+    pots
+}
+
+/// Shares a pot out between its winners.
+///
+/// A pot with low winners is halved between the high and the low hands, the odd chip going to the high half.
+/// Each half is split evenly between its winners, odd chips going one each to the first winners in seat order.
+/// A player with the best high and the best low scoops both halves; a low tied by two players is quartered.
+///
+/// # Arguments
+/// * `amount` - The chips in the pot.
+/// * `high_winners` - How many players tied for the best high hand.
+/// * `low_winners` - How many players tied for the best qualifying low, 0 if no low qualified.
+///
+/// # Returns
+///
+/// The chips won by each high winner and by each low winner, in the order the winners are listed.
+pub fn split_pot(amount: i32, high_winners: usize, low_winners: usize) -> (Vec<i32>, Vec<i32>) {
+    let low_half = if low_winners > 0 { amount / 2 } else { 0 };
+    (split_evenly(amount - low_half, high_winners), split_evenly(low_half, low_winners))
+}
+
+fn split_evenly(amount: i32, winners: usize) -> Vec<i32> {
+    if winners == 0 {
+        return Vec::new();
+    }
+    let share = amount / winners as i32;
+    let odd_chips = amount % winners as i32;
+    (0..winners as i32).map(|i| share + if i < odd_chips { 1 } else { 0 }).collect()
+}
+
+/// Awards every pot to the best eligible hand, splitting ties evenly.
+/// In hi/lo games the best eligible low takes half of each pot it qualifies for, as worked out by `split_pot`.
+///
+/// # Arguments
+/// * `seats` - The seats in the hand. Winners have their stacks credited.
+/// * `pots` - The pots built by `build_pots`. Their `winners` and `low_winners` are filled in.
+/// * `hands` - The evaluated hand of every player still in the hand, in seat order.
+/// * `lowball` - Whether the lowest `rank` is the best hand, as in Razz and 2-7 Triple Draw.
+fn award_pots(seats: &mut [Seat], pots: &mut [Pot], hands: &[ShowdownHand], lowball: bool) {
+    for pot in pots.iter_mut() {
+        let mut best_hand: Option<HandValue> = None;
+        for hand in hands.iter() {
+            if !pot.eligible.contains(&hand.player) {
+                continue;
+            }
+            let ordering = best_hand.map(|best| {
+                if lowball {
+                    compare_hands(&best, &hand.rank)
+                } else {
+                    compare_hands(&hand.rank, &best)
+                }
+            });
+            match ordering {
+                Some(std::cmp::Ordering::Less) => {}
+                Some(std::cmp::Ordering::Equal) => {
+                    pot.winners.push(hand.player.clone());
+                }
+                _ => {
+                    best_hand = Some(hand.rank);
+                    pot.winners.clear();
+                    pot.winners.push(hand.player.clone());
+                }
+            }
+        }
+        if pot.winners.is_empty() {
+            continue;
+        }
+
+        let best_low = hands
+            .iter()
+            .filter(|hand| pot.eligible.contains(&hand.player))
+            .filter_map(|hand| hand.low)
+            .min();
+        if let Some(best_low) = best_low {
+            pot.low_winners = hands
+                .iter()
+                .filter(|hand| pot.eligible.contains(&hand.player) && hand.low == Some(best_low))
+                .map(|hand| hand.player.clone())
+                .collect();
+        }
+
+        let (high_shares, low_shares) = split_pot(pot.amount, pot.winners.len(), pot.low_winners.len());
+        let shares = pot.winners.iter().zip(high_shares).chain(pot.low_winners.iter().zip(low_shares));
+        for (winner, share) in shares {
+            if let Some(seat) = seats.iter_mut().find(|s| &s.name == winner) {
+                seat.stack += share;
+            }
+        }
+    }
+}
+
+/// Orders up-cards for the 7 Card Stud bring-in: lowest rank first, Aces high,
+/// ties broken by suit from lowest to highest: clubs, diamonds, hearts, spades.
+/// In Razz the highest up-card pays instead, Aces low, with ties going to the highest suit,
+/// so the key is turned around and the lowest key still brings it in.
+fn bring_in_key(card: i32, razz: bool) -> (i32, i32) {
+    let suit = match card / 13 {
+        3 => 0, // clubs
+        1 => 1, // diamonds
+        0 => 2, // hearts
+        _ => 3, // spades
+    };
+    if razz {
+        return (-(card % 13), -suit);
+    }
+    let rank = if card % 13 == 0 { 13 } else { card % 13 };
+    (rank, suit)
+}
+
+impl Engine {
+    /// Seats the players for a new hand. Nothing is dealt until `start_hand` is called.
+    ///
+    /// # Arguments
+    /// * `game_type` - `FIVE_CARD_DRAW`, `SEVEN_CARD_STUD`, `TEXAS_HOLD_EM`, `OMAHA`, `OMAHA_HI_LO`,
+    ///   `SEVEN_CARD_STUD_HI_LO`, `RAZZ` or `DEUCE_TO_SEVEN_TRIPLE_DRAW`.
+    /// * `players` - `(name, stack)` for every player at the table, in seat order.
+    /// * `deck` - The deck to deal from, already shuffled by the caller.
+    /// * `first_player` - Index of the player who acts first this hand.
+    pub fn new(game_type: i32, players: Vec<(String, i32)>, deck: Deck, first_player: usize) -> Self {
+        let seats: Vec<Seat> = players
+            .into_iter()
+            .map(|(name, stack)| Seat {
+                name,
+                stack,
+                starting_stack: stack,
+                hand: Vec::new(),
+                state: player::IN_GAME,
+                current_bet: 0,
+                contributed: 0,
+                acted: false,
+                dealt_in: false,
+            })
+            .collect();
+        let first_player = if seats.is_empty() { 0 } else { first_player % seats.len() };
+        Engine {
+            game_type,
+            seats,
+            deck,
+            phase: START_OF_ROUND,
+            community_cards: Vec::new(),
+            current_max_bet: 0,
+            current_player: None,
+            first_player,
+            blinds: None,
+            street: 0,
+            ante: if is_draw(game_type) { ANTE_AMOUNT } else { 0 },
+            bring_in: BRING_IN_AMOUNT,
+            small_blind: SMALL_BLIND,
+            big_blind: BIG_BLIND,
+            betting_structure: default_betting_structure(game_type),
+            last_raise: 0,
+            bets: 0,
+            pots: Vec::new(),
+            finished: false,
+            log: Vec::new(),
+        }
+    }
+
+    /// Posts the forced bets, deals the first cards and opens the first betting round.
+    /// Players who cannot cover the ante (or have no chips at all) sit the hand out.
+    pub fn start_hand(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.seats.is_empty() {
+            self.finished = true;
+            return events;
+        }
+        events.push(Event::HandStarted { first_player: self.seats[self.first_player].name.clone() });
+
+        for i in 0..self.seats.len() {
+            let dealt_in = self.can_be_dealt_in(self.seats[i].stack);
+            let seat = &mut self.seats[i];
+            seat.dealt_in = dealt_in;
+            if !seat.dealt_in {
+                seat.state = player::FOLDED;
+            }
+        }
+
+        match self.game_type {
+            FIVE_CARD_DRAW | DEUCE_TO_SEVEN_TRIPLE_DRAW => {
+                self.post_antes(&mut events);
+                self.set_phase(DEAL_CARDS, &mut events);
+                self.deal_hole_cards(5, self.first_player, &mut events);
+                self.begin_betting_round(FIRST_BETTING_ROUND, self.first_player, &mut events);
+            }
+            SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ => {
+                self.post_antes(&mut events);
+                self.set_phase(DEAL_CARDS, &mut events);
+                let order = self.seat_order(self.first_player);
+                for &i in order.iter() {
+                    for face_down in [true, true, false] {
+                        let card = self.deal_card();
+                        self.seats[i].hand.push(if face_down { card + FACE_DOWN } else { card });
+                    }
+                    events.push(Event::CardsDealt { player: self.seats[i].name.clone(), cards: self.seats[i].hand.clone() });
+                }
+                self.post_bring_in(&mut events);
+            }
+            _ => {
+                self.post_antes(&mut events);
+                self.post_blinds(&mut events);
+            }
+        }
+        self.log.extend(events.iter().cloned());
+        events
+    }
+
+    /// Whether a player with the given stack can cover the forced bets and be dealt in.
+    /// Draw games need the whole ante, every other game at least one chip.
+    pub fn can_be_dealt_in(&self, stack: i32) -> bool {
+        let required = if is_draw(self.game_type) { self.ante.max(1) } else { 1 };
+        stack >= required
+    }
+
+    /// Applies one player's action.
+    ///
+    /// # Arguments
+    /// * `player_name` - The player taking the action.
+    /// * `action` - The action to take.
+    ///
+    /// # Returns
+    ///
+    /// The events caused by the action, ending with `HandFinished` if it ended the hand.
+    /// The engine is unchanged when an error is returned.
+    pub fn apply(&mut self, player_name: &str, action: Action) -> Result<Vec<Event>, EngineError> {
+        if self.finished {
+            return Err(EngineError::HandOver);
+        }
+        let i = self.seat_index(player_name).ok_or(EngineError::UnknownPlayer)?;
+        if self.current_player != Some(i) {
+            return Err(EngineError::NotYourTurn);
+        }
+
+        let mut events = Vec::new();
+        if self.phase == DRAW {
+            match action {
+                Action::DrawCards { indices } => self.draw_cards(i, indices, &mut events)?,
+                _ => return Err(EngineError::WrongPhase),
+            }
+            self.log.extend(events.iter().cloned());
+            return Ok(events);
+        }
+
+        let call_amount = self.current_max_bet - self.seats[i].current_bet;
+        let amount = match action {
+            Action::Check => {
+                if call_amount > 0 {
+                    return Err(EngineError::InvalidAction);
+                }
+                self.seats[i].state = player::CHECKED;
+                0
+            }
+            Action::Call => {
+                // calling with fewer chips than the bet puts the player all in for less
+                let posted = self.post(i, call_amount, true);
+                if self.seats[i].state != player::ALL_IN {
+                    self.seats[i].state = player::CALLED;
+                }
+                posted
+            }
+            Action::Raise { amount } => {
+                if amount <= call_amount || amount < self.min_raise() || amount > self.max_raise() {
+                    return Err(EngineError::InvalidAction);
+                }
+                let posted = self.post(i, amount, true);
+                if self.seats[i].state != player::ALL_IN {
+                    self.seats[i].state = player::RAISED;
+                }
+                self.raise_bet(i);
+                posted
+            }
+            Action::AllIn => {
+                let stack = self.seats[i].stack;
+                if stack == 0 || stack > self.max_raise() {
+                    return Err(EngineError::InvalidAction);
+                }
+                let posted = self.post(i, stack, true);
+                self.raise_bet(i);
+                posted
+            }
+            Action::Fold => {
+                self.seats[i].state = player::FOLDED;
+                0
+            }
+            Action::DrawCards { .. } => return Err(EngineError::WrongPhase),
+        };
+        self.seats[i].acted = true;
+        events.push(Event::PlayerActed { player: player_name.to_string(), action, amount });
+        self.advance_turn(i + 1, &mut events);
+        self.log.extend(events.iter().cloned());
+        Ok(events)
+    }
+
+    /// Folds a player out of turn, used when they disconnect in the middle of a hand.
+    /// If it was their turn the action moves on to the next player.
+    pub fn fold_player(&mut self, player_name: &str) -> Vec<Event> {
+        let mut events = Vec::new();
+        let i = match self.seat_index(player_name) {
+            Some(i) => i,
+            None => return events,
+        };
+        if self.finished || self.seats[i].state == player::FOLDED {
+            return events;
+        }
+        self.seats[i].state = player::FOLDED;
+        events.push(Event::PlayerActed { player: player_name.to_string(), action: Action::Fold, amount: 0 });
+        if self.current_player == Some(i) {
+            if self.phase == DRAW {
+                self.advance_draw(i + 1, &mut events);
+            } else {
+                self.advance_turn(i + 1, &mut events);
+            }
+        } else if self.active_count() <= 1 {
+            self.showdown(&mut events);
+        }
+        self.log.extend(events.iter().cloned());
+        events
+    }
+
+    /// Total chips put in during this hand.
+    pub fn pot(&self) -> i32 {
+        self.seats.iter().map(|s| s.contributed).sum()
+    }
+
+    /// Chips the player to act needs to put in to call.
+    pub fn call_amount(&self) -> i32 {
+        match self.current_player {
+            Some(i) => (self.current_max_bet - self.seats[i].current_bet).max(0),
+            None => 0,
+        }
+    }
+
+    /// The fewest chips the player to act may put in to raise, calling included.
+    /// In no-limit and pot-limit the raise has to be at least the biggest raise of the round, and never
+    /// less than the big blind. In fixed-limit it is the bet of the round. A shorter stack can only go all in.
+    pub fn min_raise(&self) -> i32 {
+        if self.current_player.is_none() {
+            return 0;
+        }
+        (self.call_amount() + self.full_raise()).min(self.max_raise())
+    }
+
+    /// The smallest raise that reopens the betting: the bet of the round in fixed-limit, otherwise
+    /// the biggest raise of the round and never less than the big blind.
+    fn full_raise(&self) -> i32 {
+        if self.betting_structure == FIXED_LIMIT {
+            self.fixed_bet()
+        } else {
+            self.last_raise.max(self.big_blind)
+        }
+    }
+
+    /// The most chips the player to act may put in, calling included.
+    /// In pot-limit they may raise by at most the size of the pot after their call, which already
+    /// holds this round's bets. In fixed-limit they may raise by the bet of the round until it has been
+    /// bet and raised `MAX_BETS_PER_ROUND` times, then only call. In no-limit it is their whole stack.
+    /// A player who has acted and faces only an all-in for less than a full raise can only call.
+    pub fn max_raise(&self) -> i32 {
+        let i = match self.current_player {
+            Some(i) => i,
+            None => return 0,
+        };
+        let stack = self.seats[i].stack;
+        let call_amount = self.call_amount();
+        if self.seats[i].acted {
+            return stack.min(call_amount);
+        }
+        match self.betting_structure {
+            POT_LIMIT => stack.min(call_amount + self.pot() + call_amount),
+            FIXED_LIMIT if self.bets >= MAX_BETS_PER_ROUND => stack.min(call_amount),
+            FIXED_LIMIT => stack.min(call_amount + self.fixed_bet()),
+            _ => stack,
+        }
+    }
+
+    /// The bet of the round in fixed-limit: the small bet, the big blind, for the first two betting rounds
+    /// and the big bet, twice that, from the turn, 5th street or the second draw on.
+    /// 5 Card Draw has only two betting rounds, the big bet is made after the draw.
+    fn fixed_bet(&self) -> i32 {
+        let big_bet_street = if self.game_type == FIVE_CARD_DRAW { 1 } else { 2 };
+        if self.street >= big_bet_street {
+            2 * self.big_blind
+        } else {
+            self.big_blind
+        }
+    }
+
+    /// The action taken for the player to act when their clock runs out:
+    /// stand pat in the draw, check if it is free and fold otherwise.
+    pub fn timeout_action(&self) -> Action {
+        if self.phase == DRAW {
+            Action::DrawCards { indices: Vec::new() }
+        } else if self.call_amount() == 0 {
+            Action::Check
+        } else {
+            Action::Fold
+        }
+    }
+
+    pub fn current_player_name(&self) -> Option<&str> {
+        self.current_player.map(|i| self.seats[i].name.as_str())
+    }
+
+    pub fn seat_index(&self, player_name: &str) -> Option<usize> {
+        self.seats.iter().position(|s| s.name == player_name)
+    }
+
+    /// Collects the names of every player who won at least part of a pot, high or low.
+    pub fn winners(&self) -> Vec<String> {
+        let mut winners: Vec<String> = Vec::new();
+        for pot in self.pots.iter() {
+            for winner in pot.winners.iter().chain(pot.low_winners.iter()) {
+                if !winners.contains(winner) {
+                    winners.push(winner.clone());
+                }
+            }
+        }
+        winners
+    }
+
+    /// The players whose hands are shown down: everyone still in play at the showdown, unless
+    /// the others all folded. Empty until the hand reaches the showdown.
+    pub fn shown_down(&self) -> Vec<String> {
+        let contenders: Vec<String> = self
+            .seats
+            .iter()
+            .filter(|seat| seat.dealt_in && seat.state != player::FOLDED)
+            .map(|seat| seat.name.clone())
+            .collect();
+        if self.phase != SHOWDOWN || contenders.len() < 2 {
+            return Vec::new();
+        }
+        contenders
+    }
+
+    /// The players dealt in who lost every chip, with the stack each started the hand with.
+    pub fn busted(&self) -> Vec<(String, i32)> {
+        self.seats
+            .iter()
+            .filter(|seat| seat.dealt_in && seat.stack == 0)
+            .map(|seat| (seat.name.clone(), seat.starting_stack))
+            .collect()
+    }
+
+    /// Chips at the table when the hand was set up, before any forced bets.
+    pub fn starting_chips(&self) -> i32 {
+        self.seats.iter().map(|seat| seat.starting_stack).sum()
+    }
+
+    /// Chips at the table now: the stacks, plus the chips put in until the pots are awarded at the showdown.
+    pub fn chips_in_play(&self) -> i32 {
+        let stacks: i32 = self.seats.iter().map(|seat| seat.stack).sum();
+        if self.finished {
+            stacks
+        } else {
+            stacks + self.pot()
+        }
+    }
+
+    /// Checks that the hand has neither created nor lost chips: the stacks and the pot add up to the chips the
+    /// hand started with, no stack has gone below zero and the pots awarded at the showdown hold every chip put in.
+    ///
+    /// # Returns
+    /// * `Ok(())` - Every chip is accounted for.
+    /// * `Err(String)` - What is wrong, followed by every event of the hand so far, one per line.
+    pub fn check_chips(&self) -> Result<(), String> {
+        let mut problems: Vec<String> = Vec::new();
+        let (expected, actual) = (self.starting_chips(), self.chips_in_play());
+        if actual != expected {
+            problems.push(format!("the stacks and the pot hold {} chips, the hand started with {}", actual, expected));
+        }
+        for seat in self.seats.iter().filter(|seat| seat.stack < 0 || seat.contributed < 0) {
+            problems.push(format!("{} has a stack of {} after putting in {}", seat.name, seat.stack, seat.contributed));
+        }
+        let awarded: i32 = self.pots.iter().map(|pot| pot.amount).sum();
+        if self.finished && !self.pots.is_empty() && awarded != self.pot() {
+            problems.push(format!("the pots hold {} chips, {} were put in", awarded, self.pot()));
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let mut report = problems.join("\n");
+        for event in self.log.iter() {
+            report.push_str(&format!("\n  {:?}", event));
+        }
+        Err(report)
+    }
+
+    fn set_phase(&mut self, phase: i32, events: &mut Vec<Event>) {
+        if self.phase != phase {
+            self.phase = phase;
+            events.push(Event::PhaseChanged { phase });
+        }
+    }
+
+    /// Seats still in the hand (all-in players included), starting at `start` and going round the table.
+    fn seat_order(&self, start: usize) -> Vec<usize> {
+        let count = self.seats.len();
+        (0..count)
+            .map(|k| (start + k) % count)
+            .filter(|&i| self.seats[i].dealt_in && self.seats[i].state != player::FOLDED)
+            .collect()
+    }
+
+    fn active_count(&self) -> usize {
+        self.seats.iter().filter(|s| s.dealt_in && s.state != player::FOLDED).count()
+    }
+
+    fn can_act(&self, i: usize) -> bool {
+        let seat = &self.seats[i];
+        seat.dealt_in && seat.state != player::FOLDED && seat.state != player::ALL_IN
+    }
+
+    /// Moves up to `amount` chips from a seat into the pot. A seat that runs out of chips is all in.
+    /// Live chips count towards the current bet, antes do not.
+    fn post(&mut self, i: usize, amount: i32, live: bool) -> i32 {
+        let seat = &mut self.seats[i];
+        let posted = amount.min(seat.stack).max(0);
+        seat.stack -= posted;
+        seat.contributed += posted;
+        if live {
+            seat.current_bet += posted;
+        }
+        if seat.stack == 0 && seat.state != player::FOLDED {
+            seat.state = player::ALL_IN;
+        }
+        posted
+    }
+
+    /// Lifts the bet to the raiser's current bet after a raise or an all-in, however it was sent.
+    /// Only a full raise reopens the betting.
+    fn raise_bet(&mut self, raiser: usize) {
+        let raise = self.seats[raiser].current_bet - self.current_max_bet;
+        if raise >= self.full_raise() {
+            self.reopen_betting(raiser);
+        } else if raise > 0 {
+            // all in for less than a full raise: the others call the extra chips, but whoever
+            // has already acted cannot raise again
+            self.current_max_bet = self.seats[raiser].current_bet;
+        }
+    }
+
+    /// Raises the bet to the raiser's current bet; everyone else has to act again.
+    fn reopen_betting(&mut self, raiser: usize) {
+        self.last_raise = self.last_raise.max(self.seats[raiser].current_bet - self.current_max_bet);
+        self.bets += 1;
+        self.current_max_bet = self.seats[raiser].current_bet;
+        for (i, seat) in self.seats.iter_mut().enumerate() {
+            if i != raiser {
+                seat.acted = false;
+            }
+        }
+    }
+
+    /// Deals the next card of a street. No game seats more players than it has cards for, so only
+    /// a draw can run the deck out, and `draw_cards` checks for that before it deals.
+    fn deal_card(&mut self) -> i32 {
+        self.deck.deal().expect("the deck holds a card for every seat the game allows")
+    }
+
+    fn deal_hole_cards(&mut self, count: usize, start: usize, events: &mut Vec<Event>) {
+        let order = self.seat_order(start);
+        for _ in 0..count {
+            for &i in order.iter() {
+                let card = self.deal_card();
+                self.seats[i].hand.push(card);
+            }
+        }
+        for &i in order.iter() {
+            events.push(Event::CardsDealt { player: self.seats[i].name.clone(), cards: self.seats[i].hand.clone() });
+        }
+    }
+
+    /// Everyone dealt in posts the ante, when the table plays with one. Antes go in the pot
+    /// but do not count towards the first bet.
+    fn post_antes(&mut self, events: &mut Vec<Event>) {
+        if self.ante <= 0 {
+            return;
+        }
+        self.set_phase(ANTE, events);
+        for i in self.seat_order(self.first_player) {
+            let amount = self.post(i, self.ante, false);
+            events.push(Event::AntePosted { player: self.seats[i].name.clone(), amount });
+        }
+    }
+
+    /// The lowest up-card pays the bring-in, betting continues with the next player.
+    fn post_bring_in(&mut self, events: &mut Vec<Event>) {
+        let bring_in_seat = self
+            .seat_order(self.first_player)
+            .into_iter()
+            .min_by_key(|&i| bring_in_key(self.seats[i].hand[2], self.game_type == RAZZ));
+        let i = match bring_in_seat {
+            Some(i) => i,
+            None => return self.showdown(events),
+        };
+        self.set_phase(BRING_IN, events);
+        let amount = self.post(i, self.bring_in, true);
+        self.current_max_bet = self.seats[i].current_bet;
+        if self.seats[i].state != player::ALL_IN {
+            self.seats[i].state = player::CALLED;
+        }
+        events.push(Event::BringInPosted { player: self.seats[i].name.clone(), amount });
+
+        self.set_phase(BETTING_ROUND, events);
+        for seat in self.seats.iter_mut() {
+            seat.acted = false;
+        }
+        self.seats[i].acted = true;
+        self.advance_turn(i + 1, events);
+    }
+
+    /// The seats posting the small and big blind: the ones the lobby chose if they were dealt in,
+    /// otherwise the two players after the button. Heads-up the button posts the small blind, and
+    /// as the button sits just before the first player that is the player after them.
+    fn blind_seats(&self, order: &[usize]) -> (Option<usize>, usize) {
+        if let Some((small, big)) = self.blinds {
+            if big < self.seats.len() && self.seats[big].dealt_in {
+                let small = small.filter(|&small| small < self.seats.len() && small != big && self.seats[small].dealt_in);
+                return (small, big);
+            }
+        }
+        if order.len() == 2 {
+            (Some(order[1]), order[0])
+        } else {
+            (Some(order[0]), order[1])
+        }
+    }
+
+    /// Posts the small and big blind, deals the hole cards and opens the pre-flop betting.
+    /// Hold'em deals two hole cards each, Omaha four.
+    /// The first player after the big blind acts first before the flop, which heads-up is the button.
+    fn post_blinds(&mut self, events: &mut Vec<Event>) {
+        self.set_phase(SMALL_AND_BIG_BLIND, events);
+        let order = self.seat_order(self.first_player);
+        let hole_cards = if is_omaha(self.game_type) { 4 } else { 2 };
+        if order.len() < 2 {
+            self.deal_hole_cards(hole_cards, self.first_player, events);
+            return self.showdown(events);
+        }
+        let (small, big) = self.blind_seats(&order);
+        if let Some(small) = small {
+            let amount = self.post(small, self.small_blind, true);
+            events.push(Event::BlindPosted { player: self.seats[small].name.clone(), amount, big_blind: false });
+        }
+        let amount = self.post(big, self.big_blind, true);
+        events.push(Event::BlindPosted { player: self.seats[big].name.clone(), amount, big_blind: true });
+        self.current_max_bet = small.map(|small| self.seats[small].current_bet).unwrap_or(0).max(self.seats[big].current_bet);
+        self.last_raise = self.big_blind;
+        self.bets = 1;
+
+        self.deal_hole_cards(hole_cards, self.first_player, events);
+        self.set_phase(SMALL_AND_BIG_BLIND, events);
+        for seat in self.seats.iter_mut() {
+            seat.acted = false;
+        }
+        self.advance_turn(big + 1, events);
+    }
+
+    fn begin_betting_round(&mut self, phase: i32, first: usize, events: &mut Vec<Event>) {
+        self.set_phase(phase, events);
+        for seat in self.seats.iter_mut() {
+            seat.acted = false;
+        }
+        self.advance_turn(first, events);
+    }
+
+    /// A betting round is over once every player who can still act has acted and matched the bet.
+    /// With at most one such player left there is nobody to bet against once they have matched.
+    fn betting_complete(&self) -> bool {
+        let can_act: Vec<&Seat> = (0..self.seats.len())
+            .filter(|&i| self.can_act(i))
+            .map(|i| &self.seats[i])
+            .collect();
+        if can_act.len() <= 1 {
+            return can_act.iter().all(|s| s.current_bet >= self.current_max_bet);
+        }
+        can_act.iter().all(|s| s.acted && s.current_bet == self.current_max_bet)
+    }
+
+    /// Hands the turn to the next player, starting at seat `from`, who still has to act.
+    fn advance_turn(&mut self, from: usize, events: &mut Vec<Event>) {
+        if self.active_count() <= 1 {
+            return self.showdown(events);
+        }
+        if self.betting_complete() {
+            return self.end_betting_round(events);
+        }
+        let count = self.seats.len();
+        for k in 0..count {
+            let i = (from + k) % count;
+            if self.can_act(i) && (!self.seats[i].acted || self.seats[i].current_bet < self.current_max_bet) {
+                self.current_player = Some(i);
+                events.push(Event::TurnChanged {
+                    player: self.seats[i].name.clone(),
+                    phase: self.phase,
+                    call_amount: self.call_amount(),
+                });
+                return;
+            }
+        }
+        self.end_betting_round(events);
+    }
+
+    /// Clears the round's bets and moves the hand on to the next street, drawing round or showdown.
+    fn end_betting_round(&mut self, events: &mut Vec<Event>) {
+        self.current_player = None;
+        self.current_max_bet = 0;
+        self.last_raise = 0;
+        self.bets = 0;
+        for seat in self.seats.iter_mut() {
+            seat.current_bet = 0;
+            seat.acted = false;
+            if seat.state != player::FOLDED && seat.state != player::ALL_IN {
+                seat.state = player::IN_GAME;
+            }
+        }
+        self.street += 1;
+
+        match self.game_type {
+            FIVE_CARD_DRAW | DEUCE_TO_SEVEN_TRIPLE_DRAW => {
+                // one draw in 5 Card Draw, three in Triple Draw
+                let draws = if self.game_type == DEUCE_TO_SEVEN_TRIPLE_DRAW { 3 } else { 1 };
+                if self.street <= draws {
+                    self.set_phase(DRAW, events);
+                    self.advance_draw(self.first_player, events);
+                } else {
+                    self.showdown(events);
+                }
+            }
+            SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ => {
+                if self.street >= 5 {
+                    return self.showdown(events);
+                }
+                // 4th to 6th street are dealt face up, 7th street face down
+                self.set_phase(DEAL_CARDS, events);
+                let face_down = self.street == 4;
+                for i in self.seat_order(self.first_player) {
+                    let card = self.deal_card();
+                    self.seats[i].hand.push(if face_down { card + FACE_DOWN } else { card });
+                    events.push(Event::CardsDealt { player: self.seats[i].name.clone(), cards: self.seats[i].hand.clone() });
+                }
+                let first = self.best_showing();
+                self.begin_betting_round(BETTING_ROUND, first, events);
+            }
+            _ => {
+                if self.street >= 4 {
+                    return self.showdown(events);
+                }
+                // flop, turn, then river
+                self.set_phase(DEAL_CARDS, events);
+                let count = if self.community_cards.is_empty() { 3 } else { 1 };
+                let cards: Vec<i32> = (0..count).map(|_| self.deal_card()).collect();
+                self.community_cards.extend(cards.iter());
+                events.push(Event::CommunityCardsDealt { cards });
+                self.begin_betting_round(BETTING_ROUND, self.first_player, events);
+            }
+        }
+    }
+
+    /// The seat showing the best up-cards acts first from 4th street on in 7 Card Stud.
+    /// In Razz the best up-cards are the lowest.
+    fn best_showing(&self) -> usize {
+        let razz = self.game_type == RAZZ;
+        let mut best: Option<(HandValue, usize)> = None;
+        for i in self.seat_order(self.first_player) {
+            let up_cards: Vec<i32> = self.seats[i].hand.iter().copied().filter(|&c| c < FACE_DOWN).collect();
+            let showing = if razz { get_ace_to_five_low(&up_cards) } else { get_hand_type(&up_cards) };
+            let better = match best {
+                Some((best_showing, _)) if razz => compare_hands(&showing, &best_showing) == std::cmp::Ordering::Less,
+                Some((best_showing, _)) => compare_hands(&showing, &best_showing) == std::cmp::Ordering::Greater,
+                None => true,
+            };
+            if better {
+                best = Some((showing, i));
+            }
+        }
+        best.map(|(_, i)| i).unwrap_or(self.first_player)
+    }
+
+    /// Hands the draw to the next player still in the hand who has not drawn yet.
+    fn advance_draw(&mut self, from: usize, events: &mut Vec<Event>) {
+        if self.active_count() <= 1 {
+            return self.showdown(events);
+        }
+        let count = self.seats.len();
+        for k in 0..count {
+            let i = (from + k) % count;
+            if self.seats[i].dealt_in && self.seats[i].state != player::FOLDED && !self.seats[i].acted {
+                self.current_player = Some(i);
+                events.push(Event::TurnChanged { player: self.seats[i].name.clone(), phase: DRAW, call_amount: 0 });
+                return;
+            }
+        }
+        self.current_player = None;
+        self.begin_betting_round(SECOND_BETTING_ROUND, self.first_player, events);
+    }
+
+    fn draw_cards(&mut self, i: usize, mut indices: Vec<usize>, events: &mut Vec<Event>) -> Result<(), EngineError> {
+        indices.sort();
+        indices.dedup();
+        if indices.iter().any(|&index| index >= self.seats[i].hand.len()) {
+            return Err(EngineError::InvalidAction);
+        }
+        if indices.len() > self.deck.cards_left() {
+            return Err(EngineError::NotEnoughCards);
+        }
+        // the thrown cards are discarded after the new ones are dealt, so a reshuffle never deals them straight back
+        let mut discards = Vec::new();
+        for &index in indices.iter() {
+            if let Some(card) = self.deck.deal() {
+                discards.push(std::mem::replace(&mut self.seats[i].hand[index], card));
+            }
+        }
+        self.deck.discard(&discards);
+        self.seats[i].acted = true;
+        let name = self.seats[i].name.clone();
+        events.push(Event::CardsDrawn { player: name.clone(), count: indices.len() });
+        events.push(Event::CardsDealt { player: name, cards: self.seats[i].hand.clone() });
+        self.advance_draw(i + 1, events);
+        Ok(())
+    }
+
+    /// Evaluates a hand still in play for the showdown.
+    fn evaluate(&self, i: usize) -> HandValue {
+        let hand = &self.seats[i].hand;
+        match self.game_type {
+            FIVE_CARD_DRAW => get_hand_type(hand),
+            DEUCE_TO_SEVEN_TRIPLE_DRAW => get_deuce_to_seven_low(hand),
+            SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO => get_best_hand(hand),
+            RAZZ => get_ace_to_five_low(hand),
+            OMAHA | OMAHA_HI_LO => get_best_omaha_hand(hand, &self.community_cards),
+            _ => {
+                let mut cards = hand.clone();
+                cards.extend(self.community_cards.iter());
+                get_best_hand(&cards)
+            }
+        }
+    }
+
+    /// Evaluates the qualifying low of a hand still in play, `None` outside the hi/lo games.
+    fn evaluate_low(&self, i: usize) -> Option<LowHand> {
+        let hand = &self.seats[i].hand;
+        match self.game_type {
+            OMAHA_HI_LO => get_omaha_low_hand(hand, &self.community_cards),
+            SEVEN_CARD_STUD_HI_LO => get_low_hand(hand),
+            _ => None,
+        }
+    }
+
+    /// Turns every hand still in play face up and awards the main pot and side pots. Folded hands stay face down,
+    /// and so does a hand everyone else folded to, which wins without being shown down.
+    fn showdown(&mut self, events: &mut Vec<Event>) {
+        self.current_player = None;
+        self.set_phase(SHOWDOWN, events);
+        let shown_down = self.shown_down();
+        // an uncontested hand is not evaluated either, its board or up cards may not all be out
+        let contested = !shown_down.is_empty();
+        for seat in self.seats.iter_mut().filter(|seat| shown_down.contains(&seat.name)) {
+            for card in seat.hand.iter_mut() {
+                if *card >= FACE_DOWN {
+                    *card -= FACE_DOWN;
+                }
+            }
+        }
+
+        let contenders: Vec<usize> = (0..self.seats.len())
+            .filter(|&i| self.seats[i].dealt_in && self.seats[i].state != player::FOLDED)
+            .collect();
+        let hands: Vec<ShowdownHand> = contenders
+            .iter()
+            .map(|&i| ShowdownHand {
+                player: self.seats[i].name.clone(),
+                cards: if contested { self.seats[i].hand.clone() } else { Vec::new() },
+                rank: if contested { self.evaluate(i) } else { (0, 0, 0, 0, 0, 0) },
+                low: if contested { self.evaluate_low(i) } else { None },
+            })
+            .collect();
+
+        let entries: Vec<(String, i32, i32)> = self
+            .seats
+            .iter()
+            .map(|s| (s.name.clone(), s.contributed, s.state))
+            .collect();
+        let mut pots = build_pots(&entries);
+        award_pots(&mut self.seats, &mut pots, &hands, is_lowball(self.game_type));
+        self.pots = pots.clone();
+        self.finished = true;
+
+        events.push(Event::Showdown { hands, pots });
+        events.push(Event::HandFinished { winners: self.winners() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lobby::{max_players, NO_LIMIT, TEXAS_HOLD_EM};
+    use rand::Rng;
+
+    fn players(stacks: &[i32]) -> Vec<(String, i32)> {
+        stacks.iter().enumerate().map(|(i, &stack)| (format!("p{}", i), stack)).collect()
+    }
+
+    fn total_chips(engine: &Engine) -> i32 {
+        engine.seats.iter().map(|s| s.stack).sum()
+    }
+
+    /// Plays the hand out with the given action for whoever is to act.
+    fn play_out(engine: &mut Engine, action: Action) -> Vec<Event> {
+        let mut events = Vec::new();
+        while !engine.finished {
+            let name = engine.current_player_name().unwrap().to_string();
+            let action = if engine.phase == DRAW { Action::DrawCards { indices: vec![] } } else { action.clone() };
+            events.extend(engine.apply(&name, action).unwrap());
+        }
+        events
+    }
+
+    fn entry(name: &str, contribution: i32, state: i32) -> (String, i32, i32) {
+        (name.to_string(), contribution, state)
+    }
+
+    #[test]
+    fn test_build_pots_no_all_in() {
+        let entries = vec![
+            entry("a", 50, player::CALLED),
+            entry("b", 50, player::CHECKED),
+            entry("c", 20, player::FOLDED),
+        ];
+        let pots = build_pots(&entries);
+        assert_eq!(pots.len(), 1);
+        assert_eq!(pots[0].amount, 120);
+        assert_eq!(pots[0].eligible, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn test_build_pots_short_all_in() {
+        // c is all in for 30, a and b keep betting to 100
+        let entries = vec![
+            entry("a", 100, player::CALLED),
+            entry("b", 100, player::RAISED),
+            entry("c", 30, player::ALL_IN),
+        ];
+        let pots = build_pots(&entries);
+        assert_eq!(pots.len(), 2);
+        assert_eq!(pots[0].amount, 90);
+        assert_eq!(pots[0].eligible, vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        assert_eq!(pots[1].amount, 140);
+        assert_eq!(pots[1].eligible, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn test_build_pots_multiple_all_ins_and_dead_money() {
+        let entries = vec![
+            entry("a", 20, player::ALL_IN),
+            entry("b", 60, player::ALL_IN),
+            entry("c", 80, player::FOLDED),
+        ];
+        let pots = build_pots(&entries);
+        assert_eq!(pots.len(), 2);
+        assert_eq!(pots[0].amount, 60);
+        assert_eq!(pots[0].eligible, vec!["a".to_string(), "b".to_string()]);
+        // c folded after putting in more than anyone could match, those chips stay in the last pot
+        assert_eq!(pots[1].amount, 100);
+        assert_eq!(pots[1].eligible, vec!["b".to_string()]);
+        let total: i32 = pots.iter().map(|p| p.amount).sum();
+        assert_eq!(total, 160);
+    }
+
+    #[test]
+    fn test_split_pot() {
+        // without a low the high hands split the whole pot
+        assert_eq!(split_pot(101, 2, 0), (vec![51, 50], vec![]));
+        // the odd chip of the halves goes to the high hand
+        assert_eq!(split_pot(101, 1, 1), (vec![51], vec![50]));
+        // a low tied by two players is quartered
+        assert_eq!(split_pot(100, 1, 2), (vec![50], vec![25, 25]));
+        assert_eq!(split_pot(103, 1, 2), (vec![52], vec![26, 25]));
+    }
+
+    fn showdown_hand(name: &str, rank: HandValue, low: Option<LowHand>) -> ShowdownHand {
+        ShowdownHand { player: name.to_string(), cards: Vec::new(), rank, low }
+    }
+
+    #[test]
+    fn test_award_pots_hi_lo() {
+        let entries = vec![
+            entry("p0", 101, player::CALLED),
+            entry("p1", 101, player::CALLED),
+            entry("p2", 101, player::CALLED),
+        ];
+        let flush = (5, 13, 10, 8, 6, 3);
+        let pair = (1, 9, 13, 12, 4, 0);
+        let high_card = (0, 13, 8, 6, 4, 3);
+
+        // p0 takes the high half, p1 and p2 tie for the low and get a quarter each
+        let mut seats = Engine::new(OMAHA_HI_LO, players(&[0, 0, 0]), Deck::new(), 0).seats;
+        let mut pots = build_pots(&entries);
+        let hands = vec![
+            showdown_hand("p0", flush, None),
+            showdown_hand("p1", pair, Some([8, 6, 4, 3, 1])),
+            showdown_hand("p2", high_card, Some([8, 6, 4, 3, 1])),
+        ];
+        award_pots(&mut seats, &mut pots, &hands, false);
+        assert_eq!(pots[0].winners, vec!["p0".to_string()]);
+        assert_eq!(pots[0].low_winners, vec!["p1".to_string(), "p2".to_string()]);
+        assert_eq!(seats.iter().map(|s| s.stack).collect::<Vec<_>>(), vec![152, 76, 75]);
+
+        // the best high and the best low scoop the pot
+        let mut seats = Engine::new(OMAHA_HI_LO, players(&[0, 0, 0]), Deck::new(), 0).seats;
+        let mut pots = build_pots(&entries);
+        let hands = vec![
+            showdown_hand("p0", flush, Some([7, 5, 4, 2, 1])),
+            showdown_hand("p1", pair, Some([8, 6, 4, 3, 1])),
+            showdown_hand("p2", high_card, None),
+        ];
+        award_pots(&mut seats, &mut pots, &hands, false);
+        assert_eq!(pots[0].low_winners, vec!["p0".to_string()]);
+        assert_eq!(seats.iter().map(|s| s.stack).collect::<Vec<_>>(), vec![303, 0, 0]);
+
+        // without a qualifying low the high hand takes it all
+        let mut seats = Engine::new(OMAHA_HI_LO, players(&[0, 0, 0]), Deck::new(), 0).seats;
+        let mut pots = build_pots(&entries);
+        let hands = vec![showdown_hand("p0", flush, None), showdown_hand("p1", pair, None)];
+        award_pots(&mut seats, &mut pots, &hands, false);
+        assert!(pots[0].low_winners.is_empty());
+        assert_eq!(seats[0].stack, 303);
+    }
+
+    #[test]
+    fn test_hi_lo_hands_are_played_out() {
+        for game_type in [OMAHA_HI_LO, SEVEN_CARD_STUD_HI_LO] {
+            let mut engine = Engine::new(game_type, players(&[100, 100, 100]), Deck::new(), 0);
+            engine.start_hand();
+            // calling nothing is a check, so this also calls the stud bring-in
+            let events = play_out(&mut engine, Action::Call);
+            let hands = events
+                .iter()
+                .find_map(|e| match e {
+                    Event::Showdown { hands, .. } => Some(hands.clone()),
+                    _ => None,
+                })
+                .unwrap();
+            assert_eq!(hands.len(), 3);
+            assert!(hands.iter().all(|hand| hand.cards.len() == if game_type == OMAHA_HI_LO { 4 } else { 7 }));
+            assert_eq!(total_chips(&engine), 300);
+        }
+    }
+
+    #[test]
+    fn test_five_card_draw_hand() {
+        // an unshuffled deck deals p0 the odd hearts and p1 the even hearts
+        let mut engine = Engine::new(FIVE_CARD_DRAW, players(&[100, 100]), Deck::new(), 0);
+        let events = engine.start_hand();
+        assert!(events.contains(&Event::AntePosted { player: "p0".to_string(), amount: ANTE_AMOUNT }));
+        assert_eq!(engine.seats[0].hand, vec![0, 2, 4, 6, 8]);
+        assert_eq!(engine.seats[1].hand, vec![1, 3, 5, 7, 9]);
+        assert_eq!(engine.phase, FIRST_BETTING_ROUND);
+        assert_eq!(engine.current_player_name(), Some("p0"));
+
+        engine.apply("p0", Action::Check).unwrap();
+        engine.apply("p1", Action::Check).unwrap();
+        assert_eq!(engine.phase, DRAW);
+        assert_eq!(engine.apply("p0", Action::Check), Err(EngineError::WrongPhase));
+
+        // p0 swaps the Ace for the Jack, p1 swaps the 2 for the Queen and has the better flush
+        let events = engine.apply("p0", Action::DrawCards { indices: vec![0] }).unwrap();
+        assert!(events.contains(&Event::CardsDrawn { player: "p0".to_string(), count: 1 }));
+        engine.apply("p1", Action::DrawCards { indices: vec![0, 0] }).unwrap();
+        assert_eq!(engine.seats[0].hand, vec![10, 2, 4, 6, 8]);
+        assert_eq!(engine.seats[1].hand, vec![11, 3, 5, 7, 9]);
+        assert_eq!(engine.phase, SECOND_BETTING_ROUND);
+
+        engine.apply("p0", Action::Raise { amount: 20 }).unwrap();
+        let events = engine.apply("p1", Action::Call).unwrap();
+        assert!(engine.finished);
+        assert!(events.contains(&Event::HandFinished { winners: vec!["p1".to_string()] }));
+        assert_eq!(engine.seats[0].stack, 70);
+        assert_eq!(engine.seats[1].stack, 130);
+    }
+
+    #[test]
+    fn test_everyone_folds_to_the_last_player() {
+        let mut engine = Engine::new(FIVE_CARD_DRAW, players(&[100, 100, 100]), Deck::new(), 1);
+        engine.start_hand();
+        assert_eq!(engine.current_player_name(), Some("p1"));
+        engine.apply("p1", Action::Raise { amount: 30 }).unwrap();
+        engine.apply("p2", Action::Fold).unwrap();
+        let events = engine.apply("p0", Action::Fold).unwrap();
+        assert!(events.contains(&Event::HandFinished { winners: vec!["p1".to_string()] }));
+        assert_eq!(engine.seats[1].stack, 120);
+        assert_eq!(total_chips(&engine), 300);
+    }
+
+    #[test]
+    fn test_actions_are_validated() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[100, 100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        // p0 is first to act, p2 is the button, so p0 and p1 post the blinds and p2 acts first
+        assert_eq!(engine.current_player_name(), Some("p2"));
+        assert_eq!(engine.call_amount(), BIG_BLIND);
+        assert_eq!(engine.apply("p0", Action::Call), Err(EngineError::NotYourTurn));
+        assert_eq!(engine.apply("p2", Action::Check), Err(EngineError::InvalidAction));
+        assert_eq!(engine.apply("p2", Action::Raise { amount: 5 }), Err(EngineError::InvalidAction));
+        assert_eq!(engine.apply("p2", Action::Raise { amount: 500 }), Err(EngineError::InvalidAction));
+        assert_eq!(engine.apply("nobody", Action::Fold), Err(EngineError::UnknownPlayer));
+        assert_eq!(engine.current_player_name(), Some("p2"));
+        assert_eq!(engine.pot(), SMALL_BLIND + BIG_BLIND);
+    }
+
+    #[test]
+    fn test_texas_hold_em_streets() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[100, 100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        engine.apply("p2", Action::Call).unwrap();
+        engine.apply("p0", Action::Call).unwrap();
+        // the big blind gets the option to raise
+        assert_eq!(engine.current_player_name(), Some("p1"));
+        let events = engine.apply("p1", Action::Check).unwrap();
+        assert!(events.iter().any(|e| matches!(e, Event::CommunityCardsDealt { cards } if cards.len() == 3)));
+        assert_eq!(engine.phase, BETTING_ROUND);
+        assert_eq!(engine.current_player_name(), Some("p0"));
+
+        let events = play_out(&mut engine, Action::Check);
+        assert_eq!(engine.community_cards.len(), 5);
+        assert!(events.iter().any(|e| matches!(e, Event::Showdown { hands, .. } if hands.len() == 3)));
+        assert_eq!(total_chips(&engine), 300);
+    }
+
+    #[test]
+    fn test_omaha_pot_limit_betting() {
+        let mut engine = Engine::new(OMAHA, players(&[1000, 1000, 1000]), Deck::new(), 0);
+        engine.start_hand();
+        assert!(engine.seats.iter().all(|s| s.hand.len() == 4));
+        // a pot raise is the call of 10 plus the pot of 25 once called
+        assert_eq!(engine.max_raise(), 10 + 25);
+        assert_eq!(engine.apply("p2", Action::Raise { amount: 36 }), Err(EngineError::InvalidAction));
+        assert_eq!(engine.apply("p2", Action::AllIn), Err(EngineError::InvalidAction));
+        engine.apply("p2", Action::Raise { amount: 35 }).unwrap();
+        assert_eq!(engine.max_raise(), 30 + 80);
+        engine.apply("p0", Action::Call).unwrap();
+        engine.apply("p1", Action::Call).unwrap();
+        assert_eq!(engine.community_cards.len(), 3);
+
+        let events = play_out(&mut engine, Action::Check);
+        assert!(events.iter().any(|e| matches!(e, Event::Showdown { hands, .. } if hands.len() == 3)));
+        assert_eq!(total_chips(&engine), 3000);
+    }
+
+    #[test]
+    fn test_no_limit_min_raise() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[1000, 1000, 1000]), Deck::new(), 0);
+        engine.start_hand();
+        // the first raise is at least the big blind
+        assert_eq!(engine.min_raise(), 10 + 10);
+        assert_eq!(engine.apply("p2", Action::Raise { amount: 15 }), Err(EngineError::InvalidAction));
+        engine.apply("p2", Action::Raise { amount: 40 }).unwrap();
+        // p2 raised by 30, so the small blind calls 35 and has to raise by 30 again
+        assert_eq!(engine.min_raise(), 35 + 30);
+        assert_eq!(engine.apply("p0", Action::Raise { amount: 64 }), Err(EngineError::InvalidAction));
+        engine.apply("p0", Action::Raise { amount: 65 }).unwrap();
+        assert_eq!(engine.max_raise(), 990);
+        play_out(&mut engine, Action::Call);
+        assert_eq!(total_chips(&engine), 3000);
+    }
+
+    #[test]
+    fn test_fixed_limit_bets_and_cap() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[1000, 1000, 1000]), Deck::new(), 0);
+        engine.betting_structure = FIXED_LIMIT;
+        engine.start_hand();
+        // pre-flop every raise is the small bet
+        assert_eq!((engine.min_raise(), engine.max_raise()), (10 + 10, 10 + 10));
+        assert_eq!(engine.apply("p2", Action::Raise { amount: 30 }), Err(EngineError::InvalidAction));
+        assert_eq!(engine.apply("p2", Action::AllIn), Err(EngineError::InvalidAction));
+        engine.apply("p2", Action::Raise { amount: 20 }).unwrap();
+        engine.apply("p0", Action::Raise { amount: 25 }).unwrap();
+        engine.apply("p1", Action::Raise { amount: 30 }).unwrap();
+        // the big blind and three raises are four bets, p2 can only call
+        assert_eq!(engine.max_raise(), engine.call_amount());
+        assert_eq!(engine.apply("p2", Action::Raise { amount: 30 }), Err(EngineError::InvalidAction));
+        engine.apply("p2", Action::Call).unwrap();
+        engine.apply("p0", Action::Call).unwrap();
+        assert_eq!(engine.community_cards.len(), 3);
+        assert_eq!(engine.max_raise(), 10);
+        for name in ["p0", "p1", "p2"] {
+            engine.apply(name, Action::Check).unwrap();
+        }
+        // the big bet from the turn on
+        assert_eq!(engine.community_cards.len(), 4);
+        assert_eq!(engine.max_raise(), 20);
+        play_out(&mut engine, Action::Check);
+        assert_eq!(total_chips(&engine), 3000);
+    }
+
+    #[test]
+    fn test_short_all_in_does_not_reopen_the_betting() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[1000, 1000, 90]), Deck::new(), 0);
+        engine.start_hand();
+        engine.apply("p2", Action::Call).unwrap();
+        engine.apply("p0", Action::Call).unwrap();
+        engine.apply("p1", Action::Check).unwrap();
+        assert_eq!(engine.community_cards.len(), 3);
+
+        // p0 bets 50, p1 calls and p2 is all in for 80, a raise of 30
+        engine.apply("p0", Action::Raise { amount: 50 }).unwrap();
+        engine.apply("p1", Action::Call).unwrap();
+        engine.apply("p2", Action::AllIn).unwrap();
+        assert_eq!((engine.current_max_bet, engine.bets, engine.last_raise), (80, 1, 50));
+
+        // p0 may only call the extra 30 or fold
+        assert_eq!(engine.current_player_name(), Some("p0"));
+        assert_eq!((engine.call_amount(), engine.min_raise(), engine.max_raise()), (30, 30, 30));
+        assert_eq!(engine.apply("p0", Action::Raise { amount: 130 }), Err(EngineError::InvalidAction));
+        assert_eq!(engine.apply("p0", Action::AllIn), Err(EngineError::InvalidAction));
+        engine.apply("p0", Action::Call).unwrap();
+        assert_eq!(engine.apply("p1", Action::Raise { amount: 130 }), Err(EngineError::InvalidAction));
+        engine.apply("p1", Action::Call).unwrap();
+        assert_eq!(engine.community_cards.len(), 4);
+        play_out(&mut engine, Action::Check);
+        assert_eq!(total_chips(&engine), 2090);
+    }
+
+    #[test]
+    fn test_short_all_in_sent_as_a_raise_does_not_reopen_the_betting() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[1000, 1000, 90]), Deck::new(), 0);
+        engine.start_hand();
+        engine.apply("p2", Action::Call).unwrap();
+        engine.apply("p0", Action::Call).unwrap();
+        engine.apply("p1", Action::Check).unwrap();
+
+        // p2 puts their last 80 chips in with a raise instead of going all in
+        engine.apply("p0", Action::Raise { amount: 50 }).unwrap();
+        engine.apply("p1", Action::Call).unwrap();
+        engine.apply("p2", Action::Raise { amount: 80 }).unwrap();
+        assert_eq!(engine.seats[2].state, player::ALL_IN);
+        assert_eq!((engine.current_max_bet, engine.bets, engine.last_raise), (80, 1, 50));
+        assert_eq!(engine.current_player_name(), Some("p0"));
+        assert_eq!((engine.call_amount(), engine.min_raise(), engine.max_raise()), (30, 30, 30));
+        assert_eq!(engine.apply("p0", Action::Raise { amount: 130 }), Err(EngineError::InvalidAction));
+        engine.apply("p0", Action::Call).unwrap();
+        engine.apply("p1", Action::Call).unwrap();
+        assert_eq!(engine.community_cards.len(), 4);
+        play_out(&mut engine, Action::Check);
+        assert_eq!(total_chips(&engine), 2090);
+    }
+
+    #[test]
+    fn test_all_in_runs_out_the_board_with_side_pot() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[30, 100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        engine.apply("p2", Action::Raise { amount: 60 }).unwrap();
+        engine.apply("p0", Action::AllIn).unwrap();
+        let events = engine.apply("p1", Action::Call).unwrap();
+        assert_eq!(engine.community_cards.len(), 3);
+        assert!(!events.iter().any(|e| matches!(e, Event::Showdown { .. })));
+
+        // p1 and p2 check it down, p0 is all in
+        let events = play_out(&mut engine, Action::Check);
+        let pots = events
+            .iter()
+            .find_map(|e| match e {
+                Event::Showdown { pots, .. } => Some(pots.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(pots.len(), 2);
+        assert_eq!(pots[0].amount, 90);
+        assert_eq!(pots[1].amount, 60);
+        assert_eq!(pots[1].eligible, vec!["p1".to_string(), "p2".to_string()]);
+        assert_eq!(total_chips(&engine), 230);
+    }
+
+    #[test]
+    fn test_everyone_all_in_deals_remaining_cards() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[50, 50]), Deck::new(), 0);
+        engine.start_hand();
+        let first = engine.current_player_name().unwrap().to_string();
+        engine.apply(&first, Action::AllIn).unwrap();
+        let second = engine.current_player_name().unwrap().to_string();
+        let events = engine.apply(&second, Action::Call).unwrap();
+        assert!(engine.finished);
+        assert_eq!(engine.community_cards.len(), 5);
+        assert!(events.iter().any(|e| matches!(e, Event::HandFinished { .. })));
+        assert_eq!(total_chips(&engine), 100);
+    }
+
+    #[test]
+    fn test_heads_up_button_posts_small_blind() {
+        // p0 acts first after the flop, so p1 has the button
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[100, 100]), Deck::new(), 0);
+        let events = engine.start_hand();
+        assert!(events.contains(&Event::BlindPosted { player: "p1".to_string(), amount: SMALL_BLIND, big_blind: false }));
+        assert!(events.contains(&Event::BlindPosted { player: "p0".to_string(), amount: BIG_BLIND, big_blind: true }));
+        assert_eq!(engine.current_player_name(), Some("p1"));
+        engine.apply("p1", Action::Call).unwrap();
+        engine.apply("p0", Action::Check).unwrap();
+        assert_eq!(engine.community_cards.len(), 3);
+        assert_eq!(engine.current_player_name(), Some("p0"));
+    }
+
+    #[test]
+    fn test_dead_small_blind() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[100, 100, 100]), Deck::new(), 1);
+        engine.blinds = Some((None, 2));
+        let events = engine.start_hand();
+        assert!(!events.iter().any(|e| matches!(e, Event::BlindPosted { big_blind: false, .. })));
+        assert!(events.contains(&Event::BlindPosted { player: "p2".to_string(), amount: BIG_BLIND, big_blind: true }));
+        assert_eq!(engine.pot(), BIG_BLIND);
+        assert_eq!(engine.current_player_name(), Some("p0"));
+        play_out(&mut engine, Action::Call);
+        assert_eq!(total_chips(&engine), 300);
+    }
+
+    #[test]
+    fn test_seven_card_stud_bring_in_and_streets() {
+        // third street up-cards from an unshuffled deck: p0 shows the 3 of hearts, p1 the 6, p2 the 9
+        let mut engine = Engine::new(SEVEN_CARD_STUD, players(&[100, 100, 100]), Deck::new(), 0);
+        let events = engine.start_hand();
+        assert!(events.contains(&Event::BringInPosted { player: "p0".to_string(), amount: BRING_IN_AMOUNT }));
+        assert_eq!(engine.seats[0].hand, vec![FACE_DOWN, 1 + FACE_DOWN, 2]);
+        assert_eq!(engine.current_player_name(), Some("p1"));
+
+        engine.apply("p1", Action::Call).unwrap();
+        engine.apply("p2", Action::Call).unwrap();
+        // the bring-in has acted, everyone called so 4th street is dealt
+        assert_eq!(engine.seats[0].hand.len(), 4);
+        let events = play_out(&mut engine, Action::Check);
+        assert!(events.iter().any(|e| matches!(e, Event::Showdown { hands, .. } if hands.iter().all(|h| h.cards.len() == 7))));
+        // every card is face up after the showdown
+        assert!(engine.seats.iter().all(|s| s.hand.iter().all(|&c| c < FACE_DOWN)));
+        assert_eq!(total_chips(&engine), 300);
+    }
+
+    #[test]
+    fn test_showdown_keeps_folded_hands_face_down() {
+        let mut engine = Engine::new(SEVEN_CARD_STUD, players(&[100, 100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        engine.apply("p1", Action::Fold).unwrap();
+        let events = play_out(&mut engine, Action::Call);
+        assert_eq!(engine.shown_down(), vec!["p0".to_string(), "p2".to_string()]);
+        assert!(events.iter().any(|e| matches!(e, Event::Showdown { hands, .. } if hands.iter().all(|h| h.cards.len() == 7))));
+        assert_eq!(engine.seats[1].hand.iter().filter(|&&c| c >= FACE_DOWN).count(), 2);
+        assert!(engine.seats[0].hand.iter().all(|&c| c < FACE_DOWN));
+
+        // nobody is shown down when everyone else folds
+        let mut engine = Engine::new(SEVEN_CARD_STUD, players(&[100, 100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        engine.apply("p1", Action::Fold).unwrap();
+        let events = engine.apply("p2", Action::Fold).unwrap();
+        assert!(engine.finished);
+        assert!(engine.shown_down().is_empty());
+        assert!(events.iter().any(|e| matches!(e, Event::Showdown { hands, .. } if hands.len() == 1 && hands[0].cards.is_empty())));
+        assert_eq!(engine.seats[0].hand, vec![FACE_DOWN, 1 + FACE_DOWN, 2]);
+    }
+
+    #[test]
+    fn test_table_stakes() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[1000, 1000, 1000]), Deck::new(), 0);
+        engine.small_blind = 25;
+        engine.big_blind = 50;
+        engine.ante = 5;
+        let events = engine.start_hand();
+        assert_eq!(events.iter().filter(|e| matches!(e, Event::AntePosted { amount: 5, .. })).count(), 3);
+        assert!(events.contains(&Event::BlindPosted { player: "p1".to_string(), amount: 50, big_blind: true }));
+        // antes do not count towards the bet
+        assert_eq!(engine.call_amount(), 50);
+        assert_eq!(engine.min_raise(), 100);
+        assert_eq!(engine.pot(), 15 + 75);
+        play_out(&mut engine, Action::Call);
+        assert_eq!(total_chips(&engine), 3000);
+
+        let mut engine = Engine::new(SEVEN_CARD_STUD, players(&[100, 100, 100]), Deck::new(), 0);
+        engine.ante = 2;
+        engine.bring_in = 5;
+        engine.start_hand();
+        assert_eq!(engine.pot(), 6 + 5);
+        assert_eq!(engine.call_amount(), 5);
+    }
+
+    #[test]
+    fn test_bring_in_ties_broken_by_suit() {
+        // the 2 of clubs is lower than the 2 of hearts
+        assert!(bring_in_key(40, false) < bring_in_key(1, false));
+        // Aces are high for the bring-in
+        assert!(bring_in_key(12, false) < bring_in_key(0, false));
+        // in Razz the King of spades pays before the King of hearts, and an Ace never does
+        assert!(bring_in_key(38, true) < bring_in_key(12, true));
+        assert!(bring_in_key(1, true) < bring_in_key(0, true));
+    }
+
+    #[test]
+    fn test_razz_bring_in_by_highest_card() {
+        // p0 shows the 3 of hearts, p1 the 6, p2 the 9, so p2 brings it in and p0 acts next
+        let mut engine = Engine::new(RAZZ, players(&[100, 100, 100]), Deck::new(), 0);
+        let events = engine.start_hand();
+        assert!(events.contains(&Event::BringInPosted { player: "p2".to_string(), amount: BRING_IN_AMOUNT }));
+        assert_eq!(engine.current_player_name(), Some("p0"));
+        play_out(&mut engine, Action::Call);
+        assert_eq!(total_chips(&engine), 300);
+    }
+
+    #[test]
+    fn test_triple_draw_has_three_draws() {
+        let mut engine = Engine::new(DEUCE_TO_SEVEN_TRIPLE_DRAW, players(&[100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        let events = play_out(&mut engine, Action::Check);
+        let draws = events.iter().filter(|e| matches!(e, Event::PhaseChanged { phase } if *phase == DRAW)).count();
+        assert_eq!(draws, 3);
+        // both hold hearts flushes, 10 high for p1 is lower than Ace high for p0
+        assert_eq!(engine.winners(), vec!["p1".to_string()]);
+        assert_eq!(engine.seats[1].stack, 110);
+        assert_eq!(total_chips(&engine), 200);
+    }
+
+    #[test]
+    fn test_full_triple_draw_table_can_replace_every_card() {
+        // 25 cards dealt and 75 replaced from a 52 card deck
+        let mut deck = Deck::new();
+        deck.shuffle();
+        let mut engine = Engine::new(DEUCE_TO_SEVEN_TRIPLE_DRAW, players(&[100; 5]), deck, 0);
+        engine.start_hand();
+        let mut drawn = 0;
+        while !engine.finished {
+            let name = engine.current_player_name().unwrap().to_string();
+            let action = if engine.phase == DRAW {
+                drawn += 5;
+                Action::DrawCards { indices: vec![0, 1, 2, 3, 4] }
+            } else {
+                Action::Check
+            };
+            engine.apply(&name, action).unwrap();
+            let mut live: Vec<i32> = engine.seats.iter().flat_map(|s| s.hand.iter().copied()).collect();
+            live.sort();
+            live.dedup();
+            assert_eq!(live.len(), 25, "a card is in two hands");
+        }
+        assert_eq!(drawn, 75);
+        assert_eq!(total_chips(&engine), 500);
+    }
+
+    #[test]
+    fn test_broke_player_sits_out() {
+        let mut engine = Engine::new(FIVE_CARD_DRAW, players(&[100, 5, 100]), Deck::new(), 0);
+        engine.start_hand();
+        assert!(!engine.seats[1].dealt_in);
+        assert!(engine.seats[1].hand.is_empty());
+        let events = play_out(&mut engine, Action::Check);
+        assert!(events.iter().any(|e| matches!(e, Event::Showdown { hands, .. } if hands.len() == 2)));
+        assert_eq!(engine.seats[1].stack, 5);
+        assert_eq!(total_chips(&engine), 205);
+    }
+
+    #[test]
+    fn test_timeout_action() {
+        let mut engine = Engine::new(FIVE_CARD_DRAW, players(&[100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        assert_eq!(engine.timeout_action(), Action::Check);
+        engine.apply("p0", Action::Raise { amount: 20 }).unwrap();
+        assert_eq!(engine.timeout_action(), Action::Fold);
+        engine.apply("p1", Action::Call).unwrap();
+        assert_eq!(engine.phase, DRAW);
+        assert_eq!(engine.timeout_action(), Action::DrawCards { indices: vec![] });
+    }
+
+    #[test]
+    fn test_fold_player_out_of_turn() {
+        let mut engine = Engine::new(FIVE_CARD_DRAW, players(&[100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        let events = engine.fold_player("p1");
+        assert!(engine.finished);
+        assert!(events.contains(&Event::HandFinished { winners: vec!["p0".to_string()] }));
+        assert_eq!(engine.seats[0].stack, 110);
+    }
+
+    #[test]
+    fn test_log_keeps_every_event() {
+        let mut engine = Engine::new(FIVE_CARD_DRAW, players(&[100, 100, 100]), Deck::new(), 0);
+        let mut events = engine.start_hand();
+        events.extend(engine.apply("p0", Action::Check).unwrap());
+        // refused actions change nothing, so they are not logged
+        assert!(engine.apply("p0", Action::Check).is_err());
+        events.extend(engine.fold_player("p2"));
+        events.extend(play_out(&mut engine, Action::Check));
+        assert_eq!(engine.log, events);
+        assert_eq!(engine.seats[0].starting_stack, 100);
+    }
+
+    #[test]
+    fn test_check_chips_reports_the_hand() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[100, 100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        engine.apply("p2", Action::Call).unwrap();
+        assert_eq!(engine.check_chips(), Ok(()));
+        engine.seats[0].stack += 5;
+        let report = engine.check_chips().unwrap_err();
+        assert!(report.starts_with("the stacks and the pot hold 305 chips, the hand started with 300\n"));
+        assert!(report.contains("BlindPosted") && report.contains("PlayerActed"));
+        assert_eq!(report.lines().count(), engine.log.len() + 1);
+    }
+
+    /// Plays a hand of random players, stakes and legal actions, folding a player out of turn now and then
+    /// as a table does when they leave, and checks that every chip is accounted for after every action.
+    fn play_random_hand(game_type: i32, rng: &mut impl Rng) {
+        let count = rng.random_range(2..=max_players(game_type) as usize);
+        // short stacks, often smaller than the forced bets
+        let stacks: Vec<i32> = (0..count).map(|_| rng.random_range(1..=300)).collect();
+        let mut deck = Deck::new();
+        deck.shuffle();
+        let mut engine = Engine::new(game_type, players(&stacks), deck, rng.random_range(0..count));
+        engine.betting_structure = [NO_LIMIT, POT_LIMIT, FIXED_LIMIT][rng.random_range(0..3)];
+        engine.small_blind = rng.random_range(1..=25);
+        engine.big_blind = 2 * engine.small_blind;
+        engine.bring_in = rng.random_range(1..=engine.big_blind);
+        engine.ante = rng.random_range(0..=10);
+        engine.start_hand();
+        assert_eq!(engine.check_chips(), Ok(()));
+        while let Some(name) = engine.current_player_name().map(|name| name.to_string()) {
+            if rng.random_bool(0.02) {
+                let leaving = engine.seats[rng.random_range(0..count)].name.clone();
+                engine.fold_player(&leaving);
+                assert_eq!(engine.check_chips(), Ok(()));
+                continue;
+            }
+            let action = match rng.random_range(0..6) {
+                _ if engine.phase == DRAW => Action::DrawCards {
+                    indices: (0..rng.random_range(0..=5)).map(|_| rng.random_range(0..5)).collect(),
+                },
+                0 => Action::Check,
+                1 | 2 => Action::Call,
+                3 => Action::Raise { amount: rng.random_range(engine.min_raise()..=engine.max_raise()) },
+                4 => Action::AllIn,
+                _ => Action::Fold,
+            };
+            // a refused action changes nothing, the player times out instead
+            if engine.apply(&name, action).is_err() {
+                let action = engine.timeout_action();
+                engine.apply(&name, action).unwrap();
+            }
+            assert_eq!(engine.check_chips(), Ok(()));
+        }
+        assert!(engine.finished);
+        assert_eq!(engine.chips_in_play(), stacks.iter().sum::<i32>());
+    }
+
+    #[test]
+    fn test_random_draw_hands_conserve_chips() {
+        let mut rng = rand::rng();
+        for _ in 0..300 {
+            play_random_hand(FIVE_CARD_DRAW, &mut rng);
+            play_random_hand(DEUCE_TO_SEVEN_TRIPLE_DRAW, &mut rng);
+        }
+    }
+
+    #[test]
+    fn test_random_stud_hands_conserve_chips() {
+        let mut rng = rand::rng();
+        for _ in 0..300 {
+            play_random_hand(SEVEN_CARD_STUD, &mut rng);
+            play_random_hand(SEVEN_CARD_STUD_HI_LO, &mut rng);
+            play_random_hand(RAZZ, &mut rng);
+        }
+    }
+
+    #[test]
+    fn test_random_board_hands_conserve_chips() {
+        let mut rng = rand::rng();
+        for _ in 0..300 {
+            play_random_hand(TEXAS_HOLD_EM, &mut rng);
+            play_random_hand(OMAHA, &mut rng);
+            play_random_hand(OMAHA_HI_LO, &mut rng);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! A fast poker hand evaluator built on precomputed rank tables.
+//! 
+//! Cards use the same numbering as the rest of the server (0-51, `card % 13 == 0` is an Ace,
+//! `card / 13` is the suit). `evaluate` scores any 5, 6 or 7 card hand as a single `u32`
+//! strength without allocating: a higher strength is a better hand and equal strengths are true ties.
+//! 
+//! The strength packs the same information as the tuple returned by `hand_type::get_hand_type`:
+//! the hand type sits above five 4-bit ranks (1 for a Two up to 13 for an Ace), so it can be
+//! converted to it with `to_hand_value`.
+//! 
+//! Straights and the top five ranks of every 13-bit rank mask are looked up in tables built
+//! once on first use, everything else is a handful of bit operations.
+use lazy_static::lazy_static;
+
+const RANK_MASKS: usize = 1 << 13;
+
+const HIGH_CARD: u32 = 0;
+const ONE_PAIR: u32 = 1;
+const TWO_PAIR: u32 = 2;
+const THREE_OF_A_KIND: u32 = 3;
+const STRAIGHT: u32 = 4;
+const FLUSH: u32 = 5;
+const FULL_HOUSE: u32 = 6;
+const FOUR_OF_A_KIND: u32 = 7;
+const STRAIGHT_FLUSH: u32 = 8;
+
+lazy_static! {
+    /// High rank of the best straight in a rank mask, 0 if it holds no straight.
+    static ref STRAIGHT_HIGH: Vec<u32> = (0..RANK_MASKS).map(|mask| straight_high(mask as u16)).collect();
+    /// The five highest ranks of a rank mask packed into nibbles, highest first.
+    static ref TOP_FIVE: Vec<u32> = (0..RANK_MASKS).map(|mask| top_five(mask as u16)).collect();
+}
+
+/// Finds the highest straight in a rank mask, including the A-2-3-4-5 wheel (five-high).
+fn straight_high(mask: u16) -> u32 {
+    for high in (5..=13u32).rev() {
+        let window = 0b11111u16 << (high - 5);
+        if mask & window == window {
+            return high;
+        }
+    }
+    let wheel = (1u16 << 12) | 0b1111;
+    if mask & wheel == wheel {
+        return 4;
+    }
+    0
+}
+
+/// Packs the five highest ranks of a rank mask, padding with 0 when it holds fewer.
+fn top_five(mut mask: u16) -> u32 {
+    let mut packed = 0;
+    for _ in 0..5 {
+        let rank = top_rank(mask);
+        mask = remove_rank(mask, rank);
+        packed = (packed << 4) | rank;
+    }
+    packed
+}
+
+/// Returns the highest rank (1-13) set in a rank mask, 0 if the mask is empty.
+fn top_rank(mask: u16) -> u32 {
+    16 - mask.leading_zeros()
+}
+
+/// Clears one rank (1-13) from a rank mask, rank 0 leaves the mask untouched.
+fn remove_rank(mask: u16, rank: u32) -> u16 {
+    if rank == 0 {
+        mask
+    } else {
+        mask & !(1 << (rank - 1))
+    }
+}
+
+/// Packs a hand type and up to five ranks into a strength.
+fn pack(hand_type: u32, ranks: [u32; 5]) -> u32 {
+    ranks.iter().fold(hand_type, |packed, &rank| (packed << 4) | rank)
+}
+
+/// Evaluates a 5, 6 or 7 card hand and returns the strength of the best five cards in it.
+/// 
+/// # Arguments
+/// * `cards` - The cards to evaluate (0-51). Face-down stud cards must be decoded first.
+/// 
+/// # Returns
+/// 
+/// The strength of the hand. Strengths of different hands compare the same way the hands do.
+pub fn evaluate(cards: &[i32]) -> u32 {
+    let mut suit_masks = [0u16; 4];
+    let mut rank_counts = [0u8; 13];
+    for &card in cards {
+        let bit = if card % 13 == 0 { 12 } else { card % 13 - 1 };
+        suit_masks[(card / 13) as usize] |= 1 << bit;
+        rank_counts[bit as usize] += 1;
+    }
+
+    // seven cards can't hold a flush and a full house or quads at the same time
+    for &mask in suit_masks.iter() {
+        if mask.count_ones() >= 5 {
+            let high = STRAIGHT_HIGH[mask as usize];
+            if high > 0 {
+                return pack(STRAIGHT_FLUSH, [high, high, 0, 0, 0]);
+            }
+            return (FLUSH << 20) | TOP_FIVE[mask as usize];
+        }
+    }
+
+    let mut rank_mask = 0u16;
+    let mut quads = 0u16;
+    let mut trips = 0u16;
+    let mut pairs = 0u16;
+    for (bit, &count) in rank_counts.iter().enumerate() {
+        match count {
+            0 => continue,
+            2 => pairs |= 1 << bit,
+            3 => trips |= 1 << bit,
+            4 => quads |= 1 << bit,
+            _ => {}
+        }
+        rank_mask |= 1 << bit;
+    }
+
+    if quads != 0 {
+        let quad = top_rank(quads);
+        let kicker = top_rank(remove_rank(rank_mask, quad));
+        return pack(FOUR_OF_A_KIND, [quad, kicker, 0, 0, 0]);
+    }
+
+    if trips != 0 {
+        let trip = top_rank(trips);
+        let pair = top_rank(pairs | remove_rank(trips, trip));
+        if pair != 0 {
+            return pack(FULL_HOUSE, [trip, pair, 0, 0, 0]);
+        }
+    }
+
+    let high = STRAIGHT_HIGH[rank_mask as usize];
+    if high > 0 {
+        return pack(STRAIGHT, [high, 0, 0, 0, 0]);
+    }
+
+    if trips != 0 {
+        let trip = top_rank(trips);
+        let kickers = remove_rank(rank_mask, trip);
+        let first = top_rank(kickers);
+        let second = top_rank(remove_rank(kickers, first));
+        return pack(THREE_OF_A_KIND, [trip, first, second, 0, 0]);
+    }
+
+    if pairs.count_ones() >= 2 {
+        let high_pair = top_rank(pairs);
+        let low_pair = top_rank(remove_rank(pairs, high_pair));
+        let kicker = top_rank(remove_rank(remove_rank(rank_mask, high_pair), low_pair));
+        return pack(TWO_PAIR, [high_pair, low_pair, kicker, 0, 0]);
+    }
+
+    if pairs != 0 {
+        let pair = top_rank(pairs);
+        let kickers = TOP_FIVE[remove_rank(rank_mask, pair) as usize] >> 8;
+        return (ONE_PAIR << 20) | (pair << 16) | (kickers << 4);
+    }
+
+    (HIGH_CARD << 20) | TOP_FIVE[rank_mask as usize]
+}
+
+/// Converts a strength into the `(hand_type, rank1, rank2, rank3, rank4, rank5)` tuple used by the games module.
+pub fn to_hand_value(strength: u32) -> (i32, i32, i32, i32, i32, i32) {
+    let rank = |shift: u32| ((strength >> shift) & 0xF) as i32;
+    ((strength >> 20) as i32, rank(16), rank(12), rank(8), rank(4), rank(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_straight_table() {
+        assert_eq!(STRAIGHT_HIGH[0b1_0000_0000_1111], 4); // A 2 3 4 5
+        assert_eq!(STRAIGHT_HIGH[0b1_1111_0000_0000], 13); // 10 J Q K A
+        assert_eq!(STRAIGHT_HIGH[0b1_1000_0000_0111], 0); // K A 2 3 4 does not wrap
+        assert_eq!(STRAIGHT_HIGH[0b0_0000_0011_1111], 6); // 2 to 7 plays 7 high
+    }
+
+    #[test]
+    fn test_evaluate_seven_cards() {
+        // 3 3 3 7 7 6 6: the full house uses the higher pair
+        let strength = evaluate(&[2, 15, 28, 45, 19, 5, 18]);
+        assert_eq!(to_hand_value(strength), (6, 2, 6, 0, 0, 0));
+        // 2 3 4 5 9 King of Hearts and 6 of Diamonds: the flush beats the straight
+        let strength = evaluate(&[1, 2, 3, 4, 18, 12, 8]);
+        assert_eq!(to_hand_value(strength), (5, 12, 8, 4, 3, 2));
+        // Ace to 5 of Hearts with a King of Hearts: five-high straight flush, not an Ace-high flush
+        let strength = evaluate(&[0, 1, 2, 3, 4, 12, 18]);
+        assert_eq!(to_hand_value(strength), (8, 4, 4, 0, 0, 0));
+    }
+}
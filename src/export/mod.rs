@@ -0,0 +1,327 @@
+//! Exports stored hands in formats other poker software reads.
+//!
+//! - `pokerstars` writes the text hand history PokerStars produces, which trackers such as Holdem Manager
+//!   and PokerTracker import.
+//! - `phh` writes the Poker Hand History standard, a TOML file used by research tools.
+//!
+//! Both are built from a `HandRecord` read back from the database. Every card in the hand is known,
+//! so the hole cards of every player are written out, not only those shown down.
+use crate::engine::{split_pot, FACE_DOWN};
+use crate::history::{ActionRecord, HandRecord, PotRecord, SeatRecord};
+use crate::lobby::{DEUCE_TO_SEVEN_TRIPLE_DRAW, FIVE_CARD_DRAW, RAZZ, SEVEN_CARD_STUD, SEVEN_CARD_STUD_HI_LO};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+mod phh;
+mod pokerstars;
+
+pub use phh::phh;
+pub use pokerstars::pokerstars;
+
+// Names of the export formats, as used in the HTTP route and on the command line
+pub const POKERSTARS: &str = "pokerstars";
+pub const PHH: &str = "phh";
+
+/// Renders a hand in the named format.
+///
+/// # Returns
+///
+/// The exported hand, or `None` if `format` is not `POKERSTARS` or `PHH`.
+pub fn export(hand: &HandRecord, format: &str) -> Option<String> {
+    match format {
+        POKERSTARS => Some(pokerstars(hand)),
+        PHH => Some(phh(hand)),
+        _ => None,
+    }
+}
+
+/// The two-character code of a card, rank then suit, e.g. `Ah` or `Td`.
+/// 7 Card Stud down-cards are written as the card itself.
+pub fn card_code(card: i32) -> String {
+    let card = if card >= FACE_DOWN { card - FACE_DOWN } else { card };
+    let rank = match card % 13 {
+        0 => 'A',
+        9 => 'T',
+        10 => 'J',
+        11 => 'Q',
+        12 => 'K',
+        r => char::from(b'1' + r as u8),
+    };
+    let suit = match card / 13 {
+        0 => 'h',
+        1 => 'd',
+        2 => 's',
+        _ => 'c',
+    };
+    format!("{}{}", rank, suit)
+}
+
+/// The codes of several cards joined by `separator`.
+fn card_codes(cards: &[i32], separator: &str) -> String {
+    cards.iter().map(|&card| card_code(card)).collect::<Vec<_>>().join(separator)
+}
+
+/// The cards a draw threw away and the cards it brought in, in hand order.
+fn exchanged_cards(before: &[i32], after: &[i32]) -> (Vec<i32>, Vec<i32>) {
+    let discarded = before.iter().copied().filter(|card| !after.contains(card)).collect();
+    let drawn = after.iter().copied().filter(|card| !before.contains(card)).collect();
+    (discarded, drawn)
+}
+
+/// The player's hand going into a draw: the hand their previous draw left them, or the cards of the first deal.
+fn hand_before_draw(hand: &HandRecord, draw: &ActionRecord) -> Vec<i32> {
+    let previous_draw = hand
+        .actions
+        .iter()
+        .rev()
+        .find(|action| action.seq < draw.seq && action.player == draw.player && action.action == "draw");
+    match previous_draw {
+        Some(action) => action.cards.clone(),
+        None => hand
+            .seats
+            .iter()
+            .find(|seat| seat.player == draw.player)
+            .map(|seat| seat.starting_hand.clone())
+            .unwrap_or_default(),
+    }
+}
+
+/// The seats dealt into the hand, starting with the first seat after the button.
+fn seats_in_deal_order(hand: &HandRecord) -> Vec<&SeatRecord> {
+    let count = hand.seats.len();
+    (0..count)
+        .map(|k| &hand.seats[(hand.button as usize + 1 + k) % count])
+        .filter(|seat| seat.dealt_in)
+        .collect()
+}
+
+/// The last street the hand reached: the last street any action was taken on, or any card was dealt on
+/// when the players were all in.
+fn last_street(hand: &HandRecord) -> i32 {
+    let dealt = match hand.game_type {
+        SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ => hand.seats.iter().map(|seat| seat.hole_cards.len() as i32 - 3).max().unwrap_or(0),
+        FIVE_CARD_DRAW | DEUCE_TO_SEVEN_TRIPLE_DRAW => 0,
+        _ => match hand.board.len() {
+            0..=2 => 0,
+            n => n as i32 - 2,
+        },
+    };
+    hand.actions.iter().map(|action| action.street).max().unwrap_or(0).max(dealt)
+}
+
+/// The players who took the hand to a showdown. Empty if everyone else folded.
+fn showdown_seats(hand: &HandRecord) -> Vec<&SeatRecord> {
+    let contenders: Vec<&SeatRecord> = hand.seats.iter().filter(|seat| seat.dealt_in && !seat.folded).collect();
+    if contenders.len() > 1 {
+        contenders
+    } else {
+        Vec::new()
+    }
+}
+
+/// How a pot was split between its winners, as the engine awards it with `split_pot`: the high winners' shares,
+/// then the low winners' shares in hi/lo games.
+fn pot_shares(pot: &PotRecord) -> Vec<(&str, i32)> {
+    let (high_shares, low_shares) = split_pot(pot.amount, pot.winners.len(), pot.low_winners.len());
+    let high = pot.winners.iter().map(String::as_str).zip(high_shares);
+    let low = pot.low_winners.iter().map(String::as_str).zip(low_shares);
+    high.chain(low).collect()
+}
+
+/// What a betting action did, worked out from the chips put in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Decision {
+    Check,
+    Call { amount: i32 },
+    /// The first bet of a street, `to` is the player's total for the street.
+    Bet { to: i32 },
+    Raise { by: i32, to: i32 },
+    Fold,
+}
+
+/// Follows the chips put in during a hand so each action can be written the way hand histories
+/// expect it, as a raise to a total for the street rather than as the chips put in.
+struct BetTracker {
+    street: i32,
+    street_bets: HashMap<String, i32>,
+    max_bet: i32,
+    stacks: HashMap<String, i32>,
+}
+
+impl BetTracker {
+    fn new(hand: &HandRecord) -> Self {
+        BetTracker {
+            street: 0,
+            street_bets: HashMap::new(),
+            max_bet: 0,
+            stacks: hand.seats.iter().map(|seat| (seat.player.clone(), seat.starting_stack)).collect(),
+        }
+    }
+
+    /// Records the chips of a forced bet or betting action.
+    ///
+    /// # Returns
+    ///
+    /// What the action did, and whether it put the player all in.
+    fn record(&mut self, action: &ActionRecord) -> (Decision, bool) {
+        if action.street != self.street {
+            self.street = action.street;
+            self.street_bets.clear();
+            self.max_bet = 0;
+        }
+        let stack = self.stacks.entry(action.player.clone()).or_insert(0);
+        *stack -= action.amount;
+        let all_in = *stack <= 0 && action.amount > 0;
+        if action.action == "ante" {
+            return (Decision::Call { amount: action.amount }, all_in);
+        }
+
+        let bet = self.street_bets.entry(action.player.clone()).or_insert(0);
+        *bet += action.amount;
+        let to = *bet;
+        let result = match action.action.as_str() {
+            "check" => Decision::Check,
+            "fold" => Decision::Fold,
+            _ if to <= self.max_bet => Decision::Call { amount: action.amount },
+            _ if self.max_bet == 0 => Decision::Bet { to },
+            _ => Decision::Raise { by: to - self.max_bet, to },
+        };
+        self.max_bet = self.max_bet.max(to);
+        (result, all_in)
+    }
+
+    /// The part of the last bet of the hand nobody called, and the player it goes back to.
+    fn uncalled_bet(&self) -> Option<(String, i32)> {
+        let mut bets: Vec<(&String, i32)> = self.street_bets.iter().map(|(player, &bet)| (player, bet)).collect();
+        bets.sort_by_key(|&(_, bet)| Reverse(bet));
+        let called = bets.get(1).map(|(_, bet)| *bet).unwrap_or(0);
+        match bets.first() {
+            Some((player, bet)) if *bet > called => Some((player.to_string(), bet - called)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deck::Deck;
+    use crate::engine::{Action, Engine};
+    use crate::lobby::{DRAW, OMAHA, TEXAS_HOLD_EM};
+
+    /// The card in the last brackets of a line, e.g. the card drawn in "Dealt to p0 [2h 3h 4h 5h] [6h]".
+    fn last_card(line: &str) -> &str {
+        line.rsplit('[').next().unwrap().trim_end_matches(']')
+    }
+
+    /// Plays a hand with the given actions, checking or calling whenever the list runs out.
+    pub(super) fn played_hand(game_type: i32, stacks: &[i32], mut actions: Vec<Action>) -> HandRecord {
+        let players = stacks.iter().enumerate().map(|(i, &stack)| (format!("p{}", i), stack)).collect();
+        let mut engine = Engine::new(game_type, players, Deck::new(), 0);
+        engine.start_hand();
+        actions.reverse();
+        while !engine.finished {
+            let name = engine.current_player_name().unwrap().to_string();
+            let action = match actions.pop() {
+                Some(action) => action,
+                None if engine.phase == DRAW => Action::DrawCards { indices: vec![] },
+                None if engine.call_amount() > 0 => Action::Call,
+                None => Action::Check,
+            };
+            engine.apply(&name, action).unwrap();
+        }
+        let mut hand = HandRecord::from_engine("test table", &engine);
+        hand.id = Some(7);
+        hand.played_at = Some("2026-10-15 20:30:00".to_string());
+        hand
+    }
+
+    #[test]
+    fn test_card_code() {
+        assert_eq!(card_code(0), "Ah");
+        assert_eq!(card_code(1), "2h");
+        assert_eq!(card_code(22), "Td");
+        assert_eq!(card_code(38), "Ks");
+        assert_eq!(card_code(49), "Jc");
+        assert_eq!(card_code(49 + FACE_DOWN), "Jc");
+    }
+
+    #[test]
+    fn test_bet_tracker_reads_raises_as_totals() {
+        let hand = played_hand(
+            TEXAS_HOLD_EM,
+            &[100, 100, 100],
+            vec![Action::Raise { amount: 30 }, Action::Raise { amount: 55 }, Action::Fold, Action::Call],
+        );
+        let mut tracker = BetTracker::new(&hand);
+        let bets: Vec<Decision> = hand.actions.iter().take(6).map(|action| tracker.record(action).0).collect();
+        assert_eq!(
+            bets,
+            vec![
+                Decision::Bet { to: 5 },
+                Decision::Raise { by: 5, to: 10 },
+                Decision::Raise { by: 20, to: 30 },
+                Decision::Raise { by: 30, to: 60 },
+                Decision::Fold,
+                Decision::Call { amount: 30 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let hand = played_hand(TEXAS_HOLD_EM, &[100, 100], vec![]);
+        assert!(export(&hand, POKERSTARS).unwrap().starts_with("PokerStars Hand #7:"));
+        assert!(export(&hand, PHH).unwrap().contains("variant = \"NT\""));
+        assert_eq!(export(&hand, "csv"), None);
+    }
+
+    #[test]
+    fn test_pot_shares_hi_lo() {
+        let pot = PotRecord {
+            amount: 101,
+            eligible: vec!["a".to_string(), "b".to_string()],
+            winners: vec!["a".to_string()],
+            low_winners: vec!["a".to_string(), "b".to_string()],
+        };
+        assert_eq!(pot_shares(&pot), vec![("a", 51), ("a", 25), ("b", 25)]);
+    }
+
+    #[test]
+    fn test_export_omaha() {
+        let hand = played_hand(OMAHA, &[100, 100], vec![]);
+        let history = export(&hand, POKERSTARS).unwrap();
+        assert!(history.starts_with("PokerStars Hand #7: Omaha Pot Limit (5/10)"));
+        let dealt = history.lines().find(|line| line.starts_with("Dealt to p0")).unwrap();
+        assert_eq!(dealt.len(), "Dealt to p0 [Ah Kd Qs Jc]".len());
+        assert!(export(&hand, PHH).unwrap().contains("variant = \"PO\""));
+    }
+
+    #[test]
+    fn test_export_triple_draw_discards_from_the_last_draw() {
+        let first_card = || Action::DrawCards { indices: vec![0] };
+        let hand = played_hand(
+            DEUCE_TO_SEVEN_TRIPLE_DRAW,
+            &[100, 100],
+            vec![Action::Check, Action::Check, first_card(), first_card(), Action::Check, Action::Check, first_card()],
+        );
+        let history = export(&hand, POKERSTARS).unwrap();
+        assert!(history.starts_with("PokerStars Hand #7: Triple Draw 2-7 Lowball No Limit (Ante 10)"));
+        assert!(history.contains("*** THIRD DRAW ***"));
+        // p0's second draw throws away the card the first draw brought in
+        let drawn: Vec<&str> = history.lines().filter(|line| line.starts_with("Dealt to p0 [") && line.contains("] [")).map(last_card).collect();
+        let discarded: Vec<&str> = history.lines().filter(|line| line.starts_with("p0: discards")).map(last_card).collect();
+        assert_eq!(discarded.len(), 2);
+        assert_eq!(discarded[1], drawn[0]);
+        assert!(export(&hand, PHH).unwrap().starts_with("variant = \"N2L3D\"\nantes = [10, 10]\n"));
+    }
+
+    #[test]
+    fn test_export_razz() {
+        let hand = played_hand(RAZZ, &[100, 100], vec![]);
+        assert!(export(&hand, POKERSTARS).unwrap().starts_with("PokerStars Hand #7: Razz No Limit (Bring-in 15)"));
+        let history = export(&hand, PHH).unwrap();
+        assert!(history.starts_with("variant = \"NR\""));
+        assert!(history.contains("bring_in = 15"));
+    }
+}
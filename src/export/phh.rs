@@ -0,0 +1,242 @@
+//! The Poker Hand History (PHH) format, a TOML file per hand.
+//!
+//! Players are numbered `p1`, `p2`, ... from the first seat after the button, so in Hold'em `p1` posts the
+//! small blind, or heads-up the big blind. The variant is the betting structure, `N`, `P` or `F`, followed by the game: `T` for Hold'em,
+//! `O` for Omaha, `O/8`, `7S`, `7S/8`, `R` for Razz, `FCD` for five card draw and `2L3D` for 2-7 Triple Draw.
+//! This gives the standard's own codes such as `NT`, `FT`, `PO` and `F7S`, and codes named after them for
+//! the combinations the standard has none for, such as `NFCD` or `N7S`.
+use super::{card_codes, exchanged_cards, hand_before_draw, last_street, seats_in_deal_order, showdown_seats, BetTracker, Decision};
+use crate::history::HandRecord;
+use crate::lobby::{
+    is_stud, DEUCE_TO_SEVEN_TRIPLE_DRAW, FIVE_CARD_DRAW, FIXED_LIMIT, OMAHA, OMAHA_HI_LO, POT_LIMIT, RAZZ,
+    SEVEN_CARD_STUD, SEVEN_CARD_STUD_HI_LO,
+};
+
+fn variant(game_type: i32, betting_structure: i32) -> String {
+    let structure = match betting_structure {
+        POT_LIMIT => "P",
+        FIXED_LIMIT => "F",
+        _ => "N",
+    };
+    let game = match game_type {
+        FIVE_CARD_DRAW => "FCD",
+        SEVEN_CARD_STUD => "7S",
+        OMAHA => "O",
+        OMAHA_HI_LO => "O/8",
+        SEVEN_CARD_STUD_HI_LO => "7S/8",
+        RAZZ => "R",
+        DEUCE_TO_SEVEN_TRIPLE_DRAW => "2L3D",
+        _ => "T",
+    };
+    format!("{}{}", structure, game)
+}
+
+/// A TOML string. JSON string escapes are valid in TOML basic strings.
+fn toml_string(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+fn toml_list<T: ToString>(values: &[T]) -> String {
+    format!("[{}]", values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(", "))
+}
+
+/// Renders a hand as a PHH file.
+pub fn phh(hand: &HandRecord) -> String {
+    let seats = seats_in_deal_order(hand);
+    let number = |player: &str| seats.iter().position(|seat| seat.player == player).map(|i| i + 1).unwrap_or(0);
+    let mut actions: Vec<String> = Vec::new();
+
+    let mut tracker = BetTracker::new(hand);
+    let mut hand_actions = hand.actions.iter().peekable();
+    for street in 0..=last_street(hand) {
+        // the cards dealt at the start of the street
+        match (hand.game_type, street) {
+            (FIVE_CARD_DRAW | DEUCE_TO_SEVEN_TRIPLE_DRAW, 0) => {
+                for (i, seat) in seats.iter().enumerate() {
+                    actions.push(format!("d dh p{} {}", i + 1, card_codes(&seat.starting_hand, "")));
+                }
+            }
+            (FIVE_CARD_DRAW | DEUCE_TO_SEVEN_TRIPLE_DRAW, _) => {}
+            (SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ, _) => {
+                let (from, to) = if street == 0 { (0, 3) } else { ((street + 2) as usize, (street + 3) as usize) };
+                for (i, seat) in seats.iter().enumerate() {
+                    if seat.hole_cards.len() >= to {
+                        actions.push(format!("d dh p{} {}", i + 1, card_codes(&seat.hole_cards[from..to], "")));
+                    }
+                }
+            }
+            (_, 0) => {
+                for (i, seat) in seats.iter().enumerate() {
+                    actions.push(format!("d dh p{} {}", i + 1, card_codes(&seat.hole_cards, "")));
+                }
+            }
+            (_, _) => {
+                let (from, to) = if street == 1 { (0, 3) } else { ((street + 1) as usize, (street + 2) as usize) };
+                if hand.board.len() >= to {
+                    actions.push(format!("d db {}", card_codes(&hand.board[from..to], "")));
+                }
+            }
+        }
+
+        while let Some(action) = hand_actions.next_if(|a| a.street <= street) {
+            let player = number(&action.player);
+            match action.action.as_str() {
+                "draw" => {
+                    let before = hand_before_draw(hand, action);
+                    let (discarded, drawn) = exchanged_cards(&before, &action.cards);
+                    if discarded.is_empty() {
+                        actions.push(format!("p{} sd", player));
+                    } else {
+                        actions.push(format!("p{} sd {}", player, card_codes(&discarded, "")));
+                        actions.push(format!("d dh p{} {}", player, card_codes(&drawn, "")));
+                    }
+                }
+                // antes and blinds are given by the `antes` and `blinds_or_straddles` fields
+                "ante" | "small_blind" | "big_blind" => {
+                    tracker.record(action);
+                }
+                "bring_in" => {
+                    tracker.record(action);
+                    actions.push(format!("p{} pb", player));
+                }
+                _ => {
+                    let line = match tracker.record(action).0 {
+                        Decision::Fold => format!("p{} f", player),
+                        Decision::Check | Decision::Call { .. } => format!("p{} cc", player),
+                        Decision::Bet { to } | Decision::Raise { to, .. } => format!("p{} cbr {}", player, to),
+                    };
+                    actions.push(line);
+                }
+            }
+        }
+    }
+    for seat in showdown_seats(hand) {
+        actions.push(format!("p{} sm {}", number(&seat.player), card_codes(&seat.hole_cards, "")));
+    }
+
+    let count = seats.len();
+    let antes: Vec<i32> = vec![hand.ante; count];
+    // heads-up the button posts the small blind, and a small blind can be dead, so blinds are taken from the actions
+    let blinds: Vec<i32> = seats
+        .iter()
+        .map(|seat| {
+            hand.actions
+                .iter()
+                .filter(|a| a.player == seat.player && a.action.ends_with("_blind"))
+                .map(|a| a.amount)
+                .sum()
+        })
+        .collect();
+
+    let mut lines = Vec::new();
+    lines.push(format!("variant = {}", toml_string(&variant(hand.game_type, hand.betting_structure))));
+    lines.push(format!("antes = {}", toml_list(&antes)));
+    lines.push(format!("blinds_or_straddles = {}", toml_list(&blinds)));
+    if is_stud(hand.game_type) {
+        lines.push(format!("bring_in = {}", hand.bring_in));
+    }
+    if hand.betting_structure == FIXED_LIMIT {
+        lines.push(format!("small_bet = {}", hand.big_blind));
+        lines.push(format!("big_bet = {}", 2 * hand.big_blind));
+    } else {
+        lines.push(format!("min_bet = {}", hand.big_blind));
+    }
+    let starting_stacks: Vec<i32> = seats.iter().map(|seat| seat.starting_stack).collect();
+    lines.push(format!("starting_stacks = {}", toml_list(&starting_stacks)));
+    lines.push("actions = [".to_string());
+    for action in actions.iter() {
+        lines.push(format!("  {},", toml_string(action)));
+    }
+    lines.push("]".to_string());
+
+    let players: Vec<String> = seats.iter().map(|seat| toml_string(&seat.player)).collect();
+    lines.push(format!("players = {}", toml_list(&players)));
+    let finishing_stacks: Vec<i32> = seats.iter().map(|seat| seat.final_stack).collect();
+    lines.push(format!("finishing_stacks = {}", toml_list(&finishing_stacks)));
+    let seat_numbers: Vec<i32> = seats.iter().map(|seat| seat.seat + 1).collect();
+    lines.push(format!("seats = {}", toml_list(&seat_numbers)));
+    lines.push(format!("table = {}", toml_string(&hand.lobby_name)));
+    if let Some(id) = hand.id {
+        lines.push(format!("hand = {}", id));
+    }
+    // played_at is "YYYY-MM-DD HH:MM:SS" in UTC
+    if let Some((date, time)) = hand.played_at.as_deref().and_then(|played_at| played_at.split_once(' ')) {
+        let parts: Vec<&str> = date.split('-').collect();
+        if let [year, month, day] = parts.as_slice() {
+            lines.push(format!("year = {}", year.parse::<i32>().unwrap_or(0)));
+            lines.push(format!("month = {}", month.parse::<i32>().unwrap_or(0)));
+            lines.push(format!("day = {}", day.parse::<i32>().unwrap_or(0)));
+        }
+        lines.push(format!("time = {}", time));
+        lines.push("time_zone = \"UTC\"".to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::played_hand;
+    use super::*;
+    use crate::engine::Action;
+    use crate::lobby::{NO_LIMIT, TEXAS_HOLD_EM};
+
+    fn actions(history: &str) -> Vec<String> {
+        history
+            .lines()
+            .skip_while(|line| *line != "actions = [")
+            .skip(1)
+            .take_while(|line| *line != "]")
+            .map(|line| line.trim().trim_end_matches(',').trim_matches('"').to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_hold_em_phh() {
+        let history = phh(&played_hand(TEXAS_HOLD_EM, &[100, 200, 300], vec![Action::Raise { amount: 30 }, Action::Fold]));
+        assert!(history.starts_with("variant = \"NT\"\nantes = [0, 0, 0]\nblinds_or_straddles = [5, 10, 0]\n"));
+        assert!(history.contains("starting_stacks = [100, 200, 300]"));
+        assert!(history.contains("players = [\"p0\", \"p1\", \"p2\"]"));
+        assert!(history.contains("year = 2026\nmonth = 10\nday = 15\ntime = 20:30:00\n"));
+        let actions = actions(&history);
+        assert!(actions[..3].iter().all(|action| action.starts_with("d dh p")));
+        assert_eq!(actions[3..6], ["p3 cbr 30", "p1 f", "p2 cc"]);
+        assert!(actions[6].starts_with("d db ") && actions[6].len() == "d db AhKdQs".len());
+        assert_eq!(actions.iter().filter(|action| action.starts_with("d db ")).count(), 3);
+        assert!(actions.last().unwrap().starts_with("p3 sm "));
+    }
+
+    #[test]
+    fn test_variants() {
+        assert_eq!(variant(TEXAS_HOLD_EM, FIXED_LIMIT), "FT");
+        assert_eq!(variant(OMAHA_HI_LO, FIXED_LIMIT), "FO/8");
+        assert_eq!(variant(SEVEN_CARD_STUD, FIXED_LIMIT), "F7S");
+        assert_eq!(variant(DEUCE_TO_SEVEN_TRIPLE_DRAW, FIXED_LIMIT), "F2L3D");
+        assert_eq!(variant(OMAHA, POT_LIMIT), "PO");
+        assert_eq!(variant(FIVE_CARD_DRAW, NO_LIMIT), "NFCD");
+    }
+
+    #[test]
+    fn test_five_card_draw_phh() {
+        let history = phh(&played_hand(
+            FIVE_CARD_DRAW,
+            &[100, 100],
+            vec![Action::Check, Action::Check, Action::DrawCards { indices: vec![0, 1] }, Action::DrawCards { indices: vec![] }],
+        ));
+        assert!(history.starts_with("variant = \"NFCD\"\nantes = [10, 10]\n"));
+        let actions = actions(&history);
+        // p0 acts first, so the button is on seat 2 and p0 is p1
+        assert_eq!(actions[2..4], ["p1 cc", "p2 cc"]);
+        assert!(actions[4].starts_with("p1 sd ") && actions[4].len() == "p1 sd AhKd".len());
+        assert!(actions[5].starts_with("d dh p1 ") && actions[5].len() == "d dh p1 AhKd".len());
+        assert_eq!(actions[6], "p2 sd");
+    }
+
+    #[test]
+    fn test_seven_card_stud_phh() {
+        let history = phh(&played_hand(SEVEN_CARD_STUD, &[100, 100], vec![]));
+        assert!(history.contains("bring_in = 15"));
+        let actions = actions(&history);
+        assert_eq!(actions.iter().filter(|action| action.starts_with("d dh p1 ")).count(), 5);
+        assert_eq!(actions.iter().filter(|action| action.ends_with(" pb")).count(), 1);
+    }
+}
@@ -0,0 +1,320 @@
+//! The PokerStars text hand history format.
+use super::{card_codes, exchanged_cards, hand_before_draw, last_street, pot_shares, showdown_seats, BetTracker, Decision};
+use crate::history::{HandRecord, SeatRecord};
+use crate::lobby::{
+    is_draw, is_stud, DEUCE_TO_SEVEN_TRIPLE_DRAW, FIVE_CARD_DRAW, FIXED_LIMIT, OMAHA, OMAHA_HI_LO, POT_LIMIT, RAZZ,
+    SEVEN_CARD_STUD, SEVEN_CARD_STUD_HI_LO,
+};
+
+/// The game and stakes as PokerStars writes them in the first line of a hand.
+/// Fixed-limit games give the small and big bet as their stakes.
+fn game_name(hand: &HandRecord) -> String {
+    let game = match hand.game_type {
+        FIVE_CARD_DRAW => "5 Card Draw",
+        SEVEN_CARD_STUD => "7 Card Stud",
+        OMAHA => "Omaha",
+        OMAHA_HI_LO => "Omaha Hi/Lo",
+        SEVEN_CARD_STUD_HI_LO => "7 Card Stud Hi/Lo",
+        RAZZ => "Razz",
+        DEUCE_TO_SEVEN_TRIPLE_DRAW => "Triple Draw 2-7 Lowball",
+        _ => "Hold'em",
+    };
+    let limit = match hand.betting_structure {
+        POT_LIMIT => "Pot Limit",
+        FIXED_LIMIT => "Limit",
+        _ => "No Limit",
+    };
+    let stakes = if hand.betting_structure == FIXED_LIMIT {
+        format!("{}/{}", hand.big_blind, 2 * hand.big_blind)
+    } else if is_draw(hand.game_type) {
+        format!("Ante {}", hand.ante)
+    } else if is_stud(hand.game_type) {
+        format!("Bring-in {}", hand.bring_in)
+    } else {
+        format!("{}/{}", hand.small_blind, hand.big_blind)
+    };
+    format!("{} {} ({})", game, limit, stakes)
+}
+
+/// The most players a lobby of the game seats.
+fn table_size(game_type: i32) -> i32 {
+    match game_type {
+        FIVE_CARD_DRAW | DEUCE_TO_SEVEN_TRIPLE_DRAW => 5,
+        SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ => 7,
+        _ => 10,
+    }
+}
+
+/// The name of a street in the summary, e.g. "folded on the Flop".
+fn street_name(game_type: i32, street: i32) -> &'static str {
+    match (game_type, street) {
+        (FIVE_CARD_DRAW, 0) => "before the Draw",
+        (FIVE_CARD_DRAW, _) => "after the Draw",
+        (DEUCE_TO_SEVEN_TRIPLE_DRAW, 0) => "before the 1st Draw",
+        (DEUCE_TO_SEVEN_TRIPLE_DRAW, 1) => "after the 1st Draw",
+        (DEUCE_TO_SEVEN_TRIPLE_DRAW, 2) => "after the 2nd Draw",
+        (DEUCE_TO_SEVEN_TRIPLE_DRAW, _) => "after the 3rd Draw",
+        (SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ, 0) => "on the 3rd Street",
+        (SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ, 1) => "on the 4th Street",
+        (SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ, 2) => "on the 5th Street",
+        (SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ, 3) => "on the 6th Street",
+        (SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ, _) => "on the River",
+        (_, 0) => "before Flop",
+        (_, 1) => "on the Flop",
+        (_, 2) => "on the Turn",
+        _ => "on the River",
+    }
+}
+
+/// The header of a street and the cards dealt on it.
+fn deal_street(hand: &HandRecord, street: i32, lines: &mut Vec<String>) {
+    let dealt_in: Vec<&SeatRecord> = hand.seats.iter().filter(|seat| seat.dealt_in).collect();
+    match hand.game_type {
+        FIVE_CARD_DRAW | DEUCE_TO_SEVEN_TRIPLE_DRAW => {
+            if street == 0 {
+                lines.push("*** DEALING HANDS ***".to_string());
+                for seat in dealt_in {
+                    lines.push(format!("Dealt to {} [{}]", seat.player, card_codes(&seat.starting_hand, " ")));
+                }
+            } else {
+                let header = ["*** FIRST DRAW ***", "*** SECOND DRAW ***", "*** THIRD DRAW ***"];
+                lines.push(header[(street - 1).clamp(0, 2) as usize].to_string());
+            }
+        }
+        SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ => {
+            let header = ["*** 3rd STREET ***", "*** 4th STREET ***", "*** 5th STREET ***", "*** 6th STREET ***", "*** RIVER ***"];
+            lines.push(header[street.clamp(0, 4) as usize].to_string());
+            // three cards on 3rd street, then one per street to the players still in
+            let dealt = (street + 2) as usize;
+            for seat in dealt_in {
+                if street == 0 {
+                    lines.push(format!("Dealt to {} [{}]", seat.player, card_codes(&seat.hole_cards[..seat.hole_cards.len().min(3)], " ")));
+                } else if seat.hole_cards.len() > dealt {
+                    lines.push(format!(
+                        "Dealt to {} [{}] [{}]",
+                        seat.player,
+                        card_codes(&seat.hole_cards[..dealt], " "),
+                        card_codes(&seat.hole_cards[dealt..dealt + 1], " ")
+                    ));
+                }
+            }
+        }
+        _ => {
+            let board = &hand.board;
+            match street {
+                0 => {
+                    lines.push("*** HOLE CARDS ***".to_string());
+                    for seat in dealt_in {
+                        lines.push(format!("Dealt to {} [{}]", seat.player, card_codes(&seat.hole_cards, " ")));
+                    }
+                }
+                1 if board.len() >= 3 => lines.push(format!("*** FLOP *** [{}]", card_codes(&board[..3], " "))),
+                2 if board.len() >= 4 => {
+                    lines.push(format!("*** TURN *** [{}] [{}]", card_codes(&board[..3], " "), card_codes(&board[3..4], " ")))
+                }
+                3 if board.len() >= 5 => {
+                    lines.push(format!("*** RIVER *** [{}] [{}]", card_codes(&board[..4], " "), card_codes(&board[4..5], " ")))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Renders a hand as a PokerStars hand history.
+///
+/// Play chips are written without a currency and the time is the server's UTC time.
+pub fn pokerstars(hand: &HandRecord) -> String {
+    let mut lines = Vec::new();
+    let played_at = hand.played_at.clone().unwrap_or_default().replace('-', "/");
+    lines.push(format!(
+        "PokerStars Hand #{}: {} - {} UTC",
+        hand.id.unwrap_or(0),
+        game_name(hand),
+        played_at
+    ));
+    let button = if is_stud(hand.game_type) { String::new() } else { format!(" Seat #{} is the button", hand.button + 1) };
+    lines.push(format!("Table '{}' {}-max{}", hand.lobby_name, table_size(hand.game_type), button));
+    for seat in hand.seats.iter() {
+        let sitting_out = if seat.dealt_in { "" } else { " is sitting out" };
+        lines.push(format!("Seat {}: {} ({} in chips){}", seat.seat + 1, seat.player, seat.starting_stack, sitting_out));
+    }
+
+    let mut tracker = BetTracker::new(hand);
+    let mut actions = hand.actions.iter().peekable();
+    // antes and blinds come before the cards
+    while let Some(action) = actions.next_if(|a| a.action == "ante" || a.action.ends_with("_blind")) {
+        let (_, all_in) = tracker.record(action);
+        let posted = match action.action.as_str() {
+            "ante" => format!("{}: posts the ante {}", action.player, action.amount),
+            "small_blind" => format!("{}: posts small blind {}", action.player, action.amount),
+            _ => format!("{}: posts big blind {}", action.player, action.amount),
+        };
+        lines.push(if all_in { format!("{} and is all-in", posted) } else { posted });
+    }
+
+    for street in 0..=last_street(hand) {
+        deal_street(hand, street, &mut lines);
+        while let Some(action) = actions.next_if(|a| a.street == street) {
+            if action.action == "draw" {
+                let before = hand_before_draw(hand, action);
+                let (discarded, drawn) = exchanged_cards(&before, &action.cards);
+                if discarded.is_empty() {
+                    lines.push(format!("{}: stands pat", action.player));
+                } else {
+                    let kept: Vec<i32> = before.iter().copied().filter(|card| !discarded.contains(card)).collect();
+                    lines.push(format!("{}: discards {} cards [{}]", action.player, discarded.len(), card_codes(&discarded, " ")));
+                    lines.push(format!("Dealt to {} [{}] [{}]", action.player, card_codes(&kept, " "), card_codes(&drawn, " ")));
+                }
+                continue;
+            }
+            let (bet, all_in) = tracker.record(action);
+            let line = match (action.action.as_str(), bet) {
+                ("bring_in", _) => format!("{}: brings in for {}", action.player, action.amount),
+                (_, Decision::Check) => format!("{}: checks", action.player),
+                (_, Decision::Fold) => format!("{}: folds", action.player),
+                (_, Decision::Call { amount }) => format!("{}: calls {}", action.player, amount),
+                (_, Decision::Bet { to }) => format!("{}: bets {}", action.player, to),
+                (_, Decision::Raise { by, to }) => format!("{}: raises {} to {}", action.player, by, to),
+            };
+            lines.push(if all_in { format!("{} and is all-in", line) } else { line });
+        }
+    }
+
+    let uncalled = tracker.uncalled_bet();
+    let returned_to = |player: &str| match &uncalled {
+        Some((name, amount)) if name == player => *amount,
+        _ => 0,
+    };
+    if let Some((player, amount)) = &uncalled {
+        lines.push(format!("Uncalled bet ({}) returned to {}", amount, player));
+    }
+
+    let shown = showdown_seats(hand);
+    if !shown.is_empty() {
+        lines.push("*** SHOW DOWN ***".to_string());
+        for seat in shown.iter() {
+            lines.push(format!("{}: shows [{}]", seat.player, card_codes(&seat.hole_cards, " ")));
+        }
+    }
+    // the uncalled bet sits in the last pot the player won, it was returned rather than collected
+    let mut shares: Vec<Vec<(&str, i32)>> = hand.pots.iter().map(pot_shares).collect();
+    let mut to_return = uncalled.as_ref().map(|(_, amount)| *amount).unwrap_or(0);
+    for pot in shares.iter_mut().rev() {
+        for (winner, share) in pot.iter_mut() {
+            if returned_to(winner) > 0 {
+                let returned = to_return.min(*share);
+                *share -= returned;
+                to_return -= returned;
+            }
+        }
+    }
+    for (i, pot) in shares.iter().enumerate() {
+        let pot_name = if i == 0 { "pot".to_string() } else { format!("side pot-{}", i) };
+        for (winner, share) in pot.iter().filter(|(_, share)| *share > 0) {
+            lines.push(format!("{} collected {} from {}", winner, share, pot_name));
+        }
+    }
+
+    lines.push("*** SUMMARY ***".to_string());
+    let total: i32 = hand.pots.iter().map(|pot| pot.amount).sum::<i32>() - uncalled.as_ref().map(|(_, amount)| *amount).unwrap_or(0);
+    lines.push(format!("Total pot {} | Rake 0", total));
+    if !hand.board.is_empty() {
+        lines.push(format!("Board [{}]", card_codes(&hand.board, " ")));
+    }
+    for seat in hand.seats.iter().filter(|seat| seat.dealt_in) {
+        let button = if !is_stud(hand.game_type) && seat.seat == hand.button { " (button)" } else { "" };
+        let result = if seat.folded {
+            let street = hand
+                .actions
+                .iter()
+                .find(|a| a.player == seat.player && a.action == "fold")
+                .map(|a| a.street)
+                .unwrap_or(0);
+            format!("folded {}", street_name(hand.game_type, street))
+        } else if shown.is_empty() {
+            format!("collected ({})", seat.won - returned_to(&seat.player))
+        } else if seat.won > returned_to(&seat.player) {
+            format!("showed [{}] and won ({})", card_codes(&seat.hole_cards, " "), seat.won - returned_to(&seat.player))
+        } else {
+            format!("showed [{}] and lost", card_codes(&seat.hole_cards, " "))
+        };
+        lines.push(format!("Seat {}: {}{} {}", seat.seat + 1, seat.player, button, result));
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::played_hand;
+    use super::*;
+    use crate::engine::Action;
+    use crate::lobby::{NO_LIMIT, TEXAS_HOLD_EM};
+
+    fn name(game_type: i32, betting_structure: i32) -> String {
+        let mut hand = played_hand(game_type, &[100, 100], vec![]);
+        hand.betting_structure = betting_structure;
+        game_name(&hand)
+    }
+
+    #[test]
+    fn test_game_names() {
+        assert_eq!(name(TEXAS_HOLD_EM, NO_LIMIT), "Hold'em No Limit (5/10)");
+        assert_eq!(name(TEXAS_HOLD_EM, FIXED_LIMIT), "Hold'em Limit (10/20)");
+        assert_eq!(name(OMAHA_HI_LO, POT_LIMIT), "Omaha Hi/Lo Pot Limit (5/10)");
+        assert_eq!(name(SEVEN_CARD_STUD, FIXED_LIMIT), "7 Card Stud Limit (10/20)");
+        assert_eq!(name(FIVE_CARD_DRAW, POT_LIMIT), "5 Card Draw Pot Limit (Ante 10)");
+
+        let mut hand = played_hand(TEXAS_HOLD_EM, &[1000, 1000], vec![]);
+        hand.small_blind = 25;
+        hand.big_blind = 50;
+        assert_eq!(game_name(&hand), "Hold'em No Limit (25/50)");
+    }
+
+    #[test]
+    fn test_hold_em_history() {
+        let history = pokerstars(&played_hand(TEXAS_HOLD_EM, &[100, 100, 100], vec![Action::Raise { amount: 30 }, Action::Fold]));
+        let lines: Vec<&str> = history.lines().collect();
+        assert_eq!(lines[0], "PokerStars Hand #7: Hold'em No Limit (5/10) - 2026/10/15 20:30:00 UTC");
+        assert_eq!(lines[1], "Table 'test table' 10-max Seat #3 is the button");
+        assert_eq!(lines[2], "Seat 1: p0 (100 in chips)");
+        assert_eq!(lines[5], "p0: posts small blind 5");
+        assert_eq!(lines[6], "p1: posts big blind 10");
+        assert_eq!(lines[7], "*** HOLE CARDS ***");
+        assert_eq!(lines[11], "p2: raises 20 to 30");
+        assert_eq!(lines[12], "p0: folds");
+        assert_eq!(lines[13], "p1: calls 20");
+        assert_eq!(lines[14].split(" [").next(), Some("*** FLOP ***"));
+        assert!(history.contains("*** SHOW DOWN ***"));
+        assert!(history.contains("Total pot 65 | Rake 0"));
+        assert!(history.contains("Seat 1: p0 folded before Flop"));
+    }
+
+    #[test]
+    fn test_five_card_draw_history() {
+        let history = pokerstars(&played_hand(
+            FIVE_CARD_DRAW,
+            &[100, 100],
+            vec![Action::Check, Action::Check, Action::DrawCards { indices: vec![0, 1] }, Action::DrawCards { indices: vec![] }, Action::AllIn, Action::Fold],
+        ));
+        assert!(history.starts_with("PokerStars Hand #7: 5 Card Draw No Limit (Ante 10)"));
+        assert!(history.contains("p0: posts the ante 10\np1: posts the ante 10\n*** DEALING HANDS ***\n"));
+        assert!(history.contains("*** FIRST DRAW ***\np0: discards 2 cards ["));
+        assert!(history.contains("p1: stands pat"));
+        assert!(history.contains("p0: bets 90 and is all-in"));
+        assert!(history.contains("Uncalled bet (90) returned to p0\np0 collected 20 from pot"));
+        assert!(!history.contains("*** SHOW DOWN ***"));
+        assert!(history.contains("Seat 1: p0 collected (20)"));
+        assert!(history.contains("Seat 2: p1 (button) folded after the Draw"));
+    }
+
+    #[test]
+    fn test_seven_card_stud_history() {
+        let history = pokerstars(&played_hand(SEVEN_CARD_STUD, &[100, 100], vec![]));
+        assert!(!history.contains("is the button"));
+        assert!(history.contains("*** 3rd STREET ***"));
+        assert!(history.contains("brings in for 15"));
+        assert!(history.contains("*** RIVER ***"));
+        assert_eq!(history.lines().filter(|line| line.starts_with("Dealt to p0")).count(), 5);
+    }
+}
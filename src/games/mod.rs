@@ -1,10 +1,10 @@
 //! # Poker Game Logic Module
 //!
-//! This module implements the core game logic for various poker games, including Five Card Draw, Seven Card Stud, and Texas Hold'em. 
+//! This module implements the core game logic for various poker games, including Five Card Draw, Seven Card Stud, Texas Hold'em and Pot-Limit Omaha. 
 //! It is designed to handle all aspects of gameplay, such as dealing cards, managing betting rounds, determining winners, and updating game states.
 //!
 //! ## Features
-//! - **Game State Management**: Implements state machines for each poker variant to control the flow of the game.
+//! - **Game State Management**: Connects each player's WebSocket to their lobby's `table`, which runs the hand for every poker variant.
 //! - **Card Dealing**: Handles the distribution of cards to players, including community cards for games like Texas Hold'em.
 //! - **Betting Rounds**: Manages betting rounds, including actions like checking, raising, calling, folding, and going all-in.
 //! - **Hand Evaluation**: Determines the best hand for each player and ranks them to decide the winner.
@@ -19,6 +19,10 @@
 //! - **Five Card Draw**: A classic poker game where players are dealt five cards and can exchange cards during a drawing round.
 //! - **Seven Card Stud**: A poker game where players are dealt seven cards, with a mix of face-up and face-down cards, and must form the best five-card hand.
 //! - **Texas Hold'em**: A popular poker variant where players are dealt two private cards and share five community cards.
+//! - **Pot-Limit Omaha**: Played like Texas Hold'em with four private cards, of which every hand uses exactly two, and bets capped at the size of the pot.
+//! - **Hi/Lo**: Pot-Limit Omaha and Seven Card Stud can also be played with each pot split between the best high hand and the best 8-or-better low.
+//! - **Razz**: Seven Card Stud where the best ace-to-five low wins the whole pot.
+//! - **2-7 Triple Draw**: Five card draw with three draws, won by the best deuce-to-seven low.
 //!
 //! ## Game Flow
 //! Each poker variant follows a specific sequence of game states, such as:
@@ -53,359 +57,16 @@
 use super::*;
 use crate::lobby::{self, Lobby};
 use crate::player::{self, Player};
+use crate::protocol::{self, PotResult, ServerMessage, Showdown, ShownHand, Stats};
+use crate::engine::{split_pot, Action, Event, Pot, ShowdownHand};
+use crate::evaluator;
+use crate::hand_type::get_hand_type;
+use crate::table::TableCommand;
+use crate::tournament::mtt;
 use std::sync::Arc;
-use tokio::sync::{mpsc::UnboundedSender, Mutex};
-use warp:: ws::Message;
-
-// Method return defintions
-pub const SUCCESS: i32 = 100;
-pub const FAILED: i32 = 101;
-pub const SERVER_FULL: i32 = 102;
-pub const GAME_LOBBY_EMPTY: i32 = 103;
-pub const GAME_LOBBY_NOT_EMPTY: i32 = 104;
-pub const GAME_LOBBY_FULL: i32 = 105;
-
-pub const FIVE_CARD_DRAW: i32 = 10;
-pub const SEVEN_CARD_STUD: i32 = 11;
-pub const TEXAS_HOLD_EM: i32 = 12;
-pub const NOT_SET: i32 = 13;
-
-const SMALL_BLIND: i32 = 5;
-const BIG_BLIND: i32 = 10;
-
-/// Deals cards to players in a 7 Card Stud game.
-/// The first two cards are face-down, the third card is face-up.
-/// The last card is face-down.
-/// The function also handles the display of hands to active players.
-/// 
-/// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
-/// * `round` - The current round of the game (0 for the first round, 1 for the last card).
-/// 
-/// # Returns
-/// This function does not return a value. It updates the players' hands and displays them to active players.
-async fn deal_cards_7(lobby: &mut Lobby, round: usize) {
-    let mut players = lobby.players.lock().await;
-    let mut count;
-    for player in players.iter_mut() {
-        count = 0;
-        loop {
-            if player.state != player::FOLDED {
-                let card = lobby.deck.deal();
-                let card_value = if round == 1 {
-                    // First two cards face-down, third card face-up
-                    if player.hand.len() < 2 {
-                        card + 53 // First two cards are face-down
-                    } else {
-                        card // Third card is face-up
-                    }
-                } else if round == 5 {
-                    card + 53 // Last card is face-down
-                } else {
-                    card // All other rounds are face-up
-                };
-
-                player.hand.push(card_value);
-                if round == 1 {
-                    count += 1;
-                    if count == 3 {
-                        break;
-                    }
-                }
-                else {break}
-            }
-        }
-    }
-    // Get active players
-    let active_players: Vec<_> = players.iter().filter(|p| p.state != player::FOLDED).collect();
-    let players_tx: Vec<_> = active_players.iter().map(|p| p.tx.clone()).collect();
-    let players_hands: Vec<_> = active_players.iter().map(|p| p.hand.clone()).collect();
-    display_hand(players_tx, players_hands).await;
-}
-
-/// Deals cards to players in a Texas Hold'em game.
-/// The function handles the dealing of community cards and player hands based on the round.
-/// 
-/// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
-/// * `round` - The current round of the game (1 for pre-flop, 2 for flop, etc.).
-/// 
-/// # Returns
-/// This function does not return a value. It updates the players' hands and community cards, and displays them to active players.
-/// It also handles the display of hands to active players.
-pub async fn deal_cards_texas(lobby: &mut Lobby, round: usize) {
-    let mut players = lobby.players.lock().await;
-    let players_tx = players.iter().filter(|p| p.state != player::FOLDED).map(|p| p.tx.clone()).collect::<Vec<_>>();
-    match round {
-        0 => {
-            println!("dealing for round 0");
-            // let mut player = lobby.players.lock().await[lobby.current_player_index as usize];
-            if lobby.players.lock().await[lobby.current_player_index as usize].state != player::FOLDED {
-                // deal 2 cards to each player
-                lobby.players.lock().await[lobby.current_player_index as usize].hand.push(lobby.deck.deal());
-                lobby.players.lock().await[lobby.current_player_index as usize].hand.push(lobby.deck.deal());
-            }
-            println!("players hand {:?}", lobby.players.lock().await[lobby.current_player_index as usize].hand);
-            return;
-        }
-        1 => {
-            // for flop round, deals 3 community
-            for _ in 0..3 {
-                lobby.community_cards.push(lobby.deck.deal());
-            }
-        }
-        _ => {
-            // any other round the same
-            lobby.community_cards.push(lobby.deck.deal());
-        }
-    }
-    let players_tx = players.iter().filter(|p| p.state != player::FOLDED).map(|p| p.tx.clone()).collect::<Vec<_>>();
-    let mut message = String::from("Community cards:\n");
-    for (i, card) in lobby.community_cards.iter().enumerate() {
-        message.push_str(&format!("{}. {}\n", i + 1, translate_card(*card).await));
-    }
-    lobby.lobby_wide_send(players_tx.clone(), message).await;
-}
-
-/// Handles the betting round for players in a poker game.
-/// The function manages player actions such as checking, raising, calling, folding, and going all-in.
-/// It also updates the game state and player statistics.
-/// 
-/// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
-/// 
-/// # Returns
-/// 
-/// This function does not return a value. It updates the players' wallets and game statistics.
-/// It also handles the display of hands to active players.
-pub async fn betting_round(player: &mut Player, lobby: &mut tokio::sync::MutexGuard<'_, lobby::Lobby,>, action: ClientMessage) -> (bool, bool) {
-    let mut valid_action = true;
-    let mut reset = false;
-    let player_prev_bet = player.current_bet;
-    let current_max_bet = lobby.current_max_bet;
-
-    match action {
-        ClientMessage::Check => {
-            println!("{}: check command received", player.name);
-            // only check when there is no bet to call
-            if lobby.call_amount == 0 {
-                player.tx.send(Message::text(r#"{"message": "Checked"}"#)).unwrap();
-                player.state = player::CHECKED;
-                return (valid_action, reset);
-            } else {
-                valid_action = false;
-                return (valid_action, reset);
-            }
-        }
-        ClientMessage::Fold => {
-            println!("{}: fold command received", player.name);
-            player.state = player::FOLDED;
-            return (valid_action, reset);
-        }
-        ClientMessage::Call => {
-            println!("{}: call command received", player.name);
-            let call_amount = current_max_bet - player_prev_bet;
-            if player.wallet >= call_amount {
-                player.wallet -= call_amount;
-                lobby.pot += call_amount;
-                player.current_bet = current_max_bet;
-                if player.wallet == 0 {
-                    player.state = player::ALL_IN;
-                } else {
-                    player.state = player::CALLED;
-                }
-                return (valid_action, reset);
-            } else {
-                valid_action = false;
-                return (valid_action,reset);
-            }
-        }
-        ClientMessage::Raise { amount } => {
-            println!("{}: raise command received", player.name);
-            if amount > 0 && amount <= player.wallet {
-                if amount > current_max_bet - player_prev_bet {
-                    player.state = player::RAISED;
-                    player.wallet -= amount;
-                    lobby.pot += amount;
-                    player.current_bet += amount;
-                    lobby.current_max_bet = player.current_bet;
-                    reset = true;
-                    if player.wallet == 0 {
-                        player.state = player::ALL_IN;
-                    }
-                    return (valid_action, reset);
-                }
-            }
-            valid_action = false;
-            return (valid_action, reset);
-        }
-        ClientMessage::AllIn => {
-            println!("{}: all in command received", player.name);
-            player.state = player::ALL_IN;
-            let all_in_amount = player.wallet;
-            player.wallet = 0;
-            player.current_bet += all_in_amount;
-            lobby.pot += all_in_amount;
-            if player.current_bet > current_max_bet{
-                lobby.current_max_bet = player.current_bet;
-                reset = true;
-            }
-            return (valid_action, reset);
-        }
-        _ => {
-            valid_action = false;
-            return (valid_action, reset);
-        }
-    }
-    
-}
-
-/// Handles the drawing round for players in a poker game.
-/// The function allows players to choose between standing pat (keeping their hand) or exchanging cards.
-/// 
-/// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
-/// 
-/// # Returns
-/// 
-/// This function does not return a value. It updates the players' hands.
-/// It also handles the display of hands to active players.
-// pub async fn drawing_round(lobby: &mut Lobby) {
-//     let player_names = {
-//         let players = lobby.players.lock().await;
-//         players.iter()
-//               .filter(|p| p.state != FOLDED)
-//               .map(|p| p.name.clone())
-//               .collect::<Vec<String>>()
-//     };
-    
-//     if player_names.len() <= 1 {
-//         // Only one player left, move on
-//         return;
-//     }
-    
-//     for player_name in player_names {
-//         // Send options to the player
-//         let player_tx = {
-//             let players = lobby.players.lock().await;
-//             players.iter()
-//                   .find(|p| p.name == player_name)
-//                   .map(|p| p.tx.clone())
-//         };
-        
-//         if let Some(tx) = player_tx {
-//             let message = "Drawing round!\nChoose an option:\n    1 - Stand Pat (Keep your hand)\n    2 - Exchange cards";
-//             let _ = tx.send(Message::text(message));
-//         }
-        
-//         // Process player's choice
-//         let choice = lobby.process_player_input(&player_name).await;
-        
-//         match choice.as_str() {
-//             "1" => {
-//                 // Player chooses to stand pat
-//                 if let Some(tx) = {
-//                     let players = lobby.players.lock().await;
-//                     players.iter()
-//                           .find(|p| p.name == player_name)
-//                           .map(|p| p.tx.clone())
-//                 } {
-//                     let _ = tx.send(Message::text("You chose to Stand Pat."));
-//                 }
-                
-//                 // Broadcast to other players
-//                 lobby.broadcast(format!("{} chose to Stand Pat.", player_name)).await;
-//             }
-//             "2" => {
-//                 // Player chooses to exchange cards
-//                 if let Some(tx) = {
-//                     let players = lobby.players.lock().await;
-//                     players.iter()
-//                           .find(|p| p.name == player_name)
-//                           .map(|p| p.tx.clone())
-//                 } {
-//                     let _ = tx.send(Message::text("Enter the indices of the cards you want to exchange (comma-separated, e.g., '1,2,3')"));
-//                 }
-                
-//                 let indices_input = lobby.process_player_input(&player_name).await;
-                
-//                 // Parse indices
-//                 let indices: Vec<usize> = indices_input
-//                     .split(',')
-//                     .filter_map(|s| s.trim().parse::<usize>().ok())
-//                     .filter(|&i| i > 0) // 1-based indexing
-//                     .map(|i| i - 1) // Convert to 0-based
-//                     .collect();
-                
-//                 if !indices.is_empty() {
-//                     // Get current hand and create a new hand without exchanged cards
-//                     let current_hand = {
-//                         let players = lobby.players.lock().await;
-//                         players.iter()
-//                               .find(|p| p.name == player_name)
-//                               .map(|p| p.hand.clone())
-//                               .unwrap_or_default()
-//                     };
-                    
-//                     let mut new_hand = Vec::new();
-//                     for (i, &card) in current_hand.iter().enumerate() {
-//                         if !indices.contains(&i) {
-//                             new_hand.push(card);
-//                         }
-//                     }
-                    
-//                     // Deal new cards to replace exchanged ones
-//                     for _ in 0..indices.len() {
-//                         new_hand.push(lobby.deck.deal());
-//                     }
-                    
-//                     // Update player's hand
-//                     lobby.update_player_hand(&player_name, new_hand).await;
-                    
-//                     // Display new hand to player
-//                     let tx = {
-//                         let players = lobby.players.lock().await;
-//                         players.iter()
-//                               .find(|p| p.name == player_name)
-//                               .map(|p| p.tx.clone())
-//                     };
-                    
-//                     let hand = {
-//                         let players = lobby.players.lock().await;
-//                         players.iter()
-//                               .find(|p| p.name == player_name)
-//                               .map(|p| p.hand.clone())
-//                               .unwrap_or_default()
-//                     };
-                    
-//                     if let Some(tx) = tx {
-//                         display_hand(vec![tx], vec![hand]).await;
-//                     }
-                    
-//                     // Broadcast to other players
-//                     lobby.broadcast(format!("{} has exchanged {} cards.", player_name, indices.len())).await;
-//                 }
-//             }
-//             "Disconnect" => {
-//                 // Player disconnected
-//                 lobby.update_player_state(&player_name, FOLDED).await;
-//                 lobby.broadcast(format!("{} has disconnected and folded.", player_name)).await;
-//             }
-//             _ => {
-//                 // Invalid choice, default to standing pat
-//                 if let Some(tx) = {
-//                     let players = lobby.players.lock().await;
-//                     players.iter()
-//                           .find(|p| p.name == player_name)
-//                           .map(|p| p.tx.clone())
-//                 } {
-//                     let _ = tx.send(Message::text("Invalid choice. Standing pat by default."));
-//                 }
-//             }
-//         }
-//     }
-// }
+use tokio::sync::{oneshot, Mutex};
 
+pub const RAZZ: i32 = 17;
 
 
 /// Translates a card number into a human-readable string representation.
@@ -455,364 +116,241 @@ pub async fn translate_card(card: i32) -> String {
     return card_str;
 }
 
-/// Displays the players' hands to all active players in the game.
-/// The function formats the hands into a readable string and sends it to each player's channel.
+// for 7 card stud, we will need to determine the best hand out of the 7 cards
+/// This function takes a hand of 5 to 7 cards and returns the best 5-card hand possible.
+/// It uses the lookup-table evaluator, which scores the hand without building every 5-card combination.
 /// 
 /// # Arguments
-/// * `players_tx` - A vector of `UnboundedSender<Message>` representing the channels for each player.
-/// * `players_hands` - A vector of vectors containing the players' hands (card numbers).
+/// * `hand` - A slice of integers representing the cards in the hand.
 /// 
 /// # Returns
 /// 
-/// This function does not return a value. It sends messages to the players' channels.
-pub async fn display_hand(players_tx: Vec<UnboundedSender<Message>>, players_hands: Vec<Vec<i32>>) {
-    // let players = self.players;
-    let mut message: String;
-    let mut index = 0;
-    let mut count = 1;
-    for tx in players_tx.iter().cloned() {
-        let mut translated_cards: String = Default::default();
-        for card in players_hands[index].iter().cloned() {
-            // create a string like "count. "
-            translated_cards.push_str(&format!("{}. ", count));
-            translated_cards.push_str(translate_card(card.clone()).await.as_str());
-            translated_cards.push_str("\n");
-            count += 1;
-        }
-        count = 1;
-        message = format!("Your hand:\n{}", translated_cards.trim_end_matches(", "));
-        let _ = tx.send(Message::text(message.clone()));
-        index += 1;
+/// This function returns a tuple containing the best hand type and the ranks of the cards in the best hand.
+/// The tuple format is (hand_type, rank1, rank2, rank3, rank4, rank5).
+/// A hand with fewer than 5 or more than 7 cards is a bug in the caller: it panics in debug builds
+/// and returns a default high card hand otherwise.
+pub fn get_best_hand(hand: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
+    debug_assert!((5..=7).contains(&hand.len()), "hand of {} cards instead of 5 to 7", hand.len());
+    if hand.len() < 5 || hand.len() > 7 {
+        return (0, 0, 0, 0, 0, 0); // Return a default hand type (high card)
     }
+    evaluator::to_hand_value(evaluator::evaluate(hand))
 }
 
-// for 7 card stud, we will need to determine the best hand out of the 7 cards
-/// This function takes a hand of 7 cards and returns the best hand possible.
-/// It evaluates all combinations of 5 cards from the 7 and determines the best hand type.
+/// This function takes an Omaha hand and the board and returns the best 5-card hand possible.
+/// An Omaha hand must use exactly two of its hole cards and exactly three cards from the board,
+/// so every such combination is evaluated rather than the best of all the cards together.
 /// 
 /// # Arguments
-/// * `hand` - A slice of integers representing the 7 cards in the hand.
+/// * `hole_cards` - The player's four hole cards.
+/// * `board` - The community cards, three to five of them.
 /// 
 /// # Returns
 /// 
 /// This function returns a tuple containing the best hand type and the ranks of the cards in the best hand.
 /// The tuple format is (hand_type, rank1, rank2, rank3, rank4, rank5).
-/// 
-/// # Panics
-/// 
-/// This function will panic if the length of the hand is not 7.
-pub fn get_best_hand(hand: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
-    // Replace the assertion with a check that returns a default hand type
-    if hand.len() != 7 {
-        println!("Warning: Hand length is {} instead of 7, returning default hand", hand.len());
-        return (0, 0, 0, 0, 0, 0); // Return a default hand type (high card)
-    }
-    
-    println!("Hand: {:?}", hand);
-    let mut best_hand = (-1, -1, -1, -1, -1, -1);
-    for i in 0..=2 {
-        for j in (i + 1)..=3 {
-            for k in (j + 1)..=4 {
-                for l in (k + 1)..=5 {
-                    for m in (l + 1)..=6 {
-                        let current_hand = vec![hand[i], hand[j], hand[k], hand[l], hand[m]];
-                        let current_hand_type = get_hand_type(&current_hand);
-                        if current_hand_type > best_hand
-                        {
-                            best_hand = current_hand_type;
+/// Fewer than two hole cards or three board cards is a bug in the caller: it panics in debug builds
+/// and returns a default high card hand otherwise.
+pub fn get_best_omaha_hand(hole_cards: &[i32], board: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
+    debug_assert!(
+        hole_cards.len() >= 2 && board.len() >= 3,
+        "Omaha hand of {} cards on a board of {}",
+        hole_cards.len(),
+        board.len()
+    );
+    let mut best_strength: Option<u32> = None;
+    for i in 0..hole_cards.len() {
+        for j in (i + 1)..hole_cards.len() {
+            for a in 0..board.len() {
+                for b in (a + 1)..board.len() {
+                    for c in (b + 1)..board.len() {
+                        let hand = [hole_cards[i], hole_cards[j], board[a], board[b], board[c]];
+                        let strength = evaluator::evaluate(&hand);
+                        if best_strength.is_none_or(|best| strength > best) {
+                            best_strength = Some(strength);
                         }
                     }
                 }
             }
         }
     }
-    println!("Best hand: {:?}", best_hand);
-    best_hand
+    match best_strength {
+        Some(strength) => evaluator::to_hand_value(strength),
+        None => (0, 0, 0, 0, 0, 0),
+    }
 }
 
-/// This function takes a hand of 5 cards and returns the hand type and ranks.
-/// It evaluates the hand for various poker hands such as flush, straight, four of a kind, etc.
+/// An evaluated hand as returned by `get_hand_type`: (hand_type, rank1, rank2, rank3, rank4, rank5).
+pub type HandValue = (i32, i32, i32, i32, i32, i32);
+
+/// A qualifying 8-or-better low hand: its five ranks from the highest down, the Ace counting as 1.
+/// Lows compare as arrays and the lower one is the better hand, so 5-4-3-2-A beats 6-4-3-2-A.
+pub type LowHand = [i32; 5];
+
+/// This function takes 5 to 7 cards and returns the best 8-or-better low hand they hold.
+/// Aces are low, straights and flushes do not count against a low, and paired cards cannot both play,
+/// so the best low is simply the five lowest different ranks, if they are all 8 or lower.
 /// 
 /// # Arguments
-/// * `hand` - A slice of integers representing the 5 cards in the hand.
+/// * `hand` - A slice of integers representing the cards in the hand.
 /// 
 /// # Returns
 /// 
-/// This function returns a tuple containing the hand type and the ranks of the cards in the hand.
-/// The tuple format is (hand_type, rank1, rank2, rank3, rank4, rank5).
-pub fn get_hand_type(hand: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
-    // Remove the assertion to handle hands with fewer than 5 cards
-    let hand_size = hand.len();
-    
-    // Convert cards to ranks (1-13) and sort
-    let mut ranks: Vec<i32> = hand
-        .iter()
-        .map(|&card| if card % 13 != 0 { card % 13 } else { 13 })
-        .collect();
-    ranks.sort(); // Sort in ascending order
-    
-    let suits: Vec<i32> = hand.iter().map(|&card| card / 13).collect();
-    
-    // Only check for flush and straight if we have 5 cards
-    if hand_size == 5 {
-        // Check for flush
-        let flush = suits.iter().all(|&suit| suit == suits[0]);
-        
-        // Check for straight
-        let straight = ranks.windows(2).all(|w| w[1] == w[0] + 1);
-        
-        if flush && straight {
-            return (8, ranks[4], ranks[4], 0, 0, 0);
-        }
-        
-        if flush {
-            return (5, ranks[4], ranks[3], ranks[2], ranks[1], ranks[0]);
-        }
-        
-        if straight {
-            return (4, ranks[4], 0, 0, 0, 0);
-        }
-    }
-    
-    // Count occurrences of each rank
-    let mut rank_counts = std::collections::HashMap::new();
-    for &rank in &ranks {
-        *rank_counts.entry(rank).or_insert(0) += 1;
-    }
-    
-    // Sort ranks by count (descending), then by rank value (descending)
-    let mut rank_count_pairs: Vec<(i32, i32)> = rank_counts.into_iter().collect();
-    rank_count_pairs.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
-    
-    // Four of a kind (needs at least 4 cards)
-    if hand_size >= 4 && !rank_count_pairs.is_empty() && rank_count_pairs[0].1 == 4 {
-        let kicker = if hand_size > 4 && rank_count_pairs.len() > 1 { 
-            rank_count_pairs[1].0 
-        } else { 
-            0 
-        };
-        return (7, rank_count_pairs[0].0, kicker, 0, 0, 0);
-    }
-    
-    // Full house (needs exactly 5 cards)
-    if hand_size == 5 && rank_count_pairs.len() >= 2 && rank_count_pairs[0].1 == 3 && rank_count_pairs[1].1 == 2 {
-        return (6, rank_count_pairs[0].0, rank_count_pairs[1].0, 0, 0, 0);
-    }
-    
-    // Three of a kind (needs at least 3 cards)
-    if hand_size >= 3 && !rank_count_pairs.is_empty() && rank_count_pairs[0].1 == 3 {
-        let mut kickers = Vec::new();
-        for &(rank, count) in &rank_count_pairs[1..] {
-            if count == 1 {
-                kickers.push(rank);
-            }
-        }
-        kickers.sort_by(|a, b| b.cmp(a));
-        
-        let k1 = kickers.get(0).copied().unwrap_or(0);
-        let k2 = kickers.get(1).copied().unwrap_or(0);
-        
-        return (3, rank_count_pairs[0].0, k1, k2, 0, 0);
-    }
-    
-    // Two pair (needs at least 4 cards)
-    if hand_size >= 4 && rank_count_pairs.len() >= 2 && rank_count_pairs[0].1 == 2 && rank_count_pairs[1].1 == 2 {
-        let kicker = if hand_size >= 5 && rank_count_pairs.len() >= 3 { 
-            rank_count_pairs[2].0 
-        } else { 
-            0 
-        };
-        return (2, rank_count_pairs[0].0, rank_count_pairs[1].0, kicker, 0, 0);
+/// The low hand, or `None` if the cards do not hold five different ranks of 8 or lower.
+pub fn get_low_hand(hand: &[i32]) -> Option<LowHand> {
+    let mut ranks: Vec<i32> = hand.iter().map(|&card| card % 13 + 1).filter(|&rank| rank <= 8).collect();
+    ranks.sort();
+    ranks.dedup();
+    if ranks.len() < 5 {
+        return None;
     }
-    
-    // One pair (needs at least 2 cards)
-    if hand_size >= 2 && !rank_count_pairs.is_empty() && rank_count_pairs[0].1 == 2 {
-        let mut kickers = Vec::new();
-        for &(rank, count) in &rank_count_pairs[1..] {
-            if count == 1 {
-                kickers.push(rank);
-            }
-        }
-        kickers.sort_by(|a, b| b.cmp(a));
-        
-        let k1 = kickers.get(0).copied().unwrap_or(0);
-        let k2 = kickers.get(1).copied().unwrap_or(0);
-        let k3 = kickers.get(2).copied().unwrap_or(0);
-        
-        return (1, rank_count_pairs[0].0, k1, k2, k3, 0);
-    }
-    
-    // High card
-    let mut high_cards = Vec::new();
-    let mut ranks_desc = ranks.clone();
-    ranks_desc.sort_by(|a, b| b.cmp(a));
-    
-    for i in 0..hand_size.min(5) {
-        high_cards.push(ranks_desc[i]);
-    }
-    
-    while high_cards.len() < 5 {
-        high_cards.push(0);
-    }
-    
-    (0, 
-     *high_cards.get(0).unwrap_or(&0), 
-     *high_cards.get(1).unwrap_or(&0), 
-     *high_cards.get(2).unwrap_or(&0), 
-     *high_cards.get(3).unwrap_or(&0), 
-     *high_cards.get(4).unwrap_or(&0))
+    Some([ranks[4], ranks[3], ranks[2], ranks[1], ranks[0]])
 }
 
-
-// gets players best hand of the 7 cards
-/// this is used for 7 card stud and texas holdem
+/// This function takes an Omaha hand and the board and returns the best 8-or-better low hand possible,
+/// using exactly two hole cards and three board cards like `get_best_omaha_hand`.
+/// 
 /// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
+/// * `hole_cards` - The player's four hole cards.
+/// * `board` - The community cards, three to five of them.
 /// 
 /// # Returns
 /// 
-/// This function does not return a value. It updates the players' hands with their best hand.
-/// It also handles the display of hands to active players.
-pub async fn update_players_hand(lobby: &Lobby) {
-    let mut players = lobby.players.lock().await;
-    for player in players.iter_mut() {
-        if player.state == player::FOLDED {
-            continue;
+/// The low hand, or `None` if no two hole cards make a qualifying low with three board cards.
+pub fn get_omaha_low_hand(hole_cards: &[i32], board: &[i32]) -> Option<LowHand> {
+    let mut best_low: Option<LowHand> = None;
+    for i in 0..hole_cards.len() {
+        for j in (i + 1)..hole_cards.len() {
+            for a in 0..board.len() {
+                for b in (a + 1)..board.len() {
+                    for c in (b + 1)..board.len() {
+                        let low = get_low_hand(&[hole_cards[i], hole_cards[j], board[a], board[b], board[c]]);
+                        if let Some(low) = low {
+                            if best_low.is_none_or(|best| low < best) {
+                                best_low = Some(low);
+                            }
+                        }
+                    }
+                }
+            }
         }
-        println!("yuh{:?}",player.hand.len());
-        // Save the original hole cards
-        let original_hole_cards = if player.hand.len() >= 2 {
-            vec![player.hand[0], player.hand[1]]
-        } else {
-            println!("FUCKWIDJKOAOWIJDWIODJ");
-            Vec::new()
-        };
-        
-        // Create 7-card hand for evaluation
-        let player_hand = if lobby.game_type == TEXAS_HOLD_EM {
-            let community_cards = lobby.community_cards.clone();
-            [original_hole_cards.clone(), community_cards].concat() // make 7 cards
-        } else {
-            player.hand.clone()
-        };
-        
-        //Print player hand
-        println!("playerhand {:?}",&player_hand);
-        // Get best 5-card hand
-        let best_hand = get_best_hand(&player_hand);
-        
-        // Update player's hand to include original hole cards plus best hand info
-        player.hand = vec![
-            best_hand.0, best_hand.1, best_hand.2, best_hand.3, best_hand.4, best_hand.5,
-        ];
     }
+    best_low
 }
 
-
-// Update get_best_hand to be more tolerant of different hand sizes
-
-///This is the bring in bet for seven card draw and the rule for this is
-///The player with the lowest-ranking up-card pays the bring-in, and betting proceeds after that in normal clockwise order
-/// and to break ties in card ranks we will use the suit order of spades, hearts, diamonds, and clubs
+/// The ordering used to compare evaluated hands at every showdown.
+/// The hand type is compared first, then each rank in turn. Every tie-breaker a hand has
+/// (pair rank, kickers, every flush card, ...) is stored in the tuple, so two hands only
+/// compare equal when they are truly tied.
+/// 
 /// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
+/// * `a` - The first evaluated hand.
+/// * `b` - The second evaluated hand.
 /// 
 /// # Returns
 /// 
-/// This function does not return a value. It updates the players' wallets and game statistics.
-/// It also handles the display of hands to active players.
-pub async fn bring_in(lobby: &mut Lobby) {
-    let mut players = lobby.players.lock().await;
-    let mut lowest_up_card = 14;
-    let mut lowest_up_card_player = 0;
-    for (i, player) in players.iter().enumerate() {
-        if player.state != player::FOLDED {
-            if player.hand[2] % 13 < lowest_up_card {
-                lowest_up_card = player.hand[2] % 13;
-                lowest_up_card_player = i;
+/// `Ordering::Greater` if `a` is the better hand, `Ordering::Less` if `b` is, `Ordering::Equal` on a tie.
+pub fn compare_hands(a: &HandValue, b: &HandValue) -> std::cmp::Ordering {
+    a.0.cmp(&b.0)
+        .then(a.1.cmp(&b.1))
+        .then(a.2.cmp(&b.2))
+        .then(a.3.cmp(&b.3))
+        .then(a.4.cmp(&b.4))
+        .then(a.5.cmp(&b.5))
+}
+
+/// Every 5-card hand that can be made from the cards, or the cards themselves if there are 5 or fewer.
+fn five_card_hands(cards: &[i32]) -> Vec<Vec<i32>> {
+    let n = cards.len();
+    if n <= 5 {
+        return vec![cards.to_vec()];
+    }
+    let mut hands = Vec::new();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            for k in (j + 1)..n {
+                for l in (k + 1)..n {
+                    for m in (l + 1)..n {
+                        hands.push(vec![cards[i], cards[j], cards[k], cards[l], cards[m]]);
+                    }
+                }
             }
         }
     }
-    let bring_in = 15;
-    players[lowest_up_card_player].wallet -= bring_in;
-    players[lowest_up_card_player].current_bet += bring_in;
-    lobby.pot += bring_in;
-    players[lowest_up_card_player].state = player::CALLED;
-    let players_tx = players.iter().map(|p| p.tx.clone()).collect::<Vec<_>>();
-    lobby.lobby_wide_send(players_tx, format!("{} has the lowest up card and pays the bring-in of {}", players[lowest_up_card_player].name, bring_in)).await;
+    hands
+}
 
+/// Scores ranks by their pairs, trips and quads only, in the `get_hand_type` tuple layout.
+/// Groups come first, bigger groups then higher ranks, and the tuple is padded with 0.
+fn paired_hand_value(ranks: &[i32]) -> HandValue {
+    let mut counts: Vec<(i32, i32)> = Vec::new(); // (count, rank)
+    for &rank in ranks.iter() {
+        match counts.iter_mut().find(|(_, r)| *r == rank) {
+            Some(entry) => entry.0 += 1,
+            None => counts.push((1, rank)),
+        }
+    }
+    counts.sort_by(|a, b| b.cmp(a));
+    let first = counts.first().map(|c| c.0).unwrap_or(0);
+    let second = counts.get(1).map(|c| c.0).unwrap_or(0);
+    let hand_type = match (first, second) {
+        (4, _) => 7,
+        (3, 2) => 6,
+        (3, _) => 3,
+        (2, 2) => 2,
+        (2, _) => 1,
+        _ => 0,
+    };
+    let mut r = [0; 5];
+    for (i, (_, rank)) in counts.iter().take(5).enumerate() {
+        r[i] = *rank;
+    }
+    (hand_type, r[0], r[1], r[2], r[3], r[4])
 }
 
-/// This function is used to remove the X cards from the players hand
-/// It is used for the final round of 7 card stud where the players have to show their hands
+/// This function takes up to 7 cards and returns the best ace-to-five low hand they hold, as played in Razz.
+/// Aces are low and straights and flushes do not count, so the best hand is 5-4-3-2-A. Pairs still count
+/// against a hand, and every hand has a low value, there is no 8-or-better qualifier.
+/// 
 /// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
+/// * `hand` - A slice of integers representing the cards in the hand, or the up-cards showing.
 /// 
 /// # Returns
 /// 
-/// This function does not return a value. It updates the players' hands by removing the X cards.
-/// It also handles the display of hands to active players.
-pub async fn get_rid_of_x(lobby: &Lobby) {
-    let mut players = lobby.players.lock().await;
-    for player in players.iter_mut() {
-        if player.state == player::FOLDED {
-            continue;
-        }
-        for card in player.hand.iter_mut() {
-            if *card > 52 {
-                *card -= 53;
-            }
-        }
-        display_hand(vec![player.tx.clone()], vec![player.hand.clone()]).await;
-    }
+/// A tuple in the `get_hand_type` layout with the Ace as rank 0 and no straights or flushes.
+/// Compared with `compare_hands`, the LOWER tuple is the better low hand.
+pub fn get_ace_to_five_low(hand: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
+    five_card_hands(hand)
+        .iter()
+        .map(|cards| {
+            let ranks: Vec<i32> = cards.iter().map(|&card| card % 13).collect();
+            paired_hand_value(&ranks)
+        })
+        .min_by(compare_hands)
+        .unwrap_or((0, 0, 0, 0, 0, 0))
 }
 
-/// This function is used to handle the blinds for the poker game.
-/// It deducts the small and big blinds from the respective players' wallets,
-/// adds the blinds to the pot,
-/// and sends a message to all players about the blinds paid.
+/// This function takes up to 7 cards and returns the best deuce-to-seven low hand they hold, as played in 2-7 Triple Draw.
+/// The hand is valued as in high poker with the Ace always high, so A-2-3-4-5 is Ace high and not a straight,
+/// and straights and flushes count against the hand. The best hand is 7-5-4-3-2 of mixed suits.
 /// 
 /// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
+/// * `hand` - A slice of integers representing the cards in the hand.
 /// 
 /// # Returns
 /// 
-/// This function does not return a value. It updates the players' wallets and the pot.
-/// It also handles the display of blinds to all players.
-pub async fn blinds(lobby: &mut Lobby) {
-    let mut players = lobby.players.lock().await;
-    let small_blind_player_i = (lobby.first_betting_player + 1) % lobby.current_player_count;
-    let big_blind_player_i = (lobby.first_betting_player + 2) % lobby.current_player_count;
-    let big_blind = 10;
-    let small_blind = 5;
-
-    let mut names: Vec<String> = Vec::new();
-    // let big_blind_player = &mut players[big_blind_player_i as usize];
-    
-    let blind_player = &mut players[small_blind_player_i as usize];
-    blind_player.wallet -= small_blind;
-    blind_player.current_bet += small_blind;
-    blind_player.state = player::CALLED;
-    names.push(blind_player.name.clone());
-    println!("smal blind player current bet: {}", blind_player.current_bet);
-    println!("FUCK YOUU: {}",players[small_blind_player_i as usize].current_bet);
-
-    let blind_player = &mut players[big_blind_player_i as usize];
-    blind_player.wallet -= big_blind;
-    blind_player.current_bet += big_blind;
-    blind_player.state = player::CALLED;
-    names.push(blind_player.name.clone());
-    println!("big blind player current bet: {}", blind_player.current_bet);
-
-
-    lobby.pot += small_blind;
-    lobby.pot += big_blind;
-    lobby.current_max_bet = big_blind;
-
-
-    // Make a copy of the players for debugging
-    let players_tx = players.iter().map(|p| p.tx.clone()).collect::<Vec<_>>();
-    lobby.lobby_wide_send(players_tx, format!("{} has paid the small blind of {}\n{} has paid the big blind of {}", names[0], small_blind, names[1], big_blind)).await;
+/// A tuple in the `get_hand_type` layout. Compared with `compare_hands`, the LOWER tuple is the better low hand.
+pub fn get_deuce_to_seven_low(hand: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
+    five_card_hands(hand)
+        .iter()
+        .map(|cards| match get_hand_type(cards) {
+            // the wheel, the Ace plays high
+            (8, 4, ..) => (5, 13, 4, 3, 2, 1),
+            (4, 4, ..) => (0, 13, 4, 3, 2, 1),
+            value => value,
+        })
+        .min_by(compare_hands)
+        .unwrap_or((0, 0, 0, 0, 0, 0))
 }
 
 /// Converts a hand type integer to a readable string description.
@@ -837,633 +375,329 @@ fn hand_type_to_string(hand_type: i32) -> String {
     }
 }
 
-/// This function is used to handle the game state machine for a five-card poker game.
-/// It manages the different states of the game, including ante, dealing cards, betting rounds, drawing rounds, and showdown.
+/// Describes an 8-or-better low from its highest card down, e.g. "8-6-4-2-A low".
+fn low_hand_to_string(low: LowHand) -> String {
+    let ranks: Vec<String> = low
+        .iter()
+        .map(|&rank| if rank == 1 { "A".to_string() } else { rank.to_string() })
+        .collect();
+    format!("{} low", ranks.join("-"))
+}
+
+/// Describes a hand in Razz or 2-7 Triple Draw. An unpaired hand is named by its cards from the highest down,
+/// e.g. "7-5-4-3-2 low", anything else by its hand type.
+///
+/// # Arguments
+/// * `game_type` - `RAZZ` or `lobby::DEUCE_TO_SEVEN_TRIPLE_DRAW`.
+/// * `rank` - The hand as valued by `get_ace_to_five_low` or `get_deuce_to_seven_low`.
+fn lowball_hand_to_string(game_type: i32, rank: &HandValue) -> String {
+    if rank.0 != 0 {
+        return hand_type_to_string(rank.0);
+    }
+    let ranks: Vec<String> = [rank.1, rank.2, rank.3, rank.4, rank.5]
+        .iter()
+        .map(|&value| {
+            // A-5 lows count the Ace as 0, 2-7 lows count the Two as 1 and the Ace as 13
+            let card_rank = if game_type == RAZZ { value } else { value % 13 };
+            "A23456789TJQK".chars().nth(card_rank as usize).unwrap_or('?').to_string()
+        })
+        .collect();
+    format!("{} low", ranks.join("-"))
+}
+
+/// Collects the names of every player who won at least part of a pot, high or low.
+fn pot_winners(pots: &[Pot]) -> Vec<String> {
+    let mut winners: Vec<String> = Vec::new();
+    for pot in pots.iter() {
+        for winner in pot.winners.iter().chain(pot.low_winners.iter()) {
+            if !winners.contains(winner) {
+                winners.push(winner.clone());
+            }
+        }
+    }
+    winners
+}
+
+/// Builds the `pots` list of the `showdownHands` payload and a readable winner message.
+/// The first pot is the main pot, every following one is a side pot.
 /// 
 /// # Arguments
+/// * `pots` - The pots awarded by the engine at the showdown.
+/// 
+/// # Returns
 /// 
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
+/// A tuple of the result of each pot and the combined winner message.
+fn showdown_pots(pots: &[Pot]) -> (Vec<PotResult>, String) {
+    let mut pots_data = Vec::new();
+    let mut messages = Vec::new();
+    for (i, pot) in pots.iter().enumerate() {
+        let pot_name = if i == 0 { "Main pot".to_string() } else { format!("Side pot {}", i) };
+        pots_data.push(PotResult {
+            name: pot_name.clone(),
+            amount: pot.amount,
+            eligible: pot.eligible.clone(),
+            winners: pot.winners.clone(),
+            low_winners: pot.low_winners.clone(),
+        });
+        if pot.low_winners.is_empty() {
+            if !pot.winners.is_empty() {
+                messages.push(format!("{} won the {} of ${}", pot.winners.join(", "), pot_name.to_lowercase(), pot.amount));
+            }
+        } else {
+            let (high_shares, low_shares) = split_pot(pot.amount, pot.winners.len(), pot.low_winners.len());
+            messages.push(format!(
+                "{} won the high half of the {} (${}), {} won the low half (${})",
+                pot.winners.join(", "),
+                pot_name.to_lowercase(),
+                high_shares.iter().sum::<i32>(),
+                pot.low_winners.join(", "),
+                low_shares.iter().sum::<i32>()
+            ));
+        }
+    }
+    let winner_message = if messages.is_empty() {
+        "No winners determined".to_string()
+    } else {
+        messages.join("\n")
+    };
+    (pots_data, winner_message)
+}
+
+/// Converts a betting or drawing client message into an engine action.
+fn to_engine_action(message: ClientMessage) -> Option<Action> {
+    match message {
+        ClientMessage::Check => Some(Action::Check),
+        ClientMessage::Call => Some(Action::Call),
+        ClientMessage::Raise { amount } => Some(Action::Raise { amount }),
+        ClientMessage::Fold => Some(Action::Fold),
+        ClientMessage::AllIn => Some(Action::AllIn),
+        ClientMessage::DrawCards { card_indices } => Some(Action::DrawCards { indices: card_indices }),
+        _ => None,
+    }
+}
+
+/// Parses a message from a player in a game lobby.
+/// The draw screen sends `DrawCards` with its `cardIndices` either under `data` or at the top level,
+/// both forms are accepted.
+/// 
+/// # Arguments
+/// * `text` - The raw text of the WebSocket message.
 /// 
 /// # Returns
 /// 
-/// This function returns a string indicating the result of the game state machine execution.
-/// It also handles the display of game information to all players.
-pub async fn five_card_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, mut player: Player, db: Arc<Database>) -> String {
-    let player_name = player.name.clone();
-    let player_lobby = player.lobby.clone();
-    let lobby_name = player_lobby.lock().await.name.clone();
-    let tx = player.tx.clone();
-    
-    // Update player state through the lobby
-    {
-        let mut lobby = player_lobby.lock().await;
-        lobby.set_player_ready(&player_name, false).await;
-        lobby.update_player_state(&player_name, player::IN_LOBBY).await;
-        player.state = player::IN_LOBBY;
+/// The parsed message, or `None` if it is not a message the server understands.
+fn parse_client_message(text: &str) -> Option<ClientMessage> {
+    let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
+    if let Ok(message) = client_msg {
+        return Some(message);
     }
-    
-    println!("{} has joined lobby: {}", player_name, player_lobby.lock().await.name);
-    player_lobby.lock().await.new_player_join().await;
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    if value.get("action").and_then(|a| a.as_str()) != Some("DrawCards") {
+        return None;
+    }
+    let card_indices = value
+        .get("cardIndices")?
+        .as_array()?
+        .iter()
+        .filter_map(|index| index.as_u64().map(|i| i as usize))
+        .collect();
+    Some(ClientMessage::DrawCards { card_indices })
+}
 
+/// Describes a player's action for everyone at the table.
+fn describe_action(player_name: &str, action: &Action, amount: i32) -> String {
+    match action {
+        Action::Check => format!("{} checked", player_name),
+        Action::Call => format!("{} called {}", player_name, amount),
+        Action::Raise { .. } => format!("{} raised {}", player_name, amount),
+        Action::AllIn => format!("{} went all in with {}", player_name, amount),
+        Action::Fold => format!("{} folded", player_name),
+        Action::DrawCards { indices } => format!("{} exchanged {} cards.", player_name, indices.len()),
+    }
+}
 
-    // Add a delay of one second
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-    // update player attribute from db
-    let stats = db.player_stats(&player_name).await;
-    if let Ok(stats) = stats {
-        player.wallet = stats.wallet;
-        player.games_played = stats.games_played;
-        player.games_won = stats.games_won;
-    } else {
-        tx.send(Message::text(r#"{"error": "Failed to retrieve wallet"}"#)).unwrap();
-        // add player to be deleted, then kick to server
+/// Builds the `showdown` message shown to everyone at the end of a hand.
+/// 
+/// # Arguments
+/// * `lobby` - The lobby the hand was played in.
+/// * `hands` - The hands still in play, as returned by the engine.
+/// * `pots` - The pots awarded by the engine, starting with the main pot.
+fn showdown_message(lobby: &Lobby, hands: &[ShowdownHand], pots: &[Pot]) -> ServerMessage {
+    let winners = pot_winners(pots);
+    let hands_data: Vec<ShownHand> = hands
+        .iter()
+        .map(|hand| ShownHand {
+            player_name: hand.player.clone(),
+            hand: hand.cards.clone(),
+            hand_rank: hand.rank,
+            hand_name: if lobby::is_lowball(lobby.game_type) {
+                lowball_hand_to_string(lobby.game_type, &hand.rank)
+            } else {
+                hand_type_to_string(hand.rank.0)
+            },
+            low_hand: hand.low,
+            low_name: hand.low.map(low_hand_to_string),
+            winner: winners.contains(&hand.player),
+        })
+        .collect();
+    let (pots_data, winner_message) = showdown_pots(pots);
+    ServerMessage::Showdown {
+        data: Showdown {
+            hands: hands_data,
+            community_cards: lobby.community_cards.clone(),
+            hi_lo: lobby::is_hi_lo(lobby.game_type),
+            pot: pots.iter().map(|pot| pot.amount).sum::<i32>(),
+            pots: pots_data,
+            winner_message,
+        },
     }
-    
-    loop {
-        match player.state {
-            player::IN_LOBBY => {
-                println!("player {} is in lobby", player_name);
-
-                loop {
-                    let result = {
-                        // Get next message from the player's websocket
-                        let mut rx = player.rx.lock().await;
-                        match rx.next().await {
-                            Some(res) => res,
-                            None => continue,
-                        }
-                    };
-                    
-                    if let Ok(msg) = result {
-                        if let Ok(text) = msg.to_str() {
-                            // Parse the incoming JSON message
-                            let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                            
-            
-                            match client_msg {
-                                Ok(ClientMessage::Quit) => {
-                                    // QUIT LOBBY - Return to server lobby
-                                    let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                        server_lobby.lock().await.remove_lobby(lobby_name).await;
-                                    } else {
-                                        server_lobby.lock().await.update_lobby_names_status(lobby_name).await;
-                                    }
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-                                    
-                                    // Send redirect back to server lobby
-                                    tx.send(Message::text(r#"{"message": "Leaving lobby...", "redirect": "server_lobby"}"#)).unwrap();
-                                    return "Normal".to_string();
-                                }
-                                Ok(ClientMessage::Disconnect) => {
-                                    // Player disconnected entirely
-                                    let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                        server_lobby.lock().await.remove_lobby(lobby_name.clone()).await;
-                                    } else {
-                                        server_lobby.lock().await.update_lobby_names_status(lobby_name).await;
-                                    }
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-            
-                                    server_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    
-                                    // Update player stats from database
-                                    if let Err(e) = db.update_player_stats(&player).await {
-                                        eprintln!("Failed to update player stats: {}", e);
-                                    }
-                                    
-                                    return "Disconnect".to_string();
-                                }
-                                Ok(ClientMessage::ShowLobbyInfo) => {
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-                                }
-                                Ok(ClientMessage::Ready) => {
-                                        // READY UP - through the lobby
-                                        player_lobby.lock().await.check_ready(player_name.clone()).await;
-                                        player_lobby.lock().await.send_player_list().await;
-                                }
-                                Ok(ClientMessage::ShowStats) => {
-                                    // Get and send player stats
-                                    let stats = db.player_stats(&player_name).await;
-
-                                    if let Ok(stats) = stats {
-                                        player.wallet = stats.wallet;
-                                        player.games_played = stats.games_played;
-                                        player.games_won = stats.games_won;
-                                        player_lobby.lock().await.update_player_reference(&player).await;
-                                        let stats_json = serde_json::json!({
-                                            "stats": {
-                                                "username": player_name,
-                                                "gamesPlayed": stats.games_played,
-                                                "gamesWon": stats.games_won,
-                                                "wallet": stats.wallet
-                                            }
-                                        });
-                                        tx.send(Message::text(stats_json.to_string())).unwrap();
-                                    } else {
-                                        tx.send(Message::text(r#"{"error": "Failed to retrieve stats"}"#)).unwrap();
-                                    }
-                                }
-                                Ok(ClientMessage::StartGame) => {
-                                    // Start the game
-                                    println!("player: {}, received start game", player.name.clone());
-                                    let mut player_lobby_guard = player_lobby.lock().await;
-                                    player_lobby_guard.turns_remaining -= 1;
-                                    println!("turns remaining: {}", player_lobby_guard.turns_remaining);
-                                    if player_lobby_guard.turns_remaining == 0 {
-                                        player_lobby_guard.setup_game().await;
-                                    }
-                                    player.state = player::IN_GAME;
-                                    player.current_bet = 0;
-                                    break;
-                                    
-                                }
-                                _ => {
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                }
+}
+
+/// The message that tells a player it is their turn to draw.
+/// The five card draw screen opens its card picker on it.
+fn draw_prompt() -> ServerMessage {
+    ServerMessage::DrawPrompt {
+        message: "Your turn to draw cards.".to_string(),
+        action: "draw".to_string(),
+        your_turn: true,
+        game_state: lobby::DRAW,
+    }
+}
+
+/// Turns engine events into messages for everyone in the lobby, then sends the updated
+/// game info and player list. Hole cards are never broadcast, each player only sees
+/// their own in the player list built by `send_player_list`.
+/// 
+/// # Arguments
+/// * `lobby` - The lobby the events happened in, already synced with its engine.
+/// * `events` - The events returned by the engine.
+pub async fn broadcast_events(lobby: &Lobby, events: &[Event]) {
+    for event in events.iter() {
+        match event {
+            Event::PhaseChanged { phase } => {
+                let message = match *phase {
+                    lobby::ANTE => "Ante Round",
+                    lobby::DEAL_CARDS => "Dealing Cards.....",
+                    lobby::SMALL_AND_BIG_BLIND => "Blinds Round",
+                    lobby::FIRST_BETTING_ROUND | lobby::SECOND_BETTING_ROUND | lobby::BETTING_ROUND => "Betting Round",
+                    lobby::DRAW => "Drawing Round",
+                    lobby::SHOWDOWN => "Showdown Round",
+                    _ => continue,
+                };
+                lobby.broadcast(message.to_string()).await;
             }
-            _ => { // player is in game
-                println!("player {} is in game", player_name);
-                let stats = db.player_stats(&player_name).await;
-                if let Ok(stats) = stats {
-                    player.wallet = stats.wallet;
-                    player.games_played = 0;
-                    player.games_won = 0;
-                } else {
-                    tx.send(Message::text(r#"{"error": "Failed to retrieve wallet"}"#)).unwrap();
-                    // add player to be deleted, then kick to server
+            Event::AntePosted { player, amount } => {
+                lobby.broadcast(format!("{} paid the ante of {}", player, amount)).await;
+            }
+            Event::BlindPosted { player, amount, big_blind } => {
+                let blind = if *big_blind { "big" } else { "small" };
+                lobby.broadcast(format!("{} has paid the {} blind of {}", player, blind, amount)).await;
+            }
+            Event::BringInPosted { player, amount } => {
+                lobby.broadcast(format!("{} has the lowest up card and pays the bring-in of {}", player, amount)).await;
+            }
+            Event::CommunityCardsDealt { cards } => {
+                let mut card_names = Vec::new();
+                for card in cards.iter() {
+                    card_names.push(translate_card(*card).await);
                 }
-                let mut exit = false;
-                while !exit {
-                    if let Ok(mut lobby_guard) = player_lobby.try_lock(){
-                        if lobby_guard.game_state == lobby::JOINABLE {
-                            drop(lobby_guard);
-                            tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
-                        } else {
-                            if lobby_guard.current_player_turn == player_name{
-                                match  lobby_guard.game_state {
-                                    lobby::START_OF_ROUND => {
-                                        lobby_guard.game_state = lobby::ANTE;
-                                        
-                                        // Initialize turns counter for tracking player actions
-                                        lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                        lobby_guard.send_lobby_game_info().await;
-                                    }
-                                    lobby::ANTE => {
-                                        println!("ante round current player: {}", player_name);
-                                        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                                        tx.send(Message::text(r#"{"message": "Ante Round"}"#)).unwrap();
-                                        println!("ante round message sent to player: {}", player_name);
-                                        if player.wallet >= 10 {
-                                            // Deduct ante from player wallet and add to pot
-                                            player.wallet -= 10;
-                                            player.games_played += 1;
-                                            lobby_guard.update_player_reference(&player).await;
-                                            lobby_guard.pot += 10;
-                                        } else {
-                                            // Not enough money, mark as folded
-                                            player.state = player::FOLDED;
-                                            lobby_guard.update_player_state(&player_name, player::FOLDED).await;
-                                        }
-                                        lobby_guard.turns_remaining -= 1;
-                                        {
-                                            let stats_json = serde_json::json!({
-                                                "stats": {
-                                                    "username": player_name,
-                                                    "gamesPlayed": player.games_played,
-                                                    "gamesWon": player.games_won,
-                                                    "wallet": player.wallet
-                                                }
-                                            });
-                                            tx.send(Message::text(stats_json.to_string())).unwrap();
-                                        }
-                                        if lobby_guard.turns_remaining == 0{
-                                            if lobby_guard.check_end_game().await {
-                                                // game over if all or all-but-one players are folded or disconnected
-                                                lobby_guard.game_state = lobby::SHOWDOWN;
-                                            } else {
-                                                // carry on if multiple players are still in the game
-                                                lobby_guard.game_state = lobby::DEAL_CARDS;
-                                            }
-                                            lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                            lobby_guard.get_next_player(true).await;
-                                            println!("ante round complete");
-                                        } else {
-                                            lobby_guard.get_next_player(false).await;
-                                        }
-                                        lobby_guard.send_lobby_game_info().await;
-                                    }
-                                    lobby::DEAL_CARDS => {
-                                        println!("deal round current player: {}", player_name);
-                                        tx.send(Message::text(r#"{"message": "Dealing Cards....."}"#)).unwrap();
-                                        // Deal 5 cards to each active player
-                                        if player.state != player::FOLDED {
-                                            if player.hand.len() < 5 {
-                                                player.hand.push(lobby_guard.deck.deal());
-                                                lobby_guard.update_player_hand(&player_name, player.clone().hand).await;
-                                            } else {
-                                                lobby_guard.turns_remaining -= 1;
-                                                if lobby_guard.turns_remaining == 0 {
-                                                    if lobby_guard.check_end_game().await {
-                                                        // game over if all or all-but-one players are folded or disconnected
-                                                        lobby_guard.game_state = lobby::SHOWDOWN;
-                                                    } else {
-                                                        // carry on if multiple players are still in the game
-                                                        lobby_guard.game_state = lobby::FIRST_BETTING_ROUND;
-                                                    }
-                                                    lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                                    lobby_guard.get_next_player(true).await;
-                                                    lobby_guard.send_player_list().await;
-                                                    lobby_guard.send_lobby_game_info().await;
-                                                    println!("all cards dealt, moving to first betting round with player turn: {}", lobby_guard.current_player_turn);
-                                                    continue;
-                                                }
-                                            }
-                                        }
-                                        lobby_guard.get_next_player(false).await;
-                                    }
-                                    lobby::FIRST_BETTING_ROUND | lobby::SECOND_BETTING_ROUND => {
-                                        println!("betting round current player {}", player_name);
-                                        // skip the player if they are folded or all in
-                                        if player.state != player::FOLDED && player.state != player::ALL_IN {
-                                            lobby_guard.send_player_list().await;
-                                            lobby_guard.send_lobby_game_info().await;
-                                            loop {
-                                                let result = {
-                                                    // Get next message from the player's websocket
-                                                    let mut rx = player.rx.lock().await;
-                                                    match rx.next().await {
-                                                        Some(res) => res,
-                                                        None => continue,
-                                                    }
-                                                };
-    
-                                                if let Ok(msg) = result {
-                                                    if let Ok(text) = msg.to_str() {
-                                                        // Parse the incoming JSON message
-                                                        let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                                                        match client_msg {
-                                                            Ok(ClientMessage::Disconnect) => {
-                                                                /*
-                                                                Add current player into to-be-rmoved list and keep their player reference active within the players vector
-                                                                
-                                                                
-                                                                 */
-    
-    
-                                                                // // Player disconnected entirely
-                                                                // let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                                                // if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                                                //     lobby_guard.remove_lobby(lobby_name.clone()).await;
-                                                                // } else {
-                                                                //     lobby_guard.update_lobby_names_status(lobby_name).await;
-                                                                // }
-                                                                // lobby_guard.broadcast_player_count().await;
-                                                                // lobby_guard.send_lobby_info().await;
-                                                                // lobby_guard.send_player_list().await;
-                                        
-                                                                // lobby_guard.remove_player(player_name.clone()).await;
-                                                                // lobby_guard.broadcast_player_count().await;
-                                                                
-                                                                // // Update player stats from database
-                                                                // if let Err(e) = db.update_player_stats(&player).await {
-                                                                //     eprintln!("Failed to update player stats: {}", e);
-                                                                // }
-                                                                
-                                                                // return "Disconnect".to_string();
-                                                            }
-                                                            _ => {
-                                                                // pass in the players input and validate it (check, call, raise, fold, all in)
-                                                                if let Ok(action) = client_msg {
-                                                                    let (valid_action, reset) = betting_round(&mut player, &mut lobby_guard, action).await;
-                                                                    if valid_action {
-                                                                        println!("valid action");
-                                                                        // update the server lobby player reference with updated clone data
-                                                                        lobby_guard.update_player_reference(&player).await;
-                                                                        if reset {
-                                                                            println!("reseting turns remaining");
-                                                                            // reset the turns_remaining counter if the player raised
-                                                                            lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                                                        }
-                                                                        break;
-                                                                    }
-                                                                } else {
-                                                                    println!("Invalid client message received BAD, they try again");
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        lobby_guard.turns_remaining -= 1;
-                                        println!("player {} finish turn", player_name);
-                                        if lobby_guard.check_end_game().await {
-                                            lobby_guard.clear_betting().await;
-                                            player.current_bet = 0;
-                                            lobby_guard.game_state = lobby::SHOWDOWN;
-                                        } else {
-                                            if lobby_guard.turns_remaining == 0 {
-                                                if lobby_guard.game_state == lobby::FIRST_BETTING_ROUND {
-                                                    lobby_guard.game_state = lobby::DRAW;
-                                                } else if lobby_guard.game_state == lobby::SECOND_BETTING_ROUND {
-                                                    lobby_guard.game_state = lobby::SHOWDOWN;
-                                                }
-                                                lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                                lobby_guard.clear_betting().await;
-                                                player.current_bet = 0;
-                                                lobby_guard.get_next_player(true).await;
-                                                println!("betting round finished, next player turn: {}", lobby_guard.current_player_turn);
-                                            } else {
-                                                lobby_guard.get_next_player(false).await;
-                                                println!("next player turn: {}", lobby_guard.current_player_turn);
-                                            }
-                                        }
-                                        lobby_guard.send_lobby_game_info().await;
-                                        lobby_guard.send_player_list().await;
-                                        
-                                    }
-                                    lobby::DRAW => {
-                                        println!("drawing round for player {}", player_name);
-                                        tx.send(Message::text(r#"{"message": "Drawing Round"}"#)).unwrap();
-                                        player.current_bet = 0; // reset attribute from betting round
-                                        lobby_guard.update_player_reference(&player).await;
-                                        
-                                        // Check if current player isn't folded
-                                        if player.state != player::FOLDED {
-                                            // Notify player it's their turn to draw
-                                            let turn_message = serde_json::json!({
-                                                "message": "Your turn to draw cards.",
-                                                "action": "draw",
-                                                "yourTurn": true,
-                                                "gameState": lobby::DRAW
-                                            });
-                                            tx.send(Message::text(turn_message.to_string())).unwrap();
-                                              
-                                            // Send game info with the DRAW_PHASE state to trigger UI
-                                            lobby_guard.send_lobby_game_info().await;
-                                            
-                                            // Wait for player's selection of cards to exchange
-                                            loop {
-                                                let result = {
-                                                    // Get next message from the player's websocket
-                                                    let mut rx = player.rx.lock().await;
-                                                    match rx.next().await {
-                                                        Some(res) => res,
-                                                        None => continue,
-                                                    }
-                                                };
-                                                if let Ok(msg) = result {
-                                                    if let Ok(text) = msg.to_str() {
-                                                        // Parse the incoming JSON message
-                                                        let draw_msg: Result<serde_json::Value, _> = serde_json::from_str(text);
-                                                        
-                                                        if let Ok(draw_data) = draw_msg {
-                                                            // Check if this is a DrawCards action
-                                                            if let Some("DrawCards") = draw_data.get("action").and_then(|a| a.as_str()) {
-                                                                // Get the indices of cards to replace
-                                                                if let Some(indices) = draw_data.get("cardIndices").and_then(|i| i.as_array()) {
-                                                                    let indices: Vec<usize> = indices
-                                                                        .iter()
-                                                                        .filter_map(|idx| idx.as_i64().map(|i| i as usize))
-                                                                        .collect();
-                                                                    
-                                                                    // Get current hand
-                                                                    let mut new_hand = player.hand.clone();
-                                                                    
-                                                                    // Replace selected cards with new ones
-                                                                    for &idx in indices.iter() {
-                                                                        if idx < new_hand.len() {
-                                                                            new_hand[idx] = lobby_guard.deck.deal();
-                                                                        }
-                                                                    }
-                                                                    
-                                                                    // Update player's hand
-                                                                    player.hand = new_hand.clone();
-                                                                    lobby_guard.update_player_hand(&player_name, new_hand).await;
-                                                                    
-                                                                    // Broadcast to other players
-                                                                    let exchanged_count = indices.len();
-                                                                    lobby_guard.broadcast(format!("{} exchanged {} cards.", player_name, exchanged_count)).await;
-                                                                    println!("{} exchanged {} cards.", player_name, exchanged_count);
-                                                                    
-                                                                    // Move to the next player
-                                                                    lobby_guard.turns_remaining -= 1;
-                                                                    if lobby_guard.turns_remaining == 0 {
-                                                                        // All players have completed their draws
-                                                                        lobby_guard.game_state = lobby::SECOND_BETTING_ROUND;
-                                                                        lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                                                        lobby_guard.get_next_player(true).await;
-                                                                        lobby_guard.send_lobby_game_info().await;
-                                                                        lobby_guard.send_player_list().await;
-                                                                        println!("Drawing round complete, moving to second betting round");
-                                                                    } else {
-                                                                        lobby_guard.get_next_player(false).await;
-                                                                        lobby_guard.send_lobby_game_info().await;
-                                                                        lobby_guard.send_player_list().await;
-                                                                    }
-                                                                    
-                                                                    // Update game info
-                                                                    break;
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        } else {
-                                            // Skip players who are folded or all-in
-                                            lobby_guard.turns_remaining -= 1;
-                                            if lobby_guard.turns_remaining == 0 {
-                                                // lobby_guard.game_state = SECOND_BETTING_ROUND;
-                                                lobby_guard.game_state = SHOWDOWN;
-    
-                                                lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                                lobby_guard.get_next_player(true).await;
-                                            } else {
-                                                lobby_guard.get_next_player(false).await;
-                                            }
-                                            lobby_guard.send_lobby_game_info().await;
-                                            lobby_guard.send_player_list().await;
-                                        }
-                                    }
-                                    lobby::SHOWDOWN => {
-                                        tx.send(Message::text(r#"{"message": "Showdown Round"}"#)).unwrap();
-                                        lobby_guard.turns_remaining -= 1;
-                                        if lobby_guard.turns_remaining == 0 {
-                                            // First determine winner(s) before creating showdown data
-                                            let (winners, num_winners) = lobby_guard.showdown().await;
-                                            let showdown_data;
-                                            {
-                                                // Display all players' hands to everyone
-                                                let players = lobby_guard.players.lock().await;
-                                                
-                                                // Construct data for all active hands
-                                                let mut all_hands_data = Vec::new();
-                                                for player in players.iter() {
-                                                    if player.state != player::FOLDED {
-                                                        // Check if this player is a winner
-                                                        let is_winner = winners.contains(&player.name);
-                                                        
-                                                        let hand_data = serde_json::json!({
-                                                            "playerName": player.name,
-                                                            "hand": player.hand[0..].to_vec(), // Remove hand type from the array sent
-                                                            "winner": is_winner // Set winner flag based on the calculated winners
-                                                        });
-                                                        all_hands_data.push(hand_data);
-                                                    }
-                                                }
-                                                
-                                                // Create a formatted winner message
-                                                let winner_message = if winners.len() > 0 {
-                                                    format!("{} won the pot of ${}", winners.join(", "), lobby_guard.pot)
-                                                } else {
-                                                    "No winners determined".to_string()
-                                                };
-                                                let pot_share = lobby_guard.pot/(num_winners as i32);
-                                                
-                                                // Send all hands data to all players - using proper command format
-                                                showdown_data = serde_json::json!({
-                                                    "command": "showdownHands",
-                                                    "data": {
-                                                        "hands": all_hands_data,
-                                                        "pot": pot_share,
-                                                        "winnerMessage": winner_message
-                                                    }
-                                                });
-                                            }
-                                            lobby_guard.broadcast_json(showdown_data.to_string()).await;
-                                            println!("Showdown data sent to all players");
-                                            
-                                            // Wait briefly before ending the round
-                                            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                                            lobby_guard.game_state = lobby::UPDATE_DB;
-                                            lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                            lobby_guard.get_next_player(true).await;
-                                        } else {
-                                            // Proceed to next player
-                                            lobby_guard.get_next_player(false).await;
-                                        }
-                                    }
-                                    lobby::UPDATE_DB => {
-                                        // Update player stats and wallets in database
-                                        tx.send(Message::text(r#"{"message": "Game Ended"}"#)).unwrap();
-                                        lobby_guard.turns_remaining -= 1;
-                                        lobby_guard.get_next_player(false).await;
-                                        if lobby_guard.turns_remaining == 0 {
-                                            println!("player {} activating finished_game", player_name);
-                                            lobby_guard.finished_game().await;
-                                            lobby_guard.send_lobby_game_info().await;
-                                            lobby_guard.send_player_list().await;
-                                            println!("finished_game completed");
-                                        }
-                                        player.hand.clear();
-                                        player.state = player::IN_LOBBY;
-                                        exit = true;
-                                        drop(lobby_guard);                                
-                                    }
-                                    _ => {
-                                        panic!("Invalid game state: {}", lobby_guard.game_state);
-                                    }
-                                }
-                            } else {
-                                drop(lobby_guard);
-                            }
-                        }
-                    }
-                    let result = {
-                        // Get next message from the player's websocket
-                        let mut rx = player.rx.lock().await;
-                        match rx.next().await {
-                            Some(res) => res,
-                            None => continue,
-                        }
-                    };
-                    if let Ok(msg) = result {
-                        if let Ok(text) = msg.to_str() {
-                            // Parse the incoming JSON message
-                            let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                            match client_msg {
-                                Ok(ClientMessage::Disconnect) => {
-                                    // Player disconnected entirely
-                                    {
-                                        let mut lobby_guard = player_lobby.lock().await;
-                                        // Add to the to_be_deleted list
-                                        // Update player state to folded if in a game
-                                        if !lobby_guard.to_be_deleted.contains(&player_name) {
-                                            lobby_guard.to_be_deleted.push(player_name.clone());
-                                        }
-                                        lobby_guard.update_player_state(&player_name, player::FOLDED).await;
-                                        
-                                        // Mark player as disconnected for UI display
-                                        let mut players = lobby_guard.players.lock().await;
-                                        if let Some(p) = players.iter_mut().find(|p| p.name == player_name) {
-                                            p.disconnected = true;
-                                        }
-                                        
-                                        // Notify other players
-                                        let disconnect_msg = serde_json::json!({
-                                            "message": format!("{} has disconnected and folded.", player_name),
-                                            "playerDisconnected": {
-                                                "name": player_name,
-                                                "state": player::FOLDED
-                                            }
-                                        });
-                                        lobby_guard.broadcast_json(disconnect_msg.to_string()).await;
-
-                                        lobby_guard.send_lobby_game_info().await;
-                                        lobby_guard.send_player_list().await;
-                                    }
-
-                                    if let Err(e) = db.update_player_stats(&player).await {
-                                        eprintln!("Failed to update player stats: {}", e);
-                                    }
-
-                                    
-                                    return "Disconnect".to_string();
-                                }
-                                _ => {
-                                    continue;
-                                }
-                            }
-                        }
+                lobby.broadcast(format!("Community cards: {}", card_names.join(", "))).await;
+            }
+            Event::TurnChanged { player, phase, .. } => {
+                if *phase == lobby::DRAW {
+                    if let Some(drawing_player) = lobby.get_player_by_name(player).await {
+                        let _ = protocol::send(&drawing_player.tx, &draw_prompt());
                     }
                 }
             }
+            Event::PlayerActed { player, action, amount } => {
+                lobby.broadcast(describe_action(player, action, *amount)).await;
+            }
+            Event::CardsDrawn { player, count } => {
+                lobby.broadcast(format!("{} exchanged {} cards.", player, count)).await;
+            }
+            Event::Showdown { hands, pots } => {
+                lobby.broadcast_message(&showdown_message(lobby, hands, pots)).await;
+            }
+            Event::HandStarted { .. } | Event::CardsDealt { .. } | Event::HandFinished { .. } => {}
         }
-        
-        // After each state transition, check for spectators
-        // check_for_spectators(lobby_).await;
     }
+    lobby.send_lobby_game_info().await;
+    lobby.send_player_list().await;
 }
 
-/// This function is used to handle the game state machine for a seven-card poker game.
-/// It manages the different states of the game, including dealing cards, betting rounds, and showdown.
+/// Removes a player from their game lobby, deleting the lobby if it is now empty.
+/// A player leaving a table of a multi-table tournament is knocked out of it.
+pub async fn leave_lobby(server_lobby: &Arc<Mutex<Lobby>>, player_lobby: &Arc<Mutex<Lobby>>, player_name: &str, lobby_name: &str) {
+    let mtt_table = player_lobby.lock().await.mtt.clone();
+    let lobby_status = player_lobby.lock().await.remove_player(player_name.to_string()).await;
+    // the tables of a tournament still running are closed by the tournament
+    let tournament_running = match mtt_table.as_ref() {
+        Some(table) => !table.tournament.lock().await.event.finished,
+        None => false,
+    };
+    if lobby_status == lobby::GAME_LOBBY_EMPTY && !tournament_running {
+        server_lobby.lock().await.remove_lobby(lobby_name.to_string()).await;
+    } else {
+        server_lobby.lock().await.update_lobby_names_status(lobby_name.to_string()).await;
+    }
+    server_lobby.lock().await.broadcast_player_count().await;
+    player_lobby.lock().await.send_lobby_info().await;
+    player_lobby.lock().await.send_player_list().await;
+    if let Some(table) = mtt_table {
+        mtt::forfeit(&table.tournament, server_lobby, player_name).await;
+    }
+}
+
+/// Follows a player moved to other tables of a multi-table tournament to the lobby they sit in now.
+async fn current_table(player_lobby: &Arc<Mutex<Lobby>>, player_name: &str) -> Arc<Mutex<Lobby>> {
+    let mut lobby = player_lobby.clone();
+    loop {
+        let moved = lobby.lock().await.moved_to.remove(player_name);
+        match moved {
+            Some(next) => lobby = next,
+            None => return lobby,
+        }
+    }
+}
+
+/// Hands a command to the table task of the player's game lobby.
+fn send_to_table(player_lobby: &Lobby, command: TableCommand) {
+    if let Some(table) = player_lobby.table.as_ref() {
+        if table.send(command).is_err() {
+            println!("Table of lobby {} is no longer running", player_lobby.name);
+        }
+    }
+}
+
+/// Tells the table the player disconnected, then removes them from the server lobby.
+/// The table keeps their seat for a while if they are in the middle of a hand.
+async fn disconnect_player(server_lobby: &Arc<Mutex<Lobby>>, player_lobby: &Arc<Mutex<Lobby>>, player_name: &str) {
+    send_to_table(&*player_lobby.lock().await, TableCommand::Disconnect { player: player_name.to_string() });
+    server_lobby.lock().await.remove_player(player_name.to_string()).await;
+    server_lobby.lock().await.broadcast_player_count().await;
+}
+
+/// Runs one player's connection while they sit in a game lobby, for every game type.
+/// 
+/// The hand itself is played by the lobby's table task. This loop reads the player's messages
+/// and forwards their actions to the table, which applies them and broadcasts the results,
+/// so nothing here depends on the variant being played.
 /// 
 /// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
+/// * `server_lobby` - The server lobby the player came from.
+/// * `player` - The player, already added to their game lobby.
+/// * `db` - The database, used to read the player's wallet and stats.
 /// 
 /// # Returns
 /// 
-/// This function does not return a value. It updates the game state and player statistics.
-/// It also handles the display of game information to all players.
-
-pub async fn seven_card_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, mut player: Player, db: Arc<Database>) -> String {
+/// "Normal" when the player goes back to the server lobby, "Disconnect" when they disconnect.
+async fn game_state_machine(server_lobby: Arc<Mutex<Lobby>>, mut player: Player, db: Arc<Database>) -> String {
     let player_name = player.name.clone();
     let player_lobby = player.lobby.clone();
     let lobby_name = player_lobby.lock().await.name.clone();
     let tx = player.tx.clone();
-    
+
     // Update player state through the lobby
     {
         let mut lobby = player_lobby.lock().await;
@@ -1471,1171 +705,260 @@ pub async fn seven_card_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, mut
         lobby.update_player_state(&player_name, player::IN_LOBBY).await;
         player.state = player::IN_LOBBY;
     }
-    
-    println!("{} has joined lobby: {}", player_name, player_lobby.lock().await.name);
-    player_lobby.lock().await.new_player_join().await;
 
+    println!("{} has joined lobby: {}", player_name, lobby_name);
+    player_lobby.lock().await.new_player_join().await;
 
     // Add a delay of one second
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-    // update player attribute from db
-    let stats = db.player_stats(&player_name).await;
-    if let Ok(stats) = stats {
-        player.wallet = stats.wallet;
-        player.games_played = stats.games_played;
-        player.games_won = stats.games_won;
+    // in a Sit-and-Go the buy-in pays for the starting stack, at a cash table the player buys their stack out of their wallet
+    let tournament = player_lobby.lock().await.tournament.is_some();
+    let mtt_table = player_lobby.lock().await.mtt.clone();
+    if let Some(table) = mtt_table {
+        // the buy-in was paid on registering for the multi-table tournament
+        let starting_stack = table.tournament.lock().await.event.starting_stack;
+        player.wallet = starting_stack;
+        player_lobby.lock().await.set_player_wallet(&player_name, starting_stack).await;
+    } else if tournament {
+        let registered = player_lobby.lock().await.register(&player_name).await;
+        if let Err(error) = registered {
+            let _ = protocol::send(&tx, &ServerMessage::error(error));
+            leave_lobby(&server_lobby, &player_lobby, &player_name, &lobby_name).await;
+            let _ = protocol::send(&tx, &ServerMessage::redirect("Leaving lobby...", "server_lobby"));
+            return "Normal".to_string();
+        }
     } else {
-        tx.send(Message::text(r#"{"error": "Failed to retrieve wallet"}"#)).unwrap();
-        // add player to be deleted, then kick to server
-    }
-    
-    loop {
-        match player.state {
-            player::IN_LOBBY => {
-                println!("player {} is in lobby", player_name);
-
-                loop {
-                    let result = {
-                        // Get next message from the player's websocket
-                        let mut rx = player.rx.lock().await;
-                        match rx.next().await {
-                            Some(res) => res,
-                            None => continue,
-                        }
-                    };
-                    
-                    if let Ok(msg) = result {
-                        if let Ok(text) = msg.to_str() {
-                            // Parse the incoming JSON message
-                            let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                            
-            
-                            match client_msg {
-                                Ok(ClientMessage::Quit) => {
-                                    // QUIT LOBBY - Return to server lobby
-                                    let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                        server_lobby.lock().await.remove_lobby(lobby_name).await;
-                                    } else {
-                                        server_lobby.lock().await.update_lobby_names_status(lobby_name).await;
-                                    }
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-                                    
-                                    // Send redirect back to server lobby
-                                    tx.send(Message::text(r#"{"message": "Leaving lobby...", "redirect": "server_lobby"}"#)).unwrap();
-                                    return "Normal".to_string();
-                                }
-                                Ok(ClientMessage::Disconnect) => {
-                                    // Player disconnected entirely
-                                    let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                        server_lobby.lock().await.remove_lobby(lobby_name.clone()).await;
-                                    } else {
-                                        server_lobby.lock().await.update_lobby_names_status(lobby_name).await;
-                                    }
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-            
-                                    server_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    
-                                    // Update player stats from database
-                                    if let Err(e) = db.update_player_stats(&player).await {
-                                        eprintln!("Failed to update player stats: {}", e);
-                                    }
-                                    
-                                    return "Disconnect".to_string();
-                                }
-                                Ok(ClientMessage::ShowLobbyInfo) => {
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-                                }
-                                Ok(ClientMessage::Ready) => {
-                                        // READY UP - through the lobby
-                                        player_lobby.lock().await.check_ready(player_name.clone()).await;
-                                        player_lobby.lock().await.send_player_list().await;
-                                }
-                                Ok(ClientMessage::ShowStats) => {
-                                    // Get and send player stats
-                                    let stats = db.player_stats(&player_name).await;
-
-                                    if let Ok(stats) = stats {
-                                        player.wallet = stats.wallet;
-                                        player.games_played = stats.games_played;
-                                        player.games_won = stats.games_won;
-                                        player_lobby.lock().await.update_player_reference(&player).await;
-                                        let stats_json = serde_json::json!({
-                                            "stats": {
-                                                "username": player_name,
-                                                "gamesPlayed": stats.games_played,
-                                                "gamesWon": stats.games_won,
-                                                "wallet": stats.wallet
-                                            }
-                                        });
-                                        tx.send(Message::text(stats_json.to_string())).unwrap();
-                                    } else {
-                                        tx.send(Message::text(r#"{"error": "Failed to retrieve stats"}"#)).unwrap();
-                                    }
-                                }
-                                Ok(ClientMessage::StartGame) => {
-                                    // Start the game
-                                    println!("player: {}, received start game", player.name.clone());
-                                    let mut player_lobby_guard = player_lobby.lock().await;
-                                    player_lobby_guard.turns_remaining -= 1;
-                                    println!("turns remaining: {}", player_lobby_guard.turns_remaining);
-                                    if player_lobby_guard.turns_remaining == 0 {
-                                        player_lobby_guard.setup_game().await;
-                                    }
-                                    player.state = player::IN_GAME;
-                                    break;
-                                    
-                                }
-                                _ => {
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            _ => { // player is in game
-                // Load player stats from database for wallet
-                let stats = db.player_stats(&player_name).await;
-                if let Ok(stats) = stats {
-                    player.wallet = stats.wallet;
-                    player.games_played = 0;
-                    player.games_won = 0;
-                } else {
-                    tx.send(Message::text(r#"{"error": "Failed to retrieve wallet"}"#)).unwrap();
-                }
-                let mut exit = false;           
-                while !exit {
-                    if let Ok(mut lobby_guard) = player_lobby.try_lock() {
-                        if lobby_guard.current_player_turn == player_name {
-                            match lobby_guard.game_state {
-                                JOINABLE => {
-                                    drop(lobby_guard);
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
-                                }
-                                START_OF_ROUND => {
-                                    lobby_guard.game_state = DEAL_CARDS;
-                                    lobby_guard.deck.shuffle(); // Shuffle the deck at the start of the round
-                                    // Initialize turns counter for tracking player actions
-                                    lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                    player.current_bet = 0;
-                                    lobby_guard.send_lobby_game_info().await;
-                                }
-                                DEAL_CARDS => {
-                                    // Seven-card stud dealing logic
-                                    player.current_bet = 0;
-                                    lobby_guard.update_player_reference(&player).await;
-                                    println!("DEALING CARDS to player {}", player.name.clone());
-                                    if player.state != player::FOLDED {
-                                        // Deal cards according to the rules of Seven Card Stud
-                                        if lobby_guard.deal_card_counter == 0 {
-                                            // check if the player has money to play. if they do not, skip them and player state = folded
-                                            if player.wallet == 0 {
-                                                player.state = player::FOLDED;
-                                                lobby_guard.update_player_state(&player_name, player.state).await;
-                                                continue;
-                                            }
-                                            // First dealing round: 2 down, 1 up
-                                            // Deal first two cards face down
-                                            for _ in 0..2 {
-                                                let card = lobby_guard.deck.deal();
-                                                player.hand.push(card + 53); // +53 indicates face down
-                                            }
-                                            // Deal third card face up
-                                            player.hand.push(lobby_guard.deck.deal());
-
-                                            // they are dealt cards, they played a game
-                                            player.games_played += 1;
-                                            lobby_guard.update_player_reference(&player).await;
-                                        }
-                                        else if lobby_guard.deal_card_counter >= 1 && lobby_guard.deal_card_counter < 4 {
-                                            // Deal one face-up card
-                                            player.hand.push(lobby_guard.deck.deal());
-                                        } else if lobby_guard.deal_card_counter == 4 {
-                                            // Deal the final card face down
-                                            let card = lobby_guard.deck.deal();
-                                            player.hand.push(card + 53); // +53 indicates face down
-                                        }
-                                        lobby_guard.update_player_hand(&player_name, player.clone().hand).await;
-                                    }
-                                    lobby_guard.turns_remaining -= 1;
-                                    if lobby_guard.turns_remaining == 0 {
-                                        /*
-                                            calculate the player with best hand of face up cards
-                                            and update to the next current player
-                                        */
-                                        if lobby_guard.deal_card_counter == 0 {
-                                            lobby_guard.game_state = BRING_IN;
-                                            // If this player has the lowest up card, they pay bring-in
-                                            let mut lowest_up_card = 14;                                    
-                                            let mut lowest_up_card_player_idx = -1;
-                                            {
-                                                let players = lobby_guard.players.lock().await;
-                                                for (i, p) in players.iter().enumerate() {
-                                                    if p.state != player::FOLDED && p.hand.len() >= 3 {
-                                                        let card_value = if p.hand[2] % 13 == 0 { 13 } else { p.hand[2] % 13 }; // Treat Ace as the highest card
-                                                        if card_value < lowest_up_card {
-                                                            lowest_up_card = card_value;
-                                                            lowest_up_card_player_idx = i as i32;
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                            let player_name = lobby_guard.players.lock().await[lowest_up_card_player_idx as usize].name.clone();
-                                            lobby_guard.current_player_turn = player_name;
-                                            lobby_guard.current_player_index = lowest_up_card_player_idx;
-                                        }
-                                        else if lobby_guard.deal_card_counter < 4 {
-                                            lobby_guard.game_state = BETTING_ROUND;
-                                            let best_p_index ;
-                                            {
-                                                let players = lobby_guard.players.lock().await;
-                                                let mut best_hand = (-1, -1, -1, -1, -1, -1); // Initialize best hand tuple
-                                                let mut best_player_index = -1;
-                                                
-                                                for (i, p) in players.iter().enumerate() {
-                                                    if (p.state != player::FOLDED && p.state != player::ALL_IN) && p.hand.len() >= 3 {
-                                                        let face_up_cards: Vec<i32> = p.hand.iter()
-                                                            .skip(2) // Skip the first two face-down cards
-                                                            .filter(|&&card| card <= 52) // Only include face-up cards
-                                                            .map(|&card| card) // Keep original card values
-                                                            .collect();
-                                                        println!("{} cards {:?}", p.name, p.hand);
-                                                        println!("{} face up cards {:?}", p.name, face_up_cards);
-                                                        let hand_type = get_hand_type(&face_up_cards); // Use only the face-up cards
-                                                        println!("{} hand type: {:?}", p.name, hand_type);
-                                                        if hand_type > best_hand {
-                                                            best_hand = hand_type;
-                                                            best_player_index = i as i32;
-                                                            println!("player index with best hand {}", best_player_index)
-                                                        }
-                                                    }
-                                                    else {best_player_index = 0;}
-                                                    
-                                                }
-                                                // drop(players);
-                                                // if best_player_index != -1 {
-                                                //     lobby_guard.current_player_index = best_player_index;
-                                                // } else {
-                                                //     lobby_guard.current_player_index = lobby_guard.current_player_index; // Default to current player if no valid hands found
-                                                // }
-                                                best_p_index = best_player_index;
-                                            }
-                                            lobby_guard.current_player_index = best_p_index;
-                                            let player_name = lobby_guard.players.lock().await[best_p_index as usize].name.clone();
-                                            println!("player name of best current hand {}", player_name);
-                                            lobby_guard.current_player_turn = player_name;
-                                        } 
-                                        else {
-                                            lobby_guard.game_state = BETTING_ROUND;
-                                            // order is preserved in the last betting round since last card is face down, we already know who had the strongest hand
-                                            lobby_guard.get_next_player(false).await;
-                                        }
-                                        lobby_guard.deal_card_counter += 1;
-                                        lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                        lobby_guard.send_player_list().await;
-                                        lobby_guard.send_lobby_game_info().await;
-                                    } else {
-                                        lobby_guard.get_next_player(false).await;
-                                        lobby_guard.send_player_list().await;
-                                        lobby_guard.send_lobby_game_info().await;
-                                    }
-                                }
-                                BRING_IN => {
-                                    player.current_bet = 0;
-                                    lobby_guard.update_player_reference(&player).await;
-                                    lobby_guard.broadcast("Bring In stage".to_string()).await;
-                                    
-                                    let bring_in_amount = 15; // Standard bring-in amount
-                                    player.wallet -= bring_in_amount;
-                                    player.current_bet += bring_in_amount;
-                                    player.state = player::CALLED;
-                                    lobby_guard.pot += bring_in_amount;
-                                    lobby_guard.current_max_bet = bring_in_amount;
-                                    lobby_guard.update_player_reference(&player).await;
-                                    
-                                    // Broadcast the bring-in action
-                                    lobby_guard.broadcast(format!("{} has the lowest up card and pays the bring-in of {}", player_name, bring_in_amount)).await;
-                                    
-                                    // Set up for the betting round
-                                    lobby_guard.game_state = BETTING_ROUND;
-                                    lobby_guard.turns_remaining = lobby_guard.current_player_count - 1; // -1 because bring-in player already acted
-                                    
-                                    // Move to next player BEFORE sending game info (this is the key fix)
-                                    lobby_guard.get_next_player(false).await; // Use true to ensure we move to the next valid player
-                                    
-                                    // Now send game info with the new player turn already set
-                                    lobby_guard.send_lobby_game_info().await;
-                                    lobby_guard.send_player_list().await;
-                                }
-                                lobby::BETTING_ROUND => {
-                                    println!("betting round current player {}", player_name);
-                                    tx.send(Message::text(r#"{"message": "Betting Round"}"#)).unwrap();
-                                    // Add this debug logging to verify values
-                                    println!("Player {}: current_bet={}, lobby.current_max_bet={}", 
-                                    player_name, player.current_bet, lobby_guard.current_max_bet);
-
-                                    // skip the player if they are folded or all in
-                                    if player.state != player::FOLDED && player.state != player::ALL_IN {
-                                        loop {
-                                            let result = {
-                                                // Get next message from the player's websocket
-                                                let mut rx = player.rx.lock().await;
-                                                match rx.next().await {
-                                                    Some(res) => res,
-                                                    None => continue,
-                                                }
-                                            };
-
-                                            if let Ok(msg) = result {
-                                                if let Ok(text) = msg.to_str() {
-                                                    // Parse the incoming JSON message
-                                                    let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                                                    match client_msg {
-                                                        Ok(ClientMessage::Disconnect) => {
-                                                            /*
-                                                            Add current player into to-be-rmoved list and keep their player reference active within the players vector
-                                                            
-                                                            
-                                                             */
-                                                        }
-                                                        _ => {
-                                                            // pass in the players input and validate it (check, call, raise, fold, all in)
-                                                            if let Ok(action) = client_msg {
-                                                                let (valid_action, reset) = betting_round(&mut player, &mut lobby_guard, action).await;
-                                                                if valid_action {
-                                                                    println!("valid action");
-                                                                    // update the server lobby player reference with updated clone data
-                                                                    lobby_guard.update_player_reference(&player).await;
-                                                                    if reset {
-                                                                        println!("reseting turns remaining");
-                                                                        // reset the turns_remaining counter if the player raised
-                                                                        lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                                                    }
-                                                                    break;
-                                                                }
-                                                            } else {
-                                                                println!("Invalid client message received BAD, they try again");
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    lobby_guard.update_player_reference(&player).await;
-                                    lobby_guard.turns_remaining -= 1;
-                                    println!("player {} finish turn", player_name);
-                                    if lobby_guard.check_end_game().await {
-                                        lobby_guard.clear_betting().await;
-                                        lobby_guard.get_next_player(true).await;
-                                        lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                        lobby_guard.game_state = lobby::SHOWDOWN;
-                                    } else {
-                                        if lobby_guard.turns_remaining == 0 {
-                                            if lobby_guard.betting_round_counter < 4 {
-                                                println!("moving to dealing another card");
-                                                lobby_guard.game_state = lobby::DEAL_CARDS;
-                                            } else if lobby_guard.betting_round_counter == 4 {
-                                                lobby_guard.game_state = lobby::SHOWDOWN;
-                                            }
-                                            lobby_guard.betting_round_counter += 1;
-                                            // broadcast the betting round count
-                                            println!("Betting round {}", lobby_guard.betting_round_counter);
-                                            lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                            lobby_guard.clear_betting().await;
-                                            lobby_guard.get_next_player(false).await;
-                                            println!("betting round finished, next player turn: {}", lobby_guard.current_player_turn);
-                                        } else {
-                                            lobby_guard.get_next_player(false).await;
-                                            println!("next player turn: {}", lobby_guard.current_player_turn);
-                                        }
-                                    }
-                                    lobby_guard.send_lobby_game_info().await;
-                                    lobby_guard.send_player_list().await;
-                                    
-                                }
-                                SHOWDOWN => {
-                                    player.current_bet = 0;
-                                    tx.send(Message::text(r#"{"message": "Showdown"}"#)).unwrap();
-                                    lobby_guard.turns_remaining -= 1; 
-                                    if lobby_guard.turns_remaining == 0 {
-                                        get_rid_of_x(&lobby_guard).await;
-                                        lobby_guard.send_player_list().await;
-    
-                                        // create a copy of players hands
-                                        let mut player_hands_copy = Vec::new();
-                                        for player in lobby_guard.players.lock().await.iter() {
-                                            if player.state != player::FOLDED{
-                                                let mut hand = player.hand.clone();
-                                                // Remove face-down cards (53) from the hand
-                                                hand.retain(|&card| card <= 52);
-                                                player_hands_copy.push(hand);
-                                            }
-                                        }
-                                        // Find best 5-card hand from 7 cards
-                                        update_players_hand(&lobby_guard).await;
-                                        
-                                        // Determine winner(s) and award pot
-                                        let (winners, num_winners) = lobby_guard.showdown().await;
-    
-                                        // reasign hands so ui doesnt ruin everything
-                                        {
-                                            let mut players = lobby_guard.players.lock().await;
-                                            let mut hand_iter = player_hands_copy.iter();
-                                            for player in players.iter_mut() {
-                                                if player.state != player::FOLDED {
-                                                    if let Some(hand) = hand_iter.next() {
-                                                        player.hand = hand.clone();
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        
-                                        let showdown_data;
-                                        {
-                                            // Display all players' hands to everyone
-                                            let players = lobby_guard.players.lock().await;
-                                            
-                                            // Construct data for all active hands
-                                            let mut all_hands_data = Vec::new();
-                                            for player in players.iter() {
-                                                if player.state != player::FOLDED {
-                                                    // Check if this player is a winner
-                                                    let is_winner = winners.contains(&player.name);
-                                                    
-                                                    let hand_data = serde_json::json!({
-                                                        "playerName": player.name,
-                                                        "hand": player.hand[0..].to_vec(), // Remove hand type from the array sent
-                                                        "winner": is_winner // Set winner flag based on the calculated winners
-                                                    });
-                                                    all_hands_data.push(hand_data);
-                                                }
-                                            }
-                                            
-                                            // Create a formatted winner message
-                                            let winner_message = if winners.len() > 0 {
-                                                format!("{} won the pot of ${}", winners.join(", "), lobby_guard.pot)
-                                            } else {
-                                                "No winners determined".to_string()
-                                            };
-                                            
-                                            let pot_share = lobby_guard.pot/num_winners;
-                                            // Send all hands data to all players - using proper command format
-                                            showdown_data = serde_json::json!({
-                                                "command": "showdownHands",
-                                                "data": {
-                                                    "hands": all_hands_data,
-                                                    "pot": pot_share,
-                                                    "winnerMessage": winner_message
-                                                }
-                                            });
-                                        }
-                                        lobby_guard.broadcast_json(showdown_data.to_string()).await;
-                                        println!("Showdown data sent to all players");
-    
-                                        // Wait briefly before ending the round
-                                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                                        
-                                        lobby_guard.game_state = UPDATE_DB;
-                                        lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                        lobby_guard.get_next_player(true).await;
-                                    } else {
-                                        lobby_guard.get_next_player(false).await;
-                                    }                                
-                                    // Reveal all face-down cards
-                                }
-                                UPDATE_DB => {
-                                    tx.send(Message::text(r#"{"message": "Game Ended"}"#)).unwrap();
-                                    lobby_guard.turns_remaining -= 1;
-                                    lobby_guard.get_next_player(false).await;
-                                    if lobby_guard.turns_remaining == 0 {
-                                        println!("player {} activating finished_game", player_name);
-                                        lobby_guard.finished_game().await;
-                                        lobby_guard.send_lobby_game_info().await;
-                                        lobby_guard.send_player_list().await;
-                                        println!("finished_game completed");
-                                    }
-                                    player.hand.clear();
-                                    player.state = player::IN_LOBBY;
-                                    player.current_bet = 0;
-                                    exit = true;
-                                    drop(lobby_guard);
-                                }
-                                _ => {
-                                    panic!("Invalid game state: {}", lobby_guard.game_state);
-                                }
-                            }
-                        } else {
-                            drop(lobby_guard);
-                        }
-                    }
-                    
-                    // Handle incoming messages when it's not player's turn
-                    let result = {
-                        let mut rx = player.rx.lock().await;
-                        match rx.next().await {
-                            Some(res) => res,
-                            None => continue,
-                        }
-                    };
-                    
-                    if let Ok(msg) = result {
-                        if let Ok(text) = msg.to_str() {
-                            let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                            match client_msg {
-                                Ok(ClientMessage::Disconnect) => {
-                                    // Handle player disconnection
-                                    let lobby_name = player_lobby.lock().await.name.clone();
-                                    let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                        server_lobby.lock().await.remove_lobby(lobby_name.clone()).await;
-                                    } else {
-                                        server_lobby.lock().await.update_lobby_names_status(lobby_name).await;
-                                    }
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-            
-                                    server_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    
-                                    // Update player stats from database
-                                    if let Err(e) = db.update_player_stats(&player).await {
-                                        eprintln!("Failed to update player stats: {}", e);
-                                    }
-
-                                    
-                                    return "Disconnect".to_string();
-                                }
-                                _ => {
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                }
+        let bought = player_lobby.lock().await.buy_in(&player_name, None).await;
+        match bought {
+            Ok(stack) => player.wallet = stack,
+            Err(error) => {
+                let _ = protocol::send(&tx, &ServerMessage::error(error));
+                leave_lobby(&server_lobby, &player_lobby, &player_name, &lobby_name).await;
+                let _ = protocol::send(&tx, &ServerMessage::redirect("Leaving lobby...", "server_lobby"));
+                return "Normal".to_string();
             }
         }
     }
+
+    play_at_table(server_lobby, player, db).await
 }
 
-/// This function is used to handle the game state machine for a Texas Hold'em poker game.
-/// It manages the different states of the game, including blinds, dealing cards, betting rounds, and showdown.
+/// Finds the game lobby holding the seat of a player whose connection dropped.
+pub async fn find_held_seat(server_lobby: &Arc<Mutex<Lobby>>, player_name: &str) -> Option<Arc<Mutex<Lobby>>> {
+    let lobbies = server_lobby.lock().await.lobbies.lock().await.clone();
+    for lobby in lobbies {
+        let held = lobby
+            .lock()
+            .await
+            .get_player_by_name(player_name)
+            .await
+            .is_some_and(|p| p.disconnected);
+        if held {
+            return Some(lobby);
+        }
+    }
+    None
+}
+
+/// Asks the table of `player_lobby` to give the player their seat back on a new connection.
 /// 
 /// # Arguments
-/// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
+/// * `player_lobby` - The lobby holding the player's seat.
+/// * `player` - The player with the sender and receiver of the new connection.
 /// 
 /// # Returns
 /// 
-/// This function does not return a value. It updates the game state and player statistics.
-/// It also handles the display of game information to all players.
-pub async fn texas_holdem_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, mut player: Player, db: Arc<Database>) -> String {
-    let player_name = player.name.clone();
-    let player_lobby = player.lobby.clone();
-    let lobby_name = player_lobby.lock().await.name.clone();
-    let tx = player.tx.clone();
-    
-    // Update player state through the lobby
+/// `true` if the seat was still held and is now attached to the new connection.
+pub async fn reclaim_seat(player_lobby: &Arc<Mutex<Lobby>>, player: Player) -> bool {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    send_to_table(&*player_lobby.lock().await, TableCommand::Reconnect { player, reply: reply_tx });
+    reply_rx.await.unwrap_or(false)
+}
+
+/// Runs the connection of a player who got their seat back with `reclaim_seat`.
+/// The player is sent the lobby, the hand in progress and their own cards again before play resumes.
+/// 
+/// # Arguments
+/// * `server_lobby` - The server lobby.
+/// * `player` - The player, with `lobby` set to the lobby holding their seat.
+/// * `db` - The database, used to read the player's stats.
+/// 
+/// # Returns
+/// 
+/// "Normal" when the player goes back to the server lobby, "Disconnect" when they disconnect.
+pub async fn resume_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, player: Player, db: Arc<Database>) -> String {
+    // give the client time to load the lobby page
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
     {
-        let mut lobby = player_lobby.lock().await;
-        lobby.set_player_ready(&player_name, false).await;
-        lobby.update_player_state(&player_name, player::IN_LOBBY).await;
-        player.state = player::IN_LOBBY;
+        let lobby = player.lobby.lock().await;
+        println!("{} has reconnected to lobby: {}", player.name, lobby.name);
+        lobby.send_lobby_info().await;
+        lobby.send_lobby_game_info().await;
+        lobby.send_player_list().await;
+        if lobby.game_state == lobby::DRAW && lobby.current_player_turn == player.name {
+            let _ = protocol::send(&player.tx, &draw_prompt());
+        }
     }
-    
-    println!("{} has joined lobby: {}", player_name, player_lobby.lock().await.name);
-    player_lobby.lock().await.new_player_join().await;
+    play_at_table(server_lobby, player, db).await
+}
 
+/// Reads the messages of a player seated in a game lobby until they leave it or disconnect.
+async fn play_at_table(server_lobby: Arc<Mutex<Lobby>>, player: Player, db: Arc<Database>) -> String {
+    let player_name = player.name.clone();
+    let mut player_lobby = player.lobby.clone();
+    let mut lobby_name = player_lobby.lock().await.name.clone();
+    let tx = player.tx.clone();
 
-    // Add a delay of one second
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-    // update player attribute from db
-    let stats = db.player_stats(&player_name).await;
-    if let Ok(stats) = stats {
-        player.wallet = stats.wallet;
-        player.games_played = stats.games_played;
-        player.games_won = stats.games_won;
-    } else {
-        tx.send(Message::text(r#"{"error": "Failed to retrieve wallet"}"#)).unwrap();
-        // add player to be deleted, then kick to server
-    }
-    
     loop {
-        match player.state {
-            player::IN_LOBBY => {
-                println!("player {} is in lobby", player_name);
-
-                loop {
-                    let result = {
-                        let mut rx = player.rx.lock().await;
-                        match rx.next().await {
-                            Some(res) => res,
-                            None => continue,
-                        }
-                    };
-                    
-                    if let Ok(msg) = result {
-                        if let Ok(text) = msg.to_str() {
-                            // Parse the incoming JSON message
-                            let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                            
-                            match client_msg {
-                                Ok(ClientMessage::Quit) => {
-                                    // QUIT LOBBY - Return to server lobby
-                                    let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                        server_lobby.lock().await.remove_lobby(lobby_name.clone()).await;
-                                    } else {
-                                        server_lobby.lock().await.update_lobby_names_status(lobby_name.clone()).await;
-                                    }
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-                                    
-                                    // Send redirect back to server lobby
-                                    tx.send(Message::text(r#"{"message": "Leaving lobby...", "redirect": "server_lobby"}"#)).unwrap();
-                                    return "Normal".to_string();
-                                }
-                                Ok(ClientMessage::Disconnect) => {
-                                    // Player disconnected entirely
-                                    let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                        server_lobby.lock().await.remove_lobby(lobby_name.clone()).await;
-                                    } else {
-                                        server_lobby.lock().await.update_lobby_names_status(lobby_name.clone()).await;
-                                    }
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-            
-                                    server_lobby.lock().await.remove_player(player_name.clone()).await;
-                                    server_lobby.lock().await.broadcast_player_count().await;
-                                    
-                                    // Update player stats from database
-                                    if let Err(e) = db.update_player_stats(&player).await {
-                                        eprintln!("Failed to update player stats: {}", e);
-                                    }
-                                    
-                                    return "Disconnect".to_string();
-                                }
-                                Ok(ClientMessage::ShowLobbyInfo) => {
-                                    player_lobby.lock().await.send_lobby_info().await;
-                                    player_lobby.lock().await.send_player_list().await;
-                                }
-                                Ok(ClientMessage::Ready) => {
-                                        // READY UP - through the lobby
-                                        player_lobby.lock().await.check_ready(player_name.clone()).await;
-                                        player_lobby.lock().await.send_player_list().await;
-                                }
-                                Ok(ClientMessage::ShowStats) => {
-                                    // Get and send player stats
-                                    let stats = db.player_stats(&player_name).await;
-
-                                    if let Ok(stats) = stats {
-                                        player.wallet = stats.wallet;
-                                        player.games_played = stats.games_played;
-                                        player.games_won = stats.games_won;
-                                        player_lobby.lock().await.update_player_reference(&player).await;
-                                        let stats_json = serde_json::json!({
-                                            "stats": {
-                                                "username": player_name,
-                                                "gamesPlayed": stats.games_played,
-                                                "gamesWon": stats.games_won,
-                                                "wallet": stats.wallet
-                                            }
-                                        });
-                                        tx.send(Message::text(stats_json.to_string())).unwrap();
-                                    } else {
-                                        tx.send(Message::text(r#"{"error": "Failed to retrieve stats"}"#)).unwrap();
-                                    }
-                                }
-                                Ok(ClientMessage::StartGame) => {
-                                    println!("player: {}, received start game", player.name.clone());
-                                    let mut started = false;
-                                    while !started {
-                                        if let Ok(mut player_lobby_guard) = player_lobby.try_lock() {
-                                            player_lobby_guard.turns_remaining -= 1;
-                                            println!("turns remaining: {}", player_lobby_guard.turns_remaining);
-                                            if player_lobby_guard.turns_remaining == 0 {
-                                                player_lobby_guard.setup_game().await;
-                                            }
-                                            player_lobby_guard.update_player_state(&player_name, player::IN_GAME).await;
-                                            player.state = player::IN_GAME;
-                                            player.current_bet = 0;
-                                            started = true;
-                                        }
-                                    }
-                                    break;
-                                    
-                                }
-                                _ => {
-                                    continue;
-                                }
-                            }
-                        }
-                    }
+        let result = {
+            // Get next message from the player's websocket
+            let mut rx = player.rx.lock().await;
+            rx.next().await
+        };
+        let table = current_table(&player_lobby, &player_name).await;
+        if !Arc::ptr_eq(&table, &player_lobby) {
+            lobby_name = table.lock().await.name.clone();
+            player_lobby = table;
+        }
+        let text = match result {
+            Some(Ok(msg)) => match msg.to_str() {
+                Ok(text) => text.to_string(),
+                Err(_) => continue,
+            },
+            Some(Err(_)) => continue,
+            None => {
+                // the socket closed without sending Disconnect
+                disconnect_player(&server_lobby, &player_lobby, &player_name).await;
+                return "Disconnect".to_string();
+            }
+        };
+
+        match parse_client_message(&text) {
+            Some(ClientMessage::Quit) => {
+                if player_lobby.lock().await.hand_in_progress {
+                    let _ = protocol::send(&tx, &ServerMessage::error("You cannot leave in the middle of a hand"));
+                    continue;
                 }
+                // QUIT LOBBY - Return to server lobby
+                leave_lobby(&server_lobby, &player_lobby, &player_name, &lobby_name).await;
+
+                // Send redirect back to server lobby
+                let _ = protocol::send(&tx, &ServerMessage::redirect("Leaving lobby...", "server_lobby"));
+                return "Normal".to_string();
+            }
+            Some(ClientMessage::Disconnect) => {
+                disconnect_player(&server_lobby, &player_lobby, &player_name).await;
+                return "Disconnect".to_string();
+            }
+            Some(ClientMessage::ShowLobbyInfo) => {
+                player_lobby.lock().await.send_lobby_info().await;
+                player_lobby.lock().await.send_player_list().await;
             }
-            _ => { // player is in game
-                // Refresh player stats at beginning of game
+            Some(ClientMessage::Ready) => {
+                // READY UP - through the lobby
+                player_lobby.lock().await.check_ready(player_name.clone()).await;
+                player_lobby.lock().await.send_player_list().await;
+            }
+            Some(ClientMessage::ShowStats) => {
+                // Get and send player stats
                 let stats = db.player_stats(&player_name).await;
                 if let Ok(stats) = stats {
-                    player.wallet = stats.wallet;
-                    player.games_played = 0;
-                    player.games_won = 0;
+                    let _ = protocol::send(&tx, &ServerMessage::Stats { stats: Stats::new(&stats) });
                 } else {
-                    tx.send(Message::text(r#"{"error": "Failed to retrieve wallet"}"#)).unwrap();
+                    let _ = protocol::send(&tx, &ServerMessage::error("Failed to retrieve stats"));
                 }
-                
-                let mut exit = false;
-                while !exit {
-                    if let Ok(mut lobby_guard) = player_lobby.try_lock() {
-                        if lobby_guard.game_state == lobby::JOINABLE {
-                            drop(lobby_guard);
-                            tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
-                        } else if lobby_guard.current_player_turn == player_name {
-                            match lobby_guard.game_state {
-                                lobby::START_OF_ROUND => {
-                                    println!("Starting new round");
-                                    lobby_guard.game_state = lobby::DEAL_CARDS;
-                                    lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                    lobby_guard.send_lobby_game_info().await;
-                                }
-                                lobby::DEAL_CARDS => {
-                                    println!("DEALING CARDS to player {}, deal card counter {}", player_name, lobby_guard.deal_card_counter);
-                                    player.current_bet = 0;
-                                    
-                                    if lobby_guard.deal_card_counter == 0 {
-
-                                        // Pre-flop: Deal 2 hole cards to each player one by one
-                                        if player.state != player::FOLDED {
-                                            // Deal 2 hole cards to this player
-                                            // player.games_played += 1; // Count this as a played game
-                                            // lobby_guard.deal_cards_texas( 0, player).await;
-                                            
-                                            player.played_game = true;
-                                            lobby_guard.update_player_played_game(&player).await;
-                                            player.hand.push(lobby_guard.deck.deal());
-                                            player.hand.push(lobby_guard.deck.deal());
-
-                                            lobby_guard.update_player_hand(&player_name, player.hand.clone()).await;
-                                            // lobby_guard.update_player_reference(&player).await;
-                                            lobby_guard.send_lobby_game_info().await;
-                                            lobby_guard.send_player_list().await;
-                                        }
-                                        
-                                        // Move to next player after dealing
-                                        lobby_guard.turns_remaining -= 1;
-                                        if lobby_guard.turns_remaining == 0 {
-                                            // All players have been dealt cards, move to betting round
-                                            lobby_guard.broadcast("All players have been dealt their hole cards.".to_string()).await;
-                                            lobby_guard.deal_card_counter += 1;
-                                            lobby_guard.game_state = SMALL_AND_BIG_BLIND;
-                                            lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                            lobby_guard.get_next_player(false).await;
-                                            lobby_guard.send_lobby_game_info().await;
-                                            // lobby_guard.send_player_list().await;
-                                            break;
-                                        } else {
-                                            lobby_guard.get_next_player(false).await;
-                                            break;
-                                        }
-                                    } 
-                                    // ---- only one player should get into below ----
-                                    else if lobby_guard.deal_card_counter == 1 {
-                                        // Flop: Deal 3 community cards (these are shared, not per-player)
-                                        lobby_guard.broadcast("Dealing the flop...".to_string()).await;
-                                        for _ in 0..3 {
-                                            let card = lobby_guard.deck.deal();
-                                            lobby_guard.community_cards.push(card);
-                                        }
-                                        // lobby_guard.deal_cards_texas(1 , player).await; // 1 = flop
-                                    } else if lobby_guard.deal_card_counter > 1 && lobby_guard.deal_card_counter <= 3 {
-                                        // Turn: Deal 1 community card (shared)
-                                        lobby_guard.broadcast("Dealing the turn...".to_string()).await;
-                                        // lobby_guard.deal_cards_texas(2 , player).await; // 1 = flop
-                                        let card = lobby_guard.deck.deal();
-                                        lobby_guard.community_cards.push(card);
-
-                                    }
-                                    lobby_guard.deal_card_counter += 1;
-                                    lobby_guard.game_state = BETTING_ROUND;
-                                    lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                    lobby_guard.get_next_player(true).await;
-                                    lobby_guard.send_lobby_game_info().await;
-
-
-                                }
-                                lobby::SMALL_AND_BIG_BLIND => {
-                                    println!("Blinds round current player: {}", player_name);
-                                    tx.send(Message::text(r#"{"message": "Blinds Round"}"#)).unwrap();
-                                    player.played_game = true;
-                                    lobby_guard.update_player_played_game(&player).await;
-
-                                    if player.state != player::FOLDED || !player.disconnected {
-                                        lobby_guard.send_lobby_game_info().await;
-                                        if !lobby_guard.big_blinds_done || !lobby_guard.small_blinds_done {
-                                            let mut blinds = 0;
-                                            if !lobby_guard.small_blinds_done {
-                                                blinds = SMALL_BLIND;
-                                                lobby_guard.turns_remaining += 1;
-                                            } else if !lobby_guard.big_blinds_done {
-                                                blinds = BIG_BLIND;
-                                                lobby_guard.turns_remaining += 1;
-                                            }
-                                            if player.wallet >= blinds {
-                                                player.wallet -= blinds;
-                                                player.current_bet = blinds;
-                                                lobby_guard.current_max_bet = blinds;
-                                                lobby_guard.update_player_reference(&player).await;
-                                                lobby_guard.pot += blinds;
-                                                if blinds == SMALL_BLIND {
-                                                    lobby_guard.small_blinds_done = true;
-                                                    println!("player {} put in small blind", player_name);
-                                                } else {
-                                                    lobby_guard.big_blinds_done = true;
-                                                    println!("player {} put in big blind", player_name);
-                                                }
-
-                                            } else {
-                                                player.state = player::FOLDED;
-                                                lobby_guard.update_player_state(&player_name, player::FOLDED).await;
-                                            }
-                                        } else {
-                                            loop {
-                                                let result = {
-                                                    // Get next message from the player's websocket
-                                                    let mut rx = player.rx.lock().await;
-                                                    match rx.next().await {
-                                                        Some(res) => res,
-                                                        None => continue,
-                                                    }
-                                                };
-    
-                                                if let Ok(msg) = result {
-                                                    if let Ok(text) = msg.to_str() {
-                                                        // Parse the incoming JSON message
-                                                        let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                                                        match client_msg {
-                                                            Ok(ClientMessage::Disconnect) => {
-                                                                /*
-                                                                Add current player into to-be-rmoved list and keep their player reference active within the players vector
-                                                                
-                                                                
-                                                                 */
-    
-    
-                                                                // // Player disconnected entirely
-                                                                // let lobby_status = player_lobby.lock().await.remove_player(player_name.clone()).await;
-                                                                // if lobby_status == lobby::GAME_LOBBY_EMPTY {
-                                                                //     lobby_guard.remove_lobby(lobby_name.clone()).await;
-                                                                // } else {
-                                                                //     lobby_guard.update_lobby_names_status(lobby_name).await;
-                                                                // }
-                                                                // lobby_guard.broadcast_player_count().await;
-                                                                // lobby_guard.send_lobby_info().await;
-                                                                // lobby_guard.send_player_list().await;
-                                        
-                                                                // lobby_guard.remove_player(player_name.clone()).await;
-                                                                // lobby_guard.broadcast_player_count().await;
-                                                                
-                                                                // // Update player stats from database
-                                                                // if let Err(e) = db.update_player_stats(&player).await {
-                                                                //     eprintln!("Failed to update player stats: {}", e);
-                                                                // }
-                                                                
-                                                                // return "Disconnect".to_string();
-                                                            }
-                                                            _ => {
-                                                                // pass in the players input and validate it (check, call, raise, fold, all in)
-                                                                if let Ok(action) = client_msg {
-                                                                    let (valid_action, reset) = betting_round(&mut player, &mut lobby_guard, action).await;
-                                                                    if valid_action {
-                                                                        println!("valid action");
-                                                                        // update the server lobby player reference with updated clone data
-                                                                        lobby_guard.update_player_reference(&player).await;
-                                                                        if reset {
-                                                                            println!("reseting turns remaining");
-                                                                            // reset the turns_remaining counter if the player raised
-                                                                            lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                                                        }
-                                                                        break;
-                                                                    }
-                                                                } else {
-                                                                    println!("Invalid client message received BAD, they try again");
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    lobby_guard.turns_remaining -= 1;
-                                    println!("player {} finish turn", player_name);
-                                    if lobby_guard.check_end_game().await {
-                                        lobby_guard.clear_betting().await;
-                                        player.current_bet = 0;
-                                        lobby_guard.game_state = lobby::SHOWDOWN;
-                                    } else {
-                                        if lobby_guard.turns_remaining == 0 {
-                                            lobby_guard.game_state = lobby::DEAL_CARDS;
-                                            lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                            lobby_guard.clear_betting().await;
-                                            player.current_bet = 0;
-                                            lobby_guard.get_next_player(true).await;
-                                            println!("betting round finished, next player turn: {}", lobby_guard.current_player_turn);
-                                        } else {
-                                            lobby_guard.get_next_player(false).await;
-                                            println!("next player turn: {}", lobby_guard.current_player_turn);
-                                        }
-                                    }
-                                    lobby_guard.send_lobby_game_info().await;
-                                    lobby_guard.send_player_list().await;
-                                }
-                                
-                                lobby::BETTING_ROUND => {
-                                    println!("Betting round for player {}", player_name);
-                                    tx.send(Message::text(r#"{"message": "Betting Round"}"#)).unwrap();
-                                    
-                                    if player.state != player::FOLDED && player.state != player::ALL_IN {
-                                        // Process player's betting action
-                                        loop {
-                                            let result = {
-                                                let mut rx = player.rx.lock().await;
-                                                match rx.next().await {
-                                                    Some(res) => res,
-                                                    None => continue,
-                                                }
-                                            };
-                                            
-                                            if let Ok(msg) = result {
-                                                if let Ok(text) = msg.to_str() {
-                                                    let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                                                    if let Ok(action) = client_msg {
-                                                        let (valid_action, reset) = betting_round(&mut player, &mut lobby_guard, action).await;
-                                                        if valid_action {
-                                                            lobby_guard.update_player_reference(&player).await;
-                                                            if reset {
-                                                                lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                                            }
-                                                            break;
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    
-                                    lobby_guard.turns_remaining -= 1;
-                                    println!("Player {} finished turn", player_name);
-                                    
-                                    if lobby_guard.check_end_game().await {
-                                        // Game ended early (e.g., all but one player folded)
-                                        lobby_guard.clear_betting().await;
-                                        lobby_guard.game_state = lobby::SHOWDOWN;
-                                    } else if lobby_guard.turns_remaining == 0 {
-                                        // All players acted, move to next phase
-                                        if lobby_guard.deal_card_counter < 4 {
-                                            lobby_guard.game_state = lobby::DEAL_CARDS;
-                                        } else {
-                                            lobby_guard.game_state = lobby::SHOWDOWN;
-                                        }
-                                        lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                        lobby_guard.clear_betting().await;
-                                        lobby_guard.get_next_player(true).await;
-                                    } else {
-                                        lobby_guard.get_next_player(false).await;
-                                    }
-                                    
-                                    lobby_guard.send_lobby_game_info().await;
-                                    lobby_guard.send_player_list().await;
-                                }
-                                lobby::SHOWDOWN => {
-                                    println!("Showdown round");
-                                    lobby_guard.broadcast("------ Showdown! ------".to_string()).await;
-                                    
-                                    lobby_guard.turns_remaining -= 1;
-                                    if lobby_guard.turns_remaining == 0 {
-                                        // Store original hands before evaluation
-                                        let original_hands = {
-                                            let players = lobby_guard.players.lock().await;
-                                            let mut hands_map = std::collections::HashMap::new();
-                                            for p in players.iter() {
-                                                if p.hand.len() >= 2 {
-                                                    hands_map.insert(p.name.clone(), p.hand.clone());
-                                                }
-                                            }
-                                            hands_map
-                                        };
-                                        
-                                        // Find best 5-card hands from 7 cards
-                                        update_players_hand(&lobby_guard).await;
-                                        
-                                        // Get winners before generating the UI data
-                                        let winners = lobby_guard.showdown_texas().await;
-
-
-                                        
-                                        // Prepare showdown data with the original hands
-                                        let showdown_data;
-                                        {
-                                            let players = lobby_guard.players.lock().await;
-                                            
-                                            let mut all_hands_data = Vec::new();
-                                            for player in players.iter() {
-                                                if player.state != player::FOLDED {
-                                                    // Get original hand to display
-                                                    let original_hand = original_hands.get(&player.name).cloned().unwrap_or_default();
-                                                    // Check if player is winner
-                                                    let is_winner = winners.contains(&player.name);
-                                                    
-                                                    let hand_data = serde_json::json!({
-                                                        "playerName": player.name,
-                                                        "hand": original_hand, // Use original hands for UI
-                                                        "handRank": player.hand, // Best hand data stored here for ranking
-                                                        "handName": hand_type_to_string(player.hand[0]), // Readable hand type
-                                                        "winner": is_winner
-                                                    });
-                                                    all_hands_data.push(hand_data);
-                                                }
-                                            }
-                                            
-                                            // Create winner message
-                                            let winner_message = if winners.len() > 0 {
-                                                format!("{} won the pot of ${}", winners.join(", "), lobby_guard.pot)
-                                            } else {
-                                                "No winners determined".to_string()
-                                            };
-
-                                            let num_winners = winners.len() as i32;
-                                            let pot_share = lobby_guard.pot/num_winners;
-                                            
-                                            // Send data to clients
-                                            showdown_data = serde_json::json!({
-                                                "command": "showdownHands",
-                                                "data": {
-                                                    "hands": all_hands_data,
-                                                    "communityCards": lobby_guard.community_cards.clone(),
-                                                    "pot": pot_share,
-                                                    "winnerMessage": winner_message
-                                                }
-                                            });
-                                        }
-                                        
-                                        // Broadcast showdown result to all players
-                                        lobby_guard.broadcast_json(showdown_data.to_string()).await;
-                                        
-                                        // Wait briefly before advancing
-                                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                                        
-                                        // Move to next game state
-                                        lobby_guard.game_state = lobby::UPDATE_DB;
-                                        lobby_guard.turns_remaining = lobby_guard.current_player_count;
-                                        lobby_guard.get_next_player(true).await;
-                                    } else {
-                                        // Proceed to next player's turn
-                                        lobby_guard.get_next_player(false).await;
-                                    }
-                                }
-                            
-                                lobby::UPDATE_DB => {
-                                    // Update player stats and wallets in database
-                                    tx.send(Message::text(r#"{"message": "Game Ended"}"#)).unwrap();
-                                    if player.played_game {
-                                        player.games_played += 1;
-                                    }
-                                    if player.won_game{
-                                        player.games_won += 1;
-                                    }
-                                    lobby_guard.update_player_reference(&player).await;
-                                    lobby_guard.turns_remaining -= 1;
-                                    lobby_guard.get_next_player(false).await;
-
-                                    if lobby_guard.turns_remaining == 0 {
-                                        println!("player {} activating finished_game", player_name);
-                                        lobby_guard.finished_game().await;
-                                        lobby_guard.send_lobby_game_info().await;
-                                        lobby_guard.send_player_list().await;
-                                        println!("finished_game completed");
-                                    }
-                                    player.hand.clear();
-                                    player.state = player::IN_LOBBY;
-                                    exit = true;
-                                    drop(lobby_guard);                                
-                                }
-                                _ => {
-                                    panic!("Invalid game state: {}", lobby_guard.game_state);
-                                }
-                            }
-                        } else {
-                            drop(lobby_guard);
-                        }
-                    }
-                    
-                    // Handle incoming messages when it's not player's turn
-                    let result = {
-                        let mut rx = player.rx.lock().await;
-                        match rx.next().await {
-                            Some(res) => res,
-                            None => continue,
-                        }
-                    };
-                    
-                    if let Ok(msg) = result {
-                        if let Ok(text) = msg.to_str() {
-                            let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
-                            match client_msg {
-                                Ok(ClientMessage::Disconnect) => {
-                                    // Handle player disconnection
-                                    let mut lobby_guard = player_lobby.lock().await;
-                                    
-                                    // Mark as disconnected and folded if in a game
-                                    if !lobby_guard.to_be_deleted.contains(&player_name) {
-                                        lobby_guard.to_be_deleted.push(player_name.clone());
-                                    }
-                                    lobby_guard.update_player_state(&player_name, player::FOLDED).await;
-                                    
-                                    // Notify other players
-                                    let disconnect_msg = serde_json::json!({
-                                        "message": format!("{} has disconnected and folded.", player_name),
-                                        "playerDisconnected": {
-                                            "name": player_name,
-                                            "state": player::FOLDED
-                                        }
-                                    });
-                                    lobby_guard.broadcast_json(disconnect_msg.to_string()).await;
-
-                                    // Update game info
-                                    lobby_guard.send_lobby_game_info().await;
-                                    lobby_guard.send_player_list().await;
-                                    
-                                    if let Err(e) = db.update_player_stats(&player).await {
-                                        eprintln!("Failed to update player stats: {}", e);
-                                    }
-                                    
-                                    return "Disconnect".to_string();
-                                }
-                                _ => {
-                                    continue;
-                                }
-                            }
-                        }
-                    }
+            }
+            Some(ClientMessage::BuyIn { amount }) => {
+                // top up or rebuy at a cash table, between hands
+                let bought = player_lobby.lock().await.buy_in(&player_name, amount).await;
+                if let Err(error) = bought {
+                    let _ = protocol::send(&tx, &ServerMessage::error(error));
                 }
             }
+            Some(ClientMessage::StartGame) => {
+                // The table starts the hand once every player has asked to start
+                let command = TableCommand::StartGame { player: player_name.clone() };
+                send_to_table(&*player_lobby.lock().await, command);
+            }
+            Some(message) => {
+                let action = match to_engine_action(message) {
+                    Some(action) => action,
+                    None => continue,
+                };
+                let command = TableCommand::Act { player: player_name.clone(), action };
+                send_to_table(&*player_lobby.lock().await, command);
+            }
+            None => {
+                println!("Invalid client message received from {}", player_name);
+            }
         }
     }
 }
 
+/// This function is used to handle the game state machine for a five-card poker game.
+/// Ante, dealing, the two betting rounds, the drawing round and the showdown are run by the engine;
+/// this is the adapter between the player's WebSocket and the lobby's engine.
+/// 
+/// # Arguments
+/// * `server_lobby` - The server lobby the player came from.
+/// * `player` - The player, already added to their game lobby.
+/// * `db` - The database, used to read the player's wallet and stats.
+/// 
+/// # Returns
+/// 
+/// This function returns a string indicating the result of the game state machine execution.
+pub async fn five_card_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, player: Player, db: Arc<Database>) -> String {
+    game_state_machine(server_lobby, player, db).await
+}
+
+/// This function is used to handle the game state machine for a seven-card poker game.
+/// Dealing, the bring-in, the five betting rounds and the showdown are run by the engine;
+/// this is the adapter between the player's WebSocket and the lobby's engine.
+/// 
+/// # Arguments
+/// * `server_lobby` - The server lobby the player came from.
+/// * `player` - The player, already added to their game lobby.
+/// * `db` - The database, used to read the player's wallet and stats.
+/// 
+/// # Returns
+/// 
+/// This function returns a string indicating the result of the game state machine execution.
+pub async fn seven_card_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, player: Player, db: Arc<Database>) -> String {
+    game_state_machine(server_lobby, player, db).await
+}
+
+/// This function is used to handle the game state machine for a Texas Hold'em poker game.
+/// Blinds, dealing, the four betting rounds and the showdown are run by the engine;
+/// this is the adapter between the player's WebSocket and the lobby's engine.
+/// 
+/// # Arguments
+/// * `server_lobby` - The server lobby the player came from.
+/// * `player` - The player, already added to their game lobby.
+/// * `db` - The database, used to read the player's wallet and stats.
+/// 
+/// # Returns
+/// 
+/// This function returns a string indicating the result of the game state machine execution.
+pub async fn texas_holdem_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, player: Player, db: Arc<Database>) -> String {
+    game_state_machine(server_lobby, player, db).await
+}
+
+/// This function is used to handle the game state machine for a Pot-Limit Omaha poker game.
+/// Blinds, the four hole cards, the four pot-limit betting rounds and the showdown are run by the engine;
+/// this is the adapter between the player's WebSocket and the lobby's engine.
+/// 
+/// # Arguments
+/// * `server_lobby` - The server lobby the player came from.
+/// * `player` - The player, already added to their game lobby.
+/// * `db` - The database, used to read the player's wallet and stats.
+/// 
+/// # Returns
+/// 
+/// This function returns a string indicating the result of the game state machine execution.
+pub async fn omaha_game_state_machine(server_lobby: Arc<Mutex<Lobby>>, player: Player, db: Arc<Database>) -> String {
+    game_state_machine(server_lobby, player, db).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2701,6 +1024,258 @@ mod tests {
         assert_eq!(result, (8, 6, 6, 0, 0, 0)); // Straight flush from 3 to 7
     }
 
-    
+    #[test]
+    fn test_get_hand_type_wheel_straight() {
+        let hand = vec![0, 14, 28, 42, 4]; // Ace Hearts, 2 Diamond, 3 Spade, 4 Club, 5 Hearts
+        let result = get_hand_type(&hand);
+        assert_eq!(result, (4, 4, 0, 0, 0, 0)); // Five-high straight
+        
+        let six_high = get_hand_type(&[1, 15, 29, 43, 5]); // 2 Hearts to 6 Hearts, mixed suits
+        assert!(six_high > result);
+    }
+
+    #[test]
+    fn test_get_hand_type_wheel_straight_flush() {
+        let hand = vec![13, 14, 15, 16, 17]; // Ace to 5 of Diamonds
+        let result = get_hand_type(&hand);
+        assert_eq!(result, (8, 4, 4, 0, 0, 0)); // Five-high straight flush
+        
+        let six_high = get_hand_type(&[14, 15, 16, 17, 18]); // 2 to 6 of Diamonds
+        assert!(six_high > result);
+        assert!(result > get_hand_type(&[0, 13, 26, 39, 1])); // beats four Aces
+    }
+
+    #[test]
+    fn test_get_hand_type_royal_flush() {
+        let royal = get_hand_type(&[26, 35, 36, 37, 38]); // Ace, 10, Jack, Queen, King of Spades
+        assert_eq!(royal, (8, 13, 13, 0, 0, 0));
+        let king_high = get_hand_type(&[34, 35, 36, 37, 38]); // 9 to King of Spades
+        assert!(royal > king_high);
+        // Ace high without a straight is just a flush
+        assert_eq!(get_hand_type(&[26, 27, 36, 37, 38]).0, 5);
+    }
+
+    #[test]
+    fn test_get_hand_type_no_wraparound_straight() {
+        let hand = vec![11, 25, 39, 1, 15]; // Queen, King, Ace, 2, 3
+        assert_eq!(get_hand_type(&hand).0, 0);
+    }
+
+    #[test]
+    fn test_compare_hands_pair_kickers() {
+        let pair_ace_kicker = get_hand_type(&[2, 15, 0, 48, 18]); // 3 3 A 10 6
+        let pair_king_kicker = get_hand_type(&[28, 41, 12, 47, 19]); // 3 3 K 9 7
+        assert_eq!(compare_hands(&pair_ace_kicker, &pair_king_kicker), std::cmp::Ordering::Greater);
+        
+        let last_kicker_low = get_hand_type(&[2, 15, 0, 48, 17]); // 3 3 A 10 5
+        assert_eq!(compare_hands(&pair_ace_kicker, &last_kicker_low), std::cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn test_compare_hands_flush_below_top_card() {
+        let flush_high = get_hand_type(&[12, 10, 7, 4, 2]); // K J 8 5 3 of Hearts
+        let flush_low = get_hand_type(&[25, 23, 20, 17, 14]); // K J 8 5 2 of Diamonds
+        assert_eq!(compare_hands(&flush_high, &flush_low), std::cmp::Ordering::Greater);
+        assert_eq!(compare_hands(&flush_low, &flush_high), std::cmp::Ordering::Less);
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_compare_hands_true_tie() {
+        let straight_a = get_hand_type(&[2, 16, 30, 44, 19]); // 3 to 7
+        let straight_b = get_hand_type(&[15, 29, 43, 5, 32]); // 3 to 7, different suits
+        assert_eq!(compare_hands(&straight_a, &straight_b), std::cmp::Ordering::Equal);
+        
+        let two_pair_a = get_hand_type(&[2, 15, 32, 45, 18]); // 3 3 7 7 6
+        let two_pair_b = get_hand_type(&[28, 41, 6, 19, 44]); // 3 3 7 7 6
+        assert_eq!(compare_hands(&two_pair_a, &two_pair_b), std::cmp::Ordering::Equal);
+    }
+
+    /// The 21-combination brute force `get_best_hand` used before the lookup-table evaluator.
+    fn brute_force_best_hand(hand: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
+        let mut best_hand = (-1, -1, -1, -1, -1, -1);
+        let n = hand.len();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                for k in (j + 1)..n {
+                    for l in (k + 1)..n {
+                        for m in (l + 1)..n {
+                            let current_hand_type = get_hand_type(&[hand[i], hand[j], hand[k], hand[l], hand[m]]);
+                            if current_hand_type > best_hand {
+                                best_hand = current_hand_type;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        best_hand
+    }
+
+    #[test]
+    fn test_evaluator_matches_get_hand_type_for_all_five_card_hands() {
+        for a in 0..48 {
+            for b in (a + 1)..49 {
+                for c in (b + 1)..50 {
+                    for d in (c + 1)..51 {
+                        for e in (d + 1)..52 {
+                            let hand = [a, b, c, d, e];
+                            assert_eq!(evaluator::to_hand_value(evaluator::evaluate(&hand)), get_hand_type(&hand), "hand {:?}", hand);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_best_hand_matches_brute_force() {
+        for size in [6, 7] {
+            for _ in 0..20000 {
+                let mut deck = Deck::new();
+                deck.shuffle();
+                let hand: Vec<i32> = (0..size).map(|_| deck.deal().unwrap()).collect();
+                assert_eq!(get_best_hand(&hand), brute_force_best_hand(&hand), "hand {:?}", hand);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_best_omaha_hand_uses_two_hole_cards_and_three_board_cards() {
+        // four hearts on the board and one in hand is no flush in Omaha
+        let hole_cards = vec![0, 14, 27, 40]; // Ace Hearts, 2 Diamond, 2 Spade, 2 Club
+        let board = vec![4, 6, 8, 10, 51]; // 5 7 9 Jack of Hearts, King Club
+        assert_eq!(get_best_hand(&[0, 14, 4, 6, 8, 10, 51]).0, 5); // a flush in Hold'em
+        assert_eq!(get_best_omaha_hand(&hole_cards, &board), (1, 1, 12, 10, 8, 0)); // only two of the 2s play
+
+        // a single hole card does not make a straight with four on the board
+        let hole_cards = vec![0, 26, 39, 2]; // Ace Hearts, Ace Spade, Ace Club, 3 Hearts
+        let board = vec![25, 24, 23, 22, 43]; // King Queen Jack 10 of Diamonds, 5 Club
+        assert_eq!(get_best_hand(&[0, 26, 25, 24, 23, 22, 43]).0, 4); // a straight in Hold'em
+        assert_eq!(get_best_omaha_hand(&hole_cards, &board).0, 1); // a pair of Aces
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn test_get_best_omaha_hand_needs_three_board_cards() {
+        get_best_omaha_hand(&[0, 26, 39, 2], &[25, 24]);
+    }
+
+    #[test]
+    fn test_get_low_hand() {
+        // Ace Hearts, 3 Diamond, 5 Spade, 8 Club, 4 Hearts, King Spade, 3 Club
+        assert_eq!(get_low_hand(&[0, 15, 30, 46, 3, 38, 41]), Some([8, 5, 4, 3, 1]));
+        // the wheel is the best low
+        assert_eq!(get_low_hand(&[0, 1, 2, 3, 17]), Some([5, 4, 3, 2, 1]));
+        // a pair leaves only four low ranks, a 9 does not qualify
+        assert_eq!(get_low_hand(&[0, 13, 1, 2, 3, 8, 12]), None);
+        assert!(Some([7, 5, 4, 3, 1]) < get_low_hand(&[0, 15, 30, 46, 3]));
+    }
+
+    #[test]
+    fn test_get_omaha_low_hand_uses_two_hole_cards() {
+        // Ace Hearts, 2 Diamond, 3 Hearts, 4 Hearts: a low needs three low cards on the board
+        let hole_cards = vec![0, 14, 2, 3];
+        let board = vec![17, 20, 50, 35]; // 5 Diamond, 8 Diamond, Queen Club, 10 Spade
+        assert_eq!(get_omaha_low_hand(&hole_cards, &board), None);
+        // only the Ace and the 2 play, the 3 and 4 in hand cannot replace the 7 and 8 on the board
+        let board = vec![17, 20, 6, 35]; // 5 Diamond, 8 Diamond, 7 Hearts, 10 Spade
+        assert_eq!(get_omaha_low_hand(&hole_cards, &board), Some([8, 7, 5, 2, 1]));
+        // three low cards in hand and two on the board do not make a low
+        assert_eq!(get_omaha_low_hand(&[0, 14, 2, 51], &[17, 20, 50, 35, 48]), None);
+    }
+
+    #[test]
+    fn test_get_ace_to_five_low_ordering() {
+        let wheel = get_ace_to_five_low(&[0, 14, 28, 42, 4]); // Ace Hearts, 2 Diamond, 3 Spade, 4 Club, 5 Hearts
+        assert_eq!(wheel, (0, 4, 3, 2, 1, 0));
+        // 6-4 beats 6-5
+        let six_four = get_ace_to_five_low(&[5, 16, 2, 27, 39]); // 6 Hearts, 4 Diamond, 3 Hearts, 2 Spade, Ace Club
+        let six_five = get_ace_to_five_low(&[5, 17, 2, 27, 39]); // 6 Hearts, 5 Diamond, 3 Hearts, 2 Spade, Ace Club
+        assert_eq!(compare_hands(&wheel, &six_four), std::cmp::Ordering::Less);
+        assert_eq!(compare_hands(&six_four, &six_five), std::cmp::Ordering::Less);
+        // a pair of Aces is worse than King high
+        let aces = get_ace_to_five_low(&[0, 13, 1, 2, 3]);
+        let king_high = get_ace_to_five_low(&[12, 24, 36, 48, 8]); // King, Queen, Jack, 10, 9
+        assert_eq!(compare_hands(&aces, &king_high), std::cmp::Ordering::Greater);
+        // straights and flushes do not count
+        assert_eq!(get_ace_to_five_low(&[1, 2, 3, 4, 5]), (0, 5, 4, 3, 2, 1)); // 2 to 6 of Hearts
+        // the best five of seven cards play, King Hearts and King Diamond are left out
+        assert_eq!(get_ace_to_five_low(&[12, 25, 0, 14, 28, 42, 4]), wheel);
+    }
+
+    #[test]
+    fn test_get_deuce_to_seven_low_ordering() {
+        let seven_five = get_deuce_to_seven_low(&[6, 17, 29, 41, 1]); // 7 Hearts, 5 Diamond, 4 Spade, 3 Club, 2 Hearts
+        assert_eq!(seven_five, (0, 6, 4, 3, 2, 1));
+        let eight_five = get_deuce_to_seven_low(&[7, 17, 29, 41, 1]); // 8 Hearts instead of the 7
+        assert_eq!(compare_hands(&seven_five, &eight_five), std::cmp::Ordering::Less);
+        // the Ace is high, A-2-3-4-5 is Ace high and no straight
+        let ace_high = get_deuce_to_seven_low(&[0, 14, 28, 42, 4]);
+        assert_eq!(ace_high, (0, 13, 4, 3, 2, 1));
+        assert_eq!(compare_hands(&eight_five, &ace_high), std::cmp::Ordering::Less);
+        // straights and flushes count against the hand
+        assert_eq!(get_deuce_to_seven_low(&[1, 15, 29, 43, 5]).0, 4); // 2 to 6, mixed suits
+        assert_eq!(get_deuce_to_seven_low(&[6, 4, 3, 2, 1]).0, 5); // 7-5-4-3-2 of Hearts
+        assert_eq!(get_deuce_to_seven_low(&[0, 1, 2, 3, 4]).0, 5); // Ace to 5 of Hearts is only a flush
+        // any unpaired hand beats a pair of 2s
+        assert_eq!(compare_hands(&ace_high, &get_deuce_to_seven_low(&[1, 14, 28, 42, 4])), std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn test_evaluator_strength_orders_like_compare_hands() {
+        for _ in 0..20000 {
+            let mut deck = Deck::new();
+            deck.shuffle();
+            let first: Vec<i32> = (0..7).map(|_| deck.deal().unwrap()).collect();
+            let second: Vec<i32> = (0..7).map(|_| deck.deal().unwrap()).collect();
+            let by_strength = evaluator::evaluate(&first).cmp(&evaluator::evaluate(&second));
+            let by_tuple = compare_hands(&brute_force_best_hand(&first), &brute_force_best_hand(&second));
+            assert_eq!(by_strength, by_tuple, "hands {:?} {:?}", first, second);
+        }
+    }
+
+    /// Evaluates every 5-card hand in the deck and checks the category counts and the number
+    /// of distinct hand values against the standard hand-ranking tables.
+    #[test]
+    fn test_get_hand_type_all_hands() {
+        let mut category_counts = [0; 9];
+        let mut distinct: Vec<std::collections::HashSet<HandValue>> = vec![std::collections::HashSet::new(); 9];
+        for a in 0..48 {
+            for b in (a + 1)..49 {
+                for c in (b + 1)..50 {
+                    for d in (c + 1)..51 {
+                        for e in (d + 1)..52 {
+                            let hand_type = get_hand_type(&[a, b, c, d, e]);
+                            category_counts[hand_type.0 as usize] += 1;
+                            distinct[hand_type.0 as usize].insert(hand_type);
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(category_counts, [1302540, 1098240, 123552, 54912, 10200, 5108, 3744, 624, 40]);
+        let distinct_counts: Vec<usize> = distinct.iter().map(|set| set.len()).collect();
+        assert_eq!(distinct_counts, vec![1277, 2860, 858, 858, 10, 1277, 156, 156, 10]);
+        assert_eq!(distinct_counts.iter().sum::<usize>(), 7462);
+    }
+
+    #[test]
+    fn test_parse_draw_cards_both_forms() {
+        let nested = parse_client_message(r#"{"action":"DrawCards","data":{"cardIndices":[0,3]}}"#);
+        assert!(matches!(nested, Some(ClientMessage::DrawCards { card_indices }) if card_indices == vec![0, 3]));
+        let top_level = parse_client_message(r#"{"action":"DrawCards","cardIndices":[4]}"#);
+        assert!(matches!(top_level, Some(ClientMessage::DrawCards { card_indices }) if card_indices == vec![4]));
+        assert!(parse_client_message(r#"{"action":"Nonsense"}"#).is_none());
+    }
+
+    #[test]
+    fn test_to_engine_action() {
+        let raise = parse_client_message(r#"{"action":"Raise","data":{"amount":25}}"#).unwrap();
+        assert_eq!(to_engine_action(raise), Some(Action::Raise { amount: 25 }));
+        let fold = parse_client_message(r#"{"action":"Fold"}"#).unwrap();
+        assert_eq!(to_engine_action(fold), Some(Action::Fold));
+        let ready = parse_client_message(r#"{"action":"Ready"}"#).unwrap();
+        assert_eq!(to_engine_action(ready), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! The hand evaluator the server started with, which scores up to 5 cards by comparing their ranks and suits.
+//!
+//! It still scores single 5-card hands: 5 Card Draw, the deuce-to-seven lows and the 7 Card Stud up-cards. The best
+//! hand of 6 or 7 cards goes through `evaluator` instead, which is much faster than trying each of the 21 five-card
+//! hands of 7 cards with it; `benches/hand_evaluator.rs` times both.
+
+/// This function takes a hand of 5 cards and returns the hand type and ranks.
+/// It evaluates the hand for various poker hands such as flush, straight, four of a kind, etc.
+/// 
+/// # Arguments
+/// * `hand` - A slice of integers representing the 5 cards in the hand.
+/// 
+/// # Returns
+/// 
+/// This function returns a tuple containing the hand type and the ranks of the cards in the hand.
+/// The tuple format is (hand_type, rank1, rank2, rank3, rank4, rank5).
+pub fn get_hand_type(hand: &[i32]) -> (i32, i32, i32, i32, i32, i32) {
+    // Remove the assertion to handle hands with fewer than 5 cards
+    let hand_size = hand.len();
+    
+    // Convert cards to ranks (1-13) and sort
+    let mut ranks: Vec<i32> = hand
+        .iter()
+        .map(|&card| if card % 13 != 0 { card % 13 } else { 13 })
+        .collect();
+    ranks.sort(); // Sort in ascending order
+    
+    let suits: Vec<i32> = hand.iter().map(|&card| card / 13).collect();
+    
+    // Only check for flush and straight if we have 5 cards
+    if hand_size == 5 {
+        // Check for flush
+        let flush = suits.iter().all(|&suit| suit == suits[0]);
+        
+        // Check for straight, the Ace also plays low in A-2-3-4-5 (the wheel)
+        let wheel = ranks == [1, 2, 3, 4, 13];
+        let straight = wheel || ranks.windows(2).all(|w| w[1] == w[0] + 1);
+        
+        // The wheel is a five-high straight
+        let straight_high = if wheel { 4 } else { ranks[4] };
+        
+        if flush && straight {
+            return (8, straight_high, straight_high, 0, 0, 0);
+        }
+        
+        if flush {
+            return (5, ranks[4], ranks[3], ranks[2], ranks[1], ranks[0]);
+        }
+        
+        if straight {
+            return (4, straight_high, 0, 0, 0, 0);
+        }
+    }
+    
+    // Count occurrences of each rank
+    let mut rank_counts = std::collections::HashMap::new();
+    for &rank in &ranks {
+        *rank_counts.entry(rank).or_insert(0) += 1;
+    }
+    
+    // Sort ranks by count (descending), then by rank value (descending)
+    let mut rank_count_pairs: Vec<(i32, i32)> = rank_counts.into_iter().collect();
+    rank_count_pairs.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
+    
+    // Four of a kind (needs at least 4 cards)
+    if hand_size >= 4 && !rank_count_pairs.is_empty() && rank_count_pairs[0].1 == 4 {
+        let kicker = if hand_size > 4 && rank_count_pairs.len() > 1 { 
+            rank_count_pairs[1].0 
+        } else { 
+            0 
+        };
+        return (7, rank_count_pairs[0].0, kicker, 0, 0, 0);
+    }
+    
+    // Full house (needs exactly 5 cards)
+    if hand_size == 5 && rank_count_pairs.len() >= 2 && rank_count_pairs[0].1 == 3 && rank_count_pairs[1].1 == 2 {
+        return (6, rank_count_pairs[0].0, rank_count_pairs[1].0, 0, 0, 0);
+    }
+    
+    // Three of a kind (needs at least 3 cards)
+    if hand_size >= 3 && !rank_count_pairs.is_empty() && rank_count_pairs[0].1 == 3 {
+        let mut kickers = Vec::new();
+        for &(rank, count) in &rank_count_pairs[1..] {
+            if count == 1 {
+                kickers.push(rank);
+            }
+        }
+        kickers.sort_by(|a, b| b.cmp(a));
+        
+        let k1 = kickers.first().copied().unwrap_or(0);
+        let k2 = kickers.get(1).copied().unwrap_or(0);
+        
+        return (3, rank_count_pairs[0].0, k1, k2, 0, 0);
+    }
+    
+    // Two pair (needs at least 4 cards)
+    if hand_size >= 4 && rank_count_pairs.len() >= 2 && rank_count_pairs[0].1 == 2 && rank_count_pairs[1].1 == 2 {
+        let kicker = if hand_size >= 5 && rank_count_pairs.len() >= 3 { 
+            rank_count_pairs[2].0 
+        } else { 
+            0 
+        };
+        return (2, rank_count_pairs[0].0, rank_count_pairs[1].0, kicker, 0, 0);
+    }
+    
+    // One pair (needs at least 2 cards)
+    if hand_size >= 2 && !rank_count_pairs.is_empty() && rank_count_pairs[0].1 == 2 {
+        let mut kickers = Vec::new();
+        for &(rank, count) in &rank_count_pairs[1..] {
+            if count == 1 {
+                kickers.push(rank);
+            }
+        }
+        kickers.sort_by(|a, b| b.cmp(a));
+        
+        let k1 = kickers.first().copied().unwrap_or(0);
+        let k2 = kickers.get(1).copied().unwrap_or(0);
+        let k3 = kickers.get(2).copied().unwrap_or(0);
+        
+        return (1, rank_count_pairs[0].0, k1, k2, k3, 0);
+    }
+    
+    // High card
+    let mut high_cards = Vec::new();
+    let mut ranks_desc = ranks.clone();
+    ranks_desc.sort_by(|a, b| b.cmp(a));
+    
+    for &rank in ranks_desc.iter().take(hand_size.min(5)) {
+        high_cards.push(rank);
+    }
+    
+    while high_cards.len() < 5 {
+        high_cards.push(0);
+    }
+    
+    (0, 
+     *high_cards.first().unwrap_or(&0), 
+     *high_cards.get(1).unwrap_or(&0), 
+     *high_cards.get(2).unwrap_or(&0), 
+     *high_cards.get(3).unwrap_or(&0), 
+     *high_cards.get(4).unwrap_or(&0))
+}
@@ -0,0 +1,281 @@
+//! Hand histories: what happened in a finished hand, in a form that can be stored and read back.
+//!
+//! A `HandRecord` is built from the engine once the hand is over, from its seats, board, pots and the log of
+//! every event the hand produced. It is saved into the `hands`, `hand_players` and `hand_actions` tables by
+//! `Database::save_hand` when the lobby settles the hand, and read back by `Database::recent_hands`, which
+//! answers a player's `RecentHands` request with their latest hands.
+//!
+//! Actions are numbered by street, the betting rounds completed before them, the way the engine counts them:
+//! antes, blinds and the first betting round are street 0, then each new card (or the draw) starts the next.
+//!
+//! Cards are stored as the engine deals them. Hole cards are the hand at the end, turned face up;
+//! the starting hand keeps the 7 Card Stud down-cards offset by `FACE_DOWN`.
+use crate::engine::{Action, Engine, Event, FACE_DOWN};
+use crate::lobby::{BETTING_ROUND, FIRST_BETTING_ROUND, SECOND_BETTING_ROUND, SMALL_AND_BIG_BLIND, START_OF_ROUND};
+use crate::player;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A finished hand.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HandRecord {
+    pub id: Option<i64>,           // set once the hand is stored
+    pub played_at: Option<String>, // set once the hand is stored
+    pub lobby_name: String,
+    pub game_type: i32,
+    pub betting_structure: i32, // NO_LIMIT, POT_LIMIT or FIXED_LIMIT
+    pub small_blind: i32,
+    pub big_blind: i32,
+    pub ante: i32,
+    pub bring_in: i32,
+    pub button: i32, // seat of the button, just before the first player to act
+    pub seats: Vec<SeatRecord>,
+    pub board: Vec<i32>,
+    pub actions: Vec<ActionRecord>,
+    pub pots: Vec<PotRecord>,
+}
+
+/// A player's seat in a finished hand.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SeatRecord {
+    pub seat: i32,
+    pub player: String,
+    pub starting_stack: i32,
+    pub final_stack: i32,
+    pub starting_hand: Vec<i32>, // the cards of the first deal
+    pub hole_cards: Vec<i32>,    // the hand at the end of the hand
+    pub dealt_in: bool,
+    pub folded: bool,
+    pub won: i32, // chips collected from the pots
+}
+
+/// One forced bet, betting action or draw.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ActionRecord {
+    pub seq: i32,
+    pub player: String,
+    pub street: i32,
+    pub phase: i32, // the lobby game state the action was taken in
+    pub action: String,
+    pub amount: i32,     // chips put in, or the number of cards exchanged for a draw
+    pub cards: Vec<i32>, // the player's hand after a draw
+}
+
+/// The main pot or a side pot, and who won it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PotRecord {
+    pub amount: i32,
+    pub eligible: Vec<String>,
+    pub winners: Vec<String>,
+    #[serde(default)] // hands stored before hi/lo games have no low winners
+    pub low_winners: Vec<String>,
+}
+
+/// The name an action is stored under.
+fn action_name(action: &Action) -> &'static str {
+    match action {
+        Action::Check => "check",
+        Action::Call => "call",
+        Action::Raise { .. } => "raise",
+        Action::Fold => "fold",
+        Action::AllIn => "all_in",
+        Action::DrawCards { .. } => "draw",
+    }
+}
+
+impl HandRecord {
+    /// Builds the record of a hand from the engine that played it.
+    ///
+    /// # Arguments
+    /// * `lobby_name` - The lobby the hand was played in.
+    /// * `engine` - The engine, once the hand is finished.
+    pub fn from_engine(lobby_name: &str, engine: &Engine) -> Self {
+        let mut actions: Vec<ActionRecord> = Vec::new();
+        let mut starting_hands: Vec<(String, Vec<i32>)> = Vec::new();
+        let mut button = 0;
+        let mut street = 0;
+        let mut phase = START_OF_ROUND;
+        for event in engine.log.iter() {
+            let (player, action, amount) = match event {
+                Event::HandStarted { first_player } => {
+                    let first = engine.seat_index(first_player).unwrap_or(0);
+                    button = (first + engine.seats.len() - 1) % engine.seats.len();
+                    continue;
+                }
+                Event::PhaseChanged { phase: new_phase } => {
+                    // leaving a betting round for anything but another betting round means it is over
+                    let betting = [FIRST_BETTING_ROUND, SECOND_BETTING_ROUND, BETTING_ROUND, SMALL_AND_BIG_BLIND];
+                    if betting.contains(&phase) && !betting.contains(new_phase) {
+                        street += 1;
+                    }
+                    phase = *new_phase;
+                    continue;
+                }
+                Event::CardsDealt { player, cards } => {
+                    if !starting_hands.iter().any(|(name, _)| name == player) {
+                        starting_hands.push((player.clone(), cards.clone()));
+                    }
+                    if let Some(last) = actions.last_mut() {
+                        if last.action == "draw" && &last.player == player {
+                            last.cards = cards.clone();
+                        }
+                    }
+                    continue;
+                }
+                Event::AntePosted { player, amount } => (player, "ante", *amount),
+                Event::BlindPosted { player, amount, big_blind } => {
+                    (player, if *big_blind { "big_blind" } else { "small_blind" }, *amount)
+                }
+                Event::BringInPosted { player, amount } => (player, "bring_in", *amount),
+                Event::PlayerActed { player, action, amount } => (player, action_name(action), *amount),
+                Event::CardsDrawn { player, count } => (player, "draw", *count as i32),
+                _ => continue,
+            };
+            actions.push(ActionRecord {
+                seq: actions.len() as i32,
+                player: player.clone(),
+                street,
+                phase,
+                action: action.to_string(),
+                amount,
+                cards: Vec::new(),
+            });
+        }
+
+        let seats = engine
+            .seats
+            .iter()
+            .enumerate()
+            .map(|(i, seat)| SeatRecord {
+                seat: i as i32,
+                player: seat.name.clone(),
+                starting_stack: seat.starting_stack,
+                final_stack: seat.stack,
+                starting_hand: starting_hands
+                    .iter()
+                    .find(|(name, _)| name == &seat.name)
+                    .map(|(_, cards)| cards.clone())
+                    .unwrap_or_default(),
+                hole_cards: seat.hand.iter().map(|&card| if card >= FACE_DOWN { card - FACE_DOWN } else { card }).collect(),
+                dealt_in: seat.dealt_in,
+                folded: seat.state == player::FOLDED,
+                won: seat.stack - seat.starting_stack + seat.contributed,
+            })
+            .collect();
+        let pots = engine
+            .pots
+            .iter()
+            .map(|pot| PotRecord {
+                amount: pot.amount,
+                eligible: pot.eligible.clone(),
+                winners: pot.winners.clone(),
+                low_winners: pot.low_winners.clone(),
+            })
+            .collect();
+
+        HandRecord {
+            id: None,
+            played_at: None,
+            lobby_name: lobby_name.to_string(),
+            game_type: engine.game_type,
+            betting_structure: engine.betting_structure,
+            small_blind: engine.small_blind,
+            big_blind: engine.big_blind,
+            ante: engine.ante,
+            bring_in: engine.bring_in,
+            button: button as i32,
+            seats,
+            board: engine.community_cards.clone(),
+            actions,
+            pots,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deck::Deck;
+    use crate::lobby::{DRAW, FIRST_BETTING_ROUND, FIVE_CARD_DRAW, SECOND_BETTING_ROUND, TEXAS_HOLD_EM};
+
+    fn players(stacks: &[i32]) -> Vec<(String, i32)> {
+        stacks.iter().enumerate().map(|(i, &stack)| (format!("p{}", i), stack)).collect()
+    }
+
+    #[test]
+    fn test_five_card_draw_record() {
+        let mut engine = Engine::new(FIVE_CARD_DRAW, players(&[100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        engine.apply("p0", Action::Raise { amount: 20 }).unwrap();
+        engine.apply("p1", Action::Call).unwrap();
+        engine.apply("p0", Action::DrawCards { indices: vec![0, 1] }).unwrap();
+        engine.apply("p1", Action::DrawCards { indices: vec![] }).unwrap();
+        engine.apply("p0", Action::Check).unwrap();
+        engine.apply("p1", Action::Fold).unwrap();
+        let record = HandRecord::from_engine("table", &engine);
+
+        assert_eq!(record.lobby_name, "table");
+        assert_eq!(record.game_type, FIVE_CARD_DRAW);
+        let summary: Vec<(&str, &str, i32, i32)> = record
+            .actions
+            .iter()
+            .map(|a| (a.player.as_str(), a.action.as_str(), a.phase, a.amount))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("p0", "ante", crate::lobby::ANTE, 10),
+                ("p1", "ante", crate::lobby::ANTE, 10),
+                ("p0", "raise", FIRST_BETTING_ROUND, 20),
+                ("p1", "call", FIRST_BETTING_ROUND, 20),
+                ("p0", "draw", DRAW, 2),
+                ("p1", "draw", DRAW, 0),
+                ("p0", "check", SECOND_BETTING_ROUND, 0),
+                ("p1", "fold", SECOND_BETTING_ROUND, 0),
+            ]
+        );
+        assert_eq!(record.actions.iter().map(|a| a.street).collect::<Vec<_>>(), vec![0, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(record.actions[4].cards, engine.seats[0].hand);
+        assert_eq!(record.button, 1);
+        assert_eq!(record.actions.iter().map(|a| a.seq).collect::<Vec<_>>(), (0..8).collect::<Vec<_>>());
+
+        let p0 = &record.seats[0];
+        assert_eq!((p0.starting_stack, p0.final_stack, p0.won), (100, 130, 60));
+        assert_eq!(p0.starting_hand.len(), 5);
+        assert_ne!(p0.starting_hand, p0.hole_cards);
+        assert!(record.seats[1].folded);
+        assert_eq!(record.seats[1].won, 0);
+        assert!(record.pots.iter().all(|pot| pot.winners == ["p0".to_string()]));
+        assert_eq!(record.pots.iter().map(|p| p.amount).sum::<i32>(), 60);
+    }
+
+    #[test]
+    fn test_pots_stored_before_hi_lo_have_no_low_winners() {
+        let pot: PotRecord = serde_json::from_str(r#"{"amount": 40, "eligible": ["p0", "p1"], "winners": ["p1"]}"#).unwrap();
+        assert_eq!(pot.winners, vec!["p1".to_string()]);
+        assert!(pot.low_winners.is_empty());
+    }
+
+    #[test]
+    fn test_texas_hold_em_record_has_blinds_and_board() {
+        let mut engine = Engine::new(TEXAS_HOLD_EM, players(&[100, 100, 100]), Deck::new(), 0);
+        engine.start_hand();
+        while !engine.finished {
+            let name = engine.current_player_name().unwrap().to_string();
+            let action = if engine.call_amount() > 0 { Action::Call } else { Action::Check };
+            engine.apply(&name, action).unwrap();
+        }
+        let record = HandRecord::from_engine("holdem", &engine);
+
+        assert_eq!(record.actions[0].action, "small_blind");
+        assert_eq!(record.actions[1].action, "big_blind");
+        assert_eq!(record.button, 2);
+        // three calls or checks pre-flop, then three checks on each of flop, turn and river
+        assert_eq!(record.actions.len(), 2 + 3 * 4);
+        assert!(record.actions[5..].chunks(3).enumerate().all(|(i, chunk)| chunk.iter().all(|a| a.street == i as i32 + 1)));
+        assert_eq!(record.board.len(), 5);
+        assert_eq!(record.seats.iter().map(|s| s.hole_cards.len()).collect::<Vec<_>>(), vec![2, 2, 2]);
+        let won: i32 = record.seats.iter().map(|s| s.won).sum();
+        assert_eq!(won, record.pots.iter().map(|p| p.amount).sum::<i32>());
+    }
+}
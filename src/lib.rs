@@ -0,0 +1,6 @@
+//! The parts of the server that stand on their own, for the benchmarks and other tools to link against.
+//! 
+//! - `evaluator` - Lookup-table hand evaluator used to score hands at showdown
+//! - `hand_type` - The original rank-and-suit evaluator for single 5-card hands
+pub mod evaluator;
+pub mod hand_type;
@@ -12,8 +12,18 @@ use crate::Deck;
 use sqlx::SqlitePool;
 use std::sync::Arc;
 use tokio::sync::{mpsc, mpsc::UnboundedSender, Mutex};
+use tokio::time::Instant;
 use warp:: ws::Message;
-use games::{get_best_hand, get_hand_type};
+use crate::database::ledger::{self, Account};
+use crate::database::Database;
+use crate::button::{self, Positions};
+use crate::engine::{self, Engine};
+use crate::history::HandRecord;
+use crate::protocol::{self, GameInfo, LobbyInfo, LobbySummary, LobbyTournament, PlayerView, Prize, ServerMessage, Spectator};
+use crate::table::TableSender;
+use crate::tournament::mtt::{MttTable, MultiTableTournament};
+use crate::tournament::{Level, Tournament};
+use std::collections::HashMap;
 
 
 // Lobby attribute definitions
@@ -33,7 +43,6 @@ pub const DRAW: i32 = 5;
 pub const BRING_IN: i32 = 50;
 
 pub const SHOWDOWN: i32 = 7;
-pub const END_OF_ROUND: i32 = 8;
 pub const UPDATE_DB: i32 = 9;
 pub const TURN_ROUND: i32 = 10;
 pub const FLOP_ROUND: i32 = 11;
@@ -51,6 +60,31 @@ pub const FIVE_CARD_DRAW: i32 = 10;
 pub const SEVEN_CARD_STUD: i32 = 11;
 pub const TEXAS_HOLD_EM: i32 = 12;
 pub const NOT_SET: i32 = 13;
+pub const OMAHA: i32 = 14;
+pub const OMAHA_HI_LO: i32 = 15;
+pub const SEVEN_CARD_STUD_HI_LO: i32 = 16;
+pub const RAZZ: i32 = 17;
+pub const DEUCE_TO_SEVEN_TRIPLE_DRAW: i32 = 18;
+
+// Betting structures
+pub const NO_LIMIT: i32 = 0;
+pub const POT_LIMIT: i32 = 1;
+pub const FIXED_LIMIT: i32 = 2;
+
+// Placeholder sent in place of a card the receiving client is not allowed to see
+pub const HIDDEN_CARD: i32 = 105;
+
+// Action clock defaults, in seconds. A time bank of 0 means players have no time bank.
+pub const DEFAULT_ACTION_TIME: u64 = 30;
+pub const DEFAULT_TIME_BANK: u64 = 0;
+pub const MIN_ACTION_TIME: u64 = 5;
+
+// Cash table buy-in limits, in big blinds. The stack a player sits with or tops up to must be in this range.
+pub const DEFAULT_MIN_BUY_IN: i32 = 40;
+pub const DEFAULT_MAX_BUY_IN: i32 = 100;
+
+/// A lobby as listed by the server lobby: (name, status, game type, player count, max players, stakes).
+pub type LobbyListing = (String, i32, i32, i32, i32, String);
 
 
 
@@ -63,45 +97,189 @@ pub struct Lobby {
     pub spectators: Arc<Mutex<Vec<Player>>>,
     pub to_be_deleted: Vec<String>,
     pub lobbies: Arc<Mutex<Vec<Arc<Mutex<Lobby>>>>>,
-    pub lobby_names_and_status: Arc<Mutex<Vec<(String, i32, i32, i32, i32)>>>, // store lobby names and their statuses
+    pub lobby_names_and_status: Arc<Mutex<Vec<LobbyListing>>>, // store lobby names, statuses and stakes
     pub game_db: SqlitePool,
     pub deck: Deck,
     pub pot: i32,
     pub current_player_count: i32,
     pub max_player_count: i32,
     pub game_state: i32,
-    pub first_betting_player: i32,
+    pub seats: Vec<Option<String>>, // players by seat number, a seat keeps its number while it is empty
+    pub positions: Option<Positions>, // the button and blinds of the last hand, in seat numbers
     pub game_type: i32,
     pub current_max_bet: i32,
     pub community_cards: Vec<i32>,
+    pub shown_down: Vec<String>, // players whose hands are revealed at the showdown
     pub current_player_turn: String,
     pub current_player_index: i32,
     pub turns_remaining: i32,
-    pub deal_card_counter: i32,
-    pub betting_round_counter: i32,
-    pub small_blinds_done: bool,
-    pub big_blinds_done: bool,
     pub call_amount: i32,
+    pub min_raise: i32, // fewest chips the player to act may put in to raise, unless they go all in
+    pub max_raise: i32, // most chips the player to act may put in, capped by the pot in pot-limit games
+    pub betting_structure: i32, // NO_LIMIT, POT_LIMIT or FIXED_LIMIT
+    pub small_blind: i32,
+    pub big_blind: i32, // also the small bet of fixed-limit games
+    pub ante: i32,
+    pub bring_in: i32,
+    pub hand_in_progress: bool, // set from the deal until the hand is settled
+    pub table: Option<TableSender>, // commands for the task running this lobby's hands
+    pub action_time: u64, // seconds each player has to act
+    pub time_bank: u64, // extra seconds each player can use over the whole session
+    pub turn_deadline: Option<Instant>, // when the player to act runs out of time
+    pub turn_time_bank: u64, // time bank left to the player to act
+    pub sitting_out: Vec<String>, // players who timed out too often and are not dealt in
+    pub min_buy_in: i32, // fewest big blinds a cash game stack can be bought or topped up to
+    pub max_buy_in: i32, // most big blinds a cash game stack can be bought or topped up to
+    pub tournament: Option<Tournament>, // set for Sit-and-Go lobbies, whose stacks are tournament chips
+    pub mtt: Option<MttTable>, // set for the tables of a multi-table tournament
+    pub moved_to: HashMap<String, Arc<Mutex<Lobby>>>, // tables players were moved to, for their connections to follow
+    pub tournaments: Arc<Mutex<Vec<Arc<Mutex<MultiTableTournament>>>>>, // multi-table tournaments, in the server lobby
+}
+
+/// The name of the game as shown in the lobby list.
+pub fn game_type_name(game_type: i32) -> &'static str {
+    match game_type {
+        FIVE_CARD_DRAW => "5 Card Draw",
+        SEVEN_CARD_STUD => "7 Card Stud",
+        TEXAS_HOLD_EM => "Texas Hold'em",
+        OMAHA => "Pot-Limit Omaha",
+        OMAHA_HI_LO => "Pot-Limit Omaha Hi/Lo",
+        SEVEN_CARD_STUD_HI_LO => "7 Card Stud Hi/Lo",
+        RAZZ => "Razz",
+        DEUCE_TO_SEVEN_TRIPLE_DRAW => "2-7 Triple Draw",
+        _ => "Unknown",
+    }
+}
+
+/// The most players a lobby of the game seats.
+pub fn max_players(game_type: i32) -> i32 {
+    match game_type {
+        FIVE_CARD_DRAW | DEUCE_TO_SEVEN_TRIPLE_DRAW => 5,
+        SEVEN_CARD_STUD | SEVEN_CARD_STUD_HI_LO | RAZZ => 7,
+        TEXAS_HOLD_EM | OMAHA | OMAHA_HI_LO => 10,
+        _ => MAX_PLAYER_COUNT,
+    }
+}
+
+/// Whether the game is dealt like 7 Card Stud, with up-cards, down-cards and a bring-in.
+pub fn is_stud(game_type: i32) -> bool {
+    game_type == SEVEN_CARD_STUD || game_type == SEVEN_CARD_STUD_HI_LO || game_type == RAZZ
+}
+
+/// Whether the game is a draw game: five hole cards, an ante and drawing rounds between the betting rounds.
+pub fn is_draw(game_type: i32) -> bool {
+    game_type == FIVE_CARD_DRAW || game_type == DEUCE_TO_SEVEN_TRIPLE_DRAW
+}
+
+/// Whether the lowest hand wins the whole pot.
+pub fn is_lowball(game_type: i32) -> bool {
+    game_type == RAZZ || game_type == DEUCE_TO_SEVEN_TRIPLE_DRAW
+}
+
+/// Whether the game is dealt like Omaha: four hole cards, a Hold'em board and pot-limit betting.
+pub fn is_omaha(game_type: i32) -> bool {
+    game_type == OMAHA || game_type == OMAHA_HI_LO
+}
+
+/// The betting structure a game is played with unless the lobby chooses another: pot-limit for Omaha,
+/// no-limit for everything else.
+pub fn default_betting_structure(game_type: i32) -> i32 {
+    if is_omaha(game_type) {
+        POT_LIMIT
+    } else {
+        NO_LIMIT
+    }
+}
+
+/// The name of a betting structure as shown to players.
+pub fn betting_structure_name(betting_structure: i32) -> &'static str {
+    match betting_structure {
+        POT_LIMIT => "Pot-Limit",
+        FIXED_LIMIT => "Fixed-Limit",
+        _ => "No-Limit",
+    }
+}
+
+/// Whether a set of forced bets can be played: the big blind and the bring-in are at least one chip,
+/// the small blind is no more than the big blind and nothing is negative.
+pub fn valid_stakes(small_blind: i32, big_blind: i32, ante: i32, bring_in: i32) -> bool {
+    big_blind > 0 && small_blind >= 0 && small_blind <= big_blind && ante >= 0 && bring_in > 0
+}
+
+/// The stakes of a lobby as shown in the lobby list, only naming the forced bets the game uses:
+/// "5/10" or "5/10 ante 1" for blinds games, "Ante 10" for draw games and "Bring-in 15 ante 2" for stud.
+pub fn stakes_text(game_type: i32, small_blind: i32, big_blind: i32, ante: i32, bring_in: i32) -> String {
+    let ante_text = if ante > 0 { format!(" ante {}", ante) } else { String::new() };
+    if is_draw(game_type) {
+        format!("Ante {}", ante)
+    } else if is_stud(game_type) {
+        format!("Bring-in {}{}", bring_in, ante_text)
+    } else {
+        format!("{}/{}{}", small_blind, big_blind, ante_text)
+    }
+}
+
+/// How many chips a player buys at a cash table.
+///
+/// # Arguments
+/// * `stack` - The player's stack at the table, 0 when they sit down.
+/// * `wallet` - The chips in their wallet.
+/// * `amount` - The chips they asked for, or `None` to fill up to the maximum, as far as the wallet goes.
+/// * `limits` - The smallest and largest stack the table allows, in chips.
+///
+/// # Returns
+/// * `Ok(i32)` - The chips to move from the wallet to the table.
+/// * `Err(&str)` - Why the player cannot buy that many.
+pub fn buy_in_amount(stack: i32, wallet: i32, amount: Option<i32>, limits: (i32, i32)) -> Result<i32, &'static str> {
+    let (min, max) = limits;
+    if stack >= max {
+        return Err("Your stack is already at the maximum buy-in");
+    }
+    let amount = amount.unwrap_or((max - stack).min(wallet));
+    if amount <= 0 || stack + amount > max {
+        return Err("The buy-in must keep your stack within the table's limits");
+    }
+    if amount > wallet {
+        return Err("You cannot afford the buy-in");
+    }
+    if stack + amount < min {
+        return Err("Your stack must be at least the minimum buy-in");
+    }
+    Ok(amount)
+}
+
+/// Whether each pot is split between the best high hand and the best 8-or-better low.
+pub fn is_hi_lo(game_type: i32) -> bool {
+    game_type == OMAHA_HI_LO || game_type == SEVEN_CARD_STUD_HI_LO
+}
+
+/// Returns the cards of a hand that someone other than its owner is allowed to see.
+/// 
+/// In 7 Card Stud the up-cards are stored as `card` and the down-cards as `card + 53`, so only
+/// the up-cards stay visible. In every other game all hole cards are private. Hidden cards are
+/// replaced by `HIDDEN_CARD` so clients can still draw the right number of card backs.
+/// 
+/// # Arguments
+/// * `hand` - The hand as stored on the player.
+/// * `game_type` - The game type of the lobby.
+/// * `revealed` - Whether the hand has been shown down, in which case it is returned unchanged.
+pub fn visible_cards(hand: &[i32], game_type: i32, revealed: bool) -> Vec<i32> {
+    if revealed {
+        return hand.to_vec();
+    }
+    hand.iter()
+        .map(|&card| if is_stud(game_type) && card <= 52 { card } else { HIDDEN_CARD })
+        .collect()
 }
 
 impl Lobby {
     pub async fn new(lobby_type: i32, lobby_name: String) -> Self {
-        let player_count;
-        match lobby_type {
-            FIVE_CARD_DRAW => {
-                player_count = 5;
-            }
-            SEVEN_CARD_STUD => {
-                player_count = 7
-            }
-            TEXAS_HOLD_EM => {
-                player_count = 10
-            }
-            _ => {
-                player_count = MAX_PLAYER_COUNT;
-            }
+        Self::with_db(lobby_type, lobby_name, SqlitePool::connect("sqlite://poker.db").await.unwrap())
+    }
 
-        }
+    /// Creates a lobby that keeps its hands in `game_db` rather than in `poker.db`.
+    pub fn with_db(lobby_type: i32, lobby_name: String, game_db: SqlitePool) -> Self {
+        let player_count = max_players(lobby_type);
         Self {
             name: lobby_name,
             players: Arc::new(Mutex::new(Vec::new())),
@@ -114,19 +292,300 @@ impl Lobby {
             max_player_count: player_count,
             pot: 0,
             game_state: JOINABLE,
-            first_betting_player: 0,
-            game_db: SqlitePool::connect("sqlite://poker.db").await.unwrap(),
+            seats: Vec::new(),
+            positions: None,
+            game_db,
             game_type: lobby_type,
             current_max_bet: 0,
             community_cards: Vec::new(),
+            shown_down: Vec::new(),
             current_player_turn: "".to_string(),
             current_player_index: 0,
             turns_remaining: 0,
-            deal_card_counter: 0,
-            betting_round_counter: 0,
-            small_blinds_done: false,
-            big_blinds_done: false,
             call_amount: 0,
+            min_raise: 0,
+            max_raise: 0,
+            betting_structure: default_betting_structure(lobby_type),
+            small_blind: engine::SMALL_BLIND,
+            big_blind: engine::BIG_BLIND,
+            ante: if is_draw(lobby_type) { engine::ANTE_AMOUNT } else { 0 },
+            bring_in: engine::BRING_IN_AMOUNT,
+            hand_in_progress: false,
+            table: None,
+            action_time: DEFAULT_ACTION_TIME,
+            time_bank: DEFAULT_TIME_BANK,
+            turn_deadline: None,
+            turn_time_bank: 0,
+            sitting_out: Vec::new(),
+            min_buy_in: DEFAULT_MIN_BUY_IN,
+            max_buy_in: DEFAULT_MAX_BUY_IN,
+            tournament: None,
+            mtt: None,
+            moved_to: HashMap::new(),
+            tournaments: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Sets the forced bets of the lobby. Any left out keep their current value.
+    ///
+    /// # Returns
+    /// `false`, leaving the stakes unchanged, if the result is not `valid_stakes`.
+    pub fn set_stakes(&mut self, small_blind: Option<i32>, big_blind: Option<i32>, ante: Option<i32>, bring_in: Option<i32>) -> bool {
+        let small_blind = small_blind.unwrap_or(self.small_blind);
+        let big_blind = big_blind.unwrap_or(self.big_blind);
+        let ante = ante.unwrap_or(self.ante);
+        let bring_in = bring_in.unwrap_or(self.bring_in);
+        if !valid_stakes(small_blind, big_blind, ante, bring_in) {
+            return false;
+        }
+        self.small_blind = small_blind;
+        self.big_blind = big_blind;
+        self.ante = ante;
+        self.bring_in = bring_in;
+        true
+    }
+
+    /// Sets the buy-in limits of a cash table, in big blinds. Any left out keep their current value.
+    ///
+    /// # Returns
+    /// `false`, leaving the limits unchanged, unless the minimum is at least one big blind and no more than the maximum.
+    pub fn set_buy_in_limits(&mut self, min_buy_in: Option<i32>, max_buy_in: Option<i32>) -> bool {
+        let min_buy_in = min_buy_in.unwrap_or(self.min_buy_in);
+        let max_buy_in = max_buy_in.unwrap_or(self.max_buy_in);
+        if min_buy_in < 1 || min_buy_in > max_buy_in {
+            return false;
+        }
+        self.min_buy_in = min_buy_in;
+        self.max_buy_in = max_buy_in;
+        true
+    }
+
+    /// Whether the lobby is a cash game table, where players buy their stacks out of their wallets.
+    pub fn is_cash_table(&self) -> bool {
+        self.game_type != NOT_SET && self.tournament.is_none() && self.mtt.is_none()
+    }
+
+    /// The smallest and largest stack a player can buy at the table, in chips.
+    pub fn buy_in_limits(&self) -> (i32, i32) {
+        (self.min_buy_in * self.big_blind, self.max_buy_in * self.big_blind)
+    }
+
+    /// Buys chips at a cash table out of the player's wallet, when they sit down or between hands to
+    /// top up or rebuy. The chips stay at the table until the player leaves and cashes out.
+    ///
+    /// # Arguments
+    /// * `player_name` - The player buying chips.
+    /// * `amount` - The chips to buy, or `None` for as many as the table allows and the wallet holds.
+    ///
+    /// # Returns
+    /// * `Ok(i32)` - The player's stack afterwards.
+    /// * `Err(&str)` - Why the player could not buy chips. Nothing is changed then.
+    pub async fn buy_in(&mut self, player_name: &str, amount: Option<i32>) -> Result<i32, &'static str> {
+        if !self.is_cash_table() {
+            return Err("There are no rebuys in a tournament");
+        }
+        let (stack, state) = match self.get_player_by_name(player_name).await {
+            Some(player) => (player.wallet, player.state),
+            None => return Err("You are not at the table"),
+        };
+        if self.hand_in_progress && state != player::IN_LOBBY && state != player::SITTING_OUT {
+            return Err("You can only buy chips between hands");
+        }
+        let db = Database::new(self.game_db.clone());
+        let wallet = match db.get_player_wallet(player_name).await {
+            Ok(wallet) => wallet,
+            Err(e) => {
+                eprintln!("Error reading the wallet of {}: {}", player_name, e);
+                return Err("Failed to retrieve wallet");
+            }
+        };
+        let amount = buy_in_amount(stack, wallet, amount, self.buy_in_limits())?;
+        match db.buy_in(player_name, &self.name, amount).await {
+            Ok(true) => {}
+            Ok(false) => return Err("You cannot afford the buy-in"),
+            Err(e) => {
+                eprintln!("Error buying in {} at {}: {}", player_name, self.name, e);
+                return Err("Failed to pay the buy-in");
+            }
+        }
+        self.set_player_wallet(player_name, stack + amount).await;
+        self.broadcast(format!("{} bought {} chips.", player_name, amount)).await;
+        self.send_player_list().await;
+        Ok(stack + amount)
+    }
+
+    /// Returns a player's stack at a cash table to their wallet as they leave it.
+    async fn cash_out(&self, player_name: &str) {
+        if !self.is_cash_table() {
+            return;
+        }
+        match Database::new(self.game_db.clone()).cash_out(player_name, &self.name).await {
+            Ok(stack) => println!("{} cashed out {} chips from {}", player_name, stack, self.name),
+            Err(e) => eprintln!("Error cashing out {} from {}: {}", player_name, self.name, e),
+        }
+    }
+
+    /// The stakes of the lobby as shown in the lobby list, the buy-in for a Sit-and-Go.
+    pub fn stakes(&self) -> String {
+        match (&self.tournament, &self.mtt) {
+            (Some(tournament), _) => format!("Sit & Go, buy-in {}", tournament.buy_in),
+            (None, Some(table)) => format!("{}, table {}", table.name, table.number),
+            (None, None) => stakes_text(self.game_type, self.small_blind, self.big_blind, self.ante, self.bring_in),
+        }
+    }
+
+    /// Sets the forced bets to the current level of the Sit-and-Go.
+    ///
+    /// # Returns
+    /// Whether the stakes changed.
+    pub fn apply_tournament_level(&mut self) -> bool {
+        let level = match &self.tournament {
+            Some(tournament) => tournament.level(),
+            None => return false,
+        };
+        self.set_level(level)
+    }
+
+    /// Sets the forced bets to a level of a tournament's blind schedule. Stud games post the small blind
+    /// as the bring-in, draw games ante it until the level has an ante.
+    ///
+    /// # Returns
+    /// Whether the stakes changed.
+    pub fn set_level(&mut self, level: Level) -> bool {
+        let ante = if is_draw(self.game_type) && level.ante == 0 { level.small_blind } else { level.ante };
+        let changed = (level.small_blind, level.big_blind, ante) != (self.small_blind, self.big_blind, self.ante);
+        self.small_blind = level.small_blind;
+        self.big_blind = level.big_blind;
+        self.ante = ante;
+        self.bring_in = level.small_blind;
+        changed
+    }
+
+    /// Registers a player who joined a Sit-and-Go: the buy-in comes out of their wallet and they get
+    /// the starting stack. Nothing happens in a cash lobby.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the player is registered, or the lobby is not a tournament.
+    /// * `Err(&str)` - Why the player could not register.
+    pub async fn register(&mut self, player_name: &str) -> Result<(), &'static str> {
+        let tournament = match self.tournament.as_mut() {
+            Some(tournament) => tournament,
+            None => return Ok(()),
+        };
+        if tournament.started() {
+            return Err("The tournament has already started");
+        }
+        if !tournament.entrants.iter().any(|name| name == player_name) {
+            let pool = Account::Tournament(&self.name);
+            let paid = Database::new(self.game_db.clone())
+                .transfer(player_name, Account::Wallet, pool, tournament.buy_in, ledger::TOURNAMENT_BUY_IN)
+                .await;
+            match paid {
+                Ok(true) => tournament.entrants.push(player_name.to_string()),
+                Ok(false) => return Err("You cannot afford the buy-in"),
+                Err(e) => {
+                    eprintln!("Error taking the buy-in of {}: {}", player_name, e);
+                    return Err("Failed to pay the buy-in");
+                }
+            }
+        }
+        let starting_stack = tournament.starting_stack;
+        self.set_player_wallet(player_name, starting_stack).await;
+        self.broadcast(format!("{} registered for the tournament.", player_name)).await;
+        Ok(())
+    }
+
+    /// A player leaving a tournament before it starts gets the buy-in back. Once it has started
+    /// they forfeit their chips and finish in the next place out.
+    async fn leave_tournament(&mut self, player_name: &str) {
+        let tournament = match self.tournament.as_mut() {
+            Some(tournament) if tournament.entrants.iter().any(|name| name == player_name) => tournament,
+            _ => return,
+        };
+        if !tournament.started() {
+            tournament.entrants.retain(|name| name != player_name);
+            let pool = Account::Tournament(&self.name);
+            let refund = Database::new(self.game_db.clone())
+                .transfer(player_name, pool, Account::Wallet, tournament.buy_in, ledger::TOURNAMENT_REFUND)
+                .await;
+            if let Err(e) = refund {
+                eprintln!("Error refunding the buy-in of {}: {}", player_name, e);
+            }
+        } else if !tournament.finished {
+            self.eliminate(vec![(player_name.to_string(), 0)]).await;
+        }
+    }
+
+    /// Knocks players out of the tournament, then pays out the prizes if only one player is left.
+    ///
+    /// # Arguments
+    /// * `busted` - `(name, stack at the start of the hand)` of every player who lost all their chips.
+    async fn eliminate(&mut self, busted: Vec<(String, i32)>) {
+        let tournament = match self.tournament.as_mut() {
+            Some(tournament) if tournament.started() && !tournament.finished => tournament,
+            _ => return,
+        };
+        let out_before = tournament.eliminated.len();
+        tournament.eliminate(busted);
+        let places = tournament.entrants.len() - out_before;
+        let messages: Vec<String> = tournament.eliminated[out_before..]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("{} is out of the tournament in place {}.", name, places - i))
+            .collect();
+        for message in messages {
+            self.broadcast(message).await;
+        }
+        self.pay_out_tournament().await;
+    }
+
+    /// Ends the tournament once one player is left and credits every paid place to the player's wallet.
+    async fn pay_out_tournament(&mut self) {
+        let prizes = match self.tournament.as_mut() {
+            Some(tournament) if tournament.started() && !tournament.finished && tournament.remaining().len() <= 1 => {
+                tournament.finished = true;
+                tournament.prizes()
+            }
+            _ => return,
+        };
+        let db = Database::new(self.game_db.clone());
+        let mut results = Vec::new();
+        for (place, (name, prize)) in prizes.iter().enumerate() {
+            match db.transfer(name, Account::Tournament(&self.name), Account::Wallet, *prize, ledger::PRIZE).await {
+                Ok(_) => println!("Paid {} chips to {} for place {} in {}", prize, name, place + 1, self.name),
+                Err(e) => eprintln!("Error paying {} chips to {}: {}", prize, name, e),
+            }
+            results.push(Prize { place: place + 1, name: name.clone(), prize: *prize });
+        }
+        let winner = prizes.first().map(|(name, _)| name.clone()).unwrap_or_default();
+        let message = ServerMessage::TournamentResults {
+            message: format!("{} wins the tournament!", winner),
+            tournament_results: results,
+        };
+        self.broadcast_message(&message).await;
+    }
+
+    /// Whether the table should deal the next hand by itself: a Sit-and-Go plays on without
+    /// waiting for players to ask until it has a winner.
+    pub fn tournament_running(&self) -> bool {
+        self.tournament.as_ref().is_some_and(|tournament| tournament.started() && !tournament.finished)
+    }
+
+    fn tournament_info(&self) -> Option<LobbyTournament> {
+        match &self.tournament {
+            Some(tournament) => Some(LobbyTournament::SitAndGo {
+                buy_in: tournament.buy_in,
+                starting_stack: tournament.starting_stack,
+                prize_pool: tournament.prize_pool(),
+                payouts: tournament.payouts.clone(),
+                level: tournament.current_level() + 1,
+                level_minutes: tournament.level_duration.as_secs() / 60,
+                entrants: tournament.entrants.len(),
+                remaining: tournament.remaining().len(),
+                started: tournament.started(),
+                finished: tournament.finished,
+            }),
+            None => self.mtt.as_ref().map(|table| LobbyTournament::MultiTable { name: table.name.clone(), table: table.number }),
         }
     }
 
@@ -138,6 +597,15 @@ impl Lobby {
         {
             let mut players = self.players.lock().await;
             player.state = player::IN_LOBBY;
+            if self.is_cash_table() {
+                // no chips until they buy in, the wallet stays off the table
+                player.wallet = 0;
+            }
+            // take the first empty seat
+            match self.seats.iter().position(|seat| seat.is_none()) {
+                Some(seat) => self.seats[seat] = Some(player.name.clone()),
+                None => self.seats.push(Some(player.name.clone())),
+            }
             players.push(player);
         } // Release the immutable borrow of self.players here
         
@@ -161,13 +629,57 @@ impl Lobby {
         self.broadcast(format!("{} has joined as a spectator", name)).await;
     }
 
+    /// Takes a player out of the lobby to seat them at another table of a multi-table tournament.
+    /// Their connection follows them through `moved_to`.
+    ///
+    /// # Returns
+    /// The player, or `None` if they are no longer in the lobby.
+    pub async fn move_player_to(&mut self, player_name: &str, lobby: &Arc<Mutex<Lobby>>) -> Option<Player> {
+        let player = {
+            let mut players = self.players.lock().await;
+            let index = players.iter().position(|p| p.name == player_name)?;
+            players.remove(index)
+        };
+        self.sitting_out.retain(|name| name != player_name);
+        for seat in self.seats.iter_mut() {
+            if seat.as_deref() == Some(player_name) {
+                *seat = None;
+            }
+        }
+        self.current_player_count -= 1;
+        self.game_state = JOINABLE;
+        self.moved_to.insert(player_name.to_string(), lobby.clone());
+        self.send_player_list().await;
+        Some(player)
+    }
+
+    /// The players with a seat, going round the table from the one due to post the next big blind.
+    pub fn seated_from_big_blind(&self) -> Vec<String> {
+        let start = self.positions.map(|positions| positions.big_blind + 1).unwrap_or(0);
+        (0..self.seats.len()).filter_map(|k| self.seats[(start + k) % self.seats.len()].clone()).collect()
+    }
+
     pub async fn remove_player(&mut self, username: String) -> i32 {
         let mut players = self.players.lock().await;
         players.retain(|p| p.name != username);
+        self.sitting_out.retain(|name| *name != username);
+        // a player who leaves a tournament table cannot come back to it
+        if let Some(table) = self.mtt.as_mut() {
+            table.seated.retain(|name| *name != username);
+        }
+        for seat in self.seats.iter_mut() {
+            if seat.as_deref() == Some(username.as_str()) {
+                *seat = None;
+            }
+        }
         let players_tx = players.iter().map(|p| p.tx.clone()).collect::<Vec<_>>();
-        self.lobby_wide_send(players_tx, format!("{} has disconnected from {}.", username, self.name)).await;
+        let message = ServerMessage::notice(format!("{} has disconnected from {}.", username, self.name));
+        self.lobby_wide_send(players_tx, message).await;
         println!("Player removed from {}: {}", self.name, username);
         self.current_player_count -= 1;
+        drop(players);
+        self.cash_out(&username).await;
+        self.leave_tournament(&username).await;
         
         let result = if self.current_player_count == 0 {
             GAME_LOBBY_EMPTY
@@ -191,7 +703,7 @@ impl Lobby {
         // This method should only be called by the server lobby
         {
             let mut lobby_names_and_status = self.lobby_names_and_status.lock().await;
-            for (name, status, _, player_count, _) in lobby_names_and_status.iter_mut() {
+            for (name, status, _, player_count, _, _) in lobby_names_and_status.iter_mut() {
                 if *name == lobby_name {
                     // Find the target lobby to get its current state
                     let lobbies = self.lobbies.lock().await;
@@ -220,9 +732,7 @@ impl Lobby {
     }
 
     pub async fn broadcast_player_count(&self) {
-        let count = self.current_player_count;
-        let message = format!(r#"{{"playerCount": {}}}"#, count);
-        self.broadcast(message).await;
+        self.broadcast_message(&ServerMessage::PlayerCount { player_count: self.current_player_count }).await;
     }
 
     pub async fn add_lobby(&self, lobby: Arc<Mutex<Lobby>>) {
@@ -237,7 +747,8 @@ impl Lobby {
             let lobby_type = lobby_guard.game_type.clone();
             let curr_player_count = lobby_guard.current_player_count.clone();
             let max_player_count = lobby_guard.max_player_count.clone();
-            self.lobby_names_and_status.lock().await.push((lobby_name, lobby_status, lobby_type, curr_player_count, max_player_count));
+            let stakes = lobby_guard.stakes();
+            self.lobby_names_and_status.lock().await.push((lobby_name, lobby_status, lobby_type, curr_player_count, max_player_count, stakes));
         }
         // Broadcast the updated lobby list
         self.broadcast_lobbies(None).await;
@@ -262,62 +773,47 @@ impl Lobby {
         self.broadcast_lobbies(None).await;
     }
 
+    /// The game lobbies as listed in the server lobby.
+    pub async fn lobby_summaries(&self) -> Vec<LobbySummary> {
+        self.get_lobby_names_and_status()
+            .await
+            .into_iter()
+            .map(|(name, status, game_type, player_count, max_players, stakes)| LobbySummary {
+                name,
+                status: if status == JOINABLE { "Joinable" } else { "Not Joinable" }.to_string(),
+                game_type: game_type_name(game_type).to_string(),
+                player_count,
+                max_players,
+                stakes,
+            })
+            .collect()
+    }
+
     // Add a new function to broadcast the lobby list:
     pub async fn broadcast_lobbies(&self, tx: Option<mpsc::UnboundedSender<Message>>) {
-        // Get the lobby information
-        let lobbies = self.get_lobby_names_and_status().await;
-        let mut lobby_list = Vec::new();
-        
-        for (lobby_name, lobby_status, lobby_type, player_count, max_player_count) in lobbies {
-            // Convert status code to string
-            let status = if lobby_status == JOINABLE {
-                "Joinable"
-            } else {
-                "Not Joinable"
-            };
-            
-            // Convert game type to readable string
-            let game_type = match lobby_type {
-                FIVE_CARD_DRAW => "5 Card Draw",
-                SEVEN_CARD_STUD => "7 Card Stud", 
-                TEXAS_HOLD_EM => "Texas Hold'em",
-                _ => "Unknown"
-            };
-            
-            lobby_list.push(serde_json::json!({
-                "name": lobby_name,
-                "status": status,
-                "type": game_type,
-                "playerCount": player_count,
-                "maxPlayers": max_player_count
-            }));
-        }
-        
-        let json = serde_json::json!({
-            "lobbies": lobby_list
-        }).to_string();
+        let message = ServerMessage::Lobbies { lobbies: self.lobby_summaries().await };
         if tx.is_none() {
             // Get all players in the server lobby
             let players = self.players.lock().await;
             let players_tx = players.iter().map(|p| p.tx.clone()).collect::<Vec<_>>();
             // Send the updated lobby list to all players
             for tx in players_tx {
-                let _ = tx.send(Message::text(json.clone()));
+                let _ = protocol::send(&tx, &message);
             }
         } else {
             // Send the updated lobby list to the specific player
             let tx = tx.unwrap();
-            let _ = tx.send(Message::text(json.clone()));
+            let _ = protocol::send(&tx, &message);
         }
     }
 
-    pub async fn get_lobby_names_and_status(&self) -> Vec<(String, i32, i32, i32, i32)> {
+    pub async fn get_lobby_names_and_status(&self) -> Vec<LobbyListing> {
         self.lobby_names_and_status.lock().await.clone()
     }
 
     pub async fn lobby_exists(&self, lobby_name: String) -> bool {
         let lobby_names_and_status = self.lobby_names_and_status.lock().await;
-        for (name, _, _, _, _) in lobby_names_and_status.iter() {
+        for (name, _, _, _, _, _) in lobby_names_and_status.iter() {
             if name == &lobby_name {
                 return true;
             }
@@ -325,19 +821,14 @@ impl Lobby {
         false
     }
 
-    pub async fn get_player_names_and_status(&self) -> Vec<(String, bool)> {
-        let players = self.players.lock().await;
-        players.iter()
-            .map(|p| (p.name.clone(), p.ready))
-            .collect()
-    }
-
-    pub async fn broadcast_json(&self, json_message: String) {
+    /// Sends a message to every player and spectator in the lobby.
+    pub async fn broadcast_message(&self, message: &ServerMessage) {
+        let message = message.to_ws();
         // Broadcast to players
         {
             let players = self.players.lock().await;
             for player in players.iter() {
-                let _ = player.tx.send(Message::text(json_message.clone()));
+                let _ = player.tx.send(message.clone());
             }
         }
 
@@ -345,35 +836,24 @@ impl Lobby {
         {
             let spectators = self.spectators.lock().await;
             for spectator in spectators.iter() {
-                let _ = spectator.tx.send(Message::text(json_message.clone()));
+                let _ = spectator.tx.send(message.clone());
             }
         }
     }
 
+    /// Tells everyone in the lobby something, as a `notice`.
     pub async fn broadcast(&self, message: String) {
-        // Check if the message is already valid JSON, otherwise format it
-        let json_message = if message.trim().starts_with('{') && message.trim().ends_with('}') {
-            // Message appears to be JSON already
-            message.clone()
-        } else {
-            // Wrap message in a JSON structure
-            serde_json::json!({
-                "message": message
-            }).to_string()
-        };
-        
-        // Use the broadcast_json function to send the message
-        self.broadcast_json(json_message).await;
+        self.broadcast_message(&ServerMessage::notice(message)).await;
     }
 
     pub async fn lobby_wide_send(
         &self,
         players_tx: Vec<UnboundedSender<Message>>,
-        message: String,
+        message: ServerMessage,
     ) {
         let mut tasks = Vec::new();
         for tx in players_tx.iter().cloned() {
-            let msg = Message::text(message.clone());
+            let msg = message.to_ws();
             tasks.push(tokio::spawn(async move {
                 let _ = tx.send(msg);
             }));
@@ -393,7 +873,7 @@ impl Lobby {
     }
 
     pub async fn check_ready(&mut self, username: String) {
-        self.turns_remaining = self.current_player_count;
+        self.turns_remaining = self.players_sitting_in();
         let mut players = self.players.lock().await;
         // self.broadcast(format!("{} is ready!", username)).await;
         if let Some(player) = players.iter_mut().find(|p| p.name == username) {
@@ -420,103 +900,162 @@ impl Lobby {
         }
     }
     
-    pub async fn setup_game(&mut self) {
-        self.first_betting_player = (self.first_betting_player + 1) % self.current_player_count;
-        self.current_player_index = self.first_betting_player;
-        self.current_player_turn = self.players.lock().await[self.first_betting_player as usize].name.clone();
-        self.turns_remaining = self.current_player_count;
-        {
+    /// Number of players in the lobby who are not sitting out.
+    fn players_sitting_in(&self) -> i32 {
+        self.current_player_count - self.sitting_out.len() as i32
+    }
+
+    /// Sits a player out: they are not dealt into the next hands until they sit back in.
+    pub async fn sit_out(&mut self, player_name: &str) {
+        if !self.sitting_out.iter().any(|name| name == player_name) {
+            self.sitting_out.push(player_name.to_string());
+        }
+        self.broadcast(format!("{} is sitting out.", player_name)).await;
+    }
+
+    /// Deals a player who was sitting out back in from the next hand.
+    /// 
+    /// # Returns
+    /// 
+    /// `false` if the player was not sitting out.
+    pub async fn sit_in(&mut self, player_name: &str) -> bool {
+        if !self.sitting_out.iter().any(|name| name == player_name) {
+            return false;
+        }
+        self.sitting_out.retain(|name| name != player_name);
+        self.turns_remaining += 1;
+        self.update_player_state(player_name, player::IN_LOBBY).await;
+        self.broadcast(format!("{} is back in.", player_name)).await;
+        true
+    }
+
+    /// Seats everyone in the lobby for a new hand in seat order, except players sitting out.
+    /// The button and the blinds move on from the last hand, skipping players who cannot be dealt in.
+    /// A Sit-and-Go starts with its first hand and deals in every player still in it, sitting out or not,
+    /// at the blinds of the current level.
+    /// 
+    /// # Returns
+    /// 
+    /// The engine for the hand, with a freshly shuffled deck. Nothing has been dealt yet.
+    pub async fn setup_game(&mut self) -> Engine {
+        self.turns_remaining = self.players_sitting_in();
+        if let Some(tournament) = self.tournament.as_mut() {
+            if !tournament.started() {
+                tournament.started_at = Some(std::time::Instant::now());
+            }
+            if self.apply_tournament_level() {
+                let level = self.tournament.as_ref().map(|tournament| tournament.current_level() + 1).unwrap_or(1);
+                let stakes = stakes_text(self.game_type, self.small_blind, self.big_blind, self.ante, self.bring_in);
+                self.broadcast(format!("Level {}: the stakes are now {}.", level, stakes)).await;
+            }
+        }
+        let dealt_in = |name: &String| match (&self.tournament, &self.mtt) {
+            (Some(tournament), _) => tournament.remaining().contains(name),
+            (None, Some(table)) => table.seated.contains(name),
+            (None, None) => !self.sitting_out.contains(name),
+        };
+        let mut seats: Vec<(String, i32)> = {
             let mut players = self.players.lock().await;
             for player in players.iter_mut() {
-                player.state = player::IN_GAME;
+                player.state = if dealt_in(&player.name) { player::IN_GAME } else { player::SITTING_OUT };
                 player.hand.clear();
                 player.current_bet = 0;
                 player.ready = false;
             }
-        }
-        self.game_state = START_OF_ROUND;
+            players
+                .iter()
+                .filter(|p| p.state == player::IN_GAME)
+                .map(|p| (p.name.clone(), p.wallet))
+                .collect()
+        };
+        let seat_number = |name: &str| self.seats.iter().position(|seat| seat.as_deref() == Some(name));
+        seats.sort_by_key(|(name, _)| seat_number(name));
+        self.deck = Deck::new();
         self.deck.shuffle();
+        self.hand_in_progress = true;
         println!("lobby {} set up for startin game.", self.name);
-    }
+        let mut engine = Engine::new(self.game_type, seats.clone(), self.deck.clone(), 0);
+        engine.betting_structure = self.betting_structure;
+        engine.small_blind = self.small_blind;
+        engine.big_blind = self.big_blind;
+        engine.ante = self.ante;
+        engine.bring_in = self.bring_in;
 
-    pub async fn check_end_game(&self) -> bool {
-        let mut active_count = 0;
-        let players = self.players.lock().await;
-        for player in players.iter() {
-            if player.state != player::FOLDED {
-                active_count += 1;
-            }
+        // move the button in seat numbers, then tell the engine which of its seats those are
+        let active: Vec<bool> = self
+            .seats
+            .iter()
+            .map(|seat| seats.iter().any(|(name, stack)| seat.as_deref() == Some(name) && engine.can_be_dealt_in(*stack)))
+            .collect();
+        if let Some(positions) = button::next_positions(self.positions, &active) {
+            let index = |seat: usize| seats.iter().position(|(name, _)| self.seats[seat].as_deref() == Some(name));
+            engine.first_player = index(button::first_to_act(&positions, &active)).unwrap_or(0);
+            engine.blinds = index(positions.big_blind).map(|big| (index(positions.small_blind), big));
+            self.positions = Some(positions);
         }
-        return active_count == 0 || active_count == 1;
+        engine
     }
 
-    pub async fn clear_betting(&mut self) {
-        self.current_max_bet = 0;
+    /// Copies the state of the hand in progress onto the lobby and its players,
+    /// which is what `send_lobby_game_info` and `send_player_list` report to clients.
+    pub async fn sync_from_engine(&mut self, engine: &Engine) {
+        self.game_state = engine.phase;
+        self.pot = engine.pot();
+        self.current_max_bet = engine.current_max_bet;
+        self.community_cards = engine.community_cards.clone();
+        self.shown_down = engine.shown_down();
+        self.call_amount = engine.call_amount();
+        self.min_raise = engine.min_raise();
+        self.max_raise = engine.max_raise();
+        self.current_player_turn = engine.current_player_name().unwrap_or("").to_string();
+        self.current_player_index = engine.current_player.map(|i| i as i32).unwrap_or(-1);
+
         let mut players = self.players.lock().await;
         for player in players.iter_mut() {
-            player.current_bet = 0;
+            if let Some(seat) = engine.seats.iter().find(|s| s.name == player.name) {
+                player.hand = seat.hand.clone();
+                player.wallet = seat.stack;
+                player.state = seat.state;
+                player.current_bet = seat.current_bet;
+            }
         }
     }
 
-    /// Handles the showdown phase of the game, where players reveal their hands and determine the winner.
-    /// The function evaluates the hands of all players and determines the winner(s) based on the hand rankings.
-    /// It also updates the players' wallets and game statistics.
-    /// 
-    /// # Arguments
-    /// * `lobby` - A mutable reference to the `Lobby` struct, which contains the game state and player information.
-    /// 
-    /// # Returns
-    /// 
-    /// This function does not return a value. It updates the players' wallets and game statistics.
-    /// It also handles the display of hands to active players.
-    pub async fn showdown(&self) -> (Vec<String>, i32) {
-        let mut players = self.players.lock().await;
-        let mut winning_players: Vec<Player> = Vec::new(); // keeps track of winning players at the end, accounting for draws
-        let mut winning_players_names: Vec<String> = Vec::new();
-        let mut winning_hand = (-1, -1, -1, -1, -1, -1); // keeps track of current highest hand, could change when incrementing between players
-        let mut winning_players_indices: Vec<i32> = Vec::new();
-        let mut player_hand_type: (i32, i32, i32, i32, i32, i32);
-        let mut winners = Vec::new();
-        for player in players.iter_mut() {
-            if player.state == player::FOLDED {
-                continue;
-            };
-            let player_hand = player.hand.clone();
-            if self.game_type == SEVEN_CARD_STUD || self.game_type == TEXAS_HOLD_EM {
-                // already has hand ranking
-                player_hand_type = (player.hand[0], player.hand[1], player.hand[2], player.hand[3], player.hand[4], player.hand[5]);
-            }
-            else {
-                player_hand_type = get_hand_type(&player_hand);
-            }
-            if player_hand_type.0 > winning_hand.0
-                || (player_hand_type.0 == winning_hand.0 && player_hand_type.1 > winning_hand.1)
-            {
-                winning_hand = player_hand_type;
-                winning_players.clear();
-                winning_players_names.clear();
-                winning_players.push(player.clone());
-                winning_players_names.push(player.name.clone());
-                winning_players_indices.clear();
-            } else if player_hand_type.0 == winning_hand.0 && player_hand_type.1 == winning_hand.1 {
-                winning_players.push(player.clone());
-                winning_players_names.push(player.name.clone());
-            }
+    /// Settles a finished hand: stacks become wallets, everyone dealt in has played a game
+    /// and every pot winner has won one. The hand history is saved with the results, then
+    /// the lobby goes back to waiting for a new hand.
+    pub async fn finish_hand(&mut self, engine: &Engine) {
+        self.game_state = UPDATE_DB;
+        let history = HandRecord::from_engine(&self.name, engine);
+        // a cash hand is settled against the stacks kept in the database with its history
+        let db = Database::new(self.game_db.clone());
+        let saved = if self.is_cash_table() { db.save_cash_hand(&history).await } else { db.save_hand(&history).await };
+        match saved {
+            Ok(hand_id) => println!("Saved hand {} of lobby {}", hand_id, self.name),
+            Err(e) => eprintln!("Error saving hand history for lobby {}: {}", self.name, e),
         }
-        let winning_player_count = winning_players.len();
-        let pot_share = self.pot / winning_player_count as i32;
-        for i in 0..winning_player_count {
-            for j in 0..players.len() {
-                if players[j].name == winning_players[i].name {
-                    winners.push(players[j].name.clone());
-                    players[j].games_won += 1;
-                    players[j].wallet += pot_share;
-                    println!("Player {} wins {}!", players[j].name, pot_share);
-                    println!("Player {} wallet: {}", players[j].name, players[j].wallet);
+
+        {
+            let winners = engine.winners();
+            let mut players = self.players.lock().await;
+            for player in players.iter_mut() {
+                if let Some(seat) = engine.seats.iter().find(|s| s.name == player.name) {
+                    player.wallet = seat.stack;
+                    if seat.dealt_in {
+                        player.played_game = true;
+                        player.games_played += 1;
+                    }
+                    if winners.contains(&player.name) {
+                        player.won_game = true;
+                        player.games_won += 1;
+                    }
                 }
             }
         }
-        (winners, winning_player_count as i32)
+        if self.tournament.is_some() {
+            self.eliminate(engine.busted()).await;
+        }
+        self.finished_game().await;
     }
 
     pub async fn finished_game(&mut self) {
@@ -525,17 +1064,20 @@ impl Lobby {
         self.pot = 0;
         self.current_max_bet = 0;
         self.community_cards.clear();
-        self.turns_remaining = self.current_player_count;
-        self.deal_card_counter = 0;
-        self.betting_round_counter = 0;
-        self.small_blinds_done = false;
-        self.big_blinds_done = false;
-        
+        self.shown_down.clear();
+        self.current_player_turn = "".to_string();
+        self.call_amount = 0;
+        self.hand_in_progress = false;
+        self.turn_deadline = None;
+
+        // Save the results before disconnected players are removed
+        self.update_db().await;
+
         // Reset players' states and hands
         {
             let mut players = self.players.lock().await;
             for player in players.iter_mut() {
-                player.state = player::IN_LOBBY;
+                player.state = if self.sitting_out.contains(&player.name) { player::SITTING_OUT } else { player::IN_LOBBY };
                 player.hand.clear();
                 player.current_bet = 0;
                 player.ready = false;
@@ -548,141 +1090,24 @@ impl Lobby {
             self.remove_player(player_name.clone()).await;
         }
         self.to_be_deleted.clear();
-
-        self.update_db().await;
-    }
-    
-    pub async fn showdown_texas(&self) -> Vec<String> {
-        let mut players = self.players.lock().await;
-        let mut winning_players: Vec<Player> = Vec::new(); // keeps track of winning players at the end, accounting for draws
-        let mut winning_players_names: Vec<String> = Vec::new();
-        let mut winning_hand = (-1, -1, -1, -1, -1, -1); // keeps track of current highest hand, could change when incrementing between players
-        let mut winning_players_indices: Vec<i32> = Vec::new();
-        let mut player_hand_type: (i32, i32, i32, i32, i32, i32);
-        let mut winners: Vec<String> = Vec::new();
-        for player in players.iter_mut() {
-            if player.state == player::FOLDED {
-                continue;
-            };
-            let player_hand = player.hand.clone();
-            
-            if self.game_type == SEVEN_CARD_STUD || self.game_type == TEXAS_HOLD_EM {
-                // already has hand ranking
-                player_hand_type = (player.hand[0], player.hand[1], player.hand[2], player.hand[3], player.hand[4], player.hand[5]);
-            }
-            else {
-                player_hand_type = get_hand_type(&player_hand);
-            }
-            
-            // Compare hand types first
-            if player_hand_type.0 > winning_hand.0 {
-                // Better hand type, clear previous winners
-                winning_hand = player_hand_type;
-                winning_players.clear();
-                winning_players_names.clear();
-                winning_players.push(player.clone());
-                winning_players_names.push(player.name.clone());
-                winning_players_indices.clear();
-            } 
-            // If hand types are equal, compare all five cards in sequence
-            else if player_hand_type.0 == winning_hand.0 {
-                // Compare first card (highest)
-                if player_hand_type.1 > winning_hand.1 {
-                    winning_hand = player_hand_type;
-                    winning_players.clear();
-                    winning_players_names.clear();
-                    winning_players.push(player.clone());
-                    winning_players_names.push(player.name.clone());
-                    winning_players_indices.clear();
-                } 
-                else if player_hand_type.1 == winning_hand.1 {
-                    // Compare second card
-                    if player_hand_type.2 > winning_hand.2 {
-                        winning_hand = player_hand_type;
-                        winning_players.clear();
-                        winning_players_names.clear();
-                        winning_players.push(player.clone());
-                        winning_players_names.push(player.name.clone());
-                        winning_players_indices.clear();
-                    }
-                    else if player_hand_type.2 == winning_hand.2 {
-                        // Compare third card
-                        if player_hand_type.3 > winning_hand.3 {
-                            winning_hand = player_hand_type;
-                            winning_players.clear();
-                            winning_players_names.clear();
-                            winning_players.push(player.clone());
-                            winning_players_names.push(player.name.clone());
-                            winning_players_indices.clear();
-                        }
-                        else if player_hand_type.3 == winning_hand.3 {
-                            // Compare fourth card
-                            if player_hand_type.4 > winning_hand.4 {
-                                winning_hand = player_hand_type;
-                                winning_players.clear();
-                                winning_players_names.clear();
-                                winning_players.push(player.clone());
-                                winning_players_names.push(player.name.clone());
-                                winning_players_indices.clear();
-                            }
-                            else if player_hand_type.4 == winning_hand.4 {
-                                // Compare fifth card
-                                if player_hand_type.5 > winning_hand.5 {
-                                    winning_hand = player_hand_type;
-                                    winning_players.clear();
-                                    winning_players_names.clear();
-                                    winning_players.push(player.clone());
-                                    winning_players_names.push(player.name.clone());
-                                    winning_players_indices.clear();
-                                }
-                                else if player_hand_type.5 == winning_hand.5 {
-                                    // It's a complete tie, add this player as co-winner
-                                    winning_players.push(player.clone());
-                                    winning_players_names.push(player.name.clone());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        
-        let winning_player_count = winning_players.len();
-        let pot_share = self.pot / winning_player_count as i32;
-        for i in 0..winning_player_count {
-            for j in 0..players.len() {
-                if players[j].name == winning_players[i].name {
-                    winners.push(players[j].name.clone());
-                    players[j].games_won += 1;
-                    players[j].wallet += pot_share;
-                    players[j].won_game = true;
-                    println!("Player {} wins {}!", players[j].name, pot_share);
-                    println!("Player {} wallet: {}", players[j].name, players[j].wallet);
-                }
-            }
-        }
-        winners
+        self.turns_remaining = self.players_sitting_in();
     }
     
     pub async fn update_db(&self) {
         // update the database with the new player stats
+        let db = Database::new(self.game_db.clone());
         let mut players = self.players.lock().await;
         for player in players.iter_mut() {
             println!("Updating player: {}", player.name);
             println!("games played: {}", player.games_played);
             println!("games won: {}", player.games_won);
-            println!("wallet: {}", player.wallet);
-            sqlx::query(
-                "UPDATE players SET games_played = games_played + ?1, games_won = games_won + ?2, wallet = ?3 WHERE name = ?4",
-            )
-            .bind(player.games_played)
-            .bind(player.games_won)
-            .bind(player.wallet)
-            .bind(&player.name)
-            .execute(&self.game_db)
-            .await
-            .unwrap();
-        
+            println!("stack: {}", player.wallet);
+            // stacks are never written to the wallet: cash game stacks are settled with the hand and kept apart until
+            // the player cashes out, tournament stacks are chips of the event and wallets only pay the buy-in and collect the prizes
+            if let Err(e) = db.update_player_stats(player).await {
+                eprintln!("Error updating the stats of {}: {}", player.name, e);
+            }
+
             player.games_played = 0;
             player.games_won = 0;
         }
@@ -703,25 +1128,11 @@ impl Lobby {
         false
     }
 
-    pub async fn update_player_played_game(&mut self, player_ref: &Player) -> bool {
-        let mut players = self.players.lock().await;
-        if let Some(player) = players.iter_mut().find(|p| p.name == player_ref.name) {
-            player.played_game = true;
-            return true;
-        }
-        false
-    }
-
-    pub async fn update_player_reference(&mut self, player_ref: &Player) {
+    /// Sets a player's wallet in this lobby, e.g. after reading it from the database.
+    pub async fn set_player_wallet(&self, player_name: &str, wallet: i32) {
         let mut players = self.players.lock().await;
-        if let Some(player) = players.iter_mut().find(|p| p.name == player_ref.name) {
-            player.hand = player_ref.hand.clone();
-            player.wallet = player_ref.wallet;
-            player.state = player_ref.state;
-            player.current_bet = player_ref.current_bet;
-            player.ready = player_ref.ready;
-            player.games_played = player_ref.games_played;
-            player.games_won = player_ref.games_won;
+        if let Some(player) = players.iter_mut().find(|p| p.name == player_name) {
+            player.wallet = wallet;
         }
     }
 
@@ -733,133 +1144,94 @@ impl Lobby {
     }
     
     
-    pub async fn get_next_player(&mut self, reset: bool) {
-        if reset{
-            self.current_player_index = self.first_betting_player;
-        } else {
-            self.current_player_index = (self.current_player_index + 1) % self.current_player_count;
-        }
-        let player = self.players.lock().await[self.current_player_index as usize].clone();
-        self.current_player_turn = player.name.clone();
-        self.call_amount = self.current_max_bet - player.current_bet;
-        println!("lobby call amount: {}", self.call_amount);
-    }
-    
-    pub async fn update_player_hand(&mut self, player_name: &str, hand: Vec<i32>) {
-        let mut players = self.players.lock().await;
-        if let Some(player) = players.iter_mut().find(|p| p.name == player_name) {
-            player.hand = hand;
-        }
-    }
-    
     /// Sends the current lobby information to the client.
     pub async fn send_lobby_info(&self) {
         // Get lobby information
-        let game_type = match self.game_type {
-            lobby::FIVE_CARD_DRAW => "5 Card Draw",
-            lobby::SEVEN_CARD_STUD => "7 Card Stud",
-            lobby::TEXAS_HOLD_EM => "Texas Hold'em",
-            _ => "Unknown"
-        };
-        
         let player_count = self.get_player_count().await;
-        let max_players = match self.game_type {
-            lobby::FIVE_CARD_DRAW => 5,
-            lobby::SEVEN_CARD_STUD => 7,
-            lobby::TEXAS_HOLD_EM => 10,
-            _ => 10
+        let lobby_info = LobbyInfo {
+            name: self.name.clone(),
+            game_type: game_type_name(self.game_type).to_string(),
+            player_count,
+            max_players: max_players(self.game_type),
+            call_amount: self.call_amount,
+            betting_structure: betting_structure_name(self.betting_structure).to_string(),
+            stakes: stakes_text(self.game_type, self.small_blind, self.big_blind, self.ante, self.bring_in),
+            min_buy_in: self.buy_in_limits().0,
+            max_buy_in: self.buy_in_limits().1,
+            tournament: self.tournament_info(),
         };
-        // Create JSON response
-        let lobby_info = serde_json::json!({
-            "lobbyInfo": {
-                "name": self.name,
-                "gameType": game_type,
-                "playerCount": player_count,
-                "maxPlayers": max_players,
-                "callAmount": self.call_amount,
-            }
-        });
         
-        self.broadcast(lobby_info.to_string()).await;
+        self.broadcast_message(&ServerMessage::LobbyInfo { lobby_info }).await;
     }
     
     pub async fn send_lobby_game_info(&self){
-        // Create JSON response
-        let game_info = serde_json::json!({
-            "gameInfo": {
-                "gameState": self.game_state,
-                "pot": self.pot,
-                "currentMaxBet": self.current_max_bet,
-                "communityCards": self.community_cards.clone(),
-                "currentPlayerTurn": self.current_player_turn,
-                "callAmount": self.call_amount,
-            }
-        });
+        let game_info = GameInfo {
+            game_state: self.game_state,
+            pot: self.pot,
+            current_max_bet: self.current_max_bet,
+            community_cards: self.community_cards.clone(),
+            current_player_turn: self.current_player_turn.clone(),
+            call_amount: self.call_amount,
+            min_raise: self.min_raise,
+            max_raise: self.max_raise,
+            betting_structure: betting_structure_name(self.betting_structure).to_string(),
+            action_time: self.action_time,
+            time_remaining: self.turn_deadline
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()).as_secs())
+                .unwrap_or(0),
+            time_bank: self.turn_time_bank,
+        };
         
-        self.broadcast(game_info.to_string()).await;
+        self.broadcast_message(&ServerMessage::GameInfo { game_info }).await;
     }
 
-    /// Sends the current player list to the client with hand information.
-    pub async fn send_player_list(&self) {
-        // Build player list with hands
-        let player_info = self.get_player_names_and_status().await;
-        let mut players = Vec::new();
-        let mut spectators = Vec::new();
-        
-        // Get all players with their hands
-        {
-            let players_lock = self.players.lock().await;
-            for (name, ready) in player_info {
-                // Find the player to get their hand and state
-                if let Some(player) = players_lock.iter().find(|p| p.name == name) {
-                    players.push(serde_json::json!({
-                        "name": name,
-                        "ready": ready,
-                        "hand": player.hand,
-                        "state": player.state,
-                        "wallet": player.wallet,
-                        "chips": player.wallet // For compatibility with UI
-                    }));
-                } else {
-                    // Fallback if player not found
-                    players.push(serde_json::json!({
-                        "name": name,
-                        "ready": ready,
-                        "hand": Vec::<i32>::new(),
-                        "state": player::IN_LOBBY
-                    }));
-                }
-            }
-        }
-        {
-            // Get all spectators
-            let spectators_lock = self.spectators.lock().await;
-            for spectator in spectators_lock.iter() {
-                spectators.push(serde_json::json!({
-                    "name": spectator.name,
-                }));
-            }
+    /// Returns the part of `owner`'s hand that `viewer` may see.
+    /// Players always see their own hand, spectators are passed as `None` and only see public cards.
+    /// Hands still in play are revealed to everyone once the game reaches the showdown, unless everyone else folded.
+    pub fn project_hand(&self, owner: &Player, viewer: Option<&str>) -> Vec<i32> {
+        let own_hand = viewer == Some(owner.name.as_str());
+        let shown_down = (self.game_state == SHOWDOWN || self.game_state == UPDATE_DB) && self.shown_down.contains(&owner.name);
+        visible_cards(&owner.hand, self.game_type, own_hand || shown_down)
+    }
+
+    /// Builds the player list as seen by one receiver.
+    fn player_list_view(&self, players: &[Player], spectators: &[Spectator], viewer: Option<&str>) -> ServerMessage {
+        let mut players_data = Vec::new();
+        for player in players.iter() {
+            players_data.push(PlayerView {
+                name: player.name.clone(),
+                ready: player.ready,
+                hand: self.project_hand(player, viewer),
+                state: player.state,
+                wallet: player.wallet,
+                chips: player.wallet, // For compatibility with UI
+            });
         }
-        
-        // Create JSON response
-        let player_list = serde_json::json!({
-            "players": players,
-            "spectators": spectators,
-        });
 
-        self.broadcast(player_list.to_string()).await;
+        ServerMessage::Players { players: players_data, spectators: spectators.to_vec() }
     }
-    
-    // Add this method to your Lobby impl
-    pub async fn reset_current_bets(&mut self) {
-        let mut players = self.players.lock().await;
-        for player in players.iter_mut() {
-            if player.state != player::FOLDED && player.state != player::ALL_IN {
-                player.current_bet = 0;
-                player.state = player::IN_GAME;
-            }
+
+    /// Sends the current player list with hand information.
+    /// Every receiver gets their own view so hole cards never reach other clients.
+    pub async fn send_player_list(&self) {
+        let players_lock = self.players.lock().await;
+        let spectators_lock = self.spectators.lock().await;
+
+        let spectators: Vec<Spectator> = spectators_lock
+            .iter()
+            .map(|spectator| Spectator { name: spectator.name.clone() })
+            .collect();
+
+        for player in players_lock.iter() {
+            let view = self.player_list_view(&players_lock, &spectators, Some(&player.name));
+            let _ = protocol::send(&player.tx, &view);
+        }
+
+        // spectators only get public information
+        let public_view = self.player_list_view(&players_lock, &spectators, None);
+        for spectator in spectators_lock.iter() {
+            let _ = protocol::send(&spectator.tx, &public_view);
         }
-        self.current_max_bet = 0;
     }
     
     // Also add this method to help with game reset
@@ -881,12 +1253,63 @@ impl Lobby {
             player.state = player::IN_LOBBY;
         }
         self.deck = Deck::new();
-        
-        // Move dealer position
-        self.first_betting_player = (self.first_betting_player + 1) % self.current_player_count;
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn test_visible_cards_hides_hole_cards() {
+        let hand = vec![0, 14, 27, 40, 51];
+        assert_eq!(visible_cards(&hand, FIVE_CARD_DRAW, false), vec![HIDDEN_CARD; 5]);
+        assert_eq!(visible_cards(&hand[..2], TEXAS_HOLD_EM, false), vec![HIDDEN_CARD; 2]);
+    }
 
+    #[test]
+    fn test_visible_cards_stud_up_cards() {
+        // two down-cards, three up-cards, final down-card
+        let hand = vec![3 + 53, 17 + 53, 25, 38, 4, 50 + 53];
+        let visible = visible_cards(&hand, SEVEN_CARD_STUD, false);
+        assert_eq!(visible, vec![HIDDEN_CARD, HIDDEN_CARD, 25, 38, 4, HIDDEN_CARD]);
+    }
+
+    #[test]
+    fn test_visible_cards_revealed() {
+        let hand = vec![12, 13];
+        assert_eq!(visible_cards(&hand, TEXAS_HOLD_EM, true), hand);
+    }
+
+    #[test]
+    fn test_stakes() {
+        assert!(valid_stakes(1, 2, 0, 1));
+        assert!(valid_stakes(0, 10, 10, 15));
+        assert!(!valid_stakes(20, 10, 0, 15));
+        assert!(!valid_stakes(5, 0, 0, 15));
+        assert!(!valid_stakes(5, 10, -1, 15));
+        assert!(!valid_stakes(5, 10, 0, 0));
 
+        assert_eq!(stakes_text(TEXAS_HOLD_EM, 25, 50, 0, 15), "25/50");
+        assert_eq!(stakes_text(OMAHA, 25, 50, 5, 15), "25/50 ante 5");
+        assert_eq!(stakes_text(DEUCE_TO_SEVEN_TRIPLE_DRAW, 5, 10, 10, 15), "Ante 10");
+        assert_eq!(stakes_text(RAZZ, 5, 10, 2, 5), "Bring-in 5 ante 2");
+    }
+
+    #[test]
+    fn test_buy_in_amount() {
+        let limits = (400, 1000);
+        // sitting down fills up to the maximum, or as far as the wallet goes
+        assert_eq!(buy_in_amount(0, 5000, None, limits), Ok(1000));
+        assert_eq!(buy_in_amount(0, 600, None, limits), Ok(600));
+        assert!(buy_in_amount(0, 300, None, limits).is_err());
+        assert!(buy_in_amount(0, 5000, Some(399), limits).is_err());
+        // topping up or rebuying never goes past the maximum
+        assert_eq!(buy_in_amount(250, 5000, None, limits), Ok(750));
+        assert_eq!(buy_in_amount(250, 5000, Some(150), limits), Ok(150));
+        assert!(buy_in_amount(250, 5000, Some(100), limits).is_err());
+        assert!(buy_in_amount(250, 5000, Some(751), limits).is_err());
+        assert!(buy_in_amount(1200, 5000, None, limits).is_err());
+        assert!(buy_in_amount(0, 500, Some(600), limits).is_err());
+    }
+}
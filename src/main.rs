@@ -31,6 +31,28 @@
 //! 
 //! Clients can connect to the server using a WebSocket client, such as `websocat` or a web browser.
 //! 
+//! Stored hands can be exported without starting the server, in the PokerStars format (the default) or as PHH:
+//! 
+//! ```bash
+//! cargo run -- export <hand id> [pokerstars|phh]
+//! ```
+//! 
+//! An export shows every player's hole cards, folded hands included, so the server does not serve them.
+//! 
+//! Accounts from before passwords existed are locked until the operator sets a password for them:
+//! 
+//! ```bash
+//! echo 'new password' | cargo run -- reset-password <username>
+//! ```
+//! 
+//! The JSON Schema of every message the server sends is printed with
+//! 
+//! ```bash
+//! cargo run -- schema
+//! ```
+//! 
+//! and served at `/protocol/schema.json`.
+//! 
 //! # Dependencies
 //! 
 //! The server uses the following dependencies:
@@ -42,14 +64,32 @@
 //! # Modules
 //! 
 //! The server is organized into the following modules:
+//! - `button` - The dealer button and blinds, moved round the table with the dead button rule
 //! - `database` - Database module for player registration, login, and statistics
 //! - `deck` - Deck module for managing the deck of cards
+//! - `engine` - Pure game engine that plays out a hand one action at a time
+//! - `evaluator` - Lookup-table hand evaluator used to score hands at showdown
+//! - `export` - Renders stored hands as PokerStars and PHH hand histories
+//! - `hand_type` - The original rank-and-suit evaluator for single 5-card hands
+//! - `history` - Records of finished hands, saved to the database for hand histories
 //! - `lobby` - Lobby module for managing players and lobbies
+//! - `protocol` - The typed messages the server sends, with the protocol version and their JSON Schema
+//! - `session` - Session tokens that let a player reconnect to their seat
+//! - `table` - One task per game lobby that applies player actions to the hand in arrival order
+//! - `tournament` - Sit-and-Go and multi-table tournaments: buy-ins, blind levels, eliminations and payouts
+mod button;
 mod database;
 mod deck;
+mod engine;
+mod export;
 mod lobby;
 mod games;
+mod history;
 mod player;
+mod protocol;
+mod session;
+mod table;
+mod tournament;
 
 use crate::player::Player;
 use futures_util::stream::SplitStream;
@@ -59,27 +99,91 @@ use warp::ws::{Message, WebSocket};
 use std::sync::Arc;
 use database::Database;
 use sqlx::SqlitePool;
-use uuid::Uuid;
 use tokio::sync::{mpsc, Mutex};
 use lobby::*;
+use poker_game::{evaluator, hand_type};
+use protocol::{ServerMessage, Stats, TournamentSummary};
 use deck::Deck;
+use session::{SessionError, Sessions};
+use tournament::mtt::{self, MultiTableTournament};
 
 use serde::Deserialize;
 use serde_json::Result as JsonResult;
 
+// hands sent for a RecentHands request without a limit, and the most it can ask for
+const RECENT_HANDS: i64 = 20;
+const MAX_RECENT_HANDS: i64 = 100;
+
 #[derive(Deserialize)]
 #[serde(tag = "action", content = "data")]
 enum ClientMessage {
     Disconnect,
-    Login { username: String },
-    Register { username: String },
+    Login { username: String, password: String },
+    Register { username: String, password: String },
+    Reconnect { session_token: String },
+    ChangePassword { old_password: String, new_password: String },
     Ready,
     Quit,
     Help,
-    CreateLobby { lobby_name: String, game_type: i32 },
+    CreateLobby {
+        lobby_name: String,
+        game_type: i32,
+        // seconds per action and per time bank, the lobby defaults are used when they are left out
+        #[serde(default)]
+        action_time: Option<u64>,
+        #[serde(default)]
+        time_bank: Option<u64>,
+        // lobby::NO_LIMIT, POT_LIMIT or FIXED_LIMIT, the game's usual structure when left out
+        #[serde(default)]
+        betting_structure: Option<i32>,
+        // forced bets in chips, the lobby defaults are used for any left out
+        #[serde(default)]
+        small_blind: Option<i32>,
+        #[serde(default)]
+        big_blind: Option<i32>,
+        #[serde(default)]
+        ante: Option<i32>,
+        #[serde(default)]
+        bring_in: Option<i32>,
+        // the smallest and largest stack a cash game player can buy, in big blinds
+        #[serde(default)]
+        min_buy_in: Option<i32>,
+        #[serde(default)]
+        max_buy_in: Option<i32>,
+        // makes the lobby a Sit-and-Go tournament, null or left out for a cash game
+        #[serde(default)]
+        sit_and_go: Option<tournament::SitAndGoOptions>,
+    },
+    // multi-table tournaments, registered for from the server lobby
+    CreateTournament {
+        tournament_name: String,
+        game_type: i32,
+        #[serde(default)]
+        betting_structure: Option<i32>,
+        // most players at a table, the game's most up to 9 when left out
+        #[serde(default)]
+        table_size: Option<usize>,
+        // the buy-in, starting stack, blind schedule and payouts, as for a Sit-and-Go
+        #[serde(default)]
+        settings: tournament::SitAndGoOptions,
+    },
+    RegisterTournament { tournament_name: String },
+    UnregisterTournament { tournament_name: String },
+    StartTournament { tournament_name: String },
+    ShowTournaments,
     JoinLobby { lobby_name: String, spectate: bool},
+    // buys chips at a cash table between hands, as many as the table allows when the amount is left out
+    BuyIn {
+        #[serde(default)]
+        amount: Option<i32>,
+    },
     ShowLobbies,
     ShowStats,
+    // the player's latest stored hands, RECENT_HANDS of them when the limit is left out
+    RecentHands {
+        #[serde(default)]
+        limit: Option<i64>,
+    },
     ShowPlayers,
     ShowLobbyInfo,
     StartGame,
@@ -93,17 +197,55 @@ enum ClientMessage {
     Raise { amount: i32 },
     Fold,
     AllIn,
-
+    DrawCards {
+        #[serde(rename = "cardIndices")]
+        card_indices: Vec<usize>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("export") {
+        export_hand_command(&args[2..]).await;
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("reset-password") {
+        reset_password_command(&args[2..]).await;
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("schema") {
+        println!("{}", protocol::schema());
+        return Ok(());
+    }
+
     let db_pool = initialize_db().await;
     let database = Arc::new(Database::new(db_pool.clone()));
+    let sessions = Sessions::new();
 
     if let Err(e) = database.reset_all_login_statuses().await {
         eprintln!("Failed to reset login statuses: {}", e);
     }
+    // the tables are gone with the last server, so the chips left on them go back to the wallets
+    match database.cash_out_all().await {
+        Ok(0) => {}
+        Ok(stacks) => println!("Returned {} table stacks to their wallets.", stacks),
+        Err(e) => eprintln!("Failed to return table stacks to wallets: {}", e),
+    }
+    // every chip in a wallet or at a table must be accounted for by the ledger
+    match database.reconcile().await {
+        Ok(report) if report.balanced() => println!("Chip ledger balanced: {} chips in wallets.", report.wallets),
+        Ok(report) => {
+            eprintln!(
+                "Chip ledger does not balance: {} chips created, {} in wallets, {} at tables, {} in prize pools.",
+                report.created, report.wallets, report.tables, report.tournaments
+            );
+            for problem in report.problems {
+                eprintln!("  {}", problem);
+            }
+        }
+        Err(e) => eprintln!("Failed to reconcile the chip ledger: {}", e),
+    }
 
     let server_lobby = Arc::new(Mutex::new(
         Lobby::new(lobby::NOT_SET, "Server Lobby".to_string()).await
@@ -114,8 +256,9 @@ async fn main() -> std::io::Result<()> {
         .and(warp::ws())
         .and(with_db(database.clone()))
         .and(with_lobby(server_lobby.clone()))
-        .map(|ws: warp::ws::Ws, db, lobby| {
-            ws.on_upgrade(move |socket| handle_connection(socket, db, lobby))
+        .and(with_sessions(sessions.clone()))
+        .map(|ws: warp::ws::Ws, db, lobby, sessions| {
+            ws.on_upgrade(move |socket| handle_connection(socket, db, lobby, sessions))
         });
 
     let index_route = warp::path::end()
@@ -134,6 +277,11 @@ async fn main() -> std::io::Result<()> {
     let stats_route = warp::path("stats")
         .map(|| warp::reply::html(include_str!("../static/stats.html")));
 
+    // The JSON Schema of the server's messages, for third-party clients
+    let schema_route = warp::path!("protocol" / "schema.json")
+        .and(warp::get())
+        .map(|| warp::reply::with_header(protocol::schema(), "content-type", "application/json"));
+
     let five_card = warp::path("five_card")
         .and(warp::fs::dir("../static/five_card.html"));
 
@@ -143,6 +291,9 @@ async fn main() -> std::io::Result<()> {
     let texas_hold_em = warp::path("texas_holdem")
         .and(warp::fs::dir("../static/texas_holdem.html"));
 
+    let omaha = warp::path("omaha")
+        .and(warp::fs::dir("../static/omaha.html"));
+
     let static_files = warp::path("static")
         .and(warp::fs::dir("../static"));
 
@@ -153,9 +304,11 @@ async fn main() -> std::io::Result<()> {
         .or(server_lobby_route)
         .or(lobby_route)
         .or(stats_route)
+        .or(schema_route)
         .or(five_card)
         .or(seven_card)
         .or(texas_hold_em)
+        .or(omaha)
         .or(static_files)
         .with(warp::cors()
             .allow_any_origin()
@@ -174,16 +327,6 @@ async fn initialize_db() -> SqlitePool {
     use std::fs;
     
     let db_path = "poker.db";
-    let schema_sql = r#"
-        CREATE TABLE IF NOT EXISTS players (
-            id TEXT PRIMARY KEY,
-            name TEXT UNIQUE,
-            games_played INTEGER DEFAULT 0,
-            games_won INTEGER DEFAULT 0,
-            wallet INTEGER DEFAULT 1000,
-            logged_in BOOLEAN DEFAULT FALSE
-        );
-    "#;
     
     // Check if database file exists
     let db_exists = Path::new(db_path).exists();
@@ -203,14 +346,11 @@ async fn initialize_db() -> SqlitePool {
         .await
         .expect("Failed to connect to database");
     
-    // If database didn't exist, initialize it with schema
-    if !db_exists {
-        println!("Initializing database with schema.");
-        match sqlx::query(schema_sql).execute(&db_pool).await {
-            Ok(_) => println!("Database schema created successfully."),
-            Err(e) => eprintln!("Error creating schema: {}", e),
-        }
-    }
+    // Create the schema for a new database, or bring an existing one up to date
+    let version = database::migrations::run(&db_pool)
+        .await
+        .expect("Failed to migrate database");
+    println!("Database schema is at version {}.", version);
     
     println!("Database connection established successfully.");
     db_pool
@@ -228,47 +368,113 @@ fn with_lobby(
     warp::any().map(move || lobby.clone())
 }
 
-/// Retrieves the names and statuses of all lobbies from the server.
-/// 
-/// This function locks the `server_lobby` asynchronously, then calls 
-/// `get_lobby_names_and_status` to obtain a list of lobby names and their 
-/// corresponding statuses.
-/// 
-/// # Returns
-/// 
-/// A list of tuples where each tuple contains the name of a lobby and its status.
-async fn get_lobbies_json(server_lobby: Arc<Mutex<Lobby>>) -> String {
-    let lobbies = server_lobby.lock().await.get_lobby_names_and_status().await;
-    let mut lobby_list = Vec::new();
-    
-    for (lobby_name, lobby_status, lobby_type, player_count, max_player_count) in lobbies {
-        // Convert status code to string
-        let status = if lobby_status == lobby::JOINABLE {
-            "Joinable"
-        } else {
-            "Not Joinable"
-        };
-        
-        // Convert game type to readable string
-        let game_type = match lobby_type {
-            lobby::FIVE_CARD_DRAW => "5 Card Draw",
-            lobby::SEVEN_CARD_STUD => "7 Card Stud", 
-            lobby::TEXAS_HOLD_EM => "Texas Hold'em",
-            _ => "Unknown"
-        };
-        
-        lobby_list.push(serde_json::json!({
-            "name": lobby_name,
-            "status": status,
-            "type": game_type,
-            "playerCount": player_count,
-            "maxPlayers": max_player_count
-        }));
+/// `export <hand id> [format]`: prints a stored hand to stdout and exits, without starting the server.
+async fn export_hand_command(args: &[String]) {
+    let usage = format!("Usage: export <hand id> [{}|{}]", export::POKERSTARS, export::PHH);
+    let hand_id = match args.first().and_then(|id| id.parse::<i64>().ok()) {
+        Some(hand_id) => hand_id,
+        None => {
+            eprintln!("{}", usage);
+            std::process::exit(2);
+        }
+    };
+    let format = args.get(1).map(String::as_str).unwrap_or(export::POKERSTARS);
+
+    let db_pool = match SqlitePool::connect("sqlite:poker.db?mode=ro").await {
+        Ok(db_pool) => db_pool,
+        Err(e) => {
+            eprintln!("Failed to open poker.db: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let hand = match Database::new(db_pool).get_hand(hand_id).await {
+        Ok(Some(hand)) => hand,
+        Ok(None) => {
+            eprintln!("Hand {} not found", hand_id);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Error reading hand {}: {}", hand_id, e);
+            std::process::exit(1);
+        }
+    };
+    match export::export(&hand, format) {
+        Some(history) => print!("{}", history),
+        None => {
+            eprintln!("{}", usage);
+            std::process::exit(2);
+        }
     }
-    
-    serde_json::json!({
-        "lobbies": lobby_list
-    }).to_string()
+}
+
+/// `reset-password <username>`: sets a player's password, read from stdin, and exits without starting the server.
+/// This is how an account from before passwords existed is unlocked.
+async fn reset_password_command(args: &[String]) {
+    let username = match args.first() {
+        Some(username) => username,
+        None => {
+            eprintln!("Usage: reset-password <username>, with the new password on stdin");
+            std::process::exit(2);
+        }
+    };
+    let mut password = String::new();
+    if std::io::stdin().read_line(&mut password).is_err() || password.trim_end_matches(['\r', '\n']).is_empty() {
+        eprintln!("The new password cannot be empty");
+        std::process::exit(2);
+    }
+    let password = password.trim_end_matches(['\r', '\n']);
+
+    let db = Database::new(initialize_db().await);
+    match db.reset_password(username, password).await {
+        Ok(true) => println!("Password set for {}.", username),
+        Ok(false) => {
+            eprintln!("No player named {}", username);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Error setting the password of {}: {}", username, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn with_sessions(
+    sessions: Sessions
+) -> impl Filter<Extract = (Sessions,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || sessions.clone())
+}
+
+/// Finds a multi-table tournament by name.
+async fn find_tournament(server_lobby: &Arc<Mutex<Lobby>>, tournament_name: &str) -> Option<Arc<Mutex<MultiTableTournament>>> {
+    let tournaments = server_lobby.lock().await.tournaments.lock().await.clone();
+    for tournament in tournaments {
+        if tournament.lock().await.name == tournament_name {
+            return Some(tournament);
+        }
+    }
+    None
+}
+
+/// The multi-table tournaments as shown in the server lobby.
+async fn tournaments_message(server_lobby: &Arc<Mutex<Lobby>>) -> ServerMessage {
+    let tournaments = server_lobby.lock().await.tournaments.lock().await.clone();
+    let mut tournament_list: Vec<TournamentSummary> = Vec::new();
+    for tournament in tournaments {
+        tournament_list.push(tournament.lock().await.info());
+    }
+    ServerMessage::Tournaments { tournaments: tournament_list }
+}
+
+/// Answers a tournament request with `message` if it succeeded, otherwise with the error, and sends
+/// everyone in the server lobby the updated list of tournaments.
+async fn reply_tournament(server_lobby: &Arc<Mutex<Lobby>>, tx: &mpsc::UnboundedSender<Message>, result: Result<(), &str>, message: String) {
+    let reply = match result {
+        Ok(()) => ServerMessage::notice(message),
+        Err(error) => ServerMessage::error(error),
+    };
+    let _ = protocol::send(tx, &reply);
+    let tournaments = tournaments_message(server_lobby).await;
+    server_lobby.lock().await.broadcast_message(&tournaments).await;
 }
 
 /// Handles a new WebSocket connection.
@@ -281,12 +487,13 @@ async fn get_lobbies_json(server_lobby: Arc<Mutex<Lobby>>) -> String {
 /// * `ws` - The WebSocket connection.
 /// * `db` - The database connection pool.
 /// * `server_lobby` - The server lobby containing all players and lobbies.
+/// * `sessions` - The session tokens handed out at login.
 /// 
 /// # Returns
 /// 
 /// This function does not return a value, but it sends messages to the client
 /// via the WebSocket connection.
-async fn handle_connection(ws: WebSocket, db: Arc<Database>, server_lobby: Arc<Mutex<Lobby>>) {
+async fn handle_connection(ws: WebSocket, db: Arc<Database>, server_lobby: Arc<Mutex<Lobby>>, sessions: Sessions) {
     // Split websocket into tx/rx and create a channel to forward messages
     let (mut ws_tx, ws_rx) = ws.split();
     let (tx, mut rx) = mpsc::unbounded_channel();
@@ -317,84 +524,153 @@ async fn handle_connection(ws: WebSocket, db: Arc<Database>, server_lobby: Arc<M
     };
 
     // Send initial welcome message
-    tx.send(Message::text(r#"{"message": "Welcome to Poker!"}"#)).unwrap();
+    let welcome = ServerMessage::Welcome { message: "Welcome to Poker!".to_string(), protocol_version: protocol::PROTOCOL_VERSION };
+    protocol::send(&tx, &welcome).unwrap();
 
     // Handle login phase
-    let logged_in_player = handle_login_phase(curr_player, db.clone(), server_lobby.clone()).await;
+    let logged_in_player = handle_login_phase(curr_player, db.clone(), server_lobby.clone(), sessions.clone()).await;
     
     // If login was successful, proceed to server lobby
-    if let Some(player) = logged_in_player {
+    if let Some(mut player) = logged_in_player {
+        if player.state == player::IN_GAME {
+            // Reconnected to a seat held at a table, go back to the server lobby when they leave it
+            let result = games::resume_game_state_machine(server_lobby.clone(), player.clone(), db.clone()).await;
+            if result == "Normal" {
+                player.state = player::IN_SERVER;
+            }
+        }
         if player.state == player::IN_SERVER {
             println!("Player logged in successfully.");
             handle_server_lobby(player.clone(), server_lobby, db.clone()).await;
         }
         
-        // Logout player when they disconnect
+        // Logout player when they disconnect, their token can bring them back for a while
         let _ = db.logout_player(&player.name).await;
+        sessions.disconnected(&player.name).await;
     }
     
     println!("Connection closed");
 }
 
+/// Sends the welcome message with a new session token and adds the player to the server lobby.
+async fn enter_server_lobby(player: &mut Player, message: String, server_lobby: &Arc<Mutex<Lobby>>, sessions: &Sessions) {
+    let welcome = ServerMessage::LoggedIn {
+        message,
+        redirect: "server_lobby".to_string(),
+        session_token: sessions.create(&player.name).await,
+    };
+    protocol::send(&player.tx, &welcome).unwrap();
+
+    player.state = player::IN_SERVER;
+    player.lobby = server_lobby.clone();
+    server_lobby.lock().await.add_player(player.clone()).await;
+    server_lobby.lock().await.broadcast_player_count().await;
+}
+
 // New helper function to handle login phase
-async fn handle_login_phase(mut player: Player, db: Arc<Database>, server_lobby: Arc<Mutex<Lobby>>) -> Option<Player> {
+async fn handle_login_phase(mut player: Player, db: Arc<Database>, server_lobby: Arc<Mutex<Lobby>>, sessions: Sessions) -> Option<Player> {
     let tx = player.tx.clone();
     
-    while let Some(result) = player.rx.lock().await.next().await {
+    loop {
+        // the lock is released before the message is handled, which may hand the player on
+        let next = {
+            let mut rx = player.rx.lock().await;
+            rx.next().await
+        };
+        let result = match next {
+            Some(result) => result,
+            None => break,
+        };
         if let Ok(msg) = result {
             if let Ok(text) = msg.to_str() {
                 // Parse incoming message
                 let client_msg: JsonResult<ClientMessage> = serde_json::from_str(text);
                 match client_msg {
-                    Ok(ClientMessage::Login { username }) => {
+                    Ok(ClientMessage::Login { username, password }) => {
+                        if password.is_empty() {
+                            protocol::send(&tx, &ServerMessage::notice("Please enter your password.")).unwrap();
+                            continue;
+                        }
                         // Attempt login
-                        if let Ok(Some(_id)) = db.login_player(&username).await {
-                            tx.send(Message::text(
-                                format!(r#"{{"message": "Welcome back, {}!", "redirect": "server_lobby"}}"#, username)
-                            )).unwrap();
-                            
+                        if let Ok(Some(_id)) = db.login_player(&username, &password).await {
                             // Update player data
                             player.name = username.clone();
                             player.id = _id.to_string();
                             player.wallet = db.get_player_wallet(&username).await.unwrap_or(1000) as i32;
-                            player.state = player::IN_SERVER;
-                            player.lobby = server_lobby.clone();
                             
                             // Add player to server lobby
-                            server_lobby.lock().await.add_player(player.clone()).await;
-                            server_lobby.lock().await.broadcast_player_count().await;
+                            enter_server_lobby(&mut player, format!("Welcome back, {}!", username), &server_lobby, &sessions).await;
                             
                             return Some(player.clone());
                         } else {
                             // Login failed, could be because user is already logged in
-                            tx.send(Message::text(r#"{"message": "Login failed. User may already be logged in or doesn't exist."}"#)).unwrap();
+                            protocol::send(&tx, &ServerMessage::notice("Login failed. Wrong username or password, or the user is already logged in.")).unwrap();
                         }
                     }
-                    Ok(ClientMessage::Register { username }) => {
+                    Ok(ClientMessage::Register { username, password }) => {
+                        if password.is_empty() {
+                            protocol::send(&tx, &ServerMessage::notice("Please choose a password.")).unwrap();
+                            continue;
+                        }
                         // Attempt registration
-                        if db.register_player(&username).await.is_ok() {
-                            tx.send(Message::text(
-                                format!(r#"{{"message": "Registration successful! Welcome, {}!", "redirect": "server_lobby"}}"#, username)
-                            )).unwrap();
-                            
+                        if let Ok(id) = db.register_player(&username, &password).await {
                             // Update player data
                             player.name = username.clone();
-                            player.id = Uuid::new_v4().to_string();
+                            player.id = id;
                             player.wallet = 1000;
-                            player.state = player::IN_SERVER;
-                            player.lobby = server_lobby.clone();
                             
                             // Add player to server lobby
-                            server_lobby.lock().await.add_player(player.clone()).await;
-                            server_lobby.lock().await.broadcast_player_count().await;
+                            let message = format!("Registration successful! Welcome, {}!", username);
+                            enter_server_lobby(&mut player, message, &server_lobby, &sessions).await;
                             
                             return Some(player.clone());
                         } else {
-                            tx.send(Message::text(r#"{"message": "Registration failed. Try again."}"#)).unwrap();
+                            protocol::send(&tx, &ServerMessage::notice("Registration failed. Try again.")).unwrap();
                         }
                     }
+                    Ok(ClientMessage::Reconnect { session_token }) => {
+                        let username = match sessions.reconnect(&session_token).await {
+                            Ok(username) => username,
+                            Err(SessionError::InUse) => {
+                                protocol::send(&tx, &ServerMessage::notice("This session is still connected elsewhere.")).unwrap();
+                                continue;
+                            }
+                            Err(SessionError::Expired) => {
+                                let expired = ServerMessage::SessionExpired {
+                                    message: "Your session has expired, please log in again.".to_string(),
+                                    session_expired: true,
+                                };
+                                protocol::send(&tx, &expired).unwrap();
+                                continue;
+                            }
+                        };
+                        player.name = username.clone();
+                        player.wallet = db.get_player_wallet(&username).await.unwrap_or(1000);
+                        // the token stands in for the password
+                        let _ = db.mark_logged_in(&username).await;
+
+                        // Take back the seat the table is holding, if there is one
+                        if let Some(held_lobby) = games::find_held_seat(&server_lobby, &username).await {
+                            player.lobby = held_lobby.clone();
+                            if games::reclaim_seat(&held_lobby, player.clone()).await {
+                                let lobby_name = held_lobby.lock().await.name.clone();
+                                let welcome = ServerMessage::LoggedIn {
+                                    message: format!("Welcome back, {}! You are back at your seat in {}.", username, lobby_name),
+                                    redirect: "lobby".to_string(),
+                                    session_token,
+                                };
+                                protocol::send(&tx, &welcome).unwrap();
+                                server_lobby.lock().await.add_player(player.clone()).await;
+                                server_lobby.lock().await.broadcast_player_count().await;
+                                player.state = player::IN_GAME;
+                                return Some(player.clone());
+                            }
+                        }
+                        enter_server_lobby(&mut player, format!("Welcome back, {}!", username), &server_lobby, &sessions).await;
+                        return Some(player.clone());
+                    }
                     Ok(ClientMessage::Quit) => {
-                        tx.send(Message::text(r#"{"message": "Goodbye!", "redirect": "index"}"#)).unwrap();
+                        protocol::send(&tx, &ServerMessage::redirect("Goodbye!", "index")).unwrap();
                         return None;
                     }
                     _ => continue,
@@ -426,7 +702,12 @@ async fn handle_server_lobby(player: Player, server_lobby: Arc<Mutex<Lobby>>, db
             let mut rx = player.rx.lock().await;
             match rx.next().await {
                 Some(res) => res,
-                None => continue,
+                None => {
+                    // the socket closed without sending Disconnect
+                    server_lobby.lock().await.remove_player(player_name.clone()).await;
+                    server_lobby.lock().await.broadcast_player_count().await;
+                    break;
+                }
             }
         };
 
@@ -443,29 +724,59 @@ async fn handle_server_lobby(player: Player, server_lobby: Arc<Mutex<Lobby>>, db
                     Ok(ClientMessage::ShowPlayers) => {
                         // Show players in the lobby
                         let player_count = server_lobby.lock().await.get_player_count().await;
-                        let msg = serde_json::json!({
-                            "playerCount": player_count
-                        });
-                        tx.send(Message::text(msg.to_string())).unwrap();
+                        protocol::send(&tx, &ServerMessage::PlayerCount { player_count }).unwrap();
                     }
                     Ok(ClientMessage::ShowLobbies) => {
                         // Get and send lobby information
-                        let lobbies_json = get_lobbies_json(server_lobby.clone()).await;
-                        tx.send(Message::text(lobbies_json)).unwrap();
+                        let lobbies = server_lobby.lock().await.lobby_summaries().await;
+                        protocol::send(&tx, &ServerMessage::Lobbies { lobbies }).unwrap();
                     }
-                    Ok(ClientMessage::CreateLobby { lobby_name, game_type }) => {
+                    Ok(ClientMessage::CreateLobby {
+                        lobby_name, game_type, action_time, time_bank, betting_structure, small_blind, big_blind, ante, bring_in,
+                        min_buy_in, max_buy_in, sit_and_go,
+                    }) => {
                         // Create a new lobby
                         if server_lobby.lock().await.lobby_exists(lobby_name.clone()).await {
-                            tx.send(Message::text(r#"{"error": "Lobby name already exists"}"#)).unwrap();
+                            protocol::send(&tx, &ServerMessage::error("Lobby name already exists")).unwrap();
                         } else {
                             // Create a new lobby with the specified name and game type
-                            let new_lobby = Arc::new(Mutex::new(Lobby::new(game_type, lobby_name.clone()).await));
+                            let mut lobby = Lobby::new(game_type, lobby_name.clone()).await;
+                            lobby.action_time = action_time.unwrap_or(lobby::DEFAULT_ACTION_TIME).max(lobby::MIN_ACTION_TIME);
+                            lobby.time_bank = time_bank.unwrap_or(lobby::DEFAULT_TIME_BANK);
+                            if let Some(betting_structure) = betting_structure {
+                                if [lobby::NO_LIMIT, lobby::POT_LIMIT, lobby::FIXED_LIMIT].contains(&betting_structure) {
+                                    lobby.betting_structure = betting_structure;
+                                }
+                            }
+                            if !lobby.set_stakes(small_blind, big_blind, ante, bring_in) {
+                                protocol::send(&tx, &ServerMessage::error("Invalid stakes")).unwrap();
+                                continue;
+                            }
+                            if !lobby.set_buy_in_limits(min_buy_in, max_buy_in) {
+                                protocol::send(&tx, &ServerMessage::error("Invalid buy-in limits")).unwrap();
+                                continue;
+                            }
+                            if let Some(options) = sit_and_go {
+                                match tournament::Tournament::from_options(options, lobby.max_player_count) {
+                                    Ok(tournament) => {
+                                        lobby.tournament = Some(tournament);
+                                        lobby.apply_tournament_level();
+                                    }
+                                    Err(error) => {
+                                        protocol::send(&tx, &ServerMessage::error(error)).unwrap();
+                                        continue;
+                                    }
+                                }
+                            }
+                            let new_lobby = Arc::new(Mutex::new(lobby));
+                            let table = table::spawn_table(server_lobby.clone(), &new_lobby);
+                            new_lobby.lock().await.table = Some(table);
                             
                             // Add the new lobby to the server
                             server_lobby.lock().await.add_lobby(new_lobby).await;
                             
                             // Send success message
-                            tx.send(Message::text(format!(r#"{{"message": "Lobby '{}' created successfully"}}"#, lobby_name))).unwrap();
+                            protocol::send(&tx, &ServerMessage::notice(format!("Lobby '{}' created successfully", lobby_name))).unwrap();
                         }
                     }
                     Ok(ClientMessage::JoinLobby { lobby_name, spectate }) => {
@@ -480,24 +791,26 @@ async fn handle_server_lobby(player: Player, server_lobby: Arc<Mutex<Lobby>>, db
                                 let player_lobby_type = player_obj.lobby.lock().await.game_type.clone();
                                 // Successfully joined the lobby
                                 println!("successful joining");
-                                tx.send(Message::text(
-                                    format!(r#"{{"message": "Successfully joined lobby: {}!", "redirect": "lobby"}}"#, lobby_name.clone())
-                                )).unwrap();
+                                let joined = ServerMessage::redirect(format!("Successfully joined lobby: {}!", lobby_name), "lobby");
+                                protocol::send(&tx, &joined).unwrap();
                                 let result;
                                 if spectate {
                                     result = join_as_spectator(server_lobby.clone(), player_obj.clone(), db.clone()).await;
                                 } else {
                                     match player_lobby_type {
-                                        lobby::FIVE_CARD_DRAW => {
+                                        lobby::FIVE_CARD_DRAW | lobby::DEUCE_TO_SEVEN_TRIPLE_DRAW => {
                                             result = games::five_card_game_state_machine(server_lobby.clone(), player_obj, db.clone()).await;
                                         }
-                                        lobby::SEVEN_CARD_STUD => {
+                                        lobby::SEVEN_CARD_STUD | lobby::SEVEN_CARD_STUD_HI_LO | lobby::RAZZ => {
                                             // result = join_lobby(server_lobby.clone(), player_obj, db.clone()).await;
                                             result = games::seven_card_game_state_machine(server_lobby.clone(), player_obj, db.clone()).await;
                                         }
                                         lobby::TEXAS_HOLD_EM => {
                                             result = games::texas_holdem_game_state_machine(server_lobby.clone(), player_obj, db.clone()).await;
                                         }
+                                        lobby::OMAHA | lobby::OMAHA_HI_LO => {
+                                            result = games::omaha_game_state_machine(server_lobby.clone(), player_obj, db.clone()).await;
+                                        }
                                         _ => {
                                             continue;
                                         }
@@ -520,7 +833,7 @@ async fn handle_server_lobby(player: Player, server_lobby: Arc<Mutex<Lobby>>, db
                                 } else {
                                     "Failed to join lobby. The lobby may be full or not joinable."
                                 };
-                                tx.send(Message::text(format!(r#"{{"message": "{}"}}"#, message))).unwrap();
+                                protocol::send(&tx, &ServerMessage::notice(message)).unwrap();
                             }
                         }
                     }
@@ -530,24 +843,72 @@ async fn handle_server_lobby(player: Player, server_lobby: Arc<Mutex<Lobby>>, db
                         
                         if let Ok(stats) = stats {
                             println!("Retrieved stats for {}: {:?}", player_name, stats);
-                            // Format stats as JSON and send to client
-                            let stats_json = serde_json::json!({
-                                "stats": {
-                                    "username": stats.name,
-                                    "gamesPlayed": stats.games_played,
-                                    "gamesWon": stats.games_won,
-                                    "wallet": stats.wallet,
-                                    "winRate": if stats.games_played > 0 {
-                                        format!("{}%", (stats.games_won as f64 / stats.games_played as f64) * 100.0)
-                                    } else {
-                                        "N/A".to_string()
-                                    }
-                                }
-                            });
-                            tx.send(Message::text(stats_json.to_string())).unwrap();
+                            protocol::send(&tx, &ServerMessage::Stats { stats: Stats::new(&stats) }).unwrap();
                         } else {
                             println!("Error retrieving stats for {}: {:?}", player_name, stats);
-                            tx.send(Message::text(r#"{"error": "Failed to retrieve stats"}"#)).unwrap();
+                            protocol::send(&tx, &ServerMessage::error("Failed to retrieve stats")).unwrap();
+                        }
+                    }
+                    Ok(ClientMessage::RecentHands { limit }) => {
+                        let limit = limit.unwrap_or(RECENT_HANDS).clamp(1, MAX_RECENT_HANDS);
+                        match db.recent_hands(&player_name, limit).await {
+                            Ok(recent_hands) => protocol::send(&tx, &ServerMessage::RecentHands { recent_hands }).unwrap(),
+                            Err(e) => {
+                                println!("Error retrieving recent hands for {}: {:?}", player_name, e);
+                                protocol::send(&tx, &ServerMessage::error("Failed to retrieve recent hands")).unwrap();
+                            }
+                        }
+                    }
+                    Ok(ClientMessage::ShowTournaments) => {
+                        protocol::send(&tx, &tournaments_message(&server_lobby).await).unwrap();
+                    }
+                    Ok(ClientMessage::CreateTournament { tournament_name, game_type, betting_structure, table_size, settings }) => {
+                        let result = if find_tournament(&server_lobby, &tournament_name).await.is_some() {
+                            Err("Tournament name already exists")
+                        } else {
+                            let tournament = MultiTableTournament::new(
+                                tournament_name.clone(), player_name.clone(), game_type, betting_structure, table_size, settings, db.clone(),
+                            );
+                            match tournament {
+                                Ok(tournament) => {
+                                    let tournaments = server_lobby.lock().await.tournaments.clone();
+                                    tournaments.lock().await.push(Arc::new(Mutex::new(tournament)));
+                                    Ok(())
+                                }
+                                Err(error) => Err(error),
+                            }
+                        };
+                        reply_tournament(&server_lobby, &tx, result, format!("Tournament '{}' created successfully", tournament_name)).await;
+                    }
+                    Ok(ClientMessage::RegisterTournament { tournament_name }) => {
+                        let result = match find_tournament(&server_lobby, &tournament_name).await {
+                            Some(tournament) => tournament.lock().await.register(&player_name).await,
+                            None => Err("No such tournament"),
+                        };
+                        reply_tournament(&server_lobby, &tx, result, format!("Registered for {}.", tournament_name)).await;
+                    }
+                    Ok(ClientMessage::UnregisterTournament { tournament_name }) => {
+                        let result = match find_tournament(&server_lobby, &tournament_name).await {
+                            Some(tournament) => tournament.lock().await.unregister(&player_name).await,
+                            None => Err("No such tournament"),
+                        };
+                        reply_tournament(&server_lobby, &tx, result, format!("Unregistered from {}, your buy-in is refunded.", tournament_name)).await;
+                    }
+                    Ok(ClientMessage::StartTournament { tournament_name }) => {
+                        let result = match find_tournament(&server_lobby, &tournament_name).await {
+                            Some(tournament) => mtt::start(&tournament, &server_lobby, &player_name).await,
+                            None => Err("No such tournament"),
+                        };
+                        let message = format!("{} is seated, the first hands are dealt in {} seconds.", tournament_name, mtt::SEATING_SECS);
+                        reply_tournament(&server_lobby, &tx, result, message).await;
+                    }
+                    Ok(ClientMessage::ChangePassword { old_password, new_password }) => {
+                        if new_password.is_empty() {
+                            protocol::send(&tx, &ServerMessage::error("The new password cannot be empty")).unwrap();
+                        } else if let Ok(true) = db.change_password(&player_name, &old_password, &new_password).await {
+                            protocol::send(&tx, &ServerMessage::notice("Password changed.")).unwrap();
+                        } else {
+                            protocol::send(&tx, &ServerMessage::error("Your current password is wrong")).unwrap();
                         }
                     }
                     _ => {
@@ -582,10 +943,11 @@ async fn join_as_spectator(server_lobby: Arc<Mutex<Lobby>>, player: Player, db:
     println!("{} is spectating lobby: {}", player_name, player_lobby.lock().await.name);
     
     // Send message about spectating
-    tx.send(Message::text(format!(
-        r#"{{"message": "You are spectating lobby: {}. You can only observe until the game is over."}}"#,
+    let spectating = format!(
+        "You are spectating lobby: {}. You can only observe until the game is over.",
         player_lobby.lock().await.name
-    ))).unwrap();
+    );
+    protocol::send(&tx, &ServerMessage::notice(spectating)).unwrap();
 
     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
     player_lobby.lock().await.send_lobby_info().await;
@@ -597,7 +959,11 @@ async fn join_as_spectator(server_lobby: Arc<Mutex<Lobby>>, player: Player, db:
             let mut rx = player.rx.lock().await;
             match rx.next().await {
                 Some(res) => res,
-                None => continue,
+                None => {
+                    // the socket closed without sending Disconnect
+                    player_lobby.lock().await.remove_spectator(player_name.clone()).await;
+                    return "Disconnect".to_string();
+                }
             }
         };
         
@@ -614,7 +980,7 @@ async fn join_as_spectator(server_lobby: Arc<Mutex<Lobby>>, player: Player, db:
                         player_lobby.lock().await.remove_spectator(player_name.clone()).await;
                         
                         // Send redirect back to server lobby
-                        tx.send(Message::text(r#"{"message": "Left spectator mode", "redirect": "server_lobby"}"#)).unwrap();
+                        protocol::send(&tx, &ServerMessage::redirect("Left spectator mode", "server_lobby")).unwrap();
                         return "Normal".to_string();
                     }
                     Ok(ClientMessage::Disconnect) => {
@@ -628,24 +994,10 @@ async fn join_as_spectator(server_lobby: Arc<Mutex<Lobby>>, player: Player, db:
                         
                         if let Ok(stats) = stats {
                             println!("Retrieved stats for {}: {:?}", player_name, stats);
-                            // Format stats as JSON and send to client
-                            let stats_json = serde_json::json!({
-                                "stats": {
-                                    "username": stats.name,
-                                    "gamesPlayed": stats.games_played,
-                                    "gamesWon": stats.games_won,
-                                    "wallet": stats.wallet,
-                                    "winRate": if stats.games_played > 0 {
-                                        format!("{}%", (stats.games_won as f64 / stats.games_played as f64) * 100.0)
-                                    } else {
-                                        "N/A".to_string()
-                                    }
-                                }
-                            });
-                            tx.send(Message::text(stats_json.to_string())).unwrap();
+                            protocol::send(&tx, &ServerMessage::Stats { stats: Stats::new(&stats) }).unwrap();
                         } else {
                             println!("Error retrieving stats for {}: {:?}", player_name, stats);
-                            tx.send(Message::text(r#"{"error": "Failed to retrieve stats"}"#)).unwrap();
+                            protocol::send(&tx, &ServerMessage::error("Failed to retrieve stats")).unwrap();
                         }
                     }
                     _ => {
@@ -16,6 +16,7 @@ pub const IN_SERVER: i32 = 6;
 pub const IN_GAME: i32 = 7;
 pub const LOGGING_IN: i32 = 8;
 pub const SPECTATOR: i32 = 9;
+pub const SITTING_OUT: i32 = 10;
 
 
 
@@ -58,10 +59,17 @@ impl Player {
                         lobby_guard.add_spectator(self.clone()).await;
                         self.lobby = lobby.clone();
                         return SUCCESS;
+                    } else if let Some(table) = lobby_guard.mtt.as_ref() {
+                        // a tournament table only seats the players the tournament put at it, even mid-hand
+                        if !table.seated.contains(&self.name) {
+                            return FAILED;
+                        }
+                        lobby_guard.add_player(self.clone()).await;
+                        self.lobby = lobby.clone();
+                        return SUCCESS;
                     } else {
-                        // Check if game is in progress
-                        if lobby_guard.game_state >= START_OF_ROUND && 
-                           lobby_guard.game_state <= END_OF_ROUND {
+                        // Check if game is in progress, or the lobby is a tournament that has started
+                        if lobby_guard.hand_in_progress || lobby_guard.tournament.as_ref().is_some_and(|t| t.started()) {
                             // Can't join as player during game
                             return FAILED;
                         }
@@ -0,0 +1,334 @@
+//! The messages the server sends to its clients.
+//!
+//! Clients send `ClientMessage`s and get `ServerMessage`s back, each a JSON object whose `type` names the
+//! message. The other keys are the ones the bundled pages have always read: a `notice` still has the `message`
+//! to show, a `lobbyInfo` message still holds the lobby under `lobbyInfo`, and so on. Nothing is sent as plain
+//! text or as hand-built JSON; every `tx.send` goes through `send` or a lobby broadcast of a `ServerMessage`.
+//!
+//! The first message on every connection is `welcome`, with the `PROTOCOL_VERSION`. The version goes up when a
+//! message changes in a way a client written for the old one would misread, not when a message or an optional
+//! key is added. `schema` generates the JSON Schema of every message, which the server serves at
+//! `/protocol/schema.json` and prints with `cargo run -- schema`, for third-party clients to validate against.
+use crate::database::PlayerStats;
+use crate::games::{HandValue, LowHand};
+use crate::history::HandRecord;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tokio::sync::mpsc::{error::SendError, UnboundedSender};
+use warp::ws::Message;
+
+pub const PROTOCOL_VERSION: i32 = 1;
+
+/// Everything the server tells a client.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ServerMessage {
+    /// The first message on every connection.
+    #[serde(rename_all = "camelCase")]
+    Welcome { message: String, protocol_version: i32 },
+    /// Something to show the player: what happened at the table or the answer to a request.
+    Notice { message: String },
+    /// A request that was refused and why.
+    Error { error: String },
+    /// The client is to open another page: `index`, `server_lobby` or `lobby`.
+    Redirect { message: String, redirect: String },
+    /// The player logged in, registered or reconnected. The token lets them reconnect after a dropped connection.
+    #[serde(rename_all = "camelCase")]
+    LoggedIn { message: String, redirect: String, session_token: String },
+    /// The session token sent to reconnect is no longer valid, the player has to log in again.
+    #[serde(rename_all = "camelCase")]
+    SessionExpired { message: String, session_expired: bool },
+    /// Players connected to the server lobby.
+    #[serde(rename_all = "camelCase")]
+    PlayerCount { player_count: i32 },
+    Lobbies { lobbies: Vec<LobbySummary> },
+    Tournaments { tournaments: Vec<TournamentSummary> },
+    /// A multi-table tournament has started and seated the player at `tournamentTable`.
+    #[serde(rename_all = "camelCase")]
+    TournamentTable { message: String, tournament_table: String, game_type: String },
+    /// A tournament is over, with the prize paid for every paid place.
+    #[serde(rename_all = "camelCase")]
+    TournamentResults { message: String, tournament_results: Vec<Prize> },
+    #[serde(rename_all = "camelCase")]
+    LobbyInfo { lobby_info: LobbyInfo },
+    /// The hand in progress.
+    #[serde(rename_all = "camelCase")]
+    GameInfo { game_info: GameInfo },
+    /// The people in a game lobby, with the cards the receiver may see.
+    Players { players: Vec<PlayerView>, spectators: Vec<Spectator> },
+    Stats { stats: Stats },
+    /// The player's latest hands, newest first, as stored in the hand history.
+    #[serde(rename_all = "camelCase")]
+    RecentHands { recent_hands: Vec<HandRecord> },
+    /// It is the player's turn to draw.
+    #[serde(rename_all = "camelCase")]
+    DrawPrompt { message: String, action: String, your_turn: bool, game_state: i32 },
+    /// A player at the table lost their connection, their seat is held for a while.
+    #[serde(rename_all = "camelCase")]
+    PlayerDisconnected { message: String, player_disconnected: DisconnectedPlayer },
+    /// The hands shown down and the pots they won.
+    Showdown { data: Showdown },
+}
+
+/// A game lobby as listed in the server lobby.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LobbySummary {
+    pub name: String,
+    pub status: String, // "Joinable" or "Not Joinable"
+    #[serde(rename = "type")]
+    pub game_type: String,
+    pub player_count: i32,
+    pub max_players: i32,
+    pub stakes: String,
+}
+
+/// A multi-table tournament as listed in the server lobby.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentSummary {
+    pub name: String,
+    pub host: String,
+    #[serde(rename = "type")]
+    pub game_type: String,
+    pub buy_in: i32,
+    pub starting_stack: i32,
+    pub prize_pool: i32,
+    pub entrants: Vec<String>,
+    pub remaining: usize,
+    pub tables: Vec<TournamentTable>,
+    pub level: usize,
+    pub hand_for_hand: bool,
+    pub status: String, // "Registering", "Running" or "Finished"
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct TournamentTable {
+    pub name: String,
+    pub players: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct Prize {
+    pub place: usize,
+    pub name: String,
+    pub prize: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LobbyInfo {
+    pub name: String,
+    pub game_type: String,
+    pub player_count: i32,
+    pub max_players: i32,
+    pub call_amount: i32,
+    pub betting_structure: String,
+    pub stakes: String,
+    pub min_buy_in: i32,
+    pub max_buy_in: i32,
+    pub tournament: Option<LobbyTournament>, // null at a cash table
+}
+
+/// The tournament a game lobby plays.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum LobbyTournament {
+    #[serde(rename_all = "camelCase")]
+    SitAndGo {
+        buy_in: i32,
+        starting_stack: i32,
+        prize_pool: i32,
+        payouts: Vec<i32>,
+        level: usize,
+        level_minutes: u64,
+        entrants: usize,
+        remaining: usize,
+        started: bool,
+        finished: bool,
+    },
+    /// A table of the multi-table tournament `name`.
+    MultiTable { name: String, table: usize },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GameInfo {
+    pub game_state: i32,
+    pub pot: i32,
+    pub current_max_bet: i32,
+    pub community_cards: Vec<i32>,
+    pub current_player_turn: String,
+    pub call_amount: i32,
+    pub min_raise: i32,
+    pub max_raise: i32,
+    pub betting_structure: String,
+    pub action_time: u64,    // seconds per action
+    pub time_remaining: u64, // seconds left on the clock of the player to act
+    pub time_bank: u64,
+}
+
+/// A player in a game lobby. `hand` only holds the cards the receiver may see, hidden ones are `HIDDEN_CARD`.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct PlayerView {
+    pub name: String,
+    pub ready: bool,
+    pub hand: Vec<i32>,
+    pub state: i32,
+    pub wallet: i32,
+    pub chips: i32, // the same as `wallet`, read by older pages
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct Spectator {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub username: String,
+    pub games_played: i32,
+    pub games_won: i32,
+    pub wallet: i32,
+    pub win_rate: String, // a percentage, or "N/A" before the first game
+}
+
+impl Stats {
+    pub fn new(stats: &PlayerStats) -> Self {
+        let win_rate = if stats.games_played > 0 {
+            format!("{}%", (stats.games_won as f64 / stats.games_played as f64) * 100.0)
+        } else {
+            "N/A".to_string()
+        };
+        Stats {
+            username: stats.name.clone(),
+            games_played: stats.games_played,
+            games_won: stats.games_won,
+            wallet: stats.wallet,
+            win_rate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct DisconnectedPlayer {
+    pub name: String,
+    pub state: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Showdown {
+    pub hands: Vec<ShownHand>,
+    pub community_cards: Vec<i32>,
+    pub hi_lo: bool,
+    pub pot: i32,
+    pub pots: Vec<PotResult>,
+    pub winner_message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ShownHand {
+    pub player_name: String,
+    pub hand: Vec<i32>,
+    pub hand_rank: HandValue,
+    pub hand_name: String,
+    pub low_hand: Option<LowHand>, // the qualifying low in hi/lo games
+    pub low_name: Option<String>,
+    pub winner: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PotResult {
+    pub name: String, // "Main pot", "Side pot 1", ...
+    pub amount: i32,
+    pub eligible: Vec<String>,
+    pub winners: Vec<String>,
+    pub low_winners: Vec<String>,
+}
+
+impl ServerMessage {
+    pub fn notice(message: impl Into<String>) -> Self {
+        ServerMessage::Notice { message: message.into() }
+    }
+
+    pub fn error(error: impl Into<String>) -> Self {
+        ServerMessage::Error { error: error.into() }
+    }
+
+    pub fn redirect(message: impl Into<String>, redirect: &str) -> Self {
+        ServerMessage::Redirect { message: message.into(), redirect: redirect.to_string() }
+    }
+
+    /// The WebSocket message carrying this message as JSON.
+    pub fn to_ws(&self) -> Message {
+        Message::text(serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+/// Sends a message to one client.
+///
+/// # Returns
+/// * `Err(SendError)` - The client's connection is closed.
+pub fn send(tx: &UnboundedSender<Message>, message: &ServerMessage) -> Result<(), SendError<Message>> {
+    tx.send(message.to_ws())
+}
+
+/// The JSON Schema of `ServerMessage`.
+pub fn schema() -> String {
+    serde_json::to_string_pretty(&schemars::schema_for!(ServerMessage)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(message: &ServerMessage) -> serde_json::Value {
+        serde_json::to_value(message).unwrap()
+    }
+
+    #[test]
+    fn test_messages_keep_their_keys() {
+        assert_eq!(
+            json(&ServerMessage::notice("Betting Round")),
+            serde_json::json!({ "type": "notice", "message": "Betting Round" })
+        );
+        let welcome = ServerMessage::Welcome { message: "Welcome to Poker!".to_string(), protocol_version: PROTOCOL_VERSION };
+        assert_eq!(json(&welcome)["protocolVersion"], PROTOCOL_VERSION);
+        let lobbies = ServerMessage::Lobbies {
+            lobbies: vec![LobbySummary {
+                name: "table".to_string(),
+                status: "Joinable".to_string(),
+                game_type: "Razz".to_string(),
+                player_count: 1,
+                max_players: 7,
+                stakes: "10/15".to_string(),
+            }],
+        };
+        // the lobby's `type` is its game, the message's is `lobbies`
+        assert_eq!(json(&lobbies)["type"], "lobbies");
+        assert_eq!(json(&lobbies)["lobbies"][0]["type"], "Razz");
+        assert_eq!(json(&lobbies)["lobbies"][0]["maxPlayers"], 7);
+        let table = LobbyTournament::MultiTable { name: "Sunday".to_string(), table: 2 };
+        assert_eq!(serde_json::to_value(&table).unwrap(), serde_json::json!({ "name": "Sunday", "table": 2 }));
+    }
+
+    #[test]
+    fn test_schema_covers_every_message() {
+        let schema: serde_json::Value = serde_json::from_str(&schema()).unwrap();
+        let types: Vec<&str> = schema["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|variant| variant["properties"]["type"]["enum"][0].as_str())
+            .collect();
+        assert_eq!(types.len(), 19);
+        for message_type in ["welcome", "notice", "error", "lobbyInfo", "gameInfo", "players", "showdown"] {
+            assert!(types.contains(&message_type), "{} is missing from the schema", message_type);
+        }
+        assert!(schema["definitions"]["GameInfo"]["required"].as_array().unwrap().contains(&"timeBank".into()));
+    }
+}
@@ -0,0 +1,141 @@
+//! Session tokens handed out at login.
+//!
+//! A token identifies a player without asking for their credentials again, so a client whose WebSocket
+//! dropped can open a new connection, send `Reconnect` with the token and pick up where they left off.
+//! Each player has at most one token: logging in again replaces it. A token is only good once the connection
+//! it was issued to has closed, and for `RECONNECT_GRACE_SECS` after that, so it never opens a second session
+//! next to a live one. Whether there is still a seat to go back to is up to the table of the lobby the player
+//! was in, which holds it for as long.
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How long a table keeps the seat of a player whose connection dropped in the middle of a hand.
+pub const RECONNECT_GRACE_SECS: u64 = 60;
+
+/// Why a token cannot be used to reconnect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionError {
+    /// The token is unknown, was revoked or its grace period is over.
+    Expired,
+    /// The player is still connected with it.
+    InUse,
+}
+
+struct Session {
+    player_name: String,
+    expires: Option<Instant>, // the end of the grace period after the connection closed, `None` while it is open
+}
+
+#[derive(Clone, Default)]
+pub struct Sessions {
+    tokens: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issues a new token for a player who just logged in, revoking any token they had before.
+    ///
+    /// # Arguments
+    /// * `player_name` - The player who logged in.
+    ///
+    /// # Returns
+    ///
+    /// The token to send back to the client.
+    pub async fn create(&self, player_name: &str) -> String {
+        let token = Uuid::new_v4().to_string();
+        let mut tokens = self.tokens.lock().await;
+        tokens.retain(|_, session| session.player_name != player_name);
+        tokens.insert(token.clone(), Session { player_name: player_name.to_string(), expires: None });
+        token
+    }
+
+    /// Starts the grace period of a player's token once their connection has closed.
+    pub async fn disconnected(&self, player_name: &str) {
+        let mut tokens = self.tokens.lock().await;
+        if let Some(session) = tokens.values_mut().find(|session| session.player_name == player_name) {
+            session.expires = Some(Instant::now() + Duration::from_secs(RECONNECT_GRACE_SECS));
+        }
+    }
+
+    /// Takes up a token on a new connection. Until that connection closes the token cannot be used again.
+    ///
+    /// # Arguments
+    /// * `token` - The token the client sent with `Reconnect`.
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The player the token was issued to.
+    /// * `Err(SessionError)` - The token has expired or is still in use.
+    pub async fn reconnect(&self, token: &str) -> Result<String, SessionError> {
+        let mut tokens = self.tokens.lock().await;
+        let session = tokens.get_mut(token).ok_or(SessionError::Expired)?;
+        match session.expires {
+            None => Err(SessionError::InUse),
+            Some(expires) if expires <= Instant::now() => {
+                tokens.remove(token);
+                Err(SessionError::Expired)
+            }
+            Some(_) => {
+                session.expires = None;
+                Ok(session.player_name.clone())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_token_identifies_player() {
+        let sessions = Sessions::new();
+        let token = sessions.create("alice").await;
+        sessions.disconnected("alice").await;
+        assert_eq!(sessions.reconnect(&token).await, Ok("alice".to_string()));
+        assert_eq!(sessions.reconnect("not a token").await, Err(SessionError::Expired));
+    }
+
+    #[tokio::test]
+    async fn test_new_login_revokes_old_token() {
+        let sessions = Sessions::new();
+        let old_token = sessions.create("alice").await;
+        let bob_token = sessions.create("bob").await;
+        let new_token = sessions.create("alice").await;
+        assert_ne!(old_token, new_token);
+        sessions.disconnected("alice").await;
+        sessions.disconnected("bob").await;
+        assert_eq!(sessions.reconnect(&old_token).await, Err(SessionError::Expired));
+        assert_eq!(sessions.reconnect(&new_token).await, Ok("alice".to_string()));
+        assert_eq!(sessions.reconnect(&bob_token).await, Ok("bob".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_token_expires_after_grace_period() {
+        let sessions = Sessions::new();
+        let token = sessions.create("alice").await;
+        sessions.disconnected("alice").await;
+        // as it is `RECONNECT_GRACE_SECS` later
+        sessions.tokens.lock().await.get_mut(&token).unwrap().expires = Some(Instant::now());
+        assert_eq!(sessions.reconnect(&token).await, Err(SessionError::Expired));
+        // an expired token is gone for good
+        assert!(sessions.tokens.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_token_cannot_be_used_twice_at_once() {
+        let sessions = Sessions::new();
+        let token = sessions.create("alice").await;
+        // the connection that logged in is still open
+        assert_eq!(sessions.reconnect(&token).await, Err(SessionError::InUse));
+        sessions.disconnected("alice").await;
+        assert_eq!(sessions.reconnect(&token).await, Ok("alice".to_string()));
+        // the reconnected session is live again
+        assert_eq!(sessions.reconnect(&token).await, Err(SessionError::InUse));
+    }
+}
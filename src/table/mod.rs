@@ -0,0 +1,675 @@
+//! One task per game lobby that owns the hand being played at it.
+//!
+//! Player connections never touch the engine. They send `TableCommand`s over the table's channel and the
+//! table task applies them one at a time, in the order they arrived, then broadcasts the resulting events to
+//! everyone in the lobby through their WebSocket senders. Nothing polls: the table only wakes up when a
+//! command arrives or the player to act runs out of time, and the lobby mutex is only held while the engine
+//! is mirrored onto the lobby and the messages are sent.
+//!
+//! Every turn runs against the lobby's action clock. Once it runs out the player's time bank, if the lobby
+//! gives one, is used up, then the table plays `Engine::timeout_action` for them. Players who time out
+//! `MAX_TIMEOUTS` turns in a row are sat out until they ask to start a hand again.
+//!
+//! A player whose connection drops in the middle of a hand keeps their seat for `RECONNECT_GRACE_SECS`.
+//! Their turns keep running against the action clock, and once the hand is over they sit out. If they
+//! reconnect in time their new connection takes over the seat, otherwise they leave the lobby.
+//!
+//! The tables of a multi-table tournament do not wait for players to ask to start. Each settled hand is handed
+//! to the tournament, which sends `TableCommand::Deal` once the table is to deal its next one.
+//!
+//! In audit mode the table checks after every action, and at the showdown, that the stacks and the pot still add
+//! up to the chips the hand started with. When they do not, the table panics with the whole hand rather than play
+//! on with the wrong stacks. Debug builds always audit, release builds when `AUDIT_CHIPS_VAR` is set.
+use crate::engine::{Action, Engine, EngineError, Event};
+use crate::games;
+use crate::lobby::Lobby;
+use crate::player::Player;
+use crate::protocol::{self, DisconnectedPlayer, ServerMessage};
+use crate::session::RECONNECT_GRACE_SECS;
+use crate::tournament::mtt;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::time::{self, Duration, Instant};
+
+/// Consecutive timeouts after which a player is sat out.
+pub const MAX_TIMEOUTS: i32 = 2;
+
+/// Environment variable that turns on the chip audit in a release build.
+pub const AUDIT_CHIPS_VAR: &str = "POKER_AUDIT_CHIPS";
+
+/// A request from a player connection to the table.
+pub enum TableCommand {
+    /// The player asked to start; the hand is dealt once everyone in the lobby has.
+    StartGame { player: String },
+    /// A betting or drawing action.
+    Act { player: String, action: Action },
+    /// The player's connection closed. Their seat is held if they are in the hand, otherwise they leave the lobby.
+    Disconnect { player: String },
+    /// A new connection for a player whose seat is held. The reply says whether they got it back.
+    Reconnect { player: Player, reply: oneshot::Sender<bool> },
+    /// Deal the next hand of a multi-table tournament table.
+    Deal,
+}
+
+pub type TableSender = mpsc::UnboundedSender<TableCommand>;
+
+/// The clock of the turn in progress.
+struct Turn {
+    player: String,
+    started: Instant,
+    deadline: Instant,
+}
+
+/// What a player has left of their time bank and how many turns in a row they timed out.
+struct Clock {
+    time_bank: Duration,
+    timeouts: i32,
+}
+
+struct Table {
+    server_lobby: Arc<Mutex<Lobby>>,
+    lobby: Weak<Mutex<Lobby>>, // weak so the lobby can be dropped, which closes the channel and ends the task
+    commands: mpsc::UnboundedReceiver<TableCommand>,
+    engine: Option<Engine>,
+    turn: Option<Turn>,
+    clocks: HashMap<String, Clock>,
+    held: HashMap<String, Instant>, // seats of disconnected players and when they are given up
+    audit_chips: bool,
+}
+
+/// Starts the task that runs the hands of a game lobby.
+///
+/// # Arguments
+/// * `server_lobby` - The server lobby, told about status changes and players leaving.
+/// * `lobby` - The game lobby the table belongs to.
+///
+/// # Returns
+///
+/// The sender player connections use to reach the table. The task stops once every sender is dropped.
+pub fn spawn_table(server_lobby: Arc<Mutex<Lobby>>, lobby: &Arc<Mutex<Lobby>>) -> TableSender {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let table = Table {
+        server_lobby,
+        lobby: Arc::downgrade(lobby),
+        commands: rx,
+        engine: None,
+        turn: None,
+        clocks: HashMap::new(),
+        held: HashMap::new(),
+        audit_chips: cfg!(debug_assertions) || std::env::var_os(AUDIT_CHIPS_VAR).is_some(),
+    };
+    tokio::spawn(table.run());
+    tx
+}
+
+impl Table {
+    async fn run(mut self) {
+        loop {
+            let deadline = self.turn.as_ref().map(|turn| turn.deadline).into_iter().chain(self.held.values().copied()).min();
+            // `None` when the player to act ran out of time or a held seat expired
+            let command = tokio::select! {
+                command = self.commands.recv() => match command {
+                    Some(command) => Some(command),
+                    None => break,
+                },
+                _ = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => None,
+            };
+            let lobby = match self.lobby.upgrade() {
+                Some(lobby) => lobby,
+                None => break,
+            };
+            let mut events = self.handle(&lobby, command).await;
+            // a Sit-and-Go deals the next hand as soon as one is settled
+            while events.iter().any(|event| matches!(event, Event::HandFinished { .. })) {
+                events = self.finish_hand(&lobby).await;
+            }
+        }
+    }
+
+    /// Handles a command, or the clock running out when `command` is `None`.
+    async fn handle(&mut self, lobby: &Arc<Mutex<Lobby>>, command: Option<TableCommand>) -> Vec<Event> {
+        match command {
+            Some(TableCommand::StartGame { player }) => self.start_game(lobby, &player).await,
+            Some(TableCommand::Act { player, action }) => self.act(lobby, &player, action).await,
+            Some(TableCommand::Disconnect { player }) => self.disconnect(lobby, &player).await,
+            Some(TableCommand::Reconnect { player, reply }) => {
+                let _ = reply.send(self.reconnect(lobby, player).await);
+                Vec::new()
+            }
+            Some(TableCommand::Deal) => self.deal_tournament_hand(lobby).await,
+            None => self.wake_up(lobby).await,
+        }
+    }
+
+    /// Counts the player's request to start and deals the hand once everyone has asked.
+    /// A player who was sitting out is dealt back in.
+    async fn start_game(&mut self, lobby: &Arc<Mutex<Lobby>>, player_name: &str) -> Vec<Event> {
+        println!("player: {}, received start game", player_name);
+        if lobby.lock().await.sit_in(player_name).await {
+            if let Some(clock) = self.clocks.get_mut(player_name) {
+                clock.timeouts = 0;
+            }
+        }
+        if self.engine.is_some() {
+            return Vec::new();
+        }
+        {
+            let mut lobby_guard = lobby.lock().await;
+            // the tournament deals the hands of its tables
+            if lobby_guard.mtt.is_some() {
+                return Vec::new();
+            }
+            lobby_guard.turns_remaining -= 1;
+            println!("turns remaining: {}", lobby_guard.turns_remaining);
+            if lobby_guard.turns_remaining > 0 {
+                return Vec::new();
+            }
+            if lobby_guard.tournament.as_ref().is_some_and(|tournament| tournament.entrants.len() < 2) {
+                lobby_guard.broadcast("The tournament needs at least two players to start.".to_string()).await;
+                return Vec::new();
+            }
+        }
+        self.deal(lobby).await
+    }
+
+    /// Sets up and deals a new hand.
+    async fn deal(&mut self, lobby: &Arc<Mutex<Lobby>>) -> Vec<Event> {
+        let (events, lobby_name) = {
+            let mut lobby_guard = lobby.lock().await;
+            let mut engine = lobby_guard.setup_game().await;
+            let events = engine.start_hand();
+            self.engine = Some(engine);
+            self.update_clock(&mut lobby_guard, &events).await;
+            games::broadcast_events(&lobby_guard, &events).await;
+            (events, lobby_guard.name.clone())
+        };
+        self.server_lobby.lock().await.update_lobby_names_status(lobby_name).await;
+        events
+    }
+
+    /// Deals the next hand at a table of a multi-table tournament. With fewer than two of its players
+    /// there the table is handed back to the tournament straight away, which knocks out those missing.
+    async fn deal_tournament_hand(&mut self, lobby: &Arc<Mutex<Lobby>>) -> Vec<Event> {
+        if self.engine.is_some() {
+            return Vec::new();
+        }
+        let short = {
+            let lobby_guard = lobby.lock().await;
+            match lobby_guard.mtt.as_ref() {
+                Some(table) => {
+                    let present = lobby_guard.players.lock().await.iter().filter(|p| table.seated.contains(&p.name)).count();
+                    (present < 2).then(|| (table.tournament.clone(), table.number))
+                }
+                None => None,
+            }
+        };
+        if let Some((tournament, number)) = short {
+            mtt::hand_finished(&tournament, &self.server_lobby, number, Vec::new()).await;
+            return Vec::new();
+        }
+        self.deal(lobby).await
+    }
+
+    /// Applies a player's action. A refused action is reported to that player only.
+    async fn act(&mut self, lobby: &Arc<Mutex<Lobby>>, player_name: &str, action: Action) -> Vec<Event> {
+        let result = match self.engine.as_mut() {
+            Some(engine) => engine.apply(player_name, action),
+            None => Err(EngineError::HandOver),
+        };
+        let mut lobby_guard = lobby.lock().await;
+        match result {
+            Ok(events) => {
+                if let Some(clock) = self.clocks.get_mut(player_name) {
+                    clock.timeouts = 0;
+                }
+                self.update_clock(&mut lobby_guard, &events).await;
+                games::broadcast_events(&lobby_guard, &events).await;
+                events
+            }
+            Err(e) => {
+                println!("{} sent an action that was refused: {}", player_name, e);
+                if let Some(player) = lobby_guard.get_player_by_name(player_name).await {
+                    let _ = protocol::send(&player.tx, &ServerMessage::error(e.to_string()));
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    /// Handles a player whose connection closed. In the middle of a hand their seat is held
+    /// for `RECONNECT_GRACE_SECS`, otherwise they leave the lobby straight away.
+    async fn disconnect(&mut self, lobby: &Arc<Mutex<Lobby>>, player_name: &str) -> Vec<Event> {
+        let in_hand = self.engine.as_ref().is_some_and(|engine| engine.seat_index(player_name).is_some());
+        if !in_hand {
+            return self.leave(lobby, player_name).await;
+        }
+        self.held.insert(player_name.to_string(), Instant::now() + Duration::from_secs(RECONNECT_GRACE_SECS));
+
+        let lobby_guard = lobby.lock().await;
+        // Mark player as disconnected for UI display
+        let state = {
+            let mut players = lobby_guard.players.lock().await;
+            match players.iter_mut().find(|p| p.name == player_name) {
+                Some(p) => {
+                    p.disconnected = true;
+                    p.state
+                }
+                None => return Vec::new(),
+            }
+        };
+
+        // Notify other players
+        let disconnect_msg = ServerMessage::PlayerDisconnected {
+            message: format!("{} has disconnected. Their seat is held for {} seconds.", player_name, RECONNECT_GRACE_SECS),
+            player_disconnected: DisconnectedPlayer { name: player_name.to_string(), state },
+        };
+        lobby_guard.broadcast_message(&disconnect_msg).await;
+        lobby_guard.send_player_list().await;
+        Vec::new()
+    }
+
+    /// Gives a held seat to the player's new connection.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the seat is no longer held, e.g. because the grace period ran out.
+    async fn reconnect(&mut self, lobby: &Arc<Mutex<Lobby>>, player: Player) -> bool {
+        if self.held.remove(&player.name).is_none() {
+            return false;
+        }
+        let mut lobby_guard = lobby.lock().await;
+        {
+            let mut players = lobby_guard.players.lock().await;
+            if let Some(p) = players.iter_mut().find(|p| p.name == player.name) {
+                p.tx = player.tx.clone();
+                p.rx = player.rx.clone();
+                p.disconnected = false;
+            }
+        }
+        // a player sat out while they were away is dealt in again
+        if lobby_guard.sit_in(&player.name).await {
+            if let Some(clock) = self.clocks.get_mut(&player.name) {
+                clock.timeouts = 0;
+            }
+        }
+        lobby_guard.broadcast(format!("{} has reconnected.", player.name)).await;
+        true
+    }
+
+    /// Plays the timeout action if the player to act ran out of time and gives up the
+    /// seats of disconnected players who did not come back in time.
+    async fn wake_up(&mut self, lobby: &Arc<Mutex<Lobby>>) -> Vec<Event> {
+        let now = Instant::now();
+        let mut events = Vec::new();
+        if self.turn.as_ref().is_some_and(|turn| turn.deadline <= now) {
+            events.extend(self.time_out(lobby).await);
+        }
+        let expired: Vec<String> = self
+            .held
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for player_name in expired {
+            self.held.remove(&player_name);
+            lobby.lock().await.broadcast(format!("{} did not reconnect in time.", player_name)).await;
+            events.extend(self.leave(lobby, &player_name).await);
+        }
+        events
+    }
+
+    /// Removes a player from the table. In the middle of a hand they are folded and keep
+    /// their seat until the hand is settled, otherwise they leave the lobby straight away.
+    async fn leave(&mut self, lobby: &Arc<Mutex<Lobby>>, player_name: &str) -> Vec<Event> {
+        let engine = match self.engine.as_mut() {
+            Some(engine) if engine.seat_index(player_name).is_some() => engine,
+            _ => {
+                self.clocks.remove(player_name);
+                let lobby_name = lobby.lock().await.name.clone();
+                games::leave_lobby(&self.server_lobby, lobby, player_name, &lobby_name).await;
+                return Vec::new();
+            }
+        };
+        let events = engine.fold_player(player_name);
+        self.clocks.remove(player_name);
+
+        let mut lobby_guard = lobby.lock().await;
+        if !lobby_guard.to_be_deleted.iter().any(|name| name == player_name) {
+            lobby_guard.to_be_deleted.push(player_name.to_string());
+        }
+        self.update_clock(&mut lobby_guard, &events).await;
+        games::broadcast_events(&lobby_guard, &events).await;
+        events
+    }
+
+    /// Plays the timeout action for the player whose clock ran out. After `MAX_TIMEOUTS`
+    /// timeouts in a row they are folded and sat out.
+    async fn time_out(&mut self, lobby: &Arc<Mutex<Lobby>>) -> Vec<Event> {
+        let (engine, player_name) = match (self.engine.as_mut(), self.turn.take()) {
+            (Some(engine), Some(turn)) => (engine, turn.player),
+            _ => return Vec::new(),
+        };
+        println!("{} ran out of time", player_name);
+        let action = engine.timeout_action();
+        let mut events = engine.apply(&player_name, action).unwrap_or_default();
+        let clock = self.clocks.entry(player_name.clone()).or_insert(Clock { time_bank: Duration::ZERO, timeouts: 0 });
+        clock.time_bank = Duration::ZERO;
+        clock.timeouts += 1;
+        let sat_out = clock.timeouts >= MAX_TIMEOUTS;
+        if sat_out {
+            events.extend(engine.fold_player(&player_name));
+        }
+
+        let mut lobby_guard = lobby.lock().await;
+        lobby_guard.broadcast(format!("{} ran out of time.", player_name)).await;
+        if sat_out {
+            lobby_guard.sit_out(&player_name).await;
+        }
+        self.update_clock(&mut lobby_guard, &events).await;
+        games::broadcast_events(&lobby_guard, &events).await;
+        events
+    }
+
+    /// Mirrors the engine onto the lobby and restarts the action clock when the turn moved.
+    /// The time the last player spent over the action time comes out of their time bank.
+    async fn update_clock(&mut self, lobby: &mut Lobby, events: &[Event]) {
+        let engine = match self.engine.as_ref() {
+            Some(engine) => engine,
+            None => return,
+        };
+        if self.audit_chips {
+            if let Err(report) = engine.check_chips() {
+                panic!("Chips are off at {}: {}", lobby.name, report);
+            }
+        }
+        lobby.sync_from_engine(engine).await;
+        let current_player = engine.current_player_name();
+        let turn_changed = events.iter().any(|event| matches!(event, Event::TurnChanged { .. }));
+        let same_turn = self.turn.as_ref().is_some_and(|turn| Some(turn.player.as_str()) == current_player);
+        if same_turn && !turn_changed {
+            return;
+        }
+
+        let now = Instant::now();
+        let action_time = Duration::from_secs(lobby.action_time);
+        if let Some(turn) = self.turn.take() {
+            if let Some(clock) = self.clocks.get_mut(&turn.player) {
+                let over = now.duration_since(turn.started).saturating_sub(action_time);
+                clock.time_bank = clock.time_bank.saturating_sub(over);
+            }
+        }
+        lobby.turn_deadline = None;
+        lobby.turn_time_bank = 0;
+        if let Some(player_name) = current_player {
+            let time_bank = Duration::from_secs(lobby.time_bank);
+            let clock = self
+                .clocks
+                .entry(player_name.to_string())
+                .or_insert(Clock { time_bank, timeouts: 0 });
+            let deadline = now + action_time + clock.time_bank;
+            lobby.turn_deadline = Some(deadline);
+            lobby.turn_time_bank = clock.time_bank.as_secs();
+            self.turn = Some(Turn { player: player_name.to_string(), started: now, deadline });
+        }
+    }
+
+    /// Gives everyone a few seconds to look at the showdown, then settles the hand,
+    /// saves the results and opens the lobby for the next hand.
+    ///
+    /// # Returns
+    ///
+    /// The events of the next hand when a Sit-and-Go deals it straight away.
+    async fn finish_hand(&mut self, lobby: &Arc<Mutex<Lobby>>) -> Vec<Event> {
+        let engine = match self.engine.take() {
+            Some(engine) => engine,
+            None => return Vec::new(),
+        };
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        let (lobby_name, lobby_empty, tournament_running, mtt_table) = {
+            let mut lobby_guard = lobby.lock().await;
+            // players still away are not dealt into the next hand
+            for player_name in self.held.keys() {
+                lobby_guard.sit_out(player_name).await;
+            }
+            lobby_guard.finish_hand(&engine).await;
+            lobby_guard.broadcast("Game Ended".to_string()).await;
+            lobby_guard.send_lobby_game_info().await;
+            lobby_guard.send_player_list().await;
+            println!("finished_game completed");
+            let mtt_table = lobby_guard.mtt.as_ref().map(|table| (table.tournament.clone(), table.number));
+            (lobby_guard.name.clone(), lobby_guard.current_player_count == 0, lobby_guard.tournament_running(), mtt_table)
+        };
+        if let Some((tournament, number)) = mtt_table {
+            // the tournament knocks out, moves and deals, and closes the table once it is broken
+            self.server_lobby.lock().await.update_lobby_names_status(lobby_name).await;
+            mtt::hand_finished(&tournament, &self.server_lobby, number, engine.busted()).await;
+            return Vec::new();
+        }
+        if lobby_empty {
+            // everyone disconnected during the hand
+            self.server_lobby.lock().await.remove_lobby(lobby_name).await;
+            return Vec::new();
+        }
+        self.server_lobby.lock().await.update_lobby_names_status(lobby_name).await;
+        if tournament_running {
+            return self.deal(lobby).await;
+        }
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+    use crate::lobby::{self, max_players, FIXED_LIMIT, NO_LIMIT, POT_LIMIT};
+    use crate::player;
+    use futures_util::StreamExt;
+    use rand::Rng;
+    use sqlx::SqlitePool;
+    use warp::test::WsClient;
+    use warp::ws::Message;
+    use warp::Filter;
+
+    type Connection = (WsClient, mpsc::UnboundedReceiver<Message>);
+
+    /// Seats a player at the lobby on a websocket of its own, the way a connection joins.
+    ///
+    /// # Returns
+    ///
+    /// The client end of the socket and what the server sends the player, kept to hold the connection open.
+    async fn join(lobby: &Arc<Mutex<Lobby>>, name: &str) -> Connection {
+        let (socket_tx, socket_rx) = oneshot::channel();
+        let socket_tx = Arc::new(std::sync::Mutex::new(Some(socket_tx)));
+        let route = warp::ws().map(move |ws: warp::ws::Ws| {
+            let socket_tx = socket_tx.clone();
+            ws.on_upgrade(move |socket| async move {
+                if let Some(socket_tx) = socket_tx.lock().unwrap().take() {
+                    let _ = socket_tx.send(socket);
+                }
+            })
+        });
+        let client = warp::test::ws().handshake(route).await.unwrap();
+        let (_, ws_rx) = socket_rx.await.unwrap().split();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let player = Player {
+            name: name.to_string(),
+            id: name.to_string(),
+            hand: Vec::new(),
+            wallet: 0,
+            tx,
+            rx: Arc::new(Mutex::new(ws_rx)),
+            state: player::IN_LOBBY,
+            current_bet: 0,
+            ready: false,
+            games_played: 0,
+            games_won: 0,
+            lobby: lobby.clone(),
+            disconnected: false,
+            played_game: false,
+            won_game: false,
+        };
+        lobby.lock().await.add_player(player).await;
+        (client, rx)
+    }
+
+    fn random_action(engine: &Engine, rng: &mut impl Rng) -> Action {
+        match rng.random_range(0..6) {
+            _ if engine.phase == lobby::DRAW => Action::DrawCards {
+                indices: (0..rng.random_range(0..=5)).map(|_| rng.random_range(0..5)).collect(),
+            },
+            0 => Action::Check,
+            1 | 2 => Action::Call,
+            3 => Action::Raise { amount: rng.random_range(engine.min_raise()..=engine.max_raise().max(engine.min_raise())) },
+            4 => Action::AllIn,
+            _ => Action::Fold,
+        }
+    }
+
+    /// A table of the game with `players` players seated, p0 first, and a database of their accounts in memory.
+    ///
+    /// # Returns
+    ///
+    /// The table, its lobby, the players' names and their connections, which have to be kept open.
+    async fn open_table(game_type: i32, players: i32) -> (Table, Arc<Mutex<Lobby>>, Vec<String>, Vec<Connection>) {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        migrations::run(&pool).await.unwrap();
+        let server_lobby = Arc::new(Mutex::new(Lobby::with_db(lobby::NOT_SET, "Server Lobby".to_string(), pool.clone())));
+        let lobby = Arc::new(Mutex::new(Lobby::with_db(game_type, "Audit".to_string(), pool.clone())));
+        let names: Vec<String> = (0..players).map(|i| format!("p{}", i)).collect();
+        let mut connections = Vec::new();
+        for name in names.iter() {
+            // the hands are saved against the players' accounts
+            sqlx::query("INSERT INTO players (id, name) VALUES (?1, ?1)").bind(name).execute(&pool).await.unwrap();
+            connections.push(join(&lobby, name).await);
+        }
+        let (_commands_tx, commands) = mpsc::unbounded_channel();
+        let table = Table {
+            server_lobby,
+            lobby: Arc::downgrade(&lobby),
+            commands,
+            engine: None,
+            turn: None,
+            clocks: HashMap::new(),
+            held: HashMap::new(),
+            audit_chips: true,
+        };
+        (table, lobby, names, connections)
+    }
+
+    /// Deals hands of random stacks and stakes through the table's command handling. Players act at random,
+    /// send actions the engine refuses and run out of time, and the table audits the chips after every action.
+    /// Once a hand is settled every chip it started with is back in the players' stacks.
+    async fn play_random_hands(game_type: i32, hands: usize) {
+        let (mut table, lobby, names, _connections) = open_table(game_type, max_players(game_type)).await;
+        let mut rng = rand::rng();
+        for _ in 0..hands {
+            let total = {
+                let mut lobby_guard = lobby.lock().await;
+                lobby_guard.betting_structure = [NO_LIMIT, POT_LIMIT, FIXED_LIMIT][rng.random_range(0..3)];
+                lobby_guard.small_blind = rng.random_range(1..=25);
+                lobby_guard.big_blind = 2 * lobby_guard.small_blind;
+                lobby_guard.bring_in = rng.random_range(1..=lobby_guard.big_blind);
+                lobby_guard.ante = rng.random_range(0..=10);
+                // short stacks, often smaller than the forced bets
+                let mut players = lobby_guard.players.lock().await;
+                for player in players.iter_mut() {
+                    player.wallet = rng.random_range(1..=300);
+                }
+                players.iter().map(|player| player.wallet).sum::<i32>()
+            };
+            let mut events = Vec::new();
+            for name in names.iter() {
+                events.extend(table.handle(&lobby, Some(TableCommand::StartGame { player: name.clone() })).await);
+            }
+            while !events.iter().any(|event| matches!(event, Event::HandFinished { .. })) {
+                let engine = table.engine.as_ref().unwrap();
+                let name = engine.current_player_name().unwrap().to_string();
+                events = if rng.random_bool(0.05) {
+                    // the player to act runs out of time
+                    table.turn.as_mut().unwrap().deadline = Instant::now();
+                    table.handle(&lobby, None).await
+                } else {
+                    let action = random_action(engine, &mut rng);
+                    table.handle(&lobby, Some(TableCommand::Act { player: name, action })).await
+                };
+            }
+            let engine = table.engine.take().unwrap();
+            assert_eq!(engine.check_chips(), Ok(()));
+            // settled the way the table does it, less the pause to look at the showdown
+            let mut lobby_guard = lobby.lock().await;
+            lobby_guard.finish_hand(&engine).await;
+            let stacks: i32 = lobby_guard.players.lock().await.iter().map(|player| player.wallet).sum();
+            assert_eq!(stacks, total);
+        }
+    }
+
+    /// Deals a hand at stacks of 100 and returns who acts first.
+    async fn deal_hand(table: &mut Table, lobby: &Arc<Mutex<Lobby>>, names: &[String]) -> String {
+        for name in names.iter() {
+            lobby.lock().await.set_player_wallet(name, 100).await;
+        }
+        for name in names.iter() {
+            table.handle(lobby, Some(TableCommand::StartGame { player: name.clone() })).await;
+        }
+        table.engine.as_ref().unwrap().current_player_name().unwrap().to_string()
+    }
+
+    /// Sends the action for whoever is to act.
+    async fn act_in_turn(table: &mut Table, lobby: &Arc<Mutex<Lobby>>, action: Action) -> Vec<Event> {
+        let player = table.engine.as_ref().unwrap().current_player_name().unwrap().to_string();
+        table.handle(lobby, Some(TableCommand::Act { player, action })).await
+    }
+
+    /// The hidden cards in `owner`'s hand as the other players and spectators see it.
+    async fn hidden_cards(lobby: &Arc<Mutex<Lobby>>, owner: &str) -> usize {
+        let lobby_guard = lobby.lock().await;
+        let players = lobby_guard.players.lock().await;
+        let owner = players.iter().find(|player| player.name == owner).unwrap();
+        lobby_guard.project_hand(owner, None).iter().filter(|&&card| card == lobby::HIDDEN_CARD).count()
+    }
+
+    #[tokio::test]
+    async fn test_only_hands_shown_down_are_revealed() {
+        let (mut table, lobby, names, _connections) = open_table(lobby::SEVEN_CARD_STUD, 3).await;
+        // whoever acts first folds and the other two call it down
+        let folded = deal_hand(&mut table, &lobby, &names).await;
+        let mut events = act_in_turn(&mut table, &lobby, Action::Fold).await;
+        while !events.iter().any(|event| matches!(event, Event::HandFinished { .. })) {
+            events = act_in_turn(&mut table, &lobby, Action::Call).await;
+        }
+        assert_eq!(lobby.lock().await.game_state, lobby::SHOWDOWN);
+        assert_eq!(hidden_cards(&lobby, &folded).await, 2);
+        for name in names.iter().filter(|&name| *name != folded) {
+            assert_eq!(hidden_cards(&lobby, name).await, 0);
+        }
+        let engine = table.engine.take().unwrap();
+        lobby.lock().await.finish_hand(&engine).await;
+
+        // the first two to act fold, so the hand left wins without being shown down
+        deal_hand(&mut table, &lobby, &names).await;
+        act_in_turn(&mut table, &lobby, Action::Fold).await;
+        act_in_turn(&mut table, &lobby, Action::Fold).await;
+        assert!(table.engine.as_ref().unwrap().finished);
+        assert_eq!(lobby.lock().await.game_state, lobby::SHOWDOWN);
+        for name in names.iter() {
+            assert_eq!(hidden_cards(&lobby, name).await, 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_random_five_card_draw_hands_conserve_chips() {
+        play_random_hands(lobby::FIVE_CARD_DRAW, 100).await;
+    }
+
+    #[tokio::test]
+    async fn test_random_seven_card_stud_hands_conserve_chips() {
+        play_random_hands(lobby::SEVEN_CARD_STUD, 100).await;
+    }
+
+    #[tokio::test]
+    async fn test_random_texas_hold_em_hands_conserve_chips() {
+        play_random_hands(lobby::TEXAS_HOLD_EM, 100).await;
+    }
+}
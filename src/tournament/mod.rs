@@ -0,0 +1,246 @@
+//! Sit-and-Go tournaments: a single table that plays until one player has all the chips.
+//!
+//! Players register by joining the lobby before the first hand, which takes the buy-in out of their wallet and
+//! gives them the starting stack instead. From then on their wallet is left alone: the stacks at the table are
+//! tournament chips. The event starts with the first hand, once every registered player has asked to start, and
+//! the table deals one hand after another until only one player is left.
+//!
+//! The blinds and antes go up on a timer. The level is checked when each hand is dealt, so a level that ends
+//! in the middle of a hand starts with the next one. Stud games post the level's small blind as the bring-in,
+//! and draw games, which have no blinds, ante the small blind until the schedule has antes of its own.
+//!
+//! Players who bust are eliminated in the order they went out. Several busting in the same hand finish in the
+//! order of the stacks they started it with. Once the event is over the prize pool, every buy-in, is paid out by
+//! the payout table, a percentage for each finishing place, and credited to the winners' wallets.
+//!
+//! Events too big for one table are run by `mtt`, over several tables with the same `Tournament` rules.
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+pub mod mtt;
+
+pub const DEFAULT_BUY_IN: i32 = 100;
+pub const DEFAULT_STARTING_STACK: i32 = 1500;
+pub const DEFAULT_LEVEL_MINUTES: u64 = 5;
+
+/// The forced bets of one level of the blind schedule.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct Level {
+    pub small_blind: i32,
+    pub big_blind: i32,
+    #[serde(default)]
+    pub ante: i32,
+}
+
+/// The Sit-and-Go settings of a `CreateLobby` message. Anything left out gets the default.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SitAndGoOptions {
+    #[serde(default)]
+    pub buy_in: Option<i32>,
+    #[serde(default)]
+    pub starting_stack: Option<i32>,
+    #[serde(default)]
+    pub level_minutes: Option<u64>,
+    #[serde(default)]
+    pub levels: Option<Vec<Level>>,
+    #[serde(default)]
+    pub payouts: Option<Vec<i32>>, // percent of the prize pool for first place, second place, ...
+}
+
+/// The blind schedule used unless the lobby sets its own, for a starting stack of 1500.
+pub fn default_levels() -> Vec<Level> {
+    [(10, 20, 0), (15, 30, 0), (25, 50, 0), (50, 100, 0), (75, 150, 10), (100, 200, 25), (150, 300, 25),
+     (200, 400, 50), (300, 600, 75), (400, 800, 100), (600, 1200, 150), (1000, 2000, 200)]
+        .iter()
+        .map(|&(small_blind, big_blind, ante)| Level { small_blind, big_blind, ante })
+        .collect()
+}
+
+/// The payout table used unless the lobby sets its own: the winner takes it all with up to three players,
+/// the top two are paid with up to six and the top three with more.
+pub fn default_payouts(seats: i32) -> Vec<i32> {
+    match seats {
+        0..=3 => vec![100],
+        4..=6 => vec![65, 35],
+        _ => vec![50, 30, 20],
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tournament {
+    pub buy_in: i32,
+    pub starting_stack: i32,
+    pub levels: Vec<Level>,
+    pub level_duration: Duration,
+    pub payouts: Vec<i32>,           // percent of the prize pool by finishing place, first place first
+    pub entrants: Vec<String>,       // registered players, in the order they joined
+    pub eliminated: Vec<String>,     // in the order they went out
+    pub started_at: Option<Instant>, // when the first hand was dealt
+    pub finished: bool,
+}
+
+impl Tournament {
+    /// Builds a tournament from the settings of a `CreateLobby` message.
+    ///
+    /// # Arguments
+    /// * `options` - The settings, defaults are used for any left out.
+    /// * `seats` - The most players the lobby seats, which picks the default payout table.
+    ///
+    /// # Returns
+    /// * `Ok(Tournament)` - The tournament, waiting for players to register.
+    /// * `Err(&str)` - What is wrong with the settings.
+    pub fn from_options(options: SitAndGoOptions, seats: i32) -> Result<Self, &'static str> {
+        let tournament = Tournament {
+            buy_in: options.buy_in.unwrap_or(DEFAULT_BUY_IN),
+            starting_stack: options.starting_stack.unwrap_or(DEFAULT_STARTING_STACK),
+            levels: options.levels.unwrap_or_else(default_levels),
+            level_duration: Duration::from_secs(60 * options.level_minutes.unwrap_or(DEFAULT_LEVEL_MINUTES)),
+            payouts: options.payouts.unwrap_or_else(|| default_payouts(seats)),
+            entrants: Vec::new(),
+            eliminated: Vec::new(),
+            started_at: None,
+            finished: false,
+        };
+        if tournament.buy_in < 0 || tournament.starting_stack <= 0 {
+            return Err("Invalid buy-in or starting stack");
+        }
+        if tournament.level_duration.is_zero() {
+            return Err("Blind levels must last at least a minute");
+        }
+        let valid_level = |l: &Level| l.big_blind > 0 && l.small_blind > 0 && l.small_blind <= l.big_blind && l.ante >= 0;
+        if tournament.levels.is_empty() || !tournament.levels.iter().all(valid_level) {
+            return Err("Invalid blind schedule");
+        }
+        if tournament.payouts.is_empty() || tournament.payouts.iter().any(|&p| p <= 0) || tournament.payouts.iter().sum::<i32>() != 100 {
+            return Err("Payouts must be positive percentages adding up to 100");
+        }
+        Ok(tournament)
+    }
+
+    /// Whether the first hand has been dealt. Players can only register before that.
+    pub fn started(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn prize_pool(&self) -> i32 {
+        self.buy_in * self.entrants.len() as i32
+    }
+
+    /// Registered players who are still in.
+    pub fn remaining(&self) -> Vec<String> {
+        self.entrants.iter().filter(|name| !self.eliminated.contains(name)).cloned().collect()
+    }
+
+    /// The index of the blind level after playing for `elapsed`. The last level lasts until the end.
+    pub fn level_index(&self, elapsed: Duration) -> usize {
+        let index = (elapsed.as_secs() / self.level_duration.as_secs().max(1)) as usize;
+        index.min(self.levels.len() - 1)
+    }
+
+    /// The index of the current level, the first until the event starts.
+    pub fn current_level(&self) -> usize {
+        self.level_index(self.started_at.map(|started_at| started_at.elapsed()).unwrap_or_default())
+    }
+
+    pub fn level(&self) -> Level {
+        self.levels[self.current_level()]
+    }
+
+    /// Records the players who busted in a hand.
+    ///
+    /// # Arguments
+    /// * `busted` - `(name, stack at the start of the hand)` of every player who lost all their chips.
+    ///   The smaller the stack they started the hand with, the earlier they went out.
+    pub fn eliminate(&mut self, mut busted: Vec<(String, i32)>) {
+        busted.sort_by_key(|(_, starting_stack)| *starting_stack);
+        for (name, _) in busted {
+            if self.entrants.contains(&name) && !self.eliminated.contains(&name) {
+                self.eliminated.push(name);
+            }
+        }
+    }
+
+    /// Players by finishing place, the winner first, once the event is over.
+    pub fn standings(&self) -> Vec<String> {
+        let mut standings = self.remaining();
+        standings.extend(self.eliminated.iter().rev().cloned());
+        standings
+    }
+
+    /// What each paid place wins, the winner first. With fewer players than paid places the places
+    /// nobody finished in are left out and the rest share the whole pool in the same proportions.
+    /// Chips that do not divide evenly go to the winner.
+    pub fn prizes(&self) -> Vec<(String, i32)> {
+        let standings = self.standings();
+        let paid = self.payouts.len().min(standings.len());
+        let total: i32 = self.payouts[..paid].iter().sum();
+        if total == 0 {
+            return Vec::new();
+        }
+        let pool = self.prize_pool();
+        let mut prizes: Vec<(String, i32)> = standings[..paid]
+            .iter()
+            .zip(self.payouts.iter())
+            .map(|(name, &percent)| (name.clone(), pool * percent / total))
+            .collect();
+        let paid_out: i32 = prizes.iter().map(|(_, prize)| prize).sum();
+        prizes[0].1 += pool - paid_out;
+        prizes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tournament(entrants: &[&str]) -> Tournament {
+        let mut tournament = Tournament::from_options(SitAndGoOptions::default(), 9).unwrap();
+        tournament.entrants = entrants.iter().map(|name| name.to_string()).collect();
+        tournament
+    }
+
+    #[test]
+    fn test_options() {
+        let tournament = tournament(&[]);
+        assert_eq!((tournament.buy_in, tournament.starting_stack), (DEFAULT_BUY_IN, DEFAULT_STARTING_STACK));
+        assert_eq!(tournament.payouts, vec![50, 30, 20]);
+        let payouts = SitAndGoOptions { payouts: Some(vec![70, 20]), ..Default::default() };
+        assert!(Tournament::from_options(payouts, 9).is_err());
+        let levels = SitAndGoOptions { levels: Some(vec![Level { small_blind: 20, big_blind: 10, ante: 0 }]), ..Default::default() };
+        assert!(Tournament::from_options(levels, 9).is_err());
+    }
+
+    #[test]
+    fn test_levels_go_up_on_a_timer() {
+        let mut tournament = tournament(&["a", "b"]);
+        assert_eq!(tournament.level(), tournament.levels[0]);
+        tournament.started_at = Some(Instant::now());
+        assert_eq!(tournament.level(), tournament.levels[0]);
+        assert_eq!(tournament.level_index(Duration::from_secs(60 * DEFAULT_LEVEL_MINUTES * 2 + 30)), 2);
+        assert_eq!(tournament.level_index(Duration::from_secs(60 * 60 * 24)), tournament.levels.len() - 1);
+    }
+
+    #[test]
+    fn test_elimination_order_and_prizes() {
+        let mut tournament = tournament(&["a", "b", "c", "d", "e", "f", "g"]);
+        tournament.eliminate(vec![("g".to_string(), 300)]);
+        // busted in the same hand: the smaller stack goes out first
+        tournament.eliminate(vec![("e".to_string(), 900), ("f".to_string(), 200)]);
+        tournament.eliminate(vec![("d".to_string(), 1000), ("c".to_string(), 2000)]);
+        tournament.eliminate(vec![("a".to_string(), 3000)]);
+        assert_eq!(tournament.remaining(), vec!["b".to_string()]);
+        assert_eq!(tournament.standings(), ["b", "a", "c", "d", "e", "f", "g"]);
+        assert_eq!(tournament.prize_pool(), 700);
+        assert_eq!(tournament.prizes(), vec![("b".to_string(), 350), ("a".to_string(), 210), ("c".to_string(), 140)]);
+    }
+
+    #[test]
+    fn test_prizes_with_fewer_players_than_places() {
+        let mut tournament = tournament(&["a", "b"]);
+        tournament.payouts = vec![50, 30, 20];
+        tournament.buy_in = 101;
+        tournament.eliminate(vec![("b".to_string(), 1500)]);
+        // 50:30 of 202 is 126.25 and 75.75, the odd chip goes to the winner
+        assert_eq!(tournament.prizes(), vec![("a".to_string(), 127), ("b".to_string(), 75)]);
+    }
+}
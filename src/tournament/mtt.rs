@@ -0,0 +1,622 @@
+//! Multi-table tournaments (MTTs): one event played over as many game lobbies as it takes to seat the field.
+//!
+//! Players register from the server lobby, paying the buy-in, until the host starts the event. The entrants
+//! are then shuffled and dealt round the fewest tables that seat them, each table a game lobby of its own with
+//! its own table task, and told where to sit. The first hands are dealt `SEATING_SECS` later.
+//!
+//! From then on the `MultiTableTournament` decides when each table deals. A table reports to it once its hand
+//! is settled, with the players who busted, and is dealt its next hand once the tournament has:
+//!
+//! - knocked out the players who busted, and any who left the table or never came to it;
+//! - broken a table, once the players left fit at one table fewer: the table with the fewest players, as soon
+//!   as it is between hands, its players going one at a time to the tables with the fewest players;
+//! - balanced the tables, moving players off a table between hands with two players more than the shortest
+//!   table, starting with the player due to post the next big blind there;
+//! - announced the final table once every player left sits at one.
+//!
+//! One knock-out from the money the tables play hand-for-hand: each table waits for every other to finish
+//! its hand, then the players who busted in it are knocked out together, in the order of the stacks they
+//! started the hand with, and every table deals at once.
+//!
+//! A moved player's connection follows them through the `moved_to` pointer the old table leaves behind.
+//! The blind schedule, the eliminations and the prizes are those of `Tournament`, shared by every table.
+use super::{SitAndGoOptions, Tournament};
+use crate::database::ledger::{self, Account};
+use crate::database::Database;
+use crate::lobby::{self, stakes_text, Lobby};
+use crate::protocol::{self, Prize, ServerMessage, TournamentSummary, TournamentTable};
+use crate::table::{self, TableCommand};
+use rand::rng;
+use rand::seq::SliceRandom;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+pub const DEFAULT_TABLE_SIZE: usize = 9;
+// seconds the entrants have to take their seats before the first hands are dealt
+pub const SEATING_SECS: u64 = 60;
+
+/// What a game lobby knows about the tournament it is a table of.
+#[derive(Clone)]
+pub struct MttTable {
+    pub tournament: Arc<Mutex<MultiTableTournament>>,
+    pub name: String, // the tournament's name
+    pub number: usize,
+    pub seated: Vec<String>, // players the tournament has at this table, only they can join it
+}
+
+/// A player moved to another table to balance the tables or break one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Move {
+    pub player: String,
+    pub from: usize,
+    pub to: usize,
+}
+
+/// The name of a table's game lobby.
+pub fn table_name(tournament_name: &str, number: usize) -> String {
+    format!("{} - Table {}", tournament_name, number)
+}
+
+/// The payout table used unless the tournament sets its own, for the number of entrants: about one in
+/// five players is paid.
+pub fn mtt_payouts(entrants: usize) -> Vec<i32> {
+    match entrants {
+        0..=9 => super::default_payouts(entrants as i32),
+        10..=18 => vec![40, 25, 15, 12, 8],
+        19..=27 => vec![35, 22, 14, 10, 8, 6, 5],
+        28..=45 => vec![30, 19, 13, 10, 8, 6, 5, 5, 4],
+        _ => vec![27, 17, 12, 9, 7, 6, 5, 4, 4, 3, 3, 3],
+    }
+}
+
+/// Seats the entrants, in the order given, round the fewest tables of `table_size` that hold them all,
+/// so no table has more than one player more than another.
+pub fn deal_tables(entrants: &[String], table_size: usize) -> Vec<Vec<String>> {
+    let count = entrants.len().div_ceil(table_size).max(1);
+    let mut tables = vec![Vec::new(); count];
+    for (i, name) in entrants.iter().enumerate() {
+        tables[i % count].push(name.clone());
+    }
+    tables
+}
+
+/// The table to break: once the players left fit at one table fewer, the table with the fewest players,
+/// the highest numbered of those.
+pub fn table_to_break(tables: &BTreeMap<usize, Vec<String>>, table_size: usize) -> Option<usize> {
+    let players: usize = tables.values().map(Vec::len).sum();
+    if tables.len() < 2 || players > (tables.len() - 1) * table_size {
+        return None;
+    }
+    tables.iter().min_by_key(|(number, players)| (players.len(), Reverse(**number))).map(|(number, _)| *number)
+}
+
+/// Whether the tables play hand-for-hand: one knock-out from the money, with more than one table left.
+pub fn hand_for_hand(remaining: usize, paid: usize, tables: usize) -> bool {
+    tables > 1 && remaining == paid + 1
+}
+
+/// The players to move before the tables between hands deal again.
+///
+/// # Arguments
+/// * `tables` - The players at each table. At a table between hands the player due to post the next
+///   big blind comes first, they are the first moved.
+/// * `table_size` - The most players a table seats.
+/// * `idle` - The tables between hands. Only they can give up players.
+///
+/// # Returns
+/// The moves, in order: every player of the table broken, if it is between hands, then those balancing
+/// the rest.
+pub fn plan_moves(tables: &BTreeMap<usize, Vec<String>>, table_size: usize, idle: &[usize]) -> Vec<Move> {
+    let mut tables = tables.clone();
+    let mut moves = Vec::new();
+    let breaking = table_to_break(&tables, table_size);
+    if let Some(from) = breaking.filter(|number| idle.contains(number)) {
+        for player in tables.remove(&from).unwrap_or_default() {
+            let to = match tables.iter().filter(|(_, players)| players.len() < table_size).min_by_key(|(_, players)| players.len()) {
+                Some((number, _)) => *number,
+                None => break,
+            };
+            tables.entry(to).or_default().push(player.clone());
+            moves.push(Move { player, from, to });
+        }
+    }
+    // a table about to be broken takes no players
+    let open = |number: usize| Some(number) != breaking;
+    while let Some(to) = tables
+        .iter()
+        .filter(|(number, _)| open(**number))
+        .min_by_key(|(_, players)| players.len())
+        .map(|(number, players)| (*number, players.len()))
+    {
+        let from = tables
+            .iter()
+            .filter(|(number, players)| open(**number) && idle.contains(*number) && players.len() >= to.1 + 2)
+            .max_by_key(|(number, players)| (players.len(), Reverse(**number)))
+            .map(|(number, _)| *number);
+        let from = match from {
+            Some(from) => from,
+            None => break,
+        };
+        let player = tables.entry(from).or_default().remove(0);
+        tables.entry(to.0).or_default().push(player.clone());
+        moves.push(Move { player, from, to: to.0 });
+    }
+    moves
+}
+
+pub struct MultiTableTournament {
+    pub name: String,
+    pub host: String, // the player who created it, who starts it
+    pub game_type: i32,
+    pub betting_structure: i32,
+    pub table_size: usize,
+    pub event: Tournament, // the buy-in, blind schedule, entrants, knock-outs and prizes
+    custom_payouts: bool,  // otherwise the payouts are set by `mtt_payouts` when the event starts
+    db: Arc<Database>,
+    pub tables: BTreeMap<usize, Vec<String>>, // the players at each table by table number
+    lobbies: BTreeMap<usize, Arc<Mutex<Lobby>>>,
+    idle: Vec<usize>,            // tables between hands
+    pending: Vec<(String, i32)>, // busted players not knocked out yet, held back during hand-for-hand play
+    pub hand_for_hand: bool,
+}
+
+impl MultiTableTournament {
+    /// Sets up a tournament open for registration.
+    ///
+    /// # Arguments
+    /// * `name` - The tournament's name, its tables are named after it.
+    /// * `host` - The player creating it.
+    /// * `game_type` - The game played at every table.
+    /// * `betting_structure` - `lobby::NO_LIMIT`, `POT_LIMIT` or `FIXED_LIMIT`, the game's usual one if left out.
+    /// * `table_size` - The most players at a table, `DEFAULT_TABLE_SIZE` or the game's most if left out.
+    /// * `options` - The buy-in, stacks, blind schedule and payouts, as for a Sit-and-Go.
+    /// * `db` - The database the buy-ins and prizes go through.
+    ///
+    /// # Returns
+    /// * `Ok(MultiTableTournament)` - The tournament.
+    /// * `Err(&str)` - What is wrong with the settings.
+    pub fn new(
+        name: String,
+        host: String,
+        game_type: i32,
+        betting_structure: Option<i32>,
+        table_size: Option<usize>,
+        options: SitAndGoOptions,
+        db: Arc<Database>,
+    ) -> Result<Self, &'static str> {
+        let most = lobby::max_players(game_type) as usize;
+        let table_size = table_size.unwrap_or(DEFAULT_TABLE_SIZE.min(most));
+        if !(2..=most).contains(&table_size) {
+            return Err("Invalid table size for the game");
+        }
+        let betting_structure = betting_structure.unwrap_or(lobby::default_betting_structure(game_type));
+        if ![lobby::NO_LIMIT, lobby::POT_LIMIT, lobby::FIXED_LIMIT].contains(&betting_structure) {
+            return Err("Invalid betting structure");
+        }
+        let custom_payouts = options.payouts.is_some();
+        let event = Tournament::from_options(options, table_size as i32)?;
+        Ok(MultiTableTournament {
+            name,
+            host,
+            game_type,
+            betting_structure,
+            table_size,
+            event,
+            custom_payouts,
+            db,
+            tables: BTreeMap::new(),
+            lobbies: BTreeMap::new(),
+            idle: Vec::new(),
+            pending: Vec::new(),
+            hand_for_hand: false,
+        })
+    }
+
+    /// Whether the entrants have been seated. Registration closes then.
+    pub fn seated(&self) -> bool {
+        !self.tables.is_empty() || self.event.finished
+    }
+
+    /// Registers a player, taking the buy-in out of their wallet.
+    pub async fn register(&mut self, player_name: &str) -> Result<(), &'static str> {
+        if self.seated() {
+            return Err("Registration has closed");
+        }
+        if self.event.entrants.iter().any(|name| name == player_name) {
+            return Err("You are already registered");
+        }
+        let pool = Account::Tournament(&self.name);
+        match self.db.transfer(player_name, Account::Wallet, pool, self.event.buy_in, ledger::TOURNAMENT_BUY_IN).await {
+            Ok(true) => {
+                self.event.entrants.push(player_name.to_string());
+                Ok(())
+            }
+            Ok(false) => Err("You cannot afford the buy-in"),
+            Err(e) => {
+                eprintln!("Error taking the buy-in of {}: {}", player_name, e);
+                Err("Failed to pay the buy-in")
+            }
+        }
+    }
+
+    /// Takes a player off the list of entrants and gives them the buy-in back, until registration closes.
+    pub async fn unregister(&mut self, player_name: &str) -> Result<(), &'static str> {
+        if self.seated() {
+            return Err("Registration has closed");
+        }
+        if !self.event.entrants.iter().any(|name| name == player_name) {
+            return Err("You are not registered");
+        }
+        self.event.entrants.retain(|name| name != player_name);
+        let pool = Account::Tournament(&self.name);
+        if let Err(e) = self.db.transfer(player_name, pool, Account::Wallet, self.event.buy_in, ledger::TOURNAMENT_REFUND).await {
+            eprintln!("Error refunding the buy-in of {}: {}", player_name, e);
+        }
+        Ok(())
+    }
+
+    /// The tournament as shown in the server lobby.
+    pub fn info(&self) -> TournamentSummary {
+        let status = if self.event.finished {
+            "Finished"
+        } else if self.seated() {
+            "Running"
+        } else {
+            "Registering"
+        };
+        TournamentSummary {
+            name: self.name.clone(),
+            host: self.host.clone(),
+            game_type: lobby::game_type_name(self.game_type).to_string(),
+            buy_in: self.event.buy_in,
+            starting_stack: self.event.starting_stack,
+            prize_pool: self.event.prize_pool(),
+            entrants: self.event.entrants.clone(),
+            remaining: self.event.remaining().len(),
+            tables: self
+                .tables
+                .iter()
+                .map(|(number, players)| TournamentTable { name: table_name(&self.name, *number), players: players.clone() })
+                .collect(),
+            level: self.event.current_level() + 1,
+            hand_for_hand: self.hand_for_hand,
+            status: status.to_string(),
+        }
+    }
+
+    /// Sends a message to everyone at every table.
+    async fn broadcast_message(&self, message: &ServerMessage) {
+        for lobby in self.lobbies.values() {
+            lobby.lock().await.broadcast_message(message).await;
+        }
+    }
+
+    /// Tells everyone at every table something, as a `notice`.
+    async fn broadcast(&self, message: String) {
+        self.broadcast_message(&ServerMessage::notice(message)).await;
+    }
+
+    /// Copies the players at each table onto its game lobby.
+    async fn sync_tables(&self) {
+        for (number, lobby) in self.lobbies.iter() {
+            if let Some(table) = lobby.lock().await.mtt.as_mut() {
+                table.seated = self.tables.get(number).cloned().unwrap_or_default();
+            }
+        }
+    }
+
+    /// Knocks players out, in the order they busted, and takes them off their tables.
+    async fn eliminate(&mut self, busted: Vec<(String, i32)>) {
+        let out_before = self.event.eliminated.len();
+        self.event.eliminate(busted);
+        let places = self.event.entrants.len() - out_before;
+        let knocked_out: Vec<String> = self.event.eliminated[out_before..].to_vec();
+        for (i, name) in knocked_out.iter().enumerate() {
+            for players in self.tables.values_mut() {
+                players.retain(|player| player != name);
+            }
+            self.broadcast(format!("{} is out of the tournament in place {}.", name, places - i)).await;
+        }
+    }
+
+    /// Moves a player to another table. A player no longer at their table is knocked out instead.
+    async fn move_player(&mut self, planned: &Move) {
+        let (from, to) = match (self.lobbies.get(&planned.from), self.lobbies.get(&planned.to)) {
+            (Some(from), Some(to)) => (from.clone(), to.clone()),
+            _ => return,
+        };
+        let player = from.lock().await.move_player_to(&planned.player, &to).await;
+        let mut player = match player {
+            Some(player) => player,
+            None => {
+                self.eliminate(vec![(planned.player.clone(), 0)]).await;
+                return;
+            }
+        };
+        if let Some(players) = self.tables.get_mut(&planned.from) {
+            players.retain(|name| *name != planned.player);
+        }
+        self.tables.entry(planned.to).or_default().push(planned.player.clone());
+        from.lock()
+            .await
+            .broadcast(format!("{} moves to table {}.", planned.player, planned.to))
+            .await;
+
+        player.lobby = to.clone();
+        let tx = player.tx.clone();
+        let mut lobby = to.lock().await;
+        lobby.add_player(player).await;
+        lobby.broadcast(format!("{} joins from table {}.", planned.player, planned.from)).await;
+        let _ = protocol::send(&tx, &ServerMessage::notice(format!("You have been moved to table {}.", planned.to)));
+    }
+
+    /// Pays the prizes into the winners' wallets and tells every table the results.
+    async fn pay_out(&mut self) {
+        self.event.finished = true;
+        let prizes = self.event.prizes();
+        let mut results = Vec::new();
+        for (place, (name, prize)) in prizes.iter().enumerate() {
+            match self.db.transfer(name, Account::Tournament(&self.name), Account::Wallet, *prize, ledger::PRIZE).await {
+                Ok(_) => println!("Paid {} chips to {} for place {} in {}", prize, name, place + 1, self.name),
+                Err(e) => eprintln!("Error paying {} chips to {}: {}", prize, name, e),
+            }
+            results.push(Prize { place: place + 1, name: name.clone(), prize: *prize });
+        }
+        let winner = prizes.first().map(|(name, _)| name.clone()).unwrap_or_default();
+        let message = ServerMessage::TournamentResults {
+            message: format!("{} wins the tournament!", winner),
+            tournament_results: results,
+        };
+        self.broadcast_message(&message).await;
+    }
+
+    /// Knocks out the busted players, breaks and balances the tables and deals the next hand at every
+    /// table between hands, unless hand-for-hand play holds them back.
+    async fn settle(&mut self, server_lobby: &Arc<Mutex<Lobby>>) {
+        if !self.event.started() || self.event.finished {
+            return;
+        }
+        if self.hand_for_hand && self.idle.len() < self.tables.len() {
+            // nobody is knocked out, moved or dealt to until every table has finished the hand
+            return;
+        }
+        let busted = std::mem::take(&mut self.pending);
+        self.eliminate(busted).await;
+        if self.event.remaining().len() <= 1 {
+            self.pay_out().await;
+            return;
+        }
+
+        let tables_before = self.tables.len();
+        for planned in plan_moves(&self.tables, self.table_size, &self.idle) {
+            self.move_player(&planned).await;
+        }
+        let broken: Vec<usize> = self.tables.iter().filter(|(_, players)| players.is_empty()).map(|(number, _)| *number).collect();
+        for number in broken {
+            self.tables.remove(&number);
+            self.idle.retain(|idle| *idle != number);
+            self.lobbies.remove(&number);
+            println!("Broke table {} of {}", number, self.name);
+            server_lobby.lock().await.remove_lobby(table_name(&self.name, number)).await;
+        }
+        self.sync_tables().await;
+        if self.tables.len() == 1 && tables_before > 1 {
+            self.broadcast("Final table!".to_string()).await;
+        }
+        let hand_for_hand = hand_for_hand(self.event.remaining().len(), self.event.payouts.len(), self.tables.len());
+        if hand_for_hand != self.hand_for_hand {
+            self.hand_for_hand = hand_for_hand;
+            let message = if hand_for_hand {
+                "One knock-out from the money: the tables now play hand-for-hand."
+            } else {
+                "The bubble has burst, the tables play on as normal."
+            };
+            self.broadcast(message.to_string()).await;
+        }
+        if self.hand_for_hand && self.idle.len() < self.tables.len() {
+            return;
+        }
+
+        let level = self.event.level();
+        let level_number = self.event.current_level() + 1;
+        for number in self.idle.clone() {
+            // a table left short waits for players to be moved to it
+            if self.tables.get(&number).map_or(0, Vec::len) < 2 {
+                continue;
+            }
+            let lobby = match self.lobbies.get(&number) {
+                Some(lobby) => lobby.clone(),
+                None => continue,
+            };
+            let mut lobby = lobby.lock().await;
+            if lobby.set_level(level) {
+                let stakes = stakes_text(lobby.game_type, lobby.small_blind, lobby.big_blind, lobby.ante, lobby.bring_in);
+                lobby.broadcast(format!("Level {}: the stakes are now {}.", level_number, stakes)).await;
+            }
+            if let Some(table) = lobby.table.as_ref() {
+                let _ = table.send(TableCommand::Deal);
+            }
+            self.idle.retain(|idle| *idle != number);
+        }
+    }
+}
+
+/// Seats the entrants and opens a table for each group of them, then deals the first hands once the
+/// players have had `SEATING_SECS` to sit down.
+///
+/// # Arguments
+/// * `tournament` - The tournament to start.
+/// * `server_lobby` - The server lobby the tables are listed in and the entrants are waiting in.
+/// * `player_name` - The player starting it, who must be the host.
+///
+/// # Returns
+/// * `Ok(())` - If the tables are open.
+/// * `Err(&str)` - Why the tournament cannot start.
+pub async fn start(tournament: &Arc<Mutex<MultiTableTournament>>, server_lobby: &Arc<Mutex<Lobby>>, player_name: &str) -> Result<(), &'static str> {
+    let mut mtt = tournament.lock().await;
+    if mtt.host != player_name {
+        return Err("Only the host can start the tournament");
+    }
+    if mtt.seated() {
+        return Err("The tournament has already started");
+    }
+    if mtt.event.entrants.len() < 2 {
+        return Err("The tournament needs at least two players to start");
+    }
+    if !mtt.custom_payouts {
+        mtt.event.payouts = mtt_payouts(mtt.event.entrants.len());
+    }
+    let mut entrants = mtt.event.entrants.clone();
+    entrants.shuffle(&mut rng());
+    for (i, players) in deal_tables(&entrants, mtt.table_size).into_iter().enumerate() {
+        let number = i + 1;
+        let mut lobby = Lobby::new(mtt.game_type, table_name(&mtt.name, number)).await;
+        lobby.betting_structure = mtt.betting_structure;
+        lobby.max_player_count = mtt.table_size as i32;
+        lobby.set_level(mtt.event.levels[0]);
+        lobby.mtt = Some(MttTable { tournament: tournament.clone(), name: mtt.name.clone(), number, seated: players.clone() });
+        let lobby = Arc::new(Mutex::new(lobby));
+        let table = table::spawn_table(server_lobby.clone(), &lobby);
+        lobby.lock().await.table = Some(table);
+        server_lobby.lock().await.add_lobby(lobby.clone()).await;
+        mtt.tables.insert(number, players);
+        mtt.lobbies.insert(number, lobby);
+        mtt.idle.push(number);
+    }
+
+    // tell every entrant in the server lobby where to sit
+    let players = server_lobby.lock().await.players.lock().await.clone();
+    for player in players.iter() {
+        let number = mtt.tables.iter().find(|(_, seated)| seated.contains(&player.name)).map(|(number, _)| *number);
+        if let Some(number) = number {
+            let message = ServerMessage::TournamentTable {
+                message: format!("{} has started, take your seat at table {}.", mtt.name, number),
+                tournament_table: table_name(&mtt.name, number),
+                game_type: lobby::game_type_name(mtt.game_type).to_string(),
+            };
+            let _ = protocol::send(&player.tx, &message);
+        }
+    }
+    println!("Started tournament {} with {} players at {} tables", mtt.name, entrants.len(), mtt.tables.len());
+    drop(mtt);
+
+    let tournament = tournament.clone();
+    let server_lobby = server_lobby.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(SEATING_SECS)).await;
+        let mut mtt = tournament.lock().await;
+        mtt.event.started_at = Some(Instant::now());
+        mtt.broadcast(format!("Shuffle up and deal! {} players are in.", mtt.event.entrants.len())).await;
+        mtt.settle(&server_lobby).await;
+    });
+    Ok(())
+}
+
+/// Called by a table of the tournament once its hand is settled. The table deals again when the
+/// tournament sends it `TableCommand::Deal`.
+///
+/// # Arguments
+/// * `tournament` - The tournament the table belongs to.
+/// * `server_lobby` - The server lobby, which lists the tables.
+/// * `number` - The table's number.
+/// * `busted` - `(name, stack at the start of the hand)` of every player who lost all their chips.
+pub async fn hand_finished(tournament: &Arc<Mutex<MultiTableTournament>>, server_lobby: &Arc<Mutex<Lobby>>, number: usize, mut busted: Vec<(String, i32)>) {
+    let mut mtt = tournament.lock().await;
+    let lobby = match mtt.lobbies.get(&number) {
+        Some(lobby) => lobby.clone(),
+        None => return,
+    };
+    let present = lobby.lock().await.seated_from_big_blind();
+    let seated = mtt.tables.get(&number).cloned().unwrap_or_default();
+    // players who left the table, or never came to it, are out
+    busted.extend(seated.iter().filter(|name| !present.contains(name)).map(|name| (name.clone(), 0)));
+    mtt.tables.insert(number, present.into_iter().filter(|name| seated.contains(name)).collect());
+    mtt.pending.extend(busted);
+    if !mtt.idle.contains(&number) {
+        mtt.idle.push(number);
+    }
+    if mtt.hand_for_hand && mtt.idle.len() < mtt.tables.len() {
+        lobby.lock().await.broadcast("Hand-for-hand: waiting for the other tables to finish the hand.".to_string()).await;
+    }
+    mtt.settle(server_lobby).await;
+}
+
+/// Knocks out a player who left their table.
+pub async fn forfeit(tournament: &Arc<Mutex<MultiTableTournament>>, server_lobby: &Arc<Mutex<Lobby>>, player_name: &str) {
+    let mut mtt = tournament.lock().await;
+    if mtt.event.remaining().iter().any(|name| name == player_name) {
+        mtt.pending.push((player_name.to_string(), 0));
+        mtt.settle(server_lobby).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("p{}", i)).collect()
+    }
+
+    fn tables(sizes: &[usize]) -> BTreeMap<usize, Vec<String>> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| (i + 1, (0..size).map(|j| format!("t{}p{}", i + 1, j)).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_deal_tables() {
+        let tables = deal_tables(&names(50), 9);
+        assert_eq!(tables.iter().map(Vec::len).collect::<Vec<_>>(), vec![9, 9, 8, 8, 8, 8]);
+        assert_eq!(deal_tables(&names(9), 9).len(), 1);
+        assert_eq!(deal_tables(&names(10), 9).iter().map(Vec::len).collect::<Vec<_>>(), vec![5, 5]);
+    }
+
+    #[test]
+    fn test_balancing_moves_the_next_big_blind_to_the_short_table() {
+        let tables = tables(&[9, 6, 8]);
+        // table 1 is between hands, so it gives a player to table 2
+        let moves = plan_moves(&tables, 9, &[1]);
+        assert_eq!(moves, vec![Move { player: "t1p0".to_string(), from: 1, to: 2 }]);
+        // tables in the middle of a hand give up nobody, nor does the short table
+        assert!(plan_moves(&tables, 9, &[]).is_empty());
+        assert!(plan_moves(&tables, 9, &[2]).is_empty());
+    }
+
+    #[test]
+    fn test_breaking_a_table() {
+        // of two tables as short, the higher numbered is broken
+        assert_eq!(table_to_break(&tables(&[5, 5, 5]), 9), Some(3));
+        assert_eq!(table_to_break(&tables(&[5, 5, 9]), 9), None);
+        let tables = tables(&[7, 4, 6]);
+        // 17 players fit at two tables of 9: table 2 is broken once it is between hands
+        assert_eq!(table_to_break(&tables, 9), Some(2));
+        assert!(plan_moves(&tables, 9, &[1]).is_empty());
+        let moves = plan_moves(&tables, 9, &[2]);
+        assert_eq!(moves.iter().filter(|m| m.from == 2).count(), 4);
+        let mut after = tables.clone();
+        for m in moves.iter() {
+            after.get_mut(&m.from).unwrap().retain(|name| *name != m.player);
+            after.get_mut(&m.to).unwrap().push(m.player.clone());
+        }
+        assert_eq!(after.values().map(Vec::len).collect::<Vec<_>>(), vec![9, 0, 8]);
+    }
+
+    #[test]
+    fn test_hand_for_hand_and_payouts() {
+        assert!(hand_for_hand(13, 12, 2));
+        assert!(!hand_for_hand(14, 12, 2));
+        assert!(!hand_for_hand(4, 3, 1));
+        for entrants in [2, 9, 10, 18, 27, 45, 50, 200] {
+            let payouts = mtt_payouts(entrants);
+            assert_eq!(payouts.iter().sum::<i32>(), 100);
+            assert!(payouts.len() <= entrants);
+            assert!(payouts.windows(2).all(|pair| pair[0] >= pair[1]));
+        }
+    }
+}